@@ -135,6 +135,13 @@ pub fn path_all_synthetic_maps() -> String {
     path("system/synthetic_maps")
 }
 
+pub fn path_attributes(map_name: &str, table_name: &str) -> String {
+    path(format!("system/attributes/{}/{}.json", map_name, table_name))
+}
+pub fn path_all_attributes(map_name: &str) -> String {
+    path(format!("system/attributes/{}", map_name))
+}
+
 // Player data (Players edit this)
 
 pub fn path_camera_state(map_name: &str) -> String {
@@ -2,9 +2,8 @@ use abstutil::{retain_btreemap, Timer};
 use geom::{Distance, PolyLine, Ring};
 use map_model::raw::{OriginalRoad, RawMap};
 use map_model::{osm, IntersectionType};
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 
-// TODO This needs to update turn restrictions too
 pub fn clip_map(map: &mut RawMap, timer: &mut Timer) {
     timer.start("clipping map to boundary");
 
@@ -31,6 +30,9 @@ pub fn clip_map(map: &mut RawMap, timer: &mut Timer) {
     // When we split an intersection out of bounds into two, one of them gets a new ID. Remember
     // that here.
     let mut extra_borders: BTreeMap<osm::NodeID, osm::NodeID> = BTreeMap::new();
+    // Trimmed roads get a new ID when an endpoint moves. Turn restrictions still refer to the old
+    // ID.
+    let mut renamed: BTreeMap<OriginalRoad, OriginalRoad> = BTreeMap::new();
 
     // First pass: Clip roads beginning out of bounds
     let road_ids: Vec<OriginalRoad> = map.roads.keys().cloned().collect();
@@ -75,14 +77,15 @@ pub fn clip_map(map: &mut RawMap, timer: &mut Timer) {
             panic!("{} interacts with border strangely", id);
         }
         i.point = mut_r.center_points[0];
-        map.roads.insert(
-            OriginalRoad {
-                osm_way_id: id.osm_way_id,
-                i1: move_i,
-                i2: id.i2,
-            },
-            mut_r,
-        );
+        let new_id = OriginalRoad {
+            osm_way_id: id.osm_way_id,
+            i1: move_i,
+            i2: id.i2,
+        };
+        if new_id != id {
+            renamed.insert(id, new_id);
+        }
+        map.roads.insert(new_id, mut_r);
     }
 
     // Second pass: clip roads ending out of bounds
@@ -130,16 +133,19 @@ pub fn clip_map(map: &mut RawMap, timer: &mut Timer) {
             panic!("{} interacts with border strangely", id);
         }
         i.point = *mut_r.center_points.last().unwrap();
-        map.roads.insert(
-            OriginalRoad {
-                osm_way_id: id.osm_way_id,
-                i1: id.i1,
-                i2: move_i,
-            },
-            mut_r,
-        );
+        let new_id = OriginalRoad {
+            osm_way_id: id.osm_way_id,
+            i1: id.i1,
+            i2: move_i,
+        };
+        if new_id != id {
+            renamed.insert(id, new_id);
+        }
+        map.roads.insert(new_id, mut_r);
     }
 
+    fix_turn_restrictions(map, &renamed, timer);
+
     retain_btreemap(&mut map.buildings, |_, b| {
         b.polygon
             .points()
@@ -234,3 +240,118 @@ pub fn clip_map(map: &mut RawMap, timer: &mut Timer) {
 
     timer.stop("clipping map to boundary");
 }
+
+// Point turn restrictions at the renamed roads, and drop any referring to roads that were clipped
+// away or disconnected from each other.
+fn fix_turn_restrictions(
+    map: &mut RawMap,
+    renamed: &BTreeMap<OriginalRoad, OriginalRoad>,
+    timer: &mut Timer,
+) {
+    // A road could be trimmed on both ends, so follow the renames
+    let lookup = |mut r: OriginalRoad| {
+        while let Some(next) = renamed.get(&r) {
+            r = *next;
+        }
+        r
+    };
+    let connected = |r1: &OriginalRoad, r2: &OriginalRoad| {
+        r1.i1 == r2.i1 || r1.i1 == r2.i2 || r1.i2 == r2.i1 || r1.i2 == r2.i2
+    };
+
+    let all_roads: BTreeSet<OriginalRoad> = map.roads.keys().cloned().collect();
+    let mut dropped = 0;
+    for (id, r) in &mut map.roads {
+        let before = r.turn_restrictions.len() + r.complicated_turn_restrictions.len();
+
+        r.turn_restrictions = std::mem::replace(&mut r.turn_restrictions, Vec::new())
+            .into_iter()
+            .map(|(rt, to)| (rt, lookup(to)))
+            .filter(|(_, to)| all_roads.contains(to) && connected(id, to))
+            .collect();
+        r.complicated_turn_restrictions =
+            std::mem::replace(&mut r.complicated_turn_restrictions, Vec::new())
+                .into_iter()
+                .map(|(via, to)| (via.into_iter().map(lookup).collect::<Vec<_>>(), lookup(to)))
+                .filter(|(via, to)| {
+                    let mut chain = vec![*id];
+                    chain.extend(via.clone());
+                    chain.push(*to);
+                    chain.iter().all(|r| all_roads.contains(r))
+                        && chain.windows(2).all(|pair| connected(&pair[0], &pair[1]))
+                })
+                .collect();
+
+        dropped += before - r.turn_restrictions.len() - r.complicated_turn_restrictions.len();
+    }
+    if dropped > 0 {
+        timer.note(format!(
+            "Dropped {} turn restrictions involving roads clipped out of the map or disconnected",
+            dropped
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use abstutil::Tags;
+    use geom::Pt2D;
+    use map_model::raw::{RawRoad, RestrictionType};
+
+    fn road() -> RawRoad {
+        RawRoad {
+            center_points: vec![Pt2D::new(0.0, 0.0), Pt2D::new(10.0, 0.0)],
+            osm_tags: Tags::new(BTreeMap::new()),
+            turn_restrictions: Vec::new(),
+            complicated_turn_restrictions: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_fix_turn_restrictions() {
+        let a = OriginalRoad::new(1, (1, 2));
+        let c = OriginalRoad::new(3, (2, 4));
+        // Clipped at the end
+        let b_old = OriginalRoad::new(2, (4, 40));
+        let b = OriginalRoad::new(2, (4, -1));
+        let e_old = OriginalRoad::new(6, (2, 50));
+        let e = OriginalRoad::new(6, (2, -4));
+        // Clipped at both ends, so it's disconnected from everything
+        let d_old = OriginalRoad::new(5, (20, 21));
+        let d_mid = OriginalRoad::new(5, (-2, 21));
+        let d = OriginalRoad::new(5, (-2, -3));
+
+        let mut map = RawMap::blank("test", "test");
+        let mut from = road();
+        from.turn_restrictions = vec![
+            (RestrictionType::BanTurns, e_old),
+            (RestrictionType::BanTurns, d_old),
+            (RestrictionType::BanTurns, c),
+        ];
+        from.complicated_turn_restrictions = vec![(vec![c], b_old), (vec![c, b_old], d_old)];
+        map.roads.insert(a, from);
+        for r in vec![b, c, d, e] {
+            map.roads.insert(r, road());
+        }
+
+        let mut renamed = BTreeMap::new();
+        renamed.insert(b_old, b);
+        renamed.insert(e_old, e);
+        renamed.insert(d_old, d_mid);
+        renamed.insert(d_mid, d);
+        fix_turn_restrictions(&mut map, &renamed, &mut Timer::throwaway());
+
+        assert_eq!(
+            map.roads[&a].turn_restrictions,
+            vec![
+                (RestrictionType::BanTurns, e),
+                (RestrictionType::BanTurns, c)
+            ]
+        );
+        assert_eq!(
+            map.roads[&a].complicated_turn_restrictions,
+            vec![(vec![c], b)]
+        );
+    }
+}
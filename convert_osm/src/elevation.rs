@@ -0,0 +1,333 @@
+use geom::{Distance, LonLat};
+use std::error::Error;
+use std::io::Cursor;
+use std::path::Path;
+use tiff::decoder::{Decoder, DecodingResult, Limits};
+use tiff::tags::Tag;
+use tiff::ColorType;
+
+// Elevation data from SRTM .hgt files (https://dds.cr.usgs.gov/srtm/version2_1/Documentation/) or
+// single-band GeoTIFFs in WGS84 lon/lat, like those exported by most DEM download tools. Many tiles
+// can be loaded at once by pointing to a directory.
+pub struct Elevation {
+    tiles: Vec<Tile>,
+}
+
+impl Elevation {
+    // The path can be one .hgt or .tif file, or a directory of them.
+    pub fn load(path: &str) -> Result<Elevation, Box<dyn Error>> {
+        let mut paths = Vec::new();
+        if Path::new(path).is_dir() {
+            for entry in std::fs::read_dir(path)? {
+                let entry = entry?.path();
+                if is_raster(&entry) {
+                    paths.push(entry);
+                }
+            }
+            paths.sort();
+        } else {
+            paths.push(Path::new(path).to_path_buf());
+        }
+
+        let mut tiles = Vec::new();
+        for p in paths {
+            println!("Reading elevation data from {}", p.display());
+            let bytes = std::fs::read(&p)?;
+            let name = p.file_name().unwrap().to_string_lossy().to_lowercase();
+            let tile = if name.ends_with(".hgt") {
+                Tile::from_hgt(&name, &bytes)
+            } else {
+                Tile::from_geotiff(&bytes)
+            }
+            .map_err(|err| format!("{}: {}", p.display(), err))?;
+            tiles.push(tile);
+        }
+        if tiles.is_empty() {
+            return Err(format!("No elevation data in {}", path).into());
+        }
+        Ok(Elevation { tiles })
+    }
+
+    // Bilinearly interpolated. None if no tile covers the point or the data there is missing.
+    pub fn get(&self, pt: LonLat) -> Option<Distance> {
+        self.tiles
+            .iter()
+            .filter_map(|t| t.get(pt.x(), pt.y()))
+            .next()
+            .map(Distance::meters)
+    }
+}
+
+fn is_raster(path: &Path) -> bool {
+    match path.extension().and_then(|x| x.to_str()) {
+        Some(ext) => vec!["hgt", "tif", "tiff"].contains(&ext.to_lowercase().as_str()),
+        None => false,
+    }
+}
+
+// A regular grid of samples. Missing samples are NaN.
+struct Tile {
+    // The lon/lat of the sample in the first row and column, the northwest corner
+    lon0: f64,
+    lat0: f64,
+    // Degrees between samples. Rows go south.
+    lon_step: f64,
+    lat_step: f64,
+    width: usize,
+    height: usize,
+    data: Vec<f32>,
+}
+
+impl Tile {
+    fn get(&self, lon: f64, lat: f64) -> Option<f64> {
+        let fx = (lon - self.lon0) / self.lon_step;
+        let fy = (self.lat0 - lat) / self.lat_step;
+        // Points within half a sample of the edge are clamped, so GeoTIFFs whose pixels are areas
+        // cover their whole extent
+        if fx < -0.5
+            || fy < -0.5
+            || fx > (self.width as f64) - 0.5
+            || fy > (self.height as f64) - 0.5
+        {
+            return None;
+        }
+        let fx = fx.max(0.0).min((self.width - 1) as f64);
+        let fy = fy.max(0.0).min((self.height - 1) as f64);
+        let x0 = fx.floor() as usize;
+        let y0 = fy.floor() as usize;
+        let x1 = (x0 + 1).min(self.width - 1);
+        let y1 = (y0 + 1).min(self.height - 1);
+        let tx = fx - (x0 as f64);
+        let ty = fy - (y0 as f64);
+
+        // Missing samples are skipped, with the remaining weights renormalized
+        let mut total = 0.0;
+        let mut total_weight = 0.0;
+        for (x, y, weight) in vec![
+            (x0, y0, (1.0 - tx) * (1.0 - ty)),
+            (x1, y0, tx * (1.0 - ty)),
+            (x0, y1, (1.0 - tx) * ty),
+            (x1, y1, tx * ty),
+        ] {
+            let value = self.data[y * self.width + x];
+            if !value.is_nan() && weight > 0.0 {
+                total += weight * f64::from(value);
+                total_weight += weight;
+            }
+        }
+        if total_weight == 0.0 {
+            // Right on a sample, or all neighbors are missing
+            let value = self.data[fy.round() as usize * self.width + fx.round() as usize];
+            if value.is_nan() {
+                return None;
+            }
+            return Some(f64::from(value));
+        }
+        Some(total / total_weight)
+    }
+
+    // The filename is the southwest corner, like N47W122.hgt. Samples are big-endian i16 meters in
+    // a square grid, 1201 wide for 3 arc-second data or 3601 for 1 arc-second. Neighboring tiles
+    // share their edge rows and columns.
+    fn from_hgt(name: &str, bytes: &[u8]) -> Result<Tile, String> {
+        let bad_name = || format!("{} isn't named like N47W122.hgt", name);
+        if name.len() < 7 {
+            return Err(bad_name());
+        }
+        let lat = name[1..3].parse::<f64>().map_err(|_| bad_name())?;
+        let lon = name[4..7].parse::<f64>().map_err(|_| bad_name())?;
+        let lat = match &name[0..1] {
+            "n" => lat,
+            "s" => -lat,
+            _ => return Err(bad_name()),
+        };
+        let lon = match &name[3..4] {
+            "e" => lon,
+            "w" => -lon,
+            _ => return Err(bad_name()),
+        };
+
+        let dim = ((bytes.len() / 2) as f64).sqrt() as usize;
+        if dim < 2 || dim * dim * 2 != bytes.len() {
+            return Err(format!("{} bytes isn't a square grid", bytes.len()));
+        }
+        let data = bytes
+            .chunks(2)
+            .map(|pair| match i16::from_be_bytes([pair[0], pair[1]]) {
+                // Voids
+                -32768 => std::f32::NAN,
+                x => f32::from(x),
+            })
+            .collect();
+        let step = 1.0 / ((dim - 1) as f64);
+        Ok(Tile {
+            lon0: lon,
+            lat0: lat + 1.0,
+            lon_step: step,
+            lat_step: step,
+            width: dim,
+            height: dim,
+            data,
+        })
+    }
+
+    // Only single-band GeoTIFFs in lon/lat are supported. Use something like
+    // `gdalwarp -t_srs EPSG:4326` to convert others.
+    fn from_geotiff(bytes: &[u8]) -> Result<Tile, String> {
+        let mut tiff = Decoder::new(Cursor::new(bytes))
+            .map_err(|err| err.to_string())?
+            .with_limits(Limits::unlimited());
+        match tiff.colortype() {
+            Ok(ColorType::Gray(_)) => {}
+            _ => return Err("only single-band GeoTIFFs are supported".to_string()),
+        }
+        let (width, height) = tiff.dimensions().map_err(|err| err.to_string())?;
+        let samples: Vec<f64> = match tiff.read_image().map_err(|err| err.to_string())? {
+            DecodingResult::U8(x) => x.into_iter().map(f64::from).collect(),
+            DecodingResult::U16(x) => x.into_iter().map(f64::from).collect(),
+            DecodingResult::U32(x) => x.into_iter().map(f64::from).collect(),
+            DecodingResult::U64(x) => x.into_iter().map(|x| x as f64).collect(),
+            DecodingResult::I8(x) => x.into_iter().map(f64::from).collect(),
+            DecodingResult::I16(x) => x.into_iter().map(f64::from).collect(),
+            DecodingResult::I32(x) => x.into_iter().map(f64::from).collect(),
+            DecodingResult::I64(x) => x.into_iter().map(|x| x as f64).collect(),
+            DecodingResult::F32(x) => x.into_iter().map(f64::from).collect(),
+            DecodingResult::F64(x) => x,
+        };
+        let nodata = tiff
+            .get_tag_ascii_string(Tag::GdalNodata)
+            .ok()
+            .and_then(|x| x.trim().parse::<f64>().ok());
+        let data = samples
+            .into_iter()
+            .map(|x| {
+                if Some(x) == nodata || x.is_nan() {
+                    std::f32::NAN
+                } else {
+                    x as f32
+                }
+            })
+            .collect();
+
+        // Georeferencing
+        let scale = tiff
+            .get_tag_f64_vec(Tag::ModelPixelScaleTag)
+            .map_err(|err| err.to_string())?;
+        let tiepoint = tiff
+            .get_tag_f64_vec(Tag::ModelTiepointTag)
+            .map_err(|err| err.to_string())?;
+        if scale.len() < 2 || tiepoint.len() < 6 {
+            return Err("bad georeferencing tags".to_string());
+        }
+        let mut pixel_is_area = true;
+        if let Ok(keys) = tiff.get_tag_u16_vec(Tag::GeoKeyDirectoryTag) {
+            for key in keys.chunks(4).skip(1) {
+                if key.len() < 4 {
+                    break;
+                }
+                // GTModelTypeGeoKey
+                if key[0] == 1024 && key[1] == 0 && key[3] != 2 {
+                    return Err("only lon/lat GeoTIFFs are supported".to_string());
+                }
+                // GTRasterTypeGeoKey
+                if key[0] == 1025 && key[1] == 0 {
+                    pixel_is_area = key[3] == 1;
+                }
+            }
+        }
+        let (lon_step, lat_step) = (scale[0], scale[1]);
+        let mut lon0 = tiepoint[3] - tiepoint[0] * lon_step;
+        let mut lat0 = tiepoint[4] + tiepoint[1] * lat_step;
+        if pixel_is_area {
+            // Samples are at the center of each pixel
+            lon0 += lon_step / 2.0;
+            lat0 -= lat_step / 2.0;
+        }
+
+        Ok(Tile {
+            lon0,
+            lat0,
+            lon_step,
+            lat_step,
+            width: width as usize,
+            height: height as usize,
+            data,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tiff::encoder::colortype::Gray32Float;
+    use tiff::encoder::TiffEncoder;
+
+    fn hgt(values: Vec<i16>) -> Vec<u8> {
+        values
+            .into_iter()
+            .flat_map(|x| x.to_be_bytes().to_vec())
+            .collect()
+    }
+
+    // A single-strip GeoTIFF of f32 samples
+    fn geotiff(width: u32, height: u32, values: Vec<f32>, nodata: &str) -> Vec<u8> {
+        let mut out = Cursor::new(Vec::new());
+        let mut tiff = TiffEncoder::new(&mut out).unwrap();
+        let mut image = tiff.new_image::<Gray32Float>(width, height).unwrap();
+        let dir = image.encoder();
+        dir.write_tag(Tag::ModelPixelScaleTag, &[0.5f64, 0.5, 0.0][..])
+            .unwrap();
+        dir.write_tag(
+            Tag::ModelTiepointTag,
+            &[0.0f64, 0.0, 0.0, 10.0, 20.0, 0.0][..],
+        )
+        .unwrap();
+        dir.write_tag(Tag::GdalNodata, nodata).unwrap();
+        image.write_data(&values).unwrap();
+        out.into_inner()
+    }
+
+    fn approx_eq(actual: Option<f64>, expected: f64) -> bool {
+        actual.map(|x| (x - expected).abs() < 1e-6).unwrap_or(false)
+    }
+
+    #[test]
+    fn test_hgt() {
+        // 3x3 grid covering 47 to 48 N, 122 to 121 W. The first row is the north edge.
+        let tile = Tile::from_hgt(
+            "n47w122.hgt",
+            &hgt(vec![0, 10, 20, 100, 110, 120, 200, -32768, 220]),
+        )
+        .unwrap();
+        // Corners
+        assert!(approx_eq(tile.get(-122.0, 48.0), 0.0));
+        assert!(approx_eq(tile.get(-121.0, 48.0), 20.0));
+        assert!(approx_eq(tile.get(-121.0, 47.0), 220.0));
+        // Halfway between samples
+        assert!(approx_eq(tile.get(-121.75, 48.0), 5.0));
+        assert!(approx_eq(tile.get(-121.75, 47.75), 55.0));
+        // Voids are skipped
+        assert!(approx_eq(tile.get(-121.25, 47.25), 150.0));
+        assert!(tile.get(-121.5, 47.0).is_none());
+        // Out of bounds
+        assert!(tile.get(-120.0, 47.5).is_none());
+    }
+
+    #[test]
+    fn test_geotiff() {
+        // 2x2 pixels, each 0.5 degrees, with the northwest corner at (10, 20)
+        let tile =
+            Tile::from_geotiff(&geotiff(2, 2, vec![1.0, 3.0, 5.0, -9999.0], "-9999")).unwrap();
+        // Pixel centers
+        assert!(approx_eq(tile.get(10.25, 19.75), 1.0));
+        assert!(approx_eq(tile.get(10.75, 19.75), 3.0));
+        assert!(approx_eq(tile.get(10.25, 19.25), 5.0));
+        // Between pixel centers
+        assert!(approx_eq(tile.get(10.5, 19.75), 2.0));
+        // Clamped near the edge
+        assert!(approx_eq(tile.get(10.0, 20.0), 1.0));
+        // No data
+        assert!(tile.get(10.75, 19.25).is_none());
+        assert!(tile.get(11.5, 19.75).is_none());
+    }
+}
@@ -17,10 +17,17 @@ pub struct OsmExtract {
     pub osm_node_ids: HashMap<HashablePt2D, NodeID>,
     // (ID, restriction type, from way ID, via node ID, to way ID)
     pub simple_turn_restrictions: Vec<(RestrictionType, WayID, NodeID, WayID)>,
-    // (relation ID, from way ID, via way ID, to way ID)
-    pub complicated_turn_restrictions: Vec<(RelationID, WayID, WayID, WayID)>,
+    // (relation ID, restriction type, from way ID, via way IDs in order, to way ID)
+    pub complicated_turn_restrictions: Vec<(RelationID, RestrictionType, WayID, Vec<WayID>, WayID)>,
     // (location, name, amenity type)
     pub amenities: Vec<(Pt2D, NamePerLanguage, String)>,
+    // Separately mapped sidewalks
+    pub sidewalks: Vec<(WayID, Vec<Pt2D>)>,
+    // highway=crossing nodes to the type of crossing
+    pub crossings: HashMap<HashablePt2D, String>,
+    // highway=stop nodes to "all" for all-way stops, or the direction they apply ("forward",
+    // "backward", or "both" if unspecified)
+    pub stop_signs: HashMap<HashablePt2D, String>,
 }
 
 pub fn extract_osm(map: &mut RawMap, opts: &Options, timer: &mut Timer) -> OsmExtract {
@@ -38,6 +45,9 @@ pub fn extract_osm(map: &mut RawMap, opts: &Options, timer: &mut Timer) -> OsmEx
         simple_turn_restrictions: Vec::new(),
         complicated_turn_restrictions: Vec::new(),
         amenities: Vec::new(),
+        sidewalks: Vec::new(),
+        crossings: HashMap::new(),
+        stop_signs: HashMap::new(),
     };
 
     timer.start_iter("processing OSM nodes", doc.nodes.len());
@@ -50,6 +60,28 @@ pub fn extract_osm(map: &mut RawMap, opts: &Options, timer: &mut Timer) -> OsmEx
             out.traffic_signals
                 .insert(node.pt.to_hashable(), !backwards);
         }
+        if node.tags.is(osm::HIGHWAY, "crossing") {
+            out.crossings.insert(
+                node.pt.to_hashable(),
+                node.tags
+                    .get("crossing")
+                    .cloned()
+                    .unwrap_or_else(|| "yes".to_string()),
+            );
+        }
+        if node.tags.is(osm::HIGHWAY, "stop") {
+            let value = if node.tags.is("stop", "all") {
+                "all"
+            } else if node.tags.is("direction", "forward") {
+                "forward"
+            } else if node.tags.is("direction", "backward") {
+                "backward"
+            } else {
+                "both"
+            };
+            out.stop_signs
+                .insert(node.pt.to_hashable(), value.to_string());
+        }
         if let Some(amenity) = node.tags.get("amenity") {
             out.amenities.push((
                 node.pt,
@@ -81,8 +113,8 @@ pub fn extract_osm(map: &mut RawMap, opts: &Options, timer: &mut Timer) -> OsmEx
 
         if is_road(&mut way.tags, opts) {
             // TODO Hardcoding these overrides. OSM is correct, these don't have
-            // sidewalks; there's a crosswalk mapped. But until we can snap sidewalks properly, do
-            // this to prevent the sidewalks from being disconnected.
+            // sidewalks; there's a crosswalk mapped. But the sidewalks here aren't mapped
+            // separately, so do this to prevent them from being disconnected.
             if id == WayID(332060260) || id == WayID(332060236) {
                 way.tags.insert(osm::SIDEWALK, "right");
             }
@@ -109,6 +141,9 @@ pub fn extract_osm(map: &mut RawMap, opts: &Options, timer: &mut Timer) -> OsmEx
             .tags
             .is_any(osm::HIGHWAY, vec!["cycleway", "footway", "path"])
         {
+            if way.tags.is(osm::HIGHWAY, "footway") && way.tags.is("footway", "sidewalk") {
+                out.sidewalks.push((id, way.pts.clone()));
+            }
             extra_footways.shapes.push(ExtraShape {
                 points: map.gps_bounds.convert_back(&way.pts),
                 attributes: way.tags.inner().clone(),
@@ -189,12 +224,8 @@ pub fn extract_osm(map: &mut RawMap, opts: &Options, timer: &mut Timer) -> OsmEx
 
         if let Some(area_type) = get_area_type(&rel.tags) {
             if rel.tags.is("type", "multipolygon") {
-                for polygon in glue_multipolygon(
-                    id,
-                    get_multipolygon_members(id, rel, &doc),
-                    &boundary,
-                    timer,
-                ) {
+                let (outer, inner) = get_multipolygon_members(id, rel, &doc);
+                for polygon in glue_multipolygon(id, outer, inner, &boundary, timer) {
                     map.areas.push(RawArea {
                         area_type,
                         osm_id: OsmID::Relation(id),
@@ -206,7 +237,7 @@ pub fn extract_osm(map: &mut RawMap, opts: &Options, timer: &mut Timer) -> OsmEx
         } else if rel.tags.is("type", "restriction") {
             let mut from_way_id: Option<WayID> = None;
             let mut via_node_id: Option<NodeID> = None;
-            let mut via_way_id: Option<WayID> = None;
+            let mut via_way_ids: Vec<WayID> = Vec::new();
             let mut to_way_id: Option<WayID> = None;
             for (role, member) in &rel.members {
                 match member {
@@ -216,7 +247,7 @@ pub fn extract_osm(map: &mut RawMap, opts: &Options, timer: &mut Timer) -> OsmEx
                         } else if role == "to" {
                             to_way_id = Some(*w);
                         } else if role == "via" {
-                            via_way_id = Some(*w);
+                            via_way_ids.push(*w);
                         }
                     }
                     OsmID::Node(n) => {
@@ -228,24 +259,33 @@ pub fn extract_osm(map: &mut RawMap, opts: &Options, timer: &mut Timer) -> OsmEx
                 }
             }
             if let Some(restriction) = rel.tags.get("restriction") {
-                if let Some(rt) = RestrictionType::new(restriction) {
-                    if let (Some(from), Some(via), Some(to)) = (from_way_id, via_node_id, to_way_id)
-                    {
+                match (
+                    RestrictionType::new(restriction),
+                    from_way_id,
+                    via_node_id,
+                    to_way_id,
+                ) {
+                    (Some(rt), Some(from), Some(via), Some(to)) => {
                         out.simple_turn_restrictions.push((rt, from, via, to));
-                    } else if let (Some(from), Some(via), Some(to)) =
-                        (from_way_id, via_way_id, to_way_id)
-                    {
-                        if rt == RestrictionType::BanTurns {
-                            out.complicated_turn_restrictions.push((id, from, via, to));
-                        } else {
-                            timer.warn(format!(
-                                "Weird complicated turn restriction \"{}\" from {} to {} via {}: \
-                                 {}",
-                                restriction, from, to, via, id
-                            ));
-                        }
+                    }
+                    (Some(rt), Some(from), None, Some(to)) if !via_way_ids.is_empty() => {
+                        out.complicated_turn_restrictions
+                            .push((id, rt, from, via_way_ids, to));
+                    }
+                    _ => {
+                        timer.warn(format!(
+                            "Couldn't understand turn restriction {} (\"{}\")",
+                            id, restriction
+                        ));
                     }
                 }
+            } else if let Some(x) = rel.tags.get("restriction:conditional") {
+                // Time conditions aren't modeled, and applying these all day would ban turns that
+                // are usually legal
+                timer.warn(format!(
+                    "Not applying turn restriction {}, because it only applies sometimes ({})",
+                    id, x
+                ));
             }
         } else if is_bldg(&rel.tags) {
             match multipoly_geometry(id, rel, &doc) {
@@ -311,7 +351,13 @@ pub fn extract_osm(map: &mut RawMap, opts: &Options, timer: &mut Timer) -> OsmEx
 
     // Special case the coastline.
     println!("{} ways of coastline", coastline_groups.len());
-    for polygon in glue_multipolygon(RelationID(-1), coastline_groups, &boundary, timer) {
+    for polygon in glue_multipolygon(
+        RelationID(-1),
+        coastline_groups,
+        Vec::new(),
+        &boundary,
+        timer,
+    ) {
         let mut osm_tags = Tags::new(BTreeMap::new());
         osm_tags.insert("water", "ocean");
         // Put it at the beginning, so that it's naturally beneath island areas
@@ -358,7 +404,7 @@ pub fn extract_osm(map: &mut RawMap, opts: &Options, timer: &mut Timer) -> OsmEx
     }
 
     // Hack to fix z-ordering for Green Lake (and probably other places). Put water and islands
-    // last. I think the more proper fix is interpreting "inner" roles in relations.
+    // last. Holes from "inner" roles are handled, but islands are often mapped separately.
     map.areas.sort_by_key(|a| match a.area_type {
         AreaType::Island => 2,
         AreaType::Water => 1,
@@ -526,106 +572,166 @@ fn get_area_type(tags: &Tags) -> Option<AreaType> {
     None
 }
 
+// Returns (outer, inner) ways. Members with an empty role are treated as outer.
 fn get_multipolygon_members(
     id: RelationID,
     rel: &Relation,
     doc: &Document,
-) -> Vec<(WayID, Vec<Pt2D>)> {
-    let mut pts_per_way = Vec::new();
+) -> (Vec<(WayID, Vec<Pt2D>)>, Vec<(WayID, Vec<Pt2D>)>) {
+    let mut outer = Vec::new();
+    let mut inner = Vec::new();
     for (role, member) in &rel.members {
         if let OsmID::Way(w) = member {
-            if role == "outer" {
-                pts_per_way.push((*w, doc.ways[w].pts.clone()));
+            if role == "outer" || role == "" {
+                outer.push((*w, doc.ways[w].pts.clone()));
+            } else if role == "inner" {
+                inner.push((*w, doc.ways[w].pts.clone()));
             } else {
                 println!("{} has unhandled member role {}, ignoring it", id, role);
             }
         }
     }
-    pts_per_way
+    (outer, inner)
 }
 
-// The result could be more than one disjoint polygon.
+// The result could be more than one disjoint polygon, each possibly with holes. Outer rings
+// partly clipped away are closed off along the boundary.
 fn glue_multipolygon(
     rel_id: RelationID,
-    mut pts_per_way: Vec<(WayID, Vec<Pt2D>)>,
+    outer: Vec<(WayID, Vec<Pt2D>)>,
+    inner: Vec<(WayID, Vec<Pt2D>)>,
     boundary: &Ring,
     timer: &mut Timer,
 ) -> Vec<Polygon> {
+    let (mut outer_rings, open) = glue_rings(outer);
+    for mut pts in open {
+        if pts.len() < 2 {
+            continue;
+        }
+        match PolyLine::new(pts.clone()) {
+            Ok(pl) => {
+                if let Some(ring) = glue_to_boundary(pl, boundary) {
+                    outer_rings.push(ring);
+                    continue;
+                }
+                // Give up and just connect the ends directly.
+                pts.push(pts[0]);
+                match Ring::new(pts) {
+                    Ok(ring) => outer_rings.push(ring),
+                    Err(err) => {
+                        timer.error(format!("Really weird multipolygon {}: {}", rel_id, err));
+                    }
+                }
+            }
+            Err(err) => {
+                timer.error(format!("Really weird multipolygon {}: {}", rel_id, err));
+            }
+        }
+    }
+
+    let (inner_rings, open) = glue_rings(inner);
+    let mut polygons = polygons_with_holes(outer_rings, inner_rings);
+    // Holes partly clipped away are also closed off along the boundary. They might touch the outer
+    // ring there, so cut them out instead of adding them as holes.
+    for pts in open {
+        if pts.len() < 2 {
+            continue;
+        }
+        match PolyLine::new(pts)
+            .ok()
+            .and_then(|pl| glue_to_boundary(pl, boundary))
+        {
+            Some(ring) => {
+                let hole = ring.to_polygon();
+                polygons = polygons
+                    .into_iter()
+                    .flat_map(|p| p.difference(&hole))
+                    .collect();
+            }
+            None => {
+                timer.warn(format!(
+                    "Couldn't close an inner chunk of multipolygon {} along the boundary",
+                    rel_id
+                ));
+            }
+        }
+    }
+    polygons
+}
+
+// Glue ways end-to-end into closed rings. Also returns the chains that couldn't be closed.
+fn glue_rings(mut pts_per_way: Vec<(WayID, Vec<Pt2D>)>) -> (Vec<Ring>, Vec<Vec<Pt2D>>) {
+    let mut rings = Vec::new();
+    let mut open = Vec::new();
+
     // First deal with all of the closed loops.
-    let mut polygons: Vec<Polygon> = Vec::new();
     pts_per_way.retain(|(_, pts)| {
-        if let Ok(ring) = Ring::new(pts.clone()) {
-            polygons.push(ring.to_polygon());
+        let mut deduped = pts.clone();
+        deduped.dedup();
+        if let Ok(ring) = Ring::new(deduped) {
+            rings.push(ring);
             false
         } else {
             true
         }
     });
-    if pts_per_way.is_empty() {
-        return polygons;
-    }
 
-    // The main polygon
-    let (_, mut result) = pts_per_way.pop().unwrap();
-    let mut reversed = false;
-    while !pts_per_way.is_empty() {
-        let glue_pt = *result.last().unwrap();
-        if let Some(idx) = pts_per_way
-            .iter()
-            .position(|(_, pts)| pts[0] == glue_pt || *pts.last().unwrap() == glue_pt)
-        {
-            let (_, mut append) = pts_per_way.remove(idx);
-            if append[0] != glue_pt {
-                append.reverse();
-            }
-            result.pop();
-            result.extend(append);
-        } else {
-            if reversed {
-                // TODO Investigate what's going on here. At the very least, take what we have so
-                // far and try to glue it up.
-                println!(
-                    "Throwing away {} chunks from relation {}: {:?}",
-                    pts_per_way.len(),
-                    rel_id,
-                    pts_per_way.iter().map(|(id, _)| *id).collect::<Vec<_>>()
-                );
+    while let Some((_, mut result)) = pts_per_way.pop() {
+        let mut reversed = false;
+        while result.len() < 2 || result[0] != *result.last().unwrap() {
+            let glue_pt = *result.last().unwrap();
+            if let Some(idx) = pts_per_way
+                .iter()
+                .position(|(_, pts)| pts[0] == glue_pt || *pts.last().unwrap() == glue_pt)
+            {
+                let (_, mut append) = pts_per_way.remove(idx);
+                if append[0] != glue_pt {
+                    append.reverse();
+                }
+                result.pop();
+                result.extend(append);
+            } else if reversed {
                 break;
             } else {
+                // Try extending from the other end
                 reversed = true;
                 result.reverse();
-                // Try again!
             }
         }
-    }
 
-    result.dedup();
-    if let Ok(ring) = Ring::new(result.clone()) {
-        polygons.push(ring.to_polygon());
-        return polygons;
-    }
-    if result.len() < 2 {
-        return Vec::new();
-    }
-    match PolyLine::new(result.clone()) {
-        Ok(pl) => {
-            if let Some(poly) = glue_to_boundary(pl, boundary) {
-                polygons.push(poly);
-            } else {
-                // Give up and just connect the ends directly.
-                result.push(result[0]);
-                polygons.push(Ring::must_new(result).to_polygon());
-            }
-        }
-        Err(err) => {
-            timer.error(format!("Really weird multipolygon {}: {}", rel_id, err));
+        result.dedup();
+        match Ring::new(result.clone()) {
+            Ok(ring) => rings.push(ring),
+            Err(_) => open.push(result),
         }
     }
 
-    polygons
+    (rings, open)
+}
+
+// Put each hole in the smallest outer ring containing it. Holes outside of everything are dropped.
+fn polygons_with_holes(outer: Vec<Ring>, inner: Vec<Ring>) -> Vec<Polygon> {
+    let outer_polygons: Vec<Polygon> = outer.iter().map(|r| r.clone().to_polygon()).collect();
+    let mut holes: Vec<Vec<Ring>> = outer.iter().map(|_| Vec::new()).collect();
+    for hole in inner {
+        let pt = Pt2D::center(hole.points());
+        if let Some((idx, _)) = outer_polygons
+            .iter()
+            .enumerate()
+            .filter(|(_, p)| p.contains_pt(pt))
+            .min_by(|(_, p1), (_, p2)| p1.area().partial_cmp(&p2.area()).unwrap())
+        {
+            holes[idx].push(hole);
+        }
+    }
+    outer
+        .into_iter()
+        .zip(holes)
+        .map(|(ring, holes)| Polygon::with_holes(ring, holes))
+        .collect()
 }
 
-fn glue_to_boundary(result_pl: PolyLine, boundary: &Ring) -> Option<Polygon> {
+fn glue_to_boundary(result_pl: PolyLine, boundary: &Ring) -> Option<Ring> {
     // Some ways of the multipolygon must be clipped out. First try to trace along the boundary.
     let hits = boundary.all_intersections(&result_pl);
     if hits.len() != 2 {
@@ -644,58 +750,76 @@ fn glue_to_boundary(result_pl: PolyLine, boundary: &Ring) -> Option<Polygon> {
         trimmed_pts.pop();
         trimmed_pts.extend(boundary_glue.reversed().into_points());
     }
-    Some(Ring::must_new(trimmed_pts).to_polygon())
+    Ring::new(trimmed_pts).ok()
 }
 
+// Unlike areas, buildings partly out of bounds are removed later, so there's no need to glue
+// anything to the boundary.
 fn multipoly_geometry(
     rel_id: RelationID,
     rel: &Relation,
     doc: &Document,
 ) -> Result<Polygon, String> {
-    let mut outer: Vec<Vec<Pt2D>> = Vec::new();
-    let mut inner: Vec<Vec<Pt2D>> = Vec::new();
-    for (role, member) in &rel.members {
-        if let OsmID::Way(w) = member {
-            let mut deduped = doc.ways[w].pts.clone();
-            deduped.dedup();
-            if deduped.len() < 3 {
-                continue;
-            }
-
-            if role == "outer" {
-                outer.push(deduped);
-            } else if role == "inner" {
-                inner.push(deduped);
-            } else {
-                return Err(format!("What's role {} for multipolygon {}?", role, rel_id));
-            }
-        }
-    }
-    // TODO Handle multiple outers with holes
-    if outer.len() == 0 || outer.len() > 1 && !inner.is_empty() {
+    let (outer, inner) = get_multipolygon_members(rel_id, rel, doc);
+    let (outer_rings, open) = glue_rings(outer);
+    if !open.is_empty() {
         return Err(format!(
-            "Multipolygon {} has {} outer, {} inner. Huh?",
+            "Multipolygon {} has {} outer pieces that don't form a ring",
             rel_id,
-            outer.len(),
-            inner.len()
+            open.len()
         ));
     }
-    if inner.is_empty() {
-        if outer.len() > 1 {
-            Ok(Polygon::union_all(
-                outer.into_iter().map(Polygon::buggy_new).collect(),
-            ))
-        } else {
-            Ok(Polygon::buggy_new(outer.remove(0)))
-        }
-    } else {
-        let mut inner_rings = Vec::new();
-        for pts in inner {
-            inner_rings.push(Ring::new(pts)?);
-        }
-        Ok(Polygon::with_holes(
-            Ring::new(outer.pop().unwrap())?,
-            inner_rings,
-        ))
+    let (inner_rings, _) = glue_rings(inner);
+    let mut polygons = polygons_with_holes(outer_rings, inner_rings);
+    match polygons.len() {
+        0 => Err(format!("Multipolygon {} has no outer ring", rel_id)),
+        1 => Ok(polygons.pop().unwrap()),
+        _ => Ok(Polygon::union_all(polygons)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn square(x: f64, y: f64, size: f64) -> Vec<Pt2D> {
+        vec![
+            Pt2D::new(x, y),
+            Pt2D::new(x + size, y),
+            Pt2D::new(x + size, y + size),
+            Pt2D::new(x, y + size),
+            Pt2D::new(x, y),
+        ]
+    }
+
+    #[test]
+    fn test_multipolygon_with_holes() {
+        // The first outer ring is split into two ways, one of them pointing the other way
+        let big = square(0.0, 0.0, 100.0);
+        let mut second_half = big[2..].to_vec();
+        second_half.reverse();
+        let outer = vec![
+            (WayID(1), big[0..3].to_vec()),
+            (WayID(2), square(200.0, 0.0, 50.0)),
+            (WayID(3), second_half),
+        ];
+        let inner = vec![
+            (WayID(4), square(10.0, 10.0, 20.0)),
+            (WayID(5), square(210.0, 10.0, 10.0)),
+        ];
+
+        let (outer_rings, open) = glue_rings(outer);
+        assert_eq!(outer_rings.len(), 2);
+        assert!(open.is_empty());
+        let (inner_rings, _) = glue_rings(inner);
+        let polygons = polygons_with_holes(outer_rings, inner_rings);
+        assert_eq!(polygons.len(), 2);
+
+        let covered = |x, y| polygons.iter().any(|p| p.contains_pt(Pt2D::new(x, y)));
+        assert!(covered(50.0, 50.0));
+        assert!(covered(240.0, 40.0));
+        // In the holes
+        assert!(!covered(20.0, 20.0));
+        assert!(!covered(215.0, 15.0));
     }
 }
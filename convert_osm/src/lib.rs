@@ -1,15 +1,20 @@
 mod clip;
+mod elevation;
 mod extract;
+mod osm_change;
 mod parking;
+mod pbf;
 mod reader;
+mod sidewalks;
 mod split_ways;
-mod srtm;
 mod transit;
 
+pub use crate::osm_change::edits_to_osmchange;
 use abstutil::Timer;
 use geom::{Distance, FindClosest, GPSBounds, LonLat, Pt2D, Ring};
 use map_model::raw::RawMap;
 use map_model::{osm, MapConfig, NamePerLanguage};
+use serde::{Deserialize, Serialize};
 
 pub struct Options {
     pub osm_input: String,
@@ -23,8 +28,7 @@ pub struct Options {
     pub onstreet_parking: OnstreetParking,
     pub public_offstreet_parking: PublicOffstreetParking,
     pub private_offstreet_parking: PrivateOffstreetParking,
-    // If provided, pull elevation data from this SRTM file. The SRTM parser is incorrect, so the
-    // results will be nonsense.
+    // If provided, pull elevation data from this SRTM .hgt or GeoTIFF file, or a directory of them.
     pub elevation: Option<String>,
     // OSM railway=rail will be included as light rail if so. Cosmetic only.
     pub include_railroads: bool,
@@ -32,6 +36,7 @@ pub struct Options {
 
 // What roads will have on-street parking lanes? Data from
 // https://wiki.openstreetmap.org/wiki/Key:parking:lane is always used if available.
+#[derive(Clone, Serialize, Deserialize)]
 pub enum OnstreetParking {
     // If not tagged, there won't be parking.
     JustOSM,
@@ -47,6 +52,7 @@ pub enum OnstreetParking {
 }
 
 // How many spots are available in public parking garages?
+#[derive(Clone, Serialize, Deserialize)]
 pub enum PublicOffstreetParking {
     None,
     // Pull data from
@@ -57,6 +63,7 @@ pub enum PublicOffstreetParking {
 
 // If a building doesn't have anything from public_offstreet_parking and isn't tagged as a garage
 // in OSM, how many private spots should it have?
+#[derive(Clone, Serialize, Deserialize)]
 pub enum PrivateOffstreetParking {
     FixedPerBldg(usize),
     // TODO Based on the number of residents?
@@ -71,7 +78,8 @@ pub fn convert(opts: Options, timer: &mut abstutil::Timer) -> RawMap {
         map.gps_bounds = gps_bounds;
     }
 
-    let extract = extract::extract_osm(&mut map, &opts, timer);
+    let mut extract = extract::extract_osm(&mut map, &opts, timer);
+    let sidewalks = std::mem::replace(&mut extract.sidewalks, Vec::new());
     let (amenities, pt_to_road) = split_ways::split_up_roads(&mut map, extract, timer);
     clip::clip_map(&mut map, timer);
 
@@ -79,6 +87,8 @@ pub fn convert(opts: Options, timer: &mut abstutil::Timer) -> RawMap {
     // doing the parking hint matching.
     abstutil::retain_btreemap(&mut map.roads, |r, _| r.i1 != r.i2);
 
+    sidewalks::snap_separate_sidewalks(&mut map, sidewalks, opts.map_config.driving_side, timer);
+
     let all_routes = map.bus_routes.drain(..).collect::<Vec<_>>();
     let mut routes = Vec::new();
     for route in all_routes {
@@ -130,14 +140,29 @@ fn use_amenities(
 
 fn use_elevation(map: &mut RawMap, path: &str, timer: &mut Timer) {
     timer.start("apply elevation data to intersections");
-    let elevation = srtm::Elevation::load(path).unwrap();
+    let elevation = elevation::Elevation::load(path).unwrap();
+    let mut missing = 0;
     for i in map.intersections.values_mut() {
         // TODO Not sure why, but I've seen nodes from South Carolina wind up in the updated
         // Seattle extract. And I think there's a bug with clipping, because they survive to this
         // point. O_O
         if map.boundary_polygon.contains_pt(i.point) {
-            i.elevation = elevation.get(i.point.to_gps(&map.gps_bounds));
+            match elevation.get(i.point.to_gps(&map.gps_bounds)) {
+                Some(dist) => {
+                    i.elevation = dist;
+                }
+                None => {
+                    missing += 1;
+                }
+            }
         }
     }
+    if missing > 0 {
+        timer.warn(format!(
+            "{} intersections aren't covered by elevation data from {}",
+            abstutil::prettyprint_usize(missing),
+            path
+        ));
+    }
     timer.stop("apply elevation data to intersections");
 }
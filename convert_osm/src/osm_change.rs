@@ -0,0 +1,490 @@
+use abstutil::{slurp_file, Tags, Timer};
+use map_model::osm::{NodeID, WayID};
+use map_model::raw::OriginalRoad;
+use map_model::Map;
+use osmpbf::{Element, ElementReader};
+use std::collections::{BTreeMap, BTreeSet};
+use std::error::Error;
+
+// Express the map's current edits as an osmChange document
+// (https://wiki.openstreetmap.org/wiki/OsmChange), relative to the .osm or .osm.pbf file the map
+// was imported from. When only part of a way is edited, the way is split, and the new pieces get
+// negative IDs. Relations that the split way belongs to (like bus routes and turn restrictions)
+// aren't updated to reference the new pieces; check those by hand before uploading.
+pub fn edits_to_osmchange(
+    map: &Map,
+    osm_input: &str,
+    timer: &mut Timer,
+) -> Result<String, Box<dyn Error>> {
+    // First find the objects affected, so only those need to be read from the input
+    let affected = map.get_edits().to_osm_tag_changes(map, &BTreeMap::new());
+    let want_nodes: BTreeSet<NodeID> = affected.nodes.keys().cloned().collect();
+    let want_ways: BTreeSet<WayID> = affected.roads.keys().map(|r| r.osm_way_id).collect();
+
+    timer.start(format!("read {}", osm_input));
+    let input = if osm_input.ends_with(".pbf") {
+        read_pbf(osm_input, &want_nodes, &want_ways)?
+    } else {
+        read_xml(osm_input, &want_nodes, &want_ways)?
+    };
+    timer.stop(format!("read {}", osm_input));
+
+    // The edits are relative to the original tags, not whatever importing did to them
+    let original_tags: BTreeMap<WayID, Tags> = input
+        .ways
+        .iter()
+        .map(|(id, way)| (*id, Tags::new(way.tags.clone())))
+        .collect();
+    let changes = map.get_edits().to_osm_tag_changes(map, &original_tags);
+    for problem in &changes.unsupported {
+        timer.warn(format!("Can't express in OSM: {}", problem));
+    }
+
+    let mut way_changes: BTreeMap<WayID, Vec<(OriginalRoad, &BTreeMap<String, Option<String>>)>> =
+        BTreeMap::new();
+    for (id, tags) in &changes.roads {
+        way_changes
+            .entry(id.osm_way_id)
+            .or_insert_with(Vec::new)
+            .push((*id, tags));
+    }
+
+    let mut modify = Vec::new();
+    let mut create = Vec::new();
+    let mut next_id = -1;
+    for (id, edits) in &changes.nodes {
+        let node = match input.nodes.get(id) {
+            Some(n) => n,
+            None => {
+                timer.warn(format!("{} isn't in {}, skipping its edits", id, osm_input));
+                continue;
+            }
+        };
+        let tags = apply_changes(&node.tags, edits);
+        if tags != node.tags {
+            modify.push(node_xml(*id, node, &tags)?);
+        }
+    }
+    for (id, pieces) in &way_changes {
+        let way = match input.ways.get(id) {
+            Some(w) => w,
+            None => {
+                timer.warn(format!("{} isn't in {}, skipping its edits", id, osm_input));
+                continue;
+            }
+        };
+        let version = way.version.as_ref().ok_or_else(|| {
+            format!(
+                "{} has no version; was {} downloaded from the OSM API?",
+                id, osm_input
+            )
+        })?;
+
+        let groups = split_way(&way.nodes, &way.tags, pieces, timer);
+        if groups.len() == 1 && groups[0].1 == way.tags {
+            continue;
+        }
+        for (idx, (nodes, tags)) in groups.into_iter().enumerate() {
+            if idx == 0 {
+                modify.push(way_xml(id.0, Some(version.as_str()), &nodes, &tags));
+            } else {
+                create.push(way_xml(next_id, None, &nodes, &tags));
+                next_id -= 1;
+            }
+        }
+    }
+    timer.note(format!(
+        "Modified {} objects, created {} ways",
+        modify.len(),
+        create.len()
+    ));
+
+    let mut out = String::new();
+    out.push_str("<?xml version='1.0' encoding='UTF-8'?>\n");
+    out.push_str("<osmChange version=\"0.6\" generator=\"abstreet\">\n");
+    if !modify.is_empty() {
+        out.push_str("  <modify>\n");
+        for x in modify {
+            out.push_str(&x);
+        }
+        out.push_str("  </modify>\n");
+    }
+    if !create.is_empty() {
+        out.push_str("  <create>\n");
+        for x in create {
+            out.push_str(&x);
+        }
+        out.push_str("  </create>\n");
+    }
+    out.push_str("</osmChange>\n");
+    Ok(out)
+}
+
+// Unlike the readers used for importing, keep every tag and the versions, since all of it gets
+// uploaded again.
+struct OsmInput {
+    nodes: BTreeMap<NodeID, OsmNode>,
+    ways: BTreeMap<WayID, OsmWay>,
+}
+
+struct OsmNode {
+    version: Option<String>,
+    lat: String,
+    lon: String,
+    tags: BTreeMap<String, String>,
+}
+
+struct OsmWay {
+    version: Option<String>,
+    nodes: Vec<NodeID>,
+    tags: BTreeMap<String, String>,
+}
+
+fn read_xml(
+    path: &str,
+    want_nodes: &BTreeSet<NodeID>,
+    want_ways: &BTreeSet<WayID>,
+) -> Result<OsmInput, Box<dyn Error>> {
+    let bytes = slurp_file(path)?;
+    let raw_string = std::str::from_utf8(&bytes)?;
+    let tree = roxmltree::Document::parse(raw_string)?;
+
+    let mut input = OsmInput {
+        nodes: BTreeMap::new(),
+        ways: BTreeMap::new(),
+    };
+    for obj in tree.descendants() {
+        if !obj.is_element() {
+            continue;
+        }
+        match obj.tag_name().name() {
+            "node" => {
+                let id = NodeID(obj.attribute("id").unwrap().parse::<i64>()?);
+                if want_nodes.contains(&id) {
+                    input.nodes.insert(
+                        id,
+                        OsmNode {
+                            version: obj.attribute("version").map(|x| x.to_string()),
+                            lat: obj.attribute("lat").unwrap().to_string(),
+                            lon: obj.attribute("lon").unwrap().to_string(),
+                            tags: read_all_tags(obj),
+                        },
+                    );
+                }
+            }
+            "way" => {
+                let id = WayID(obj.attribute("id").unwrap().parse::<i64>()?);
+                if want_ways.contains(&id) {
+                    input.ways.insert(
+                        id,
+                        OsmWay {
+                            version: obj.attribute("version").map(|x| x.to_string()),
+                            nodes: obj
+                                .children()
+                                .filter(|c| c.tag_name().name() == "nd")
+                                .map(|c| {
+                                    NodeID(c.attribute("ref").unwrap().parse::<i64>().unwrap())
+                                })
+                                .collect(),
+                            tags: read_all_tags(obj),
+                        },
+                    );
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(input)
+}
+
+fn read_pbf(
+    path: &str,
+    want_nodes: &BTreeSet<NodeID>,
+    want_ways: &BTreeSet<WayID>,
+) -> Result<OsmInput, Box<dyn Error>> {
+    let mut input = OsmInput {
+        nodes: BTreeMap::new(),
+        ways: BTreeMap::new(),
+    };
+    ElementReader::from_path(path)?.for_each(|element| match element {
+        Element::Node(n) => {
+            let id = NodeID(n.id());
+            if want_nodes.contains(&id) {
+                input.nodes.insert(
+                    id,
+                    OsmNode {
+                        version: n.info().version().map(|x| x.to_string()),
+                        lat: n.lat().to_string(),
+                        lon: n.lon().to_string(),
+                        tags: collect_tags(n.tags()),
+                    },
+                );
+            }
+        }
+        Element::DenseNode(n) => {
+            let id = NodeID(n.id);
+            if want_nodes.contains(&id) {
+                input.nodes.insert(
+                    id,
+                    OsmNode {
+                        version: n.info().map(|i| i.version().to_string()),
+                        lat: n.lat().to_string(),
+                        lon: n.lon().to_string(),
+                        tags: collect_tags(n.tags()),
+                    },
+                );
+            }
+        }
+        Element::Way(w) => {
+            let id = WayID(w.id());
+            if want_ways.contains(&id) {
+                input.ways.insert(
+                    id,
+                    OsmWay {
+                        version: w.info().version().map(|x| x.to_string()),
+                        nodes: w.refs().map(NodeID).collect(),
+                        tags: collect_tags(w.tags()),
+                    },
+                );
+            }
+        }
+        Element::Relation(_) => {}
+    })?;
+    Ok(input)
+}
+
+fn collect_tags<'a, I: Iterator<Item = (&'a str, &'a str)>>(iter: I) -> BTreeMap<String, String> {
+    iter.map(|(k, v)| (k.to_string(), v.to_string())).collect()
+}
+
+// Each segment of the way gets the tags of the edited piece covering it, then consecutive segments
+// with the same tags are grouped. Returns the nodes and tags of each group, in order.
+fn split_way(
+    nodes: &[NodeID],
+    orig_tags: &BTreeMap<String, String>,
+    pieces: &[(OriginalRoad, &BTreeMap<String, Option<String>>)],
+    timer: &mut Timer,
+) -> Vec<(Vec<NodeID>, BTreeMap<String, String>)> {
+    let mut segment_tags: Vec<BTreeMap<String, String>> =
+        vec![orig_tags.clone(); nodes.len().saturating_sub(1)];
+    for (id, edits) in pieces {
+        // Closed ways may repeat the first node, so search for i2 after i1
+        let start = nodes.iter().position(|n| *n == id.i1);
+        let end = start.and_then(|start| {
+            nodes[start..]
+                .iter()
+                .position(|n| *n == id.i2)
+                .map(|idx| start + idx)
+        });
+        match (start, end) {
+            (Some(start), Some(end)) if start < end => {
+                let tags = apply_changes(orig_tags, edits);
+                for segment in &mut segment_tags[start..end] {
+                    *segment = tags.clone();
+                }
+            }
+            _ => {
+                timer.warn(format!(
+                    "Couldn't find {} in the current OSM way, skipping its edits",
+                    id
+                ));
+            }
+        }
+    }
+
+    let mut groups: Vec<(Vec<NodeID>, BTreeMap<String, String>)> = Vec::new();
+    for (idx, tags) in segment_tags.into_iter().enumerate() {
+        if let Some((pts, last_tags)) = groups.last_mut() {
+            if *last_tags == tags {
+                pts.push(nodes[idx + 1]);
+                continue;
+            }
+        }
+        groups.push((vec![nodes[idx], nodes[idx + 1]], tags));
+    }
+    if groups.is_empty() {
+        groups.push((nodes.to_vec(), orig_tags.clone()));
+    }
+    groups
+}
+
+fn read_all_tags(obj: roxmltree::Node) -> BTreeMap<String, String> {
+    let mut tags = BTreeMap::new();
+    for child in obj.children() {
+        if child.tag_name().name() == "tag" {
+            tags.insert(
+                child.attribute("k").unwrap().to_string(),
+                child.attribute("v").unwrap().to_string(),
+            );
+        }
+    }
+    tags
+}
+
+fn apply_changes(
+    orig: &BTreeMap<String, String>,
+    edits: &BTreeMap<String, Option<String>>,
+) -> BTreeMap<String, String> {
+    let mut tags = orig.clone();
+    for (k, v) in edits {
+        // Internal tags used during import shouldn't leak out
+        if k.starts_with("abst:") {
+            continue;
+        }
+        match v {
+            Some(v) => {
+                tags.insert(k.clone(), v.clone());
+            }
+            None => {
+                tags.remove(k);
+            }
+        }
+    }
+    tags
+}
+
+fn node_xml(id: NodeID, node: &OsmNode, tags: &BTreeMap<String, String>) -> Result<String, String> {
+    let mut out = format!(
+        "    <node id=\"{}\" version=\"{}\" lat=\"{}\" lon=\"{}\"",
+        id.0,
+        node.version
+            .as_ref()
+            .ok_or_else(|| format!("{} has no version", id))?,
+        node.lat,
+        node.lon
+    );
+    if tags.is_empty() {
+        out.push_str("/>\n");
+        return Ok(out);
+    }
+    out.push_str(">\n");
+    tags_xml(&mut out, tags);
+    out.push_str("    </node>\n");
+    Ok(out)
+}
+
+fn way_xml(
+    id: i64,
+    version: Option<&str>,
+    nodes: &[NodeID],
+    tags: &BTreeMap<String, String>,
+) -> String {
+    let mut out = format!("    <way id=\"{}\"", id);
+    if let Some(v) = version {
+        out.push_str(&format!(" version=\"{}\"", v));
+    }
+    out.push_str(">\n");
+    for n in nodes {
+        out.push_str(&format!("      <nd ref=\"{}\"/>\n", n.0));
+    }
+    tags_xml(&mut out, tags);
+    out.push_str("    </way>\n");
+    out
+}
+
+fn tags_xml(out: &mut String, tags: &BTreeMap<String, String>) {
+    for (k, v) in tags {
+        out.push_str(&format!(
+            "      <tag k=\"{}\" v=\"{}\"/>\n",
+            escape(k),
+            escape(v)
+        ));
+    }
+}
+
+fn escape(x: &str) -> String {
+    x.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tags(kv: Vec<(&str, &str)>) -> BTreeMap<String, String> {
+        kv.into_iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    fn piece(i1: i64, i2: i64) -> OriginalRoad {
+        OriginalRoad {
+            osm_way_id: WayID(100),
+            i1: NodeID(i1),
+            i2: NodeID(i2),
+        }
+    }
+
+    fn nodes(ids: Vec<i64>) -> Vec<NodeID> {
+        ids.into_iter().map(NodeID).collect()
+    }
+
+    #[test]
+    fn test_split_partial_way() {
+        let orig = tags(vec![("highway", "primary"), ("lanes", "2")]);
+        let mut edits = BTreeMap::new();
+        edits.insert("lanes".to_string(), Some("3".to_string()));
+        edits.insert("abst:endpt_fwd".to_string(), Some("true".to_string()));
+        let edited = tags(vec![("highway", "primary"), ("lanes", "3")]);
+
+        // Only the middle of the way changed
+        let groups = split_way(
+            &nodes(vec![1, 2, 3, 4, 5]),
+            &orig,
+            &[(piece(2, 4), &edits)],
+            &mut Timer::throwaway(),
+        );
+        assert_eq!(
+            groups,
+            vec![
+                (nodes(vec![1, 2]), orig.clone()),
+                (nodes(vec![2, 3, 4]), edited.clone()),
+                (nodes(vec![4, 5]), orig.clone()),
+            ]
+        );
+
+        // The whole way changed, so it isn't split
+        let groups = split_way(
+            &nodes(vec![1, 2, 3]),
+            &orig,
+            &[(piece(1, 3), &edits)],
+            &mut Timer::throwaway(),
+        );
+        assert_eq!(groups, vec![(nodes(vec![1, 2, 3]), edited)]);
+
+        // A piece that's not in the current way is skipped
+        let groups = split_way(
+            &nodes(vec![1, 2, 3]),
+            &orig,
+            &[(piece(3, 1), &edits)],
+            &mut Timer::throwaway(),
+        );
+        assert_eq!(groups, vec![(nodes(vec![1, 2, 3]), orig)]);
+    }
+
+    #[test]
+    fn test_split_closed_way() {
+        let orig = tags(vec![("highway", "residential")]);
+        let mut edits = BTreeMap::new();
+        edits.insert("highway".to_string(), None);
+        edits.insert("oneway".to_string(), Some("yes".to_string()));
+
+        // The first node is repeated at the end
+        let groups = split_way(
+            &nodes(vec![1, 2, 3, 1]),
+            &orig,
+            &[(piece(3, 1), &edits)],
+            &mut Timer::throwaway(),
+        );
+        assert_eq!(
+            groups,
+            vec![
+                (nodes(vec![1, 2, 3]), orig),
+                (nodes(vec![3, 1]), tags(vec![("oneway", "yes")])),
+            ]
+        );
+    }
+}
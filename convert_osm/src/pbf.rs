@@ -0,0 +1,232 @@
+use crate::reader::{is_useless_tag, Document, Node, Relation, Way};
+use abstutil::{prettyprint_usize, Tags, Timer};
+use geom::{GPSBounds, LonLat, Pt2D};
+use map_model::osm::{NodeID, OsmID, RelationID, WayID};
+use osmpbf::{Element, ElementReader, RelMemberType};
+use std::collections::{BTreeMap, BTreeSet};
+use std::error::Error;
+
+// Streams through a .osm.pbf file, producing the same thing as the XML reader. When bounds are
+// provided, only nodes inside them and ways touching them are kept, so huge extracts (a whole
+// state or country) can be used directly. Like osmconvert's --complete-ways, ways crossing the
+// boundary keep all of their nodes, so clip.rs can trim them properly later. This takes a second
+// pass through the file. Relations are trimmed to the members kept, and dropped if none are left.
+pub fn read(
+    path: &str,
+    input_gps_bounds: &GPSBounds,
+    timer: &mut Timer,
+) -> Result<Document, Box<dyn Error>> {
+    let clip = *input_gps_bounds != GPSBounds::new();
+    if !clip {
+        timer.warn(format!(
+            "No clipping polygon provided, so keeping everything in {}",
+            path
+        ));
+    }
+
+    let mut nodes: BTreeMap<NodeID, (LonLat, Tags)> = BTreeMap::new();
+    let mut ways: BTreeMap<WayID, (Vec<NodeID>, Tags)> = BTreeMap::new();
+    let mut relations: BTreeMap<RelationID, (Tags, Vec<(String, OsmID)>)> = BTreeMap::new();
+    let mut duplicates: Vec<OsmID> = Vec::new();
+
+    timer.start(format!("read {}", path));
+    ElementReader::from_path(path)?.for_each(|element| {
+        let (id, pt, tags) = match element {
+            Element::Node(n) => (
+                NodeID(n.id()),
+                LonLat::new(n.lon(), n.lat()),
+                read_tags(n.tags()),
+            ),
+            Element::DenseNode(n) => (
+                NodeID(n.id),
+                LonLat::new(n.lon(), n.lat()),
+                read_tags(n.tags()),
+            ),
+            Element::Way(w) => {
+                let id = WayID(w.id());
+                let refs: Vec<NodeID> = w.refs().map(NodeID).collect();
+                if clip && !refs.iter().any(|n| nodes.contains_key(n)) {
+                    return;
+                }
+                if ways.insert(id, (refs, read_tags(w.tags()))).is_some() {
+                    duplicates.push(OsmID::Way(id));
+                }
+                return;
+            }
+            Element::Relation(r) => {
+                let id = RelationID(r.id());
+                let mut members = Vec::new();
+                for member in r.members() {
+                    let role = member.role().unwrap_or("").to_string();
+                    let member_id = match member.member_type {
+                        RelMemberType::Node => OsmID::Node(NodeID(member.member_id)),
+                        RelMemberType::Way => OsmID::Way(WayID(member.member_id)),
+                        RelMemberType::Relation => OsmID::Relation(RelationID(member.member_id)),
+                    };
+                    // A .pbf lists all nodes, then ways, then relations, so only other relations
+                    // haven't been seen yet.
+                    let keep = !clip
+                        || match member_id {
+                            OsmID::Node(n) => nodes.contains_key(&n),
+                            OsmID::Way(w) => ways.contains_key(&w),
+                            OsmID::Relation(_) => true,
+                        };
+                    if keep {
+                        members.push((role, member_id));
+                    }
+                }
+                if members.is_empty() {
+                    return;
+                }
+                if relations
+                    .insert(id, (read_tags(r.tags()), members))
+                    .is_some()
+                {
+                    duplicates.push(OsmID::Relation(id));
+                }
+                return;
+            }
+        };
+        if clip && !input_gps_bounds.contains(pt) {
+            return;
+        }
+        if nodes.insert(id, (pt, tags)).is_some() {
+            duplicates.push(OsmID::Node(id));
+        }
+    })?;
+    timer.stop(format!("read {}", path));
+    if !duplicates.is_empty() {
+        return Err(format!(
+            "Duplicate {} (and {} others), your .pbf is corrupt",
+            duplicates[0],
+            duplicates.len() - 1
+        )
+        .into());
+    }
+
+    // Ways that cross the boundary need their outside nodes too
+    let mut missing: BTreeSet<NodeID> = BTreeSet::new();
+    for (refs, _) in ways.values() {
+        for n in refs {
+            if !nodes.contains_key(n) {
+                missing.insert(*n);
+            }
+        }
+    }
+    if clip && !missing.is_empty() {
+        timer.start(format!(
+            "read {} again for {} nodes outside the boundary",
+            path,
+            prettyprint_usize(missing.len())
+        ));
+        ElementReader::from_path(path)?.for_each(|element| {
+            let (id, pt, tags) = match element {
+                Element::Node(n) => (
+                    NodeID(n.id()),
+                    LonLat::new(n.lon(), n.lat()),
+                    read_tags(n.tags()),
+                ),
+                Element::DenseNode(n) => (
+                    NodeID(n.id),
+                    LonLat::new(n.lon(), n.lat()),
+                    read_tags(n.tags()),
+                ),
+                _ => {
+                    return;
+                }
+            };
+            if missing.contains(&id) {
+                nodes.insert(id, (pt, tags));
+            }
+        })?;
+        timer.stop(format!(
+            "read {} again for {} nodes outside the boundary",
+            path,
+            prettyprint_usize(missing.len())
+        ));
+    }
+
+    let gps_bounds = if clip {
+        input_gps_bounds.clone()
+    } else {
+        let mut b = GPSBounds::new();
+        for (pt, _) in nodes.values() {
+            b.update(*pt);
+        }
+        b
+    };
+
+    let mut doc = Document {
+        gps_bounds,
+        nodes: BTreeMap::new(),
+        ways: BTreeMap::new(),
+        relations: BTreeMap::new(),
+    };
+    for (id, (pt, tags)) in nodes {
+        doc.nodes.insert(
+            id,
+            Node {
+                pt: Pt2D::from_gps(pt, &doc.gps_bounds),
+                tags,
+            },
+        );
+    }
+    for (id, (refs, tags)) in ways {
+        let mut nodes = Vec::new();
+        let mut pts = Vec::new();
+        for n in refs {
+            // Just skip missing nodes
+            if let Some(node) = doc.nodes.get(&n) {
+                nodes.push(n);
+                pts.push(node.pt);
+            }
+        }
+        if !nodes.is_empty() {
+            doc.ways.insert(id, Way { nodes, pts, tags });
+        }
+    }
+    // Relations can refer to each other in any order, so finish filtering members after reading
+    // everything. Dropping an empty relation can empty out another one referring to it.
+    loop {
+        let relation_ids: BTreeSet<RelationID> = relations.keys().cloned().collect();
+        for (_, members) in relations.values_mut() {
+            members.retain(|(_, member)| match member {
+                OsmID::Node(n) => doc.nodes.contains_key(n),
+                OsmID::Way(w) => doc.ways.contains_key(w),
+                OsmID::Relation(r) => relation_ids.contains(r),
+            });
+        }
+        let empty: Vec<RelationID> = relations
+            .iter()
+            .filter(|(_, (_, members))| members.is_empty())
+            .map(|(id, _)| *id)
+            .collect();
+        if empty.is_empty() {
+            break;
+        }
+        for id in empty {
+            relations.remove(&id);
+        }
+    }
+    for (id, (tags, members)) in relations {
+        doc.relations.insert(id, Relation { tags, members });
+    }
+
+    timer.note(format!(
+        "Found {} nodes, {} ways, {} relations",
+        prettyprint_usize(doc.nodes.len()),
+        prettyprint_usize(doc.ways.len()),
+        prettyprint_usize(doc.relations.len())
+    ));
+    Ok(doc)
+}
+
+fn read_tags<'a, I: Iterator<Item = (&'a str, &'a str)>>(iter: I) -> Tags {
+    let mut tags = Tags::new(BTreeMap::new());
+    for (k, v) in iter {
+        if !is_useless_tag(k) {
+            tags.insert(k, v);
+        }
+    }
+    tags
+}
@@ -4,7 +4,8 @@ use map_model::osm::{NodeID, OsmID, RelationID, WayID};
 use std::collections::BTreeMap;
 use std::error::Error;
 
-// References to missing objects are just filtered out.
+// Reads .osm XML, or .osm.pbf through the pbf module. References to missing objects are just
+// filtered out.
 // Per https://wiki.openstreetmap.org/wiki/OSM_XML#Certainties_and_Uncertainties, we assume
 // elements come in order: nodes, ways, then relations.
 //
@@ -43,6 +44,10 @@ pub fn read(
     input_gps_bounds: &GPSBounds,
     timer: &mut Timer,
 ) -> Result<Document, Box<dyn Error>> {
+    if path.ends_with(".pbf") {
+        return crate::pbf::read(path, input_gps_bounds, timer);
+    }
+
     timer.start(format!("read {}", path));
     let bytes = slurp_file(path)?;
     let raw_string = std::str::from_utf8(&bytes)?;
@@ -184,8 +189,7 @@ fn read_tags(obj: roxmltree::Node) -> Tags {
     for child in obj.children() {
         if child.tag_name().name() == "tag" {
             let key = child.attribute("k").unwrap();
-            // Filter out really useless data
-            if key.starts_with("tiger:") || key.starts_with("old_name:") {
+            if is_useless_tag(key) {
                 continue;
             }
             tags.insert(key, child.attribute("v").unwrap());
@@ -194,6 +198,11 @@ fn read_tags(obj: roxmltree::Node) -> Tags {
     tags
 }
 
+// Filter out really useless data
+pub fn is_useless_tag(key: &str) -> bool {
+    key.starts_with("tiger:") || key.starts_with("old_name:")
+}
+
 fn scrape_bounds(doc: &roxmltree::Document) -> GPSBounds {
     let mut b = GPSBounds::new();
     for obj in doc.descendants() {
@@ -0,0 +1,244 @@
+use abstutil::{prettyprint_usize, Timer};
+use geom::{Distance, FindClosest, HashablePt2D, PolyLine, Pt2D};
+use map_model::osm;
+use map_model::osm::WayID;
+use map_model::raw::{DrivingSide, RawMap, RawRoad};
+use std::collections::HashMap;
+
+// How far past the edge of the road to look for a sidewalk
+const SEARCH_DIST: Distance = Distance::const_meters(6.0);
+// Don't look near intersections, where sidewalks of the cross streets are
+const END_BUFFER: Distance = Distance::const_meters(10.0);
+const SAMPLE_STEP: Distance = Distance::const_meters(5.0);
+// How much of the road's length needs a sidewalk nearby
+const MIN_PCT_MATCHED: f64 = 0.5;
+// Crossing nodes this close to an end of a road count as a crosswalk there
+const CROSSING_DIST: Distance = Distance::const_meters(20.0);
+
+// Many places map sidewalks as separate highway=footway + footway=sidewalk ways, instead of
+// tagging the road. Find which sides of each road have one of these running alongside, and tag the
+// road so it gets sidewalk lanes on exactly those sides. Only roads tagged sidewalk=separate or
+// with inferred sidewalks are changed.
+pub fn snap_separate_sidewalks(
+    map: &mut RawMap,
+    sidewalks: Vec<(WayID, Vec<Pt2D>)>,
+    driving_side: DrivingSide,
+    timer: &mut Timer,
+) {
+    if sidewalks.is_empty() {
+        return;
+    }
+    let mut closest: FindClosest<WayID> = FindClosest::new(&map.gps_bounds.to_bounds());
+    for (id, pts) in &sidewalks {
+        closest.add(*id, pts);
+    }
+
+    let mut snapped = 0;
+    timer.start_iter("snap separate sidewalks to roads", map.roads.len());
+    for (id, r) in map.roads.iter_mut() {
+        timer.next();
+        let separate = r.osm_tags.is(osm::SIDEWALK, "separate");
+        if !separate && !r.osm_tags.contains_key(osm::INFERRED_SIDEWALKS) {
+            continue;
+        }
+        let center = match PolyLine::new(r.center_points.clone()) {
+            Ok(pl) => pl,
+            Err(_) => continue,
+        };
+        let half_width = r.get_geometry(*id, driving_side).1 / 2.0;
+        let right = has_sidewalk(&closest, center.shift_right(half_width + SEARCH_DIST / 2.0));
+        let left = has_sidewalk(&closest, center.shift_left(half_width + SEARCH_DIST / 2.0));
+        if !right && !left {
+            // Inferred sidewalks stay as a guess. Separate sidewalks might've been clipped away,
+            // so keep assuming both sides.
+            continue;
+        }
+
+        // lane_specs mirrors everything when driving on the left, so flip to match
+        let (right, left) = match driving_side {
+            DrivingSide::Right => (right, left),
+            DrivingSide::Left => (left, right),
+        };
+        let value = match (right, left) {
+            (true, true) => "both",
+            (true, false) => "right",
+            (false, true) => "left",
+            (false, false) => unreachable!(),
+        };
+        r.osm_tags.insert(osm::SIDEWALK, value);
+        r.osm_tags.remove(osm::INFERRED_SIDEWALKS);
+        snapped += 1;
+    }
+    timer.note(format!(
+        "Snapped {} separate sidewalks onto {} roads",
+        prettyprint_usize(sidewalks.len()),
+        prettyprint_usize(snapped)
+    ));
+}
+
+// Samples along one edge of a road, checking how much of it has a sidewalk close by.
+fn has_sidewalk(closest: &FindClosest<WayID>, edge: Result<PolyLine, String>) -> bool {
+    let edge = match edge {
+        Ok(pl) => pl,
+        Err(_) => {
+            return false;
+        }
+    };
+    let mut samples = Vec::new();
+    let mut dist = END_BUFFER;
+    while dist < edge.length() - END_BUFFER {
+        samples.push(dist);
+        dist += SAMPLE_STEP;
+    }
+    if samples.is_empty() {
+        samples.push(edge.length() / 2.0);
+    }
+
+    let matched = samples
+        .iter()
+        .filter(|dist| {
+            edge.dist_along(**dist)
+                .ok()
+                .and_then(|(pt, _)| closest.closest_pt(pt, SEARCH_DIST))
+                .is_some()
+        })
+        .count();
+    (matched as f64) / (samples.len() as f64) >= MIN_PCT_MATCHED
+}
+
+// Called while splitting a way into roads, with all of the original points of one road. Records
+// crossing nodes near either end, so make::walking_turns knows where crosswalks belong. Crossings
+// in the middle of a road aren't handled yet.
+pub fn tag_crossings(r: &mut RawRoad, pts: &[Pt2D], crossings: &HashMap<HashablePt2D, String>) {
+    r.osm_tags.remove(osm::CROSSING_BACK);
+    r.osm_tags.remove(osm::CROSSING_FWD);
+    if crossings.is_empty() {
+        return;
+    }
+
+    let mut dist_along = Vec::new();
+    let mut total = Distance::ZERO;
+    for (idx, pt) in pts.iter().enumerate() {
+        if idx > 0 {
+            total += pts[idx - 1].dist_to(*pt);
+        }
+        dist_along.push(total);
+    }
+    for (pt, dist) in pts.iter().zip(dist_along) {
+        if let Some(crossing) = crossings.get(&pt.to_hashable()) {
+            if dist <= CROSSING_DIST {
+                r.osm_tags.insert(osm::CROSSING_BACK, crossing.clone());
+            }
+            if total - dist <= CROSSING_DIST {
+                r.osm_tags.insert(osm::CROSSING_FWD, crossing.clone());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use abstutil::Tags;
+    use geom::LonLat;
+    use map_model::raw::OriginalRoad;
+    use std::collections::BTreeMap;
+
+    fn road(pts: Vec<Pt2D>, kv: Vec<&str>) -> RawRoad {
+        let mut tags = Tags::new(BTreeMap::new());
+        for pair in kv {
+            let parts = pair.split('=').collect::<Vec<_>>();
+            tags.insert(parts[0], parts[1]);
+        }
+        RawRoad {
+            center_points: pts,
+            osm_tags: tags,
+            turn_restrictions: Vec::new(),
+            complicated_turn_restrictions: Vec::new(),
+        }
+    }
+
+    // One road with a separate sidewalk along only one side, and an untouched road nearby
+    fn snap(driving_side: DrivingSide) -> RawMap {
+        let mut map = RawMap::blank("test", "test");
+        map.gps_bounds.update(LonLat::new(-122.31, 47.6));
+        map.gps_bounds.update(LonLat::new(-122.30, 47.61));
+        let id = OriginalRoad::new(1, (1, 2));
+        map.roads.insert(
+            id,
+            road(
+                vec![Pt2D::new(100.0, 100.0), Pt2D::new(300.0, 100.0)],
+                vec!["highway=residential", "sidewalk=separate"],
+            ),
+        );
+        map.roads.insert(
+            OriginalRoad::new(2, (3, 4)),
+            road(
+                vec![Pt2D::new(100.0, 120.0), Pt2D::new(300.0, 120.0)],
+                vec!["highway=residential", "sidewalk=both"],
+            ),
+        );
+
+        let (center, width) = map.roads[&id].get_geometry(id, driving_side);
+        let sidewalk = center.shift_right(width / 2.0 + SEARCH_DIST / 2.0).unwrap();
+        snap_separate_sidewalks(
+            &mut map,
+            vec![(WayID(3), sidewalk.points().clone())],
+            driving_side,
+            &mut Timer::throwaway(),
+        );
+        map
+    }
+
+    #[test]
+    fn test_snap_separate_sidewalks() {
+        for (driving_side, value) in
+            vec![(DrivingSide::Right, "right"), (DrivingSide::Left, "left")]
+        {
+            let map = snap(driving_side);
+            assert_eq!(
+                map.roads[&OriginalRoad::new(1, (1, 2))]
+                    .osm_tags
+                    .get(osm::SIDEWALK),
+                Some(&value.to_string())
+            );
+            assert_eq!(
+                map.roads[&OriginalRoad::new(2, (3, 4))]
+                    .osm_tags
+                    .get(osm::SIDEWALK),
+                Some(&"both".to_string())
+            );
+        }
+    }
+
+    #[test]
+    fn test_tag_crossings() {
+        let pts = vec![
+            Pt2D::new(0.0, 0.0),
+            Pt2D::new(50.0, 0.0),
+            Pt2D::new(90.0, 0.0),
+            Pt2D::new(100.0, 0.0),
+        ];
+        let mut crossings = HashMap::new();
+        crossings.insert(pts[0].to_hashable(), "no".to_string());
+        // Too far from either end
+        crossings.insert(pts[1].to_hashable(), "zebra".to_string());
+        crossings.insert(pts[2].to_hashable(), "traffic_signals".to_string());
+
+        let mut r = road(
+            pts.clone(),
+            vec![format!("{}=uncontrolled", osm::CROSSING_FWD).as_str()],
+        );
+        tag_crossings(&mut r, &pts, &crossings);
+        assert_eq!(r.osm_tags.get(osm::CROSSING_BACK), Some(&"no".to_string()));
+        assert_eq!(
+            r.osm_tags.get(osm::CROSSING_FWD),
+            Some(&"traffic_signals".to_string())
+        );
+
+        // Old tags are cleared
+        tag_crossings(&mut r, &pts, &HashMap::new());
+        assert!(!r.osm_tags.contains_key(osm::CROSSING_BACK));
+        assert!(!r.osm_tags.contains_key(osm::CROSSING_FWD));
+    }
+}
@@ -1,9 +1,10 @@
 use crate::extract::OsmExtract;
 use abstutil::{Counter, Timer};
 use geom::{Distance, HashablePt2D, Pt2D};
-use map_model::raw::{OriginalRoad, RawIntersection, RawMap};
+use map_model::osm::WayID;
+use map_model::raw::{OriginalRoad, RawIntersection, RawMap, RawRoad, RestrictionType};
 use map_model::{osm, IntersectionType, NamePerLanguage};
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 
 // Returns amenities and a mapping of all points to split road. (Some internal points on roads are
 // removed, so this mapping isn't redundant.)
@@ -85,6 +86,8 @@ pub fn split_up_roads(
                     pt_to_road.insert(pt.to_hashable(), id);
                 }
 
+                crate::sidewalks::tag_crossings(&mut r, &pts, &input.crossings);
+                tag_stop_signs(&mut r, &pts, &input.stop_signs);
                 r.center_points = dedupe_angles(std::mem::replace(&mut pts, Vec::new()));
                 // Start a new road
                 map.roads.insert(id, r.clone());
@@ -99,6 +102,7 @@ pub fn split_up_roads(
 
     // Resolve simple turn restrictions (via a node)
     let mut restrictions = Vec::new();
+    let mut unresolved = 0;
     for (restriction, from_osm, via_osm, to_osm) in input.simple_turn_restrictions {
         let roads = map.roads_per_intersection(via_osm);
         // If some of the roads are missing, they were likely filtered out -- usually service
@@ -108,6 +112,8 @@ pub fn split_up_roads(
             roads.iter().find(|r| r.osm_way_id == to_osm),
         ) {
             restrictions.push((*from, restriction, *to));
+        } else {
+            unresolved += 1;
         }
     }
     for (from, rt, to) in restrictions {
@@ -118,45 +124,20 @@ pub fn split_up_roads(
             .push((rt, to));
     }
 
-    // Resolve complicated turn restrictions (via a way). TODO Only handle via ways immediately
-    // connected to both roads, for now
+    // Resolve complicated turn restrictions (via one or more ways)
     let mut complicated_restrictions = Vec::new();
-    for (rel_osm, from_osm, via_osm, to_osm) in input.complicated_turn_restrictions {
-        let via_candidates: Vec<OriginalRoad> = map
-            .roads
-            .keys()
-            .filter(|r| r.osm_way_id == via_osm)
-            .cloned()
-            .collect();
-        if via_candidates.len() != 1 {
-            timer.warn(format!(
-                "Couldn't resolve turn restriction from way {} to way {} via way {}. Candidate \
-                 roads for via: {:?}. See {}",
-                from_osm, to_osm, via_osm, via_candidates, rel_osm
-            ));
-            continue;
-        }
-        let via = via_candidates[0];
-
-        let maybe_from = map
-            .roads_per_intersection(via.i1)
-            .into_iter()
-            .chain(map.roads_per_intersection(via.i2).into_iter())
-            .find(|r| r.osm_way_id == from_osm);
-        let maybe_to = map
-            .roads_per_intersection(via.i1)
-            .into_iter()
-            .chain(map.roads_per_intersection(via.i2).into_iter())
-            .find(|r| r.osm_way_id == to_osm);
-        match (maybe_from, maybe_to) {
-            (Some(from), Some(to)) => {
-                complicated_restrictions.push((from, via, to));
+    for (rel_osm, rt, from_osm, via_osm, to_osm) in input.complicated_turn_restrictions {
+        match resolve_complicated_restriction(map, rt, from_osm, &via_osm, to_osm) {
+            Ok(bans) => {
+                complicated_restrictions.extend(bans);
             }
-            _ => {
+            Err(err) => {
                 timer.warn(format!(
-                    "Couldn't resolve turn restriction from {} to {} via {:?}",
-                    from_osm, to_osm, via
+                    "Couldn't resolve turn restriction from way {} to way {} via ways {:?}: {}. \
+                     See {}",
+                    from_osm, to_osm, via_osm, err, rel_osm
                 ));
+                unresolved += 1;
             }
         }
     }
@@ -167,6 +148,13 @@ pub fn split_up_roads(
             .complicated_turn_restrictions
             .push((via, to));
     }
+    if unresolved > 0 {
+        timer.warn(format!(
+            "{} turn restrictions couldn't be matched to roads. Usually the ways were filtered \
+             out, like service roads.",
+            unresolved
+        ));
+    }
 
     timer.start("match traffic signals to intersections");
     // Handle traffic signals tagged on incoming ways and not at intersections
@@ -214,3 +202,231 @@ fn dedupe_angles(pts: Vec<Pt2D>) -> Vec<Pt2D> {
     }
     result
 }
+
+// Stop sign nodes this close to an end of a road apply to traffic arriving there
+const STOP_SIGN_DIST: Distance = Distance::const_meters(20.0);
+
+// Records highway=stop nodes near either end of a road, so ControlStopSign doesn't have to guess.
+// A stop sign placed on the intersection node itself belongs to every road there, so it becomes an
+// all-way stop.
+fn tag_stop_signs(r: &mut RawRoad, pts: &[Pt2D], stop_signs: &HashMap<HashablePt2D, String>) {
+    r.osm_tags.remove(osm::STOP_SIGN_BACK);
+    r.osm_tags.remove(osm::STOP_SIGN_FWD);
+    if stop_signs.is_empty() {
+        return;
+    }
+
+    let mut total = Distance::ZERO;
+    let mut dist_along = Vec::new();
+    for (idx, pt) in pts.iter().enumerate() {
+        if idx > 0 {
+            total += pts[idx - 1].dist_to(*pt);
+        }
+        dist_along.push(total);
+    }
+    for (pt, dist) in pts.iter().zip(dist_along) {
+        let direction = match stop_signs.get(&pt.to_hashable()) {
+            Some(x) => x,
+            None => continue,
+        };
+        let near_back = dist <= STOP_SIGN_DIST;
+        let near_fwd = total - dist <= STOP_SIGN_DIST;
+        let value = if direction == "all" { "all" } else { "yes" };
+        let (back, fwd) = match direction.as_ref() {
+            "forward" => (false, near_fwd),
+            "backward" => (near_back, false),
+            "all" => (near_back, near_fwd),
+            // Without a direction, assume the closer end
+            _ => {
+                if dist <= total - dist {
+                    (near_back, false)
+                } else {
+                    (false, near_fwd)
+                }
+            }
+        };
+        if back {
+            r.osm_tags.insert(osm::STOP_SIGN_BACK, value);
+        }
+        if fwd {
+            r.osm_tags.insert(osm::STOP_SIGN_FWD, value);
+        }
+    }
+}
+
+// Turns a turn restriction via one or more ways into the (from, via, to) turns it bans.
+fn resolve_complicated_restriction(
+    map: &RawMap,
+    rt: RestrictionType,
+    from_osm: WayID,
+    via_osm: &[WayID],
+    to_osm: WayID,
+) -> Result<Vec<(OriginalRoad, Vec<OriginalRoad>, OriginalRoad)>, String> {
+    let (from, via, end, to) = resolve_via_ways(map, from_osm, via_osm, to_osm)?;
+    if rt == RestrictionType::BanTurns {
+        return Ok(vec![(from, via, to)]);
+    }
+    // Only allowing one exit means banning all of the others
+    let mut bans = Vec::new();
+    for other in map.roads_per_intersection(end) {
+        if other != to && !via.contains(&other) {
+            bans.push((from, via.clone(), other));
+        }
+    }
+    Ok(bans)
+}
+
+// Find the chain of roads along the via ways connecting a road from the "from" way to one from the
+// "to" way, and the intersection where that chain ends. OSM ways might've been split into many
+// roads.
+fn resolve_via_ways(
+    map: &RawMap,
+    from_osm: WayID,
+    via_osm: &[WayID],
+    to_osm: WayID,
+) -> Result<(OriginalRoad, Vec<OriginalRoad>, osm::NodeID, OriginalRoad), String> {
+    let via_roads: Vec<OriginalRoad> = map
+        .roads
+        .keys()
+        .filter(|r| via_osm.contains(&r.osm_way_id))
+        .cloned()
+        .collect();
+    if via_roads.is_empty() {
+        return Err("the via ways are missing".to_string());
+    }
+    let find = |i: osm::NodeID, way: WayID| {
+        map.roads_per_intersection(i)
+            .into_iter()
+            .find(|r| r.osm_way_id == way && !via_roads.contains(r))
+    };
+
+    // Breadth-first search along the via roads, starting anywhere the "from" way touches them
+    let mut backrefs: HashMap<osm::NodeID, Option<(OriginalRoad, osm::NodeID)>> = HashMap::new();
+    let mut queue: VecDeque<osm::NodeID> = VecDeque::new();
+    for r in &via_roads {
+        for i in vec![r.i1, r.i2] {
+            if !backrefs.contains_key(&i) && find(i, from_osm).is_some() {
+                backrefs.insert(i, None);
+                queue.push_back(i);
+            }
+        }
+    }
+    while let Some(current) = queue.pop_front() {
+        for r in &via_roads {
+            let next = if r.i1 == current {
+                r.i2
+            } else if r.i2 == current {
+                r.i1
+            } else {
+                continue;
+            };
+            if backrefs.contains_key(&next) {
+                continue;
+            }
+            backrefs.insert(next, Some((*r, current)));
+
+            if let Some(to) = find(next, to_osm) {
+                let mut via = Vec::new();
+                let mut at = next;
+                while let Some((r, prev)) = backrefs[&at] {
+                    via.push(r);
+                    at = prev;
+                }
+                via.reverse();
+                return Ok((find(at, from_osm).unwrap(), via, next, to));
+            }
+            queue.push_back(next);
+        }
+    }
+    Err("the via ways don't connect the from and to ways".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use abstutil::Tags;
+    use std::collections::BTreeMap;
+
+    fn road() -> RawRoad {
+        RawRoad {
+            center_points: vec![Pt2D::new(0.0, 0.0), Pt2D::new(10.0, 0.0)],
+            osm_tags: Tags::new(BTreeMap::new()),
+            turn_restrictions: Vec::new(),
+            complicated_turn_restrictions: Vec::new(),
+        }
+    }
+
+    // Way 1 leads into way 2, which was split into two roads. At the end, there's a choice between
+    // ways 3 and 4. Way 5 also touches the start of the via way.
+    fn test_map() -> RawMap {
+        let mut map = RawMap::blank("test", "test");
+        for (way, i1, i2) in vec![
+            (1, 10, 11),
+            (2, 11, 12),
+            (2, 12, 13),
+            (3, 13, 14),
+            (4, 13, 15),
+            (5, 11, 16),
+        ] {
+            map.roads.insert(OriginalRoad::new(way, (i1, i2)), road());
+        }
+        map
+    }
+
+    #[test]
+    fn test_resolve_split_via_way() {
+        let map = test_map();
+        let (from, via, end, to) = resolve_via_ways(&map, WayID(1), &[WayID(2)], WayID(3)).unwrap();
+        assert_eq!(from, OriginalRoad::new(1, (10, 11)));
+        assert_eq!(
+            via,
+            vec![
+                OriginalRoad::new(2, (11, 12)),
+                OriginalRoad::new(2, (12, 13))
+            ]
+        );
+        assert_eq!(end, osm::NodeID(13));
+        assert_eq!(to, OriginalRoad::new(3, (13, 14)));
+
+        assert!(resolve_via_ways(&map, WayID(1), &[WayID(2)], WayID(5)).is_err());
+    }
+
+    #[test]
+    fn test_only_allow_becomes_bans() {
+        let map = test_map();
+        let via = vec![
+            OriginalRoad::new(2, (11, 12)),
+            OriginalRoad::new(2, (12, 13)),
+        ];
+        assert_eq!(
+            resolve_complicated_restriction(
+                &map,
+                RestrictionType::OnlyAllowTurns,
+                WayID(1),
+                &[WayID(2)],
+                WayID(3)
+            )
+            .unwrap(),
+            vec![(
+                OriginalRoad::new(1, (10, 11)),
+                via.clone(),
+                OriginalRoad::new(4, (13, 15))
+            )]
+        );
+        assert_eq!(
+            resolve_complicated_restriction(
+                &map,
+                RestrictionType::BanTurns,
+                WayID(1),
+                &[WayID(2)],
+                WayID(3)
+            )
+            .unwrap(),
+            vec![(
+                OriginalRoad::new(1, (10, 11)),
+                via,
+                OriginalRoad::new(3, (13, 14))
+            )]
+        );
+    }
+}
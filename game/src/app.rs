@@ -8,7 +8,7 @@ use crate::sandbox::{GameplayMode, TutorialState};
 use abstutil::{MeasureMemory, Timer};
 use ezgui::{EventCtx, GfxCtx, Prerender};
 use geom::{Bounds, Circle, Distance, Duration, Pt2D, Time};
-use map_model::{IntersectionID, Map, Traversable};
+use map_model::{AttributeTable, IntersectionID, Map, Traversable};
 use maplit::btreemap;
 use rand::seq::SliceRandom;
 use sim::{Analytics, GetDrawAgents, Sim, SimCallback, SimFlags};
@@ -149,6 +149,46 @@ impl App {
                     "- parking_lot_changes: {} bytes",
                     prettyprint_usize(serialized_size_bytes(&a.parking_lot_changes))
                 );
+                println!(
+                    "- parking_sessions: {} bytes",
+                    prettyprint_usize(serialized_size_bytes(&a.parking_sessions))
+                );
+                println!(
+                    "- ridehail_waits: {} bytes",
+                    prettyprint_usize(serialized_size_bytes(&a.ridehail_waits))
+                );
+                println!(
+                    "- ridehail_legs: {} bytes",
+                    prettyprint_usize(serialized_size_bytes(&a.ridehail_legs))
+                );
+                println!(
+                    "- bikeshare_changes: {} bytes",
+                    prettyprint_usize(serialized_size_bytes(&a.bikeshare_changes))
+                );
+                println!(
+                    "- park_and_ride_changes: {} bytes",
+                    prettyprint_usize(serialized_size_bytes(&a.park_and_ride_changes))
+                );
+                println!(
+                    "- charging_sessions: {} bytes",
+                    prettyprint_usize(serialized_size_bytes(&a.charging_sessions))
+                );
+                println!(
+                    "- charger_queue_changes: {} bytes",
+                    prettyprint_usize(serialized_size_bytes(&a.charger_queue_changes))
+                );
+                println!(
+                    "- charger_waits: {} bytes",
+                    prettyprint_usize(serialized_size_bytes(&a.charger_waits))
+                );
+                println!(
+                    "- zone_entry_charges: {} bytes",
+                    prettyprint_usize(serialized_size_bytes(&a.zone_entry_charges))
+                );
+                println!(
+                    "- diverted_trips: {} bytes",
+                    prettyprint_usize(serialized_size_bytes(&a.diverted_trips))
+                );
             }
         }
     }
@@ -521,6 +561,8 @@ pub struct PerMap {
     pub map: Map,
     pub draw_map: DrawMap,
     pub sim: Sim,
+    // External data joined onto map objects by iotool
+    pub attributes: Vec<AttributeTable>,
 
     pub current_selection: Option<ID>,
     pub current_flags: Flags,
@@ -545,6 +587,8 @@ impl PerMap {
         timer.stop("draw_map");
         mem.reset("DrawMap", timer);
 
+        let attributes = AttributeTable::load_all(map.get_name());
+
         let mut low_z = 0;
         let mut high_z = 0;
         for r in map.all_roads() {
@@ -556,6 +600,7 @@ impl PerMap {
             map,
             draw_map,
             sim,
+            attributes,
             current_selection: None,
             current_flags: flags.clone(),
             last_warped_from: None,
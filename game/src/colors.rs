@@ -94,6 +94,7 @@ pub struct ColorScheme {
     pub unzoomed_car: Color,
     pub unzoomed_bike: Color,
     pub unzoomed_bus: Color,
+    pub unzoomed_ridehail: Color,
     pub unzoomed_pedestrian: Color,
 
     // Agents
@@ -195,6 +196,7 @@ impl ColorScheme {
             unzoomed_car: hex("#A32015"),
             unzoomed_bike: hex("#5D9630"),
             unzoomed_bus: hex("#12409D"),
+            unzoomed_ridehail: hex("#E0B42B"),
             unzoomed_pedestrian: hex("#DF8C3D"),
 
             // Agents
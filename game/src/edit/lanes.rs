@@ -2,8 +2,8 @@ use crate::app::App;
 use crate::common::CommonState;
 use crate::edit::zones::ZoneEditor;
 use crate::edit::{
-    apply_map_edits, can_edit_lane, change_speed_limit, maybe_edit_intersection, try_change_lt,
-    try_reverse,
+    apply_map_edits, can_edit_lane, change_lane_schedule, change_parking_rules, change_speed_limit,
+    maybe_edit_intersection, parking_rules_from_panel, try_change_lt, try_reverse,
 };
 use crate::game::{State, Transition};
 use crate::helpers::ID;
@@ -13,7 +13,7 @@ use ezgui::{
     hotkey, Btn, Color, Composite, EventCtx, GfxCtx, HorizontalAlignment, Key, Outcome,
     RewriteColor, TextExt, VerticalAlignment, Widget,
 };
-use map_model::{EditCmd, LaneID, LaneType};
+use map_model::{EditCmd, LaneID, LanePeriod, LaneType};
 
 pub struct LaneEditor {
     l: LaneID,
@@ -74,7 +74,7 @@ impl LaneEditor {
         }
 
         let parent = app.primary.map.get_parent(l);
-        let col = vec![
+        let mut col = vec![
             format!(
                 "Convert this lane of {} to what type?",
                 parent.get_name(app.opts.language.as_ref())
@@ -83,6 +83,17 @@ impl LaneEditor {
             .centered_horiz(),
             Widget::custom_row(row).centered(),
             change_speed_limit(ctx, parent.speed_limit),
+        ];
+        if lt == LaneType::Driving || lt == LaneType::Bus || lt == LaneType::Parking {
+            col.push(change_lane_schedule(ctx, app.primary.map.get_l(l)));
+        }
+        if lt == LaneType::Parking {
+            col.push(change_parking_rules(
+                ctx,
+                &app.primary.map.get_l(l).parking_rules,
+            ));
+        }
+        col.extend(vec![
             Btn::text_fg("Change access restrictions").build_def(ctx, hotkey(Key::A)),
             Widget::custom_row(vec![
                 Btn::text_fg("Finish").build_def(ctx, hotkey(Key::Escape)),
@@ -96,7 +107,7 @@ impl LaneEditor {
                 },
             ])
             .centered(),
-        ];
+        ]);
 
         let composite = Composite::new(Widget::col(col))
             .aligned(HorizontalAlignment::Center, VerticalAlignment::Top)
@@ -199,14 +210,47 @@ impl State for LaneEditor {
             },
             Outcome::Changed => {
                 let parent = app.primary.map.get_parent(self.l);
+                let mut edits = app.primary.map.get_edits().clone();
                 let new = self.composite.dropdown_value("speed limit");
                 let old = parent.speed_limit;
-                let mut edits = app.primary.map.get_edits().clone();
-                edits.commands.push(EditCmd::ChangeSpeedLimit {
-                    id: parent.id,
-                    new,
-                    old,
-                });
+                if new != old {
+                    edits.commands.push(EditCmd::ChangeSpeedLimit {
+                        id: parent.id,
+                        new,
+                        old,
+                    });
+                }
+                if self.composite.has_widget("parking price") {
+                    let old = app.primary.map.get_l(self.l).parking_rules.clone();
+                    // Residents of the road get permits
+                    let zone = app
+                        .primary
+                        .map
+                        .all_buildings()
+                        .iter()
+                        .filter(|b| app.primary.map.building_to_road(b.id).id == parent.id)
+                        .map(|b| b.id)
+                        .collect();
+                    let new = parking_rules_from_panel(&self.composite, &old, zone);
+                    if new != old {
+                        edits.commands.push(EditCmd::ChangeLaneParkingRules {
+                            id: self.l,
+                            new,
+                            old,
+                        });
+                    }
+                }
+                if self.composite.has_widget("lane schedule") {
+                    let old = app.primary.map.get_l(self.l).schedule.clone();
+                    let new: Vec<LanePeriod> = self.composite.dropdown_value("lane schedule");
+                    if new != old {
+                        edits.commands.push(EditCmd::ChangeLaneSchedule {
+                            id: self.l,
+                            new,
+                            old,
+                        });
+                    }
+                }
                 apply_map_edits(ctx, app, edits);
                 return Transition::Replace(LaneEditor::new(ctx, app, self.l, self.mode.clone()));
             }
@@ -26,12 +26,14 @@ use crate::render::DrawMap;
 use crate::sandbox::{GameplayMode, SandboxMode, TimeWarpScreen};
 use abstutil::Timer;
 use ezgui::{
-    hotkey, lctrl, Btn, Choice, Color, Composite, Drawable, EventCtx, GfxCtx, HorizontalAlignment,
-    Key, Line, Menu, Outcome, PersistentSplit, RewriteColor, Text, TextExt, VerticalAlignment,
-    Widget,
+    hotkey, lctrl, Btn, Checkbox, Choice, Color, Composite, Drawable, EventCtx, GfxCtx,
+    HorizontalAlignment, Key, Line, Menu, Outcome, PersistentSplit, RewriteColor, Text, TextExt,
+    VerticalAlignment, Widget,
+};
+use geom::{Duration, Speed};
+use map_model::{
+    BuildingID, EditCmd, IntersectionID, Lane, LaneID, LanePeriod, LaneType, MapEdits, ParkingRules,
 };
-use geom::Speed;
-use map_model::{EditCmd, IntersectionID, LaneID, LaneType, MapEdits};
 use maplit::btreeset;
 use sim::DontDrawAgents;
 use std::collections::BTreeSet;
@@ -416,7 +418,9 @@ impl LoadEdits {
         // Up-front filter out proposals that definitely don't fit the current map
         for name in abstutil::list_all_objects(abstutil::path("system/proposals")) {
             let path = abstutil::path(format!("system/proposals/{}.json", name));
-            if MapEdits::load(&app.primary.map, path.clone(), &mut Timer::throwaway()).is_ok() {
+            if MapEdits::load_and_migrate(&app.primary.map, path.clone(), &mut Timer::throwaway())
+                .is_ok()
+            {
                 proposals.push(Btn::text_fg(&name).build(ctx, path, None));
             }
         }
@@ -458,14 +462,14 @@ impl State for LoadEdits {
                             abstutil::path_edits(app.primary.map.get_name(), path)
                         };
 
-                        match MapEdits::load(
+                        match MapEdits::load_and_migrate(
                             &app.primary.map,
                             path.clone(),
                             &mut Timer::throwaway(),
                         )
-                        .and_then(|edits| {
+                        .and_then(|(edits, report)| {
                             if self.mode.allows(&edits) {
-                                Ok(edits)
+                                Ok((edits, report))
                             } else {
                                 Err(
                                     "The current gameplay mode restricts edits. These edits have \
@@ -474,9 +478,18 @@ impl State for LoadEdits {
                                 )
                             }
                         }) {
-                            Ok(edits) => {
+                            Ok((edits, report)) => {
                                 apply_map_edits(ctx, app, edits);
-                                Transition::Pop
+                                if report.is_clean() {
+                                    Transition::Pop
+                                } else {
+                                    // The basemap changed since these edits were made
+                                    Transition::Replace(PopupMsg::new(
+                                        ctx,
+                                        "Some edits had to be migrated",
+                                        report.to_string().lines().collect(),
+                                    ))
+                                }
                             }
                             // TODO Hack. Have to replace ourselves, because the Menu might be
                             // invalidated now that something was chosen.
@@ -622,6 +635,148 @@ pub fn change_speed_limit(ctx: &mut EventCtx, default: Speed) -> Widget {
     ])
 }
 
+pub fn change_lane_schedule(ctx: &mut EventCtx, lane: &Lane) -> Widget {
+    let rush_hour = |lt, reversed| {
+        vec![
+            LanePeriod {
+                start: Duration::hours(7),
+                end: Duration::hours(9),
+                lt,
+                reversed,
+            },
+            LanePeriod {
+                start: Duration::hours(16),
+                end: Duration::hours(18),
+                lt,
+                reversed,
+            },
+        ]
+    };
+    let mut choices = vec![Choice::new("same all day", Vec::new())];
+    if lane.lane_type != LaneType::Bus {
+        choices.push(Choice::new(
+            "bus-only at rush hour",
+            rush_hour(LaneType::Bus, false),
+        ));
+    }
+    if lane.lane_type != LaneType::Driving {
+        choices.push(Choice::new(
+            "general traffic at rush hour",
+            rush_hour(LaneType::Driving, false),
+        ));
+    }
+    if lane.lane_type.is_for_moving_vehicles() {
+        choices.push(Choice::new(
+            "reversed at rush hour",
+            rush_hour(lane.lane_type, true),
+        ));
+    }
+    if !choices.iter().any(|c| c.data == lane.schedule) {
+        choices.push(Choice::new("custom", lane.schedule.clone()));
+    }
+
+    Widget::row(vec![
+        "Time-of-day use:".draw_text(ctx).centered_vert(),
+        Widget::dropdown(ctx, "lane schedule", lane.schedule.clone(), choices),
+    ])
+}
+
+pub fn change_parking_rules(ctx: &mut EventCtx, rules: &ParkingRules) -> Widget {
+    let mut prices: Vec<Choice<f64>> = vec![0.0, 0.5, 1.0, 2.0, 3.0, 4.0, 6.0]
+        .into_iter()
+        .map(|x| Choice::new(format!("${:.2}/hour", x), x))
+        .collect();
+    if !prices.iter().any(|c| c.data == rules.price_per_hour) {
+        prices.push(Choice::new(
+            format!("${:.2}/hour", rules.price_per_hour),
+            rules.price_per_hour,
+        ));
+    }
+
+    let mut metered_hours = vec![
+        Choice::new("all day", Vec::new()),
+        Choice::new("8am - 6pm", vec![(Duration::hours(8), Duration::hours(18))]),
+        Choice::new("8am - 8pm", vec![(Duration::hours(8), Duration::hours(20))]),
+    ];
+    if !metered_hours.iter().any(|c| c.data == rules.metered_hours) {
+        metered_hours.push(Choice::new("custom", rules.metered_hours.clone()));
+    }
+
+    let mut max_durations = vec![
+        Choice::new("none", None),
+        Choice::new("1 hour", Some(Duration::hours(1))),
+        Choice::new("2 hours", Some(Duration::hours(2))),
+        Choice::new("4 hours", Some(Duration::hours(4))),
+    ];
+    if !max_durations.iter().any(|c| c.data == rules.max_duration) {
+        max_durations.push(Choice::new(
+            rules.max_duration.unwrap().to_string(),
+            rules.max_duration,
+        ));
+    }
+
+    let mut no_parking = vec![
+        Choice::new("never", Vec::new()),
+        Choice::new(
+            "overnight (2am - 6am)",
+            vec![(Duration::hours(2), Duration::hours(6))],
+        ),
+    ];
+    if !no_parking.iter().any(|c| c.data == rules.no_parking) {
+        no_parking.push(Choice::new("custom", rules.no_parking.clone()));
+    }
+
+    Widget::col(vec![
+        Widget::row(vec![
+            "Parking price:".draw_text(ctx).centered_vert(),
+            Widget::dropdown(ctx, "parking price", rules.price_per_hour, prices),
+        ]),
+        Widget::row(vec![
+            "Metered hours:".draw_text(ctx).centered_vert(),
+            Widget::dropdown(
+                ctx,
+                "metered hours",
+                rules.metered_hours.clone(),
+                metered_hours,
+            ),
+        ]),
+        Widget::row(vec![
+            "Time limit while metered:".draw_text(ctx).centered_vert(),
+            Widget::dropdown(ctx, "time limit", rules.max_duration, max_durations),
+        ]),
+        Widget::row(vec![
+            "No parking:".draw_text(ctx).centered_vert(),
+            Widget::dropdown(ctx, "no parking", rules.no_parking.clone(), no_parking),
+        ]),
+        Checkbox::switch(
+            ctx,
+            "residential permits only",
+            None,
+            rules.permit_zone.is_some(),
+        ),
+    ])
+}
+
+// Reads the widgets from change_parking_rules. If the permit zone is newly turned on, it covers the
+// given buildings.
+pub fn parking_rules_from_panel(
+    composite: &Composite,
+    old: &ParkingRules,
+    zone: BTreeSet<BuildingID>,
+) -> ParkingRules {
+    ParkingRules {
+        price_per_hour: composite.dropdown_value("parking price"),
+        metered_hours: composite.dropdown_value("metered hours"),
+        max_duration: composite.dropdown_value("time limit"),
+        no_parking: composite.dropdown_value("no parking"),
+        permit_zone: if composite.is_checked("residential permits only") {
+            Some(old.permit_zone.clone().unwrap_or(zone))
+        } else {
+            None
+        },
+    }
+}
+
 pub fn maybe_edit_intersection(
     ctx: &mut EventCtx,
     app: &mut App,
@@ -719,6 +874,14 @@ fn make_changelist(ctx: &mut EventCtx, app: &App) -> Composite {
                 "{} intersections changed",
                 edits.original_intersections.len()
             )),
+            Line(format!(
+                "{} parking rules changed",
+                edits.original_lane_parking_rules.len() + edits.original_lot_parking_rules.len()
+            )),
+            Line(format!(
+                "{} lane schedules changed",
+                edits.original_lane_schedules.len()
+            )),
         ])
         .draw(ctx),
     ];
@@ -750,5 +913,8 @@ fn cmd_to_id(cmd: &EditCmd) -> Option<ID> {
         EditCmd::ChangeIntersection { i, .. } => Some(ID::Intersection(*i)),
         EditCmd::ChangeAccessRestrictions { id, .. } => Some(ID::Road(*id)),
         EditCmd::ChangeRouteSchedule { .. } => None,
+        EditCmd::ChangeLaneParkingRules { id, .. } => Some(ID::Lane(*id)),
+        EditCmd::ChangeLaneSchedule { id, .. } => Some(ID::Lane(*id)),
+        EditCmd::ChangeLotParkingRules { id, .. } => Some(ID::ParkingLot(*id)),
     }
 }
@@ -7,18 +7,21 @@ use crate::game::{State, Transition};
 use crate::helpers::{checkbox_per_mode, intersections_from_roads};
 use enumset::EnumSet;
 use ezgui::{
-    hotkey, Btn, Color, Composite, Drawable, EventCtx, GfxCtx, HorizontalAlignment, Key, Line,
-    Outcome, Spinner, Text, TextExt, VerticalAlignment, Widget,
+    hotkey, Btn, Choice, Color, Composite, Drawable, EventCtx, GfxCtx, HorizontalAlignment, Key,
+    Line, Outcome, Spinner, Text, TextExt, VerticalAlignment, Widget,
 };
+use geom::Duration;
 use map_model::{AccessRestrictions, EditCmd, PathConstraints, RoadID};
 use maplit::btreeset;
 use sim::TripMode;
-use std::collections::BTreeSet;
+use std::collections::{BTreeMap, BTreeSet};
 
 pub struct ZoneEditor {
     composite: Composite,
     selector: RoadSelector,
     allow_through_traffic: BTreeSet<TripMode>,
+    // Only the charge for cars can be edited here; keep the others
+    entry_charges: BTreeMap<PathConstraints, f64>,
     unzoomed: Drawable,
     zoomed: Drawable,
 
@@ -41,6 +44,7 @@ impl ZoneEditor {
             .map(|c| TripMode::from_constraints(c))
             .collect();
         let cap_vehicles_per_hour = start.access_restrictions.cap_vehicles_per_hour;
+        let entry_charges = start.access_restrictions.entry_charges.clone();
 
         let (unzoomed, zoomed, legend) = draw_zone(ctx, app, &members);
         let orig_members = members.clone();
@@ -61,6 +65,14 @@ impl ZoneEditor {
                     Spinner::new(ctx, (0, 1000), cap_vehicles_per_hour.unwrap_or(0) as isize)
                         .named("cap_vehicles"),
                 ]),
+                change_hours(ctx, &start.access_restrictions.hours),
+                change_entry_charge(
+                    ctx,
+                    entry_charges
+                        .get(&PathConstraints::Car)
+                        .cloned()
+                        .unwrap_or(0.0),
+                ),
                 Widget::custom_row(vec![
                     Btn::text_fg("Apply").build_def(ctx, hotkey(Key::Enter)),
                     Btn::text_fg("Cancel").build_def(ctx, hotkey(Key::Escape)),
@@ -72,6 +84,7 @@ impl ZoneEditor {
             orig_members,
             selector,
             allow_through_traffic,
+            entry_charges,
             unzoomed,
             zoomed,
         })
@@ -103,6 +116,13 @@ impl State for ZoneEditor {
                     // The original allow_through_traffic always includes this, and there's no way
                     // to exclude it, so stay consistent.
                     allow_through_traffic.insert(PathConstraints::Train);
+                    let mut entry_charges = self.entry_charges.clone();
+                    let charge: f64 = self.composite.dropdown_value("entry charge");
+                    if charge == 0.0 {
+                        entry_charges.remove(&PathConstraints::Car);
+                    } else {
+                        entry_charges.insert(PathConstraints::Car, charge);
+                    }
                     let new = AccessRestrictions {
                         allow_through_traffic,
                         cap_vehicles_per_hour: {
@@ -113,6 +133,8 @@ impl State for ZoneEditor {
                                 Some(n)
                             }
                         },
+                        hours: self.composite.dropdown_value("restricted hours"),
+                        entry_charges,
                     };
                     for r in &self.selector.roads {
                         let old = app.primary.map.get_r(*r).access_restrictions.clone();
@@ -221,3 +243,45 @@ fn make_instructions(ctx: &mut EventCtx, allow_through_traffic: &BTreeSet<TripMo
     }
     .named("instructions")
 }
+
+fn change_hours(ctx: &mut EventCtx, hours: &[(Duration, Duration)]) -> Widget {
+    let mut choices = vec![
+        Choice::new("all day", Vec::new()),
+        Choice::new(
+            "school run (8am - 9am)",
+            vec![(Duration::hours(8), Duration::hours(9))],
+        ),
+        Choice::new(
+            "rush hour (7am - 9am, 4pm - 6pm)",
+            vec![
+                (Duration::hours(7), Duration::hours(9)),
+                (Duration::hours(16), Duration::hours(18)),
+            ],
+        ),
+    ];
+    if !choices.iter().any(|c| c.data == hours) {
+        choices.push(Choice::new("custom", hours.to_vec()));
+    }
+
+    Widget::row(vec![
+        "Restrictions and charges apply:"
+            .draw_text(ctx)
+            .centered_vert(),
+        Widget::dropdown(ctx, "restricted hours", hours.to_vec(), choices),
+    ])
+}
+
+fn change_entry_charge(ctx: &mut EventCtx, charge: f64) -> Widget {
+    let mut choices: Vec<Choice<f64>> = vec![0.0, 2.0, 5.0, 8.0, 10.0, 15.0]
+        .into_iter()
+        .map(|x| Choice::new(format!("${:.2}", x), x))
+        .collect();
+    if !choices.iter().any(|c| c.data == charge) {
+        choices.push(Choice::new(format!("${:.2}", charge), charge));
+    }
+
+    Widget::row(vec![
+        "Charge cars to enter:".draw_text(ctx).centered_vert(),
+        Widget::dropdown(ctx, "entry charge", charge, choices),
+    ])
+}
@@ -49,15 +49,19 @@ impl Game {
 
         // Just apply this here, don't plumb to SimFlags or anything else. We recreate things using
         // these flags later, but we don't want to keep applying the same edits.
+        let mut migration_report = None;
         if let Some(edits_name) = start_with_edits {
             // TODO Maybe loading screen
             let mut timer = abstutil::Timer::new("apply initial edits");
-            let edits = map_model::MapEdits::load(
+            let (edits, report) = map_model::MapEdits::load_and_migrate(
                 &app.primary.map,
                 abstutil::path_edits(app.primary.map.get_name(), &edits_name),
                 &mut timer,
             )
             .unwrap();
+            if !report.is_clean() {
+                migration_report = Some(report);
+            }
             crate::edit::apply_map_edits(ctx, &mut app, edits);
             app.primary
                 .map
@@ -65,7 +69,7 @@ impl Game {
             app.primary.clear_sim();
         }
 
-        let states: Vec<Box<dyn State>> = if title {
+        let mut states: Vec<Box<dyn State>> = if title {
             vec![Box::new(TitleScreen::new(ctx, &mut app))]
         } else {
             // TODO We're assuming we never wind up starting freeform mode with a synthetic map
@@ -74,6 +78,13 @@ impl Game {
             });
             vec![SandboxMode::new(ctx, &mut app, mode)]
         };
+        if let Some(report) = migration_report {
+            states.push(PopupMsg::new(
+                ctx,
+                "Some edits had to be migrated",
+                report.to_string().lines().collect(),
+            ));
+        }
         if let Some(ss) = savestate {
             // TODO This is weird, we're left in Freeform mode with the wrong UI. Can't instantiate
             // PlayScenario without clobbering.
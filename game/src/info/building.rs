@@ -1,9 +1,11 @@
 use crate::app::App;
-use crate::info::{header_btns, make_table, make_tabs, Details, Tab};
+use crate::info::{attributes, header_btns, make_table, make_tabs, Details, Tab};
 use crate::render::DrawPedestrian;
 use ezgui::{Btn, Color, EventCtx, Line, Text, TextExt, Widget};
 use geom::{Angle, Circle, Distance, Speed, Time};
-use map_model::{BuildingID, LaneID, OffstreetParking, Traversable, SIDEWALK_THICKNESS};
+use map_model::{
+    AttributeKey, BuildingID, LaneID, OffstreetParking, Traversable, SIDEWALK_THICKNESS,
+};
 use sim::{DrawPedestrianInput, PedestrianID, PersonID, TripMode, TripResult};
 use std::collections::BTreeMap;
 
@@ -40,6 +42,10 @@ pub fn info(ctx: &mut EventCtx, app: &App, details: &mut Details, id: BuildingID
     }
 
     rows.extend(make_table(ctx, kv.into_iter()));
+    rows.extend(make_table(
+        ctx,
+        attributes(app, AttributeKey::Building(id)).into_iter(),
+    ));
 
     let mut txt = Text::new();
 
@@ -1,6 +1,8 @@
 use crate::app::App;
 use crate::helpers::color_for_agent_type;
-use crate::info::{header_btns, make_tabs, throughput, DataOptions, Details, Tab};
+use crate::info::{
+    attributes, header_btns, make_table, make_tabs, throughput, DataOptions, Details, Tab,
+};
 use crate::options::TrafficSignalStyle;
 use crate::render::draw_signal_phase;
 use abstutil::prettyprint_usize;
@@ -9,7 +11,7 @@ use ezgui::{
     ScatterPlot, Series, Text, Widget,
 };
 use geom::{ArrowCap, Distance, Duration, PolyLine, Polygon, Time};
-use map_model::{IntersectionID, IntersectionType, PhaseType};
+use map_model::{AttributeKey, IntersectionID, IntersectionType, PhaseType};
 use sim::AgentType;
 use std::collections::{BTreeMap, BTreeSet};
 
@@ -32,6 +34,10 @@ pub fn info(ctx: &EventCtx, app: &App, details: &mut Details, id: IntersectionID
         txt.add(Line(format!("- {}", r)));
     }
     rows.push(txt.draw(ctx));
+    rows.extend(make_table(
+        ctx,
+        attributes(app, AttributeKey::Intersection(id)).into_iter(),
+    ));
 
     if app.opts.dev {
         rows.push(Btn::text_bg1("Open OSM node").build(ctx, format!("open {}", i.orig_id), None));
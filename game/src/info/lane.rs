@@ -1,8 +1,11 @@
 use crate::app::App;
-use crate::info::{header_btns, make_table, make_tabs, throughput, DataOptions, Details, Tab};
+use crate::info::{
+    attributes, header_btns, make_table, make_tabs, throughput, DataOptions, Details, Tab,
+};
 use abstutil::prettyprint_usize;
 use ezgui::{Btn, EventCtx, Line, LinePlot, PlotOptions, Series, Text, TextExt, Widget};
-use map_model::{LaneID, PathConstraints};
+use geom::Time;
+use map_model::{AttributeKey, LaneID, PathConstraints};
 use std::collections::HashSet;
 
 pub fn info(ctx: &EventCtx, app: &App, details: &mut Details, id: LaneID) -> Vec<Widget> {
@@ -26,9 +29,32 @@ pub fn info(ctx: &EventCtx, app: &App, details: &mut Details, id: LaneID) -> Vec
         if !ban.is_empty() {
             kv.push(("No through-traffic for", ban.join(", ")));
         }
+        for (p, charge) in &r.access_restrictions.entry_charges {
+            kv.push((
+                "Charge to enter",
+                format!("${:.2} per {:?}", charge, p).to_ascii_lowercase(),
+            ));
+        }
+        if !r.access_restrictions.hours.is_empty() {
+            kv.push((
+                "Restrictions apply",
+                r.access_restrictions
+                    .hours
+                    .iter()
+                    .map(|(start, end)| {
+                        format!(
+                            "{} - {}",
+                            (Time::START_OF_DAY + *start).ampm_tostring(),
+                            (Time::START_OF_DAY + *end).ampm_tostring()
+                        )
+                    })
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            ));
+        }
         if let Some(cap) = r.access_restrictions.cap_vehicles_per_hour {
             kv.push((
-                "Cap for vehicles this hour",
+                "Cap for vehicles in the last hour",
                 format!(
                     "{} / {}",
                     prettyprint_usize(app.primary.sim.get_cap_counter(l.id)),
@@ -54,6 +80,10 @@ pub fn info(ctx: &EventCtx, app: &App, details: &mut Details, id: LaneID) -> Vec
     kv.push(("Length", l.length().describe_rounded()));
 
     rows.extend(make_table(ctx, kv.into_iter()));
+    rows.extend(make_table(
+        ctx,
+        attributes(app, AttributeKey::Road(r.id)).into_iter(),
+    ));
 
     if l.is_parking() {
         let capacity = l.number_parking_spots();
@@ -114,10 +144,7 @@ pub fn debug(ctx: &EventCtx, app: &App, details: &mut Details, id: LaneID) -> Ve
         ));
     }
 
-    if let Some(types) = l
-        .get_turn_restrictions(r)
-        .map(|types| types.collect::<Vec<_>>())
-    {
+    if let Some(types) = l.get_turn_restrictions(l.dst_i) {
         kv.push(("Turn restrictions".to_string(), format!("{:?}", types)));
     }
     for (restriction, to) in &r.turn_restrictions {
@@ -19,7 +19,9 @@ use ezgui::{
     VerticalAlignment, Widget,
 };
 use geom::{Circle, Distance, Time};
-use map_model::{AreaID, BuildingID, BusRouteID, BusStopID, IntersectionID, LaneID, ParkingLotID};
+use map_model::{
+    AreaID, AttributeKey, BuildingID, BusRouteID, BusStopID, IntersectionID, LaneID, ParkingLotID,
+};
 use sim::{
     AgentID, AgentType, Analytics, CarID, ParkingSpot, PedestrianID, PersonID, PersonState, TripID,
     VehicleType,
@@ -621,6 +623,19 @@ fn make_table<I: Into<String>>(
     .collect()
 }
 
+// Values from every attribute table for one object, labeled by table and column
+fn attributes(app: &App, key: AttributeKey) -> Vec<(String, String)> {
+    let mut kv = Vec::new();
+    for table in &app.primary.attributes {
+        if let Some(row) = table.values.get(&key) {
+            for (column, value) in row {
+                kv.push((format!("{}: {}", table.name, column), value.to_string()));
+            }
+        }
+    }
+    kv
+}
+
 fn throughput<F: Fn(&Analytics) -> Vec<(AgentType, Vec<(Time, usize)>)>>(
     ctx: &EventCtx,
     app: &App,
@@ -139,6 +139,7 @@ pub fn trips(
                         TripMode::Bike => "system/assets/meters/bike.svg",
                         TripMode::Drive => "system/assets/meters/car.svg",
                         TripMode::Transit => "system/assets/meters/bus.svg",
+                        TripMode::RideHail => "system/assets/meters/car.svg",
                     },
                 )
                 .color(RewriteColor::ChangeAll(color))
@@ -478,12 +479,16 @@ pub fn parked_car(
 
     // TODO how long idle, prev trips, next trips, etc
 
-    let p = app.primary.sim.get_owner_of_car(id).unwrap();
-    rows.push(Btn::text_bg2(format!("Owned by {}", p)).build_def(ctx, None));
-    details.hyperlinks.insert(
-        format!("Owned by {}", p),
-        Tab::PersonTrips(p, BTreeMap::new()),
-    );
+    if let Some(p) = app.primary.sim.get_owner_of_car(id) {
+        rows.push(Btn::text_bg2(format!("Owned by {}", p)).build_def(ctx, None));
+        details.hyperlinks.insert(
+            format!("Owned by {}", p),
+            Tab::PersonTrips(p, BTreeMap::new()),
+        );
+    } else {
+        // Only ride-hail vehicles don't have an owner
+        rows.push("Ride-hail vehicle".draw_text(ctx));
+    }
 
     if let Some(p) = app.primary.sim.lookup_parked_car(id) {
         match p.spot {
@@ -542,8 +547,15 @@ fn header(
                         VehicleType::Bike => ("biking", Some("system/assets/meters/bike.svg")),
                         VehicleType::Bus | VehicleType::Train => unreachable!(),
                     },
-                    AgentID::BusPassenger(_, _) => {
-                        ("riding a bus", Some("system/assets/meters/bus.svg"))
+                    AgentID::BusPassenger(_, c) => {
+                        if c.1 == VehicleType::Car {
+                            (
+                                "riding in a ride-hail vehicle",
+                                Some("system/assets/meters/car.svg"),
+                            )
+                        } else {
+                            ("riding a bus", Some("system/assets/meters/bus.svg"))
+                        }
                     }
                 }
             } else {
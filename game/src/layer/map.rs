@@ -234,6 +234,9 @@ impl Static {
         for r in &edits.changed_access_restrictions {
             colorer.add_r(*r, "modified lane/intersection");
         }
+        for l in edits.original_lane_parking_rules.keys() {
+            colorer.add_l(*l, "modified lane/intersection");
+        }
 
         Static::new(
             ctx,
@@ -255,6 +258,15 @@ impl Static {
                     "{} intersections changed",
                     edits.original_intersections.len()
                 )),
+                Line(format!(
+                    "{} parking rules changed",
+                    edits.original_lane_parking_rules.len()
+                        + edits.original_lot_parking_rules.len()
+                )),
+                Line(format!(
+                    "{} lane schedules changed",
+                    edits.original_lane_schedules.len()
+                )),
             ])
             .draw(ctx),
         )
@@ -41,6 +41,14 @@ impl Layer for Pandemic {
                 "close" => {
                     return Some(LayerOutcome::Close);
                 }
+                "export time series to CSV" => {
+                    let model = app.primary.sim.get_pandemic_model().unwrap();
+                    let path = format!("pandemic_{}.csv", app.primary.map.get_name());
+                    match model.export_history(&path) {
+                        Ok(()) => println!("Exported {}", path),
+                        Err(err) => println!("Couldn't export {}: {}", path, err),
+                    }
+                }
                 _ => unreachable!(),
             },
             _ => {
@@ -212,8 +220,14 @@ fn make_controls(
                 prettyprint_usize(model.count_dead()),
                 (model.count_dead() as f64) * pct
             )),
+            Line(format!(
+                "{} Hospitalized, {} Quarantined",
+                prettyprint_usize(model.count_hospitalized()),
+                prettyprint_usize(model.count_quarantined())
+            )),
         ])
         .draw(ctx),
+        Btn::text_fg("export time series to CSV").build_def(ctx, None),
         Widget::row(vec![
             "Filter:".draw_text(ctx),
             Widget::dropdown(
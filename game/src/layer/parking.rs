@@ -162,6 +162,16 @@ impl Occupancy {
             }
         }
 
+        let mut revenue = 0.0;
+        let mut violations = 0;
+        let sessions = &app.primary.sim.get_analytics().parking_sessions;
+        for (_, _, fee, compliant) in sessions {
+            revenue += fee;
+            if !compliant {
+                violations += 1;
+            }
+        }
+
         let composite = Composite::new(Widget::col(vec![
             Widget::row(vec![
                 Widget::draw_svg(ctx, "system/assets/tools/layers.svg"),
@@ -189,6 +199,15 @@ impl Occupancy {
                     prettyprint_usize(private_filled),
                     prettyprint_usize(private_filled + private_avail)
                 )),
+                Line(format!(
+                    "${:.2} collected from cars leaving regulated spots",
+                    revenue
+                )),
+                Line(format!(
+                    "{} / {} of those stays broke the parking rules",
+                    prettyprint_usize(violations),
+                    prettyprint_usize(sessions.len())
+                )),
             ])
             .draw(ctx),
             Widget::row(vec![
@@ -187,8 +187,9 @@ fn check_proposals() {
         ) {
             Ok(perma) => {
                 let map = map_model::Map::new(abstutil::path_map(&perma.map_name), &mut timer);
-                if let Err(err) = map_model::PermanentMapEdits::from_permanent(perma, &map) {
-                    timer.error(format!("{} is out-of-date: {}", name, err));
+                let (_, report) = map_model::PermanentMapEdits::migrate(perma, &map);
+                if !report.is_clean() {
+                    timer.error(format!("{} is out-of-date. {}", name, report));
                 }
             }
             Err(err) => {
@@ -8,7 +8,7 @@ use abstutil::prettyprint_usize;
 use ezgui::{
     Btn, Checkbox, Composite, EventCtx, Filler, GfxCtx, Line, Outcome, Text, TextExt, Widget,
 };
-use geom::Duration;
+use geom::{Distance, Duration};
 use sim::{TripEndpoint, TripID, TripPhaseType};
 
 const ROWS: usize = 20;
@@ -48,6 +48,7 @@ enum SortBy {
     DrivingDuration,
     ParkingDuration,
     WalkingDuration,
+    WalkingDistance,
     PercentOverhead,
 }
 
@@ -93,6 +94,10 @@ impl State for ParkingOverhead {
                     self.opts.change(SortBy::WalkingDuration);
                     self.recalc(ctx, app);
                 }
+                "Walking distance" => {
+                    self.opts.change(SortBy::WalkingDistance);
+                    self.recalc(ctx, app);
+                }
                 "Percent overhead" => {
                     self.opts.change(SortBy::PercentOverhead);
                     self.recalc(ctx, app);
@@ -152,13 +157,15 @@ struct Entry {
     driving_duration: Duration,
     parking_duration: Duration,
     walking_duration: Duration,
+    walking_distance: Distance,
     percent_overhead: usize,
 }
 
 fn make(ctx: &mut EventCtx, app: &App, opts: &Options) -> Composite {
     // Gather raw data
     let mut data = Vec::new();
-    for (id, phases) in app.primary.sim.get_analytics().get_all_trip_phases() {
+    let analytics = app.primary.sim.get_analytics();
+    for (id, phases) in analytics.get_all_trip_phases() {
         let trip = app.primary.sim.trip_info(id);
         if !opts.off_map_starts {
             if let TripEndpoint::Border(_, _) = trip.start {
@@ -207,6 +214,11 @@ fn make(ctx: &mut EventCtx, app: &App, opts: &Options) -> Composite {
             driving_duration,
             parking_duration,
             walking_duration,
+            walking_distance: analytics
+                .parking_overhead
+                .get(&id)
+                .map(|(_, dist)| *dist)
+                .unwrap_or(Distance::ZERO),
             percent_overhead: (100.0 * (1.0 - (driving_duration / total_duration))) as usize,
         });
     }
@@ -217,6 +229,7 @@ fn make(ctx: &mut EventCtx, app: &App, opts: &Options) -> Composite {
         SortBy::DrivingDuration => data.sort_by_key(|x| x.driving_duration),
         SortBy::ParkingDuration => data.sort_by_key(|x| x.parking_duration),
         SortBy::WalkingDuration => data.sort_by_key(|x| x.walking_duration),
+        SortBy::WalkingDistance => data.sort_by_key(|x| x.walking_distance),
         SortBy::PercentOverhead => data.sort_by_key(|x| x.percent_overhead),
     }
     if opts.descending {
@@ -235,6 +248,7 @@ fn make(ctx: &mut EventCtx, app: &App, opts: &Options) -> Composite {
                 Text::from(Line(x.driving_duration.to_string())).render_ctx(ctx),
                 Text::from(Line(x.parking_duration.to_string())).render_ctx(ctx),
                 Text::from(Line(x.walking_duration.to_string())).render_ctx(ctx),
+                Text::from(Line(x.walking_distance.to_string())).render_ctx(ctx),
                 Text::from(Line(format!("{}%", x.percent_overhead))).render_ctx(ctx),
             ],
         ));
@@ -258,6 +272,7 @@ fn make(ctx: &mut EventCtx, app: &App, opts: &Options) -> Composite {
         btn(SortBy::DrivingDuration, "Driving duration"),
         btn(SortBy::ParkingDuration, "Parking duration"),
         btn(SortBy::WalkingDuration, "Walking duration"),
+        btn(SortBy::WalkingDistance, "Walking distance"),
         btn(SortBy::PercentOverhead, "Percent overhead"),
     ];
 
@@ -269,6 +284,10 @@ fn make(ctx: &mut EventCtx, app: &App, opts: &Options) -> Composite {
                     "Trips taken by car also include time to walk between the building and \
                      parking spot, as well as the time to find parking.",
                 ),
+                Line(
+                    "Walking distance is a straight line between the parking spot and the \
+                     destination.",
+                ),
                 Line("Overhead is 1 - driving time / total time"),
                 Line("Ideally, overhead is 0% -- the entire trip is just spent driving."),
                 Line(""),
@@ -167,7 +167,10 @@ impl GameplayMode {
                 EditCmd::ChangeLaneType { .. }
                 | EditCmd::ReverseLane { .. }
                 | EditCmd::ChangeSpeedLimit { .. }
-                | EditCmd::ChangeAccessRestrictions { .. } => {
+                | EditCmd::ChangeAccessRestrictions { .. }
+                | EditCmd::ChangeLaneParkingRules { .. }
+                | EditCmd::ChangeLotParkingRules { .. }
+                | EditCmd::ChangeLaneSchedule { .. } => {
                     if !self.can_edit_lanes() {
                         return false;
                     }
@@ -163,6 +163,11 @@ impl Polygon {
             &self.points
         }
     }
+    // The outer ring followed by any holes. None if the polygon doesn't remember its rings, which
+    // is the case for all polygons without holes.
+    pub fn get_rings(&self) -> Option<&Vec<Ring>> {
+        self.rings.as_ref()
+    }
     pub fn into_points(mut self) -> Vec<Pt2D> {
         if let Some(mut rings) = self.rings.take() {
             rings.remove(0).into_points()
@@ -280,9 +285,13 @@ impl Polygon {
         result
     }
 
-    // TODO Result won't be a nice Ring
+    // TODO Result won't be a nice Ring, unless there are holes
     pub fn intersection(&self, other: &Polygon) -> Vec<Polygon> {
-        from_multi(to_geo(self.points()).intersection(&to_geo(other.points())))
+        from_multi(to_geo_with_holes(self).intersection(&to_geo_with_holes(other)))
+    }
+
+    pub fn difference(&self, other: &Polygon) -> Vec<Polygon> {
+        from_multi(to_geo_with_holes(self).difference(&to_geo_with_holes(other)))
     }
 
     pub fn convex_hull(list: Vec<Polygon>) -> Polygon {
@@ -448,25 +457,53 @@ impl Triangle {
 }
 
 fn to_geo(pts: &Vec<Pt2D>) -> geo::Polygon<f64> {
-    geo::Polygon::new(
-        geo::LineString::from(
-            pts.iter()
-                .map(|pt| geo::Point::new(pt.x(), pt.y()))
-                .collect::<Vec<_>>(),
-        ),
-        Vec::new(),
+    geo::Polygon::new(to_linestring(pts), Vec::new())
+}
+
+fn to_geo_with_holes(p: &Polygon) -> geo::Polygon<f64> {
+    if let Some(ref rings) = p.rings {
+        geo::Polygon::new(
+            to_linestring(rings[0].points()),
+            rings[1..]
+                .iter()
+                .map(|ring| to_linestring(ring.points()))
+                .collect(),
+        )
+    } else {
+        to_geo(p.points())
+    }
+}
+
+fn to_linestring(pts: &Vec<Pt2D>) -> geo::LineString<f64> {
+    geo::LineString::from(
+        pts.iter()
+            .map(|pt| geo::Point::new(pt.x(), pt.y()))
+            .collect::<Vec<_>>(),
     )
 }
 
 fn from_geo(p: geo::Polygon<f64>) -> Polygon {
-    Polygon::buggy_new(
-        p.into_inner()
-            .0
-            .into_points()
-            .into_iter()
-            .map(|pt| Pt2D::new(pt.x(), pt.y()))
-            .collect(),
-    )
+    let (exterior, interiors) = p.into_inner();
+    let outer = from_linestring(exterior);
+    if !interiors.is_empty() {
+        if let Ok(ring) = Ring::new(outer.clone()) {
+            return Polygon::with_holes(
+                ring,
+                interiors
+                    .into_iter()
+                    .filter_map(|ls| Ring::new(from_linestring(ls)).ok())
+                    .collect(),
+            );
+        }
+    }
+    Polygon::buggy_new(outer)
+}
+
+fn from_linestring(ls: geo::LineString<f64>) -> Vec<Pt2D> {
+    ls.into_points()
+        .into_iter()
+        .map(|pt| Pt2D::new(pt.x(), pt.y()))
+        .collect()
 }
 
 fn from_multi(multi: geo::MultiPolygon<f64>) -> Vec<Polygon> {
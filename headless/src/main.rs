@@ -15,7 +15,8 @@ use hyper::{Body, Request, Response, Server};
 use map_model::{CompressedTurnGroupID, ControlTrafficSignal, IntersectionID, Map, TurnGroupID};
 use serde::Serialize;
 use sim::{
-    AlertHandler, GetDrawAgents, PersonID, Sim, SimFlags, SimOptions, TripID, TripMode, VehicleType,
+    AlertHandler, CalibrationReport, GetDrawAgents, ObservedCounts, PersonID, Scenario,
+    ScenarioModifier, Sim, SimFlags, SimOptions, TripID, TripMode, VehicleType,
 };
 use std::collections::{BTreeMap, HashMap};
 use std::convert::TryFrom;
@@ -94,6 +95,23 @@ fn handle_command(
             *sim = new_sim;
             Ok(format!("sim reloaded"))
         }
+        // Like reset, but scale the number of people in the scenario. Keeps the current map,
+        // including any traffic signal changes.
+        "/sim/reset-scaled" => {
+            let pct = params["pct"].parse::<usize>()?;
+            let flags = FLAGS.read().unwrap();
+            if !flags.load.starts_with(&abstutil::path("system/scenarios/")) {
+                return Err(format!("{} isn't a scenario", flags.load).into());
+            }
+            let mut timer = Timer::new("reset sim with scaled scenario");
+            let scenario: Scenario = abstutil::read_binary(flags.load.clone(), &mut timer);
+            let mut rng = flags.make_rng();
+            let scenario = ScenarioModifier::ScalePeople(pct).apply(map, scenario, &mut rng);
+            let mut new_sim = Sim::new(map, flags.opts.clone(), &mut timer);
+            scenario.instantiate(&mut new_sim, map, &mut rng, &mut timer);
+            *sim = new_sim;
+            Ok(format!("sim reloaded with {}% of people", pct))
+        }
         "/sim/get-time" => Ok(sim.time().to_string()),
         "/sim/goto-time" => {
             let t = Time::parse(&params["t"])?;
@@ -178,6 +196,12 @@ fn handle_command(
         "/data/get-finished-trips" => Ok(abstutil::to_json(&FinishedTrips {
             trips: sim.get_analytics().finished_trips.clone(),
         })),
+        // The body is ObservedCounts
+        "/data/calibrate" => {
+            let observed: ObservedCounts = abstutil::from_json(body)?;
+            let report = CalibrationReport::new(&observed, sim.get_analytics(), map)?;
+            Ok(abstutil::to_json(&report))
+        }
         "/data/get-agent-positions" => Ok(abstutil::to_json(&AgentPositions {
             agents: sim
                 .get_unzoomed_agents(map)
@@ -0,0 +1,131 @@
+use convert_osm::{OnstreetParking, PrivateOffstreetParking, PublicOffstreetParking};
+use map_model::MapConfig;
+use serde::Deserialize;
+use std::collections::{BTreeMap, BTreeSet};
+
+// Everything needed to import one city, read from importer/config/$city.json. Each map in the city
+// is defined by a clipping polygon in data/input/$city/polygons/.
+//
+// All paths are relative to data/. Anywhere a URL is expected, a local file path works too, so
+// imports can run offline. Since JSON has no comments, the reasons behind some settings are in
+// importer/config/README.md.
+#[derive(Deserialize)]
+pub struct CityConfig {
+    // A .osm or .osm.pbf file covering every map in the city. It's stored in
+    // data/input/$city/osm/.
+    pub osm_url: String,
+    // Maps that keep reading data/input/$city/osm/$map.osm instead of clipping osm_url, so
+    // upstream changes don't sneak in
+    #[serde(default)]
+    pub pinned_extracts: BTreeSet<String>,
+    // Other files to fetch before converting anything
+    #[serde(default)]
+    pub downloads: Vec<Download>,
+    #[serde(default)]
+    pub kml_downloads: Vec<KmlDownload>,
+
+    pub map_config: MapConfig,
+    pub onstreet_parking: OnstreetParking,
+    pub public_offstreet_parking: PublicOffstreetParking,
+    pub private_offstreet_parking: PrivateOffstreetParking,
+    // Keyed by map name, to override private_offstreet_parking
+    #[serde(default)]
+    pub private_offstreet_parking_per_map: BTreeMap<String, PrivateOffstreetParking>,
+    // An SRTM .hgt or GeoTIFF file, or a directory of them
+    pub elevation: Option<String>,
+    pub include_railroads: bool,
+    // After converting OSM, match population counts to planning areas. Berlin only.
+    pub correlate_population: Option<CorrelatePopulation>,
+
+    // Where travel demand comes from. If missing, --scenario isn't supported for this city.
+    pub scenarios: Option<ScenarioSource>,
+}
+
+#[derive(Deserialize)]
+pub struct Download {
+    pub output: String,
+    pub url: String,
+}
+
+// Clipped to a polygon and converted to ExtraShapes
+#[derive(Deserialize)]
+pub struct KmlDownload {
+    // Must end in .bin
+    pub output: String,
+    pub url: String,
+    pub clip: String,
+    // If false, keep shapes that're only partly in bounds
+    pub require_all_pts_in_bounds: bool,
+}
+
+#[derive(Deserialize)]
+pub struct CorrelatePopulation {
+    // ExtraShapes with a spatial_name attribute, usually from a KmlDownload
+    pub planning_areas: String,
+    // Semicolon-delimited, with a RAUMID and E_E column
+    pub csv: String,
+}
+
+#[derive(Deserialize)]
+pub enum ScenarioSource {
+    // From https://github.com/psrc/soundcast. Seattle only.
+    Soundcast {
+        // After generating scenarios for these maps, give buildings enough private parking for the
+        // cars that start there.
+        adjust_private_parking: Vec<String>,
+    },
+}
+
+impl CityConfig {
+    pub fn load(city: &str, timer: &mut abstutil::Timer) -> CityConfig {
+        let path = format!("importer/config/{}.json", city);
+        if !abstutil::file_exists(path.clone()) {
+            panic!("Unknown city {}; {} doesn't exist", city, path);
+        }
+        abstutil::read_json(path, timer)
+    }
+
+    // Where the OSM input winds up after downloading
+    pub fn osm_path(&self, city: &str) -> String {
+        format!(
+            "input/{}/osm/{}",
+            city,
+            self.osm_url
+                .trim_end_matches('/')
+                .rsplit('/')
+                .next()
+                .unwrap()
+        )
+    }
+
+    pub fn to_options(&self, city: &str, name: &str, osm_input: String) -> convert_osm::Options {
+        convert_osm::Options {
+            osm_input,
+            city_name: city.to_string(),
+            name: name.to_string(),
+
+            clip: Some(abstutil::path(format!("input/{}/polygons/{}.poly", city, name))),
+            map_config: self.map_config.clone(),
+
+            onstreet_parking: match self.onstreet_parking {
+                OnstreetParking::Blockface(ref path) => {
+                    OnstreetParking::Blockface(abstutil::path(path))
+                }
+                ref x => x.clone(),
+            },
+            public_offstreet_parking: match self.public_offstreet_parking {
+                PublicOffstreetParking::GIS(ref path) => {
+                    PublicOffstreetParking::GIS(abstutil::path(path))
+                }
+                ref x => x.clone(),
+            },
+            private_offstreet_parking: self
+                .private_offstreet_parking_per_map
+                .get(name)
+                .unwrap_or(&self.private_offstreet_parking)
+                .clone(),
+            elevation: self.elevation.as_ref().map(abstutil::path),
+            include_railroads: self.include_railroads,
+        }
+    }
+}
@@ -1,11 +1,11 @@
 mod berlin;
-mod krakow;
+mod config;
 mod seattle;
 #[cfg(feature = "scenarios")]
 mod soundcast;
-mod tel_aviv;
 mod utils;
-mod xian;
+
+use crate::config::{CityConfig, ScenarioSource};
 
 // TODO Might be cleaner to express as a dependency graph?
 
@@ -83,9 +83,13 @@ fn main() {
     };
 
     let mut timer = abstutil::Timer::new("import map data");
+    let config = CityConfig::load(&job.city, &mut timer);
 
     let (maybe_popdat, maybe_huge_map) = if job.scenario || job.scenario_everyone {
-        assert_eq!(job.city, "seattle");
+        match config.scenarios {
+            Some(ScenarioSource::Soundcast { .. }) => {}
+            None => panic!("{} has no scenario source configured", job.city),
+        }
 
         #[cfg(feature = "scenarios")]
         {
@@ -108,13 +112,15 @@ fn main() {
 
     for name in names {
         if job.osm_to_raw {
-            match job.city.as_ref() {
-                "berlin" => berlin::osm_to_raw(&name, &mut timer),
-                "krakow" => krakow::osm_to_raw(&name, &mut timer),
-                "seattle" => seattle::osm_to_raw(&name, &mut timer),
-                "tel_aviv" => tel_aviv::osm_to_raw(&name, &mut timer),
-                "xian" => xian::osm_to_raw(&name, &mut timer),
-                x => panic!("Unknown city {}", x),
+            utils::osm_to_raw(&job.city, &name, &config, &mut timer);
+
+            if let Some(ref pop) = config.correlate_population {
+                // Always do this, it's idempotent and fast
+                berlin::correlate_population(
+                    &abstutil::path(&pop.planning_areas),
+                    &abstutil::path(&pop.csv),
+                    &mut timer,
+                );
             }
         }
 
@@ -158,10 +164,15 @@ fn main() {
             timer.stop(format!("scenario for {}", name));
 
             // This is a strange ordering.
-            if name == "downtown" || name == "south_seattle" {
-                timer.start(format!("adjust parking for {}", name));
-                seattle::adjust_private_parking(maybe_map.as_mut().unwrap(), &scenario);
-                timer.stop(format!("adjust parking for {}", name));
+            if let Some(ScenarioSource::Soundcast {
+                ref adjust_private_parking,
+            }) = config.scenarios
+            {
+                if adjust_private_parking.contains(&name) {
+                    timer.start(format!("adjust parking for {}", name));
+                    seattle::adjust_private_parking(maybe_map.as_mut().unwrap(), &scenario);
+                    timer.stop(format!("adjust parking for {}", name));
+                }
             }
         }
 
@@ -182,7 +193,9 @@ fn main() {
 fn oneshot(osm_path: String, clip: Option<String>, drive_on_right: bool, build_ch: bool) {
     let mut timer = abstutil::Timer::new("oneshot");
     println!("- Running convert_osm on {}", osm_path);
-    let name = abstutil::basename(&osm_path);
+    let name = abstutil::basename(&osm_path)
+        .trim_end_matches(".osm")
+        .to_string();
     let raw = convert_osm::convert(
         convert_osm::Options {
             osm_input: osm_path,
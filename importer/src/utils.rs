@@ -1,9 +1,10 @@
+use crate::config::CityConfig;
 use abstutil::Timer;
 use std::path::Path;
 use std::process::Command;
 
 // If the output file doesn't already exist, downloads the URL into that location. Automatically
-// uncompresses .zip and .gz files.
+// uncompresses .zip and .gz files. The URL can also be a local file.
 pub fn download(output: &str, url: &str) {
     let output = abstutil::path(output);
     if Path::new(&output).exists() {
@@ -16,12 +17,7 @@ pub fn download(output: &str, url: &str) {
 
     let tmp = "tmp_output";
     println!("- Missing {}, so downloading {}", output, url);
-    run(Command::new("curl")
-        .arg("--fail")
-        .arg("-L")
-        .arg("-o")
-        .arg(tmp)
-        .arg(url));
+    fetch(url, tmp);
 
     // Argh the Dropbox URL is .zip?dl=0
     if url.contains(".zip") {
@@ -52,6 +48,7 @@ pub fn download_kml(
     timer: &mut Timer,
 ) {
     assert!(url.ends_with(".kml"));
+    assert!(output.ends_with(".bin"));
     let output = abstutil::path(output);
     if Path::new(&output).exists() {
         println!("- {} already exists", output);
@@ -66,12 +63,7 @@ pub fn download_kml(
         std::fs::copy(output.replace(".bin", ".kml"), tmp).unwrap();
     } else {
         println!("- Missing {}, so downloading {}", output, url);
-        run(Command::new("curl")
-            .arg("--fail")
-            .arg("-L")
-            .arg("-o")
-            .arg(tmp)
-            .arg(url));
+        fetch(url, tmp);
     }
 
     println!("- Extracting KML data");
@@ -83,24 +75,35 @@ pub fn download_kml(
     std::fs::rename(tmp, output.replace(".bin", ".kml")).unwrap();
 }
 
-// Uses osmconvert to clip the input .osm (or .pbf) against a polygon and produce some output.
-// Skips if the output exists.
-pub fn osmconvert(input: &str, clipping_polygon: String, output: String) {
-    let input = abstutil::path(input);
-    let clipping_polygon = abstutil::path(clipping_polygon);
-    let output = abstutil::path(output);
-
-    if Path::new(&output).exists() {
-        println!("- {} already exists", output);
-        return;
+// Maps with a pinned .osm extract keep using it, so upstream changes don't sneak in. Otherwise read
+// straight from the city's OSM input; convert_osm only keeps what's inside the clipping polygon.
+pub fn osm_input(city: &str, name: &str, config: &CityConfig) -> String {
+    if config.pinned_extracts.contains(name) {
+        let extract = abstutil::path(format!("input/{}/osm/{}.osm", city, name));
+        if !Path::new(&extract).exists() {
+            panic!("{} is pinned to {}, which doesn't exist", name, extract);
+        }
+        println!("- Using pinned extract {}", extract);
+        return extract;
     }
-    println!("- Clipping {} to {}", input, clipping_polygon);
+    abstutil::path(config.osm_path(city))
+}
 
-    run(Command::new("osmconvert")
-        .arg(input)
-        .arg(format!("-B={}", clipping_polygon))
-        .arg("--complete-ways")
-        .arg(format!("-o={}", output)));
+// Copies local files instead of downloading, so imports can run offline
+fn fetch(url: &str, tmp: &str) {
+    if url.starts_with("http://") || url.starts_with("https://") {
+        run(Command::new("curl")
+            .arg("--fail")
+            .arg("-L")
+            .arg("-o")
+            .arg(tmp)
+            .arg(url));
+    } else {
+        let path = url.trim_start_matches("file://");
+        if let Err(err) = std::fs::copy(path, tmp) {
+            panic!("Couldn't copy {}: {}", path, err);
+        }
+    }
 }
 
 // Runs a command, asserts success. STDOUT and STDERR aren't touched.
@@ -118,6 +121,38 @@ fn run(cmd: &mut Command) {
     }
 }
 
+// Fetches everything the city needs. Files that already exist are skipped, so this is cheap to
+// repeat.
+pub fn input(city: &str, config: &CityConfig, timer: &mut Timer) {
+    download(&config.osm_path(city), &config.osm_url);
+    for dl in &config.downloads {
+        download(&dl.output, &dl.url);
+    }
+    for dl in &config.kml_downloads {
+        let bounds = geom::GPSBounds::from(
+            geom::LonLat::read_osmosis_polygon(abstutil::path(&dl.clip)).unwrap(),
+        );
+        download_kml(
+            &dl.output,
+            &dl.url,
+            &bounds,
+            dl.require_all_pts_in_bounds,
+            timer,
+        );
+    }
+}
+
+// Converts OSM to a RawMap, clipping to the map's polygon.
+pub fn osm_to_raw(city: &str, name: &str, config: &CityConfig, timer: &mut Timer) {
+    input(city, config, timer);
+    let map = convert_osm::convert(
+        config.to_options(city, name, osm_input(city, name, config)),
+        timer,
+    );
+    let output = abstutil::path(format!("input/raw_maps/{}.bin", name));
+    abstutil::write_binary(output, &map);
+}
+
 // Converts a RawMap to a Map.
 pub fn raw_to_map(name: &str, build_ch: bool, timer: &mut Timer) -> map_model::Map {
     timer.start(format!("Raw->Map for {}", name));
@@ -1,6 +1,6 @@
 use abstutil::{CmdArgs, Timer};
 use geom::{Distance, FindClosest, LonLat, Pt2D, Time};
-use map_model::Map;
+use map_model::{Aggregation, AttributeTable, JoinOptions, JoinTarget, Map, MapEdits};
 use serde::Deserialize;
 use sim::{IndividTrip, PersonID, PersonSpec, Scenario, SpawnTrip, TripEndpoint, TripMode};
 
@@ -16,7 +16,36 @@ fn main() {
             println!("{}", abstutil::to_json(&map));
             args.done();
         }
-        x => panic!("Unknown command {}. Try: import_traffic, dump_map", x),
+        "migrate_edits" => {
+            migrate_edits(
+                args.required("--map"),
+                args.required("--edits"),
+                args.enabled("--save"),
+            );
+            args.done();
+        }
+        "export_osmchange" => {
+            export_osmchange(
+                args.required("--map"),
+                args.required("--edits"),
+                args.required("--osm"),
+                args.required("--out"),
+            );
+            args.done();
+        }
+        "export_geojson" => {
+            export_geojson(args.required("--map"), args.required("--output_dir"));
+            args.done();
+        }
+        "join_attributes" => {
+            join_attributes(&mut args);
+            args.done();
+        }
+        x => panic!(
+            "Unknown command {}. Try: import_traffic, dump_map, migrate_edits, export_osmchange, \
+             export_geojson, join_attributes",
+            x
+        ),
     }
 }
 
@@ -69,6 +98,114 @@ fn import_traffic(map: String, input: String) {
     s.save();
 }
 
+// Match up edits made against an older version of the map, and report what couldn't be kept. With
+// --save, overwrite the edits with whatever survived.
+fn migrate_edits(map: String, edits: String, save: bool) {
+    let mut timer = Timer::new("migrate edits");
+    let mut map = Map::new(map, &mut timer);
+    let (edits, report) = match MapEdits::load_and_migrate(&map, edits.clone(), &mut timer) {
+        Ok(pair) => pair,
+        Err(err) => panic!("Couldn't load {}: {}", edits, err),
+    };
+    println!("{}", report);
+    if save {
+        map.must_apply_edits(edits, &mut timer);
+        map.save_edits();
+    }
+}
+
+// Express edits as an osmChange file against the .osm or .osm.pbf the map was imported from, so
+// they can be reviewed and uploaded with JOSM or similar.
+fn export_osmchange(map: String, edits: String, osm: String, out: String) {
+    let mut timer = Timer::new("export edits to osmChange");
+    let mut map = Map::new(map, &mut timer);
+    let edits = match MapEdits::load(&map, edits.clone(), &mut timer) {
+        Ok(e) => e,
+        Err(err) => panic!("Couldn't load {}: {}", edits, err),
+    };
+    map.must_apply_edits(edits, &mut timer);
+    let xml = match convert_osm::edits_to_osmchange(&map, &osm, &mut timer) {
+        Ok(xml) => xml,
+        Err(err) => panic!("Couldn't export edits: {}", err),
+    };
+    std::fs::write(&out, xml).unwrap();
+    println!("Wrote {}", out);
+}
+
+// One GeoJSON file per layer of the map
+fn export_geojson(map: String, output_dir: String) {
+    let mut timer = Timer::new("export map to GeoJSON");
+    let map = Map::new(map, &mut timer);
+    std::fs::create_dir_all(&output_dir).unwrap();
+    for (layer, fc) in map.export_geojson(&mut timer) {
+        abstutil::write_json(
+            format!("{}/{}.geojson", output_dir, layer),
+            &geojson::GeoJson::from(fc),
+        );
+    }
+}
+
+// Snap features from a GeoJSON or CSV file onto map objects and save the chosen attributes as a
+// table the game and sim can load.
+fn join_attributes(args: &mut CmdArgs) {
+    let map = args.required("--map");
+    let input = args.required("--input");
+    let name = args.required("--name");
+    let target = match args.required("--target").as_ref() {
+        "roads" => JoinTarget::Roads,
+        "intersections" => JoinTarget::Intersections,
+        "buildings" => JoinTarget::Buildings,
+        x => panic!("--target={} isn't roads, intersections, or buildings", x),
+    };
+    let aggregation = match args.optional("--aggregate").as_deref() {
+        None | Some("first") => Aggregation::First,
+        Some("sum") => Aggregation::Sum,
+        Some("mean") => Aggregation::Mean,
+        Some("max") => Aggregation::Max,
+        Some(x) => panic!("--aggregate={} isn't first, sum, mean, or max", x),
+    };
+    let max_dist = Distance::meters(
+        args.optional("--max_dist_meters")
+            .map(|x| x.parse::<f64>().unwrap())
+            .unwrap_or(50.0),
+    );
+    let columns = args
+        .optional("--columns")
+        .map(|x| x.split(',').map(|c| c.to_string()).collect())
+        .unwrap_or_else(Vec::new);
+    let lon_column = args.optional("--lon_column");
+    let lat_column = args.optional("--lat_column");
+
+    let mut timer = Timer::new("join attributes");
+    let map = Map::new(map, &mut timer);
+    let features = if input.ends_with(".csv") {
+        kml::load_csv(
+            &input,
+            lon_column.as_deref().unwrap_or("longitude"),
+            lat_column.as_deref().unwrap_or("latitude"),
+            &mut timer,
+        )
+    } else {
+        kml::load_geojson(&input, &mut timer)
+    }
+    .unwrap();
+
+    let (table, report) = AttributeTable::join(
+        name,
+        &map,
+        &features,
+        &JoinOptions {
+            target,
+            max_dist,
+            columns,
+            aggregation,
+        },
+        &mut timer,
+    );
+    print!("{}", report);
+    table.save();
+}
+
 #[derive(Deserialize)]
 struct Input {
     scenario_name: String,
@@ -110,6 +110,141 @@ pub fn load(
     Ok(ExtraShapes { shapes })
 }
 
+// One feature from a GeoJSON file or one row from a CSV file. Features aren't checked against
+// the map's bounds here, so that callers can report what they skip.
+#[derive(Debug)]
+pub struct Feature {
+    // Position of the feature or row in the source file, starting from 0
+    pub idx: usize,
+    // Multi* geometries have one part per member. Empty if the feature has no geometry.
+    pub parts: Vec<Vec<LonLat>>,
+    pub attributes: BTreeMap<String, String>,
+}
+
+// Every feature in a FeatureCollection, with its properties as attributes. Non-string properties
+// are stringified. Only the outer ring of polygons is kept.
+pub fn load_geojson(path: &str, timer: &mut Timer) -> Result<Vec<Feature>, Box<dyn Error>> {
+    timer.start(format!("read {}", path));
+    let bytes = abstutil::slurp_file(path)?;
+    let raw_string = std::str::from_utf8(&bytes)?;
+    let geojson = raw_string.parse::<geojson::GeoJson>()?;
+    timer.stop(format!("read {}", path));
+
+    let features = match geojson {
+        geojson::GeoJson::FeatureCollection(fc) => fc.features,
+        geojson::GeoJson::Feature(f) => vec![f],
+        geojson::GeoJson::Geometry(_) => {
+            return Err(format!("{} has a bare geometry, not features", path).into());
+        }
+    };
+
+    let mut results = Vec::new();
+    for (idx, feature) in features.into_iter().enumerate() {
+        let mut attributes = BTreeMap::new();
+        if let Some(props) = feature.properties {
+            for (k, v) in props {
+                let v = match v {
+                    serde_json::Value::String(s) => s,
+                    serde_json::Value::Null => continue,
+                    x => x.to_string(),
+                };
+                attributes.insert(k, v);
+            }
+        }
+        results.push(Feature {
+            idx,
+            parts: feature
+                .geometry
+                .map(|geom| geometry_parts(geom.value))
+                .unwrap_or_else(Vec::new),
+            attributes,
+        });
+    }
+
+    timer.note(format!(
+        "Got {} features from {}",
+        prettyprint_usize(results.len()),
+        path
+    ));
+    Ok(results)
+}
+
+fn geometry_parts(geom: geojson::Value) -> Vec<Vec<LonLat>> {
+    let convert = |pts: Vec<Vec<f64>>| {
+        pts.into_iter()
+            .map(|pt| LonLat::new(pt[0], pt[1]))
+            .collect()
+    };
+    match geom {
+        geojson::Value::Point(pt) => vec![convert(vec![pt])],
+        geojson::Value::MultiPoint(pts) => pts.into_iter().map(|pt| convert(vec![pt])).collect(),
+        geojson::Value::LineString(pts) => vec![convert(pts)],
+        geojson::Value::MultiLineString(lines) => lines.into_iter().map(convert).collect(),
+        geojson::Value::Polygon(mut rings) => vec![convert(rings.remove(0))],
+        geojson::Value::MultiPolygon(polygons) => polygons
+            .into_iter()
+            .map(|mut rings| convert(rings.remove(0)))
+            .collect(),
+        geojson::Value::GeometryCollection(_) => Vec::new(),
+    }
+}
+
+// Each row becomes a point, using the named longitude and latitude columns. The other columns
+// become attributes.
+pub fn load_csv(
+    path: &str,
+    lon_column: &str,
+    lat_column: &str,
+    timer: &mut Timer,
+) -> Result<Vec<Feature>, Box<dyn Error>> {
+    timer.start(format!("read {}", path));
+    let mut reader = csv::Reader::from_path(path)?;
+    let headers = reader.headers()?.clone();
+    let lon_idx = headers
+        .iter()
+        .position(|h| h == lon_column)
+        .ok_or_else(|| format!("{} has no {} column", path, lon_column))?;
+    let lat_idx = headers
+        .iter()
+        .position(|h| h == lat_column)
+        .ok_or_else(|| format!("{} has no {} column", path, lat_column))?;
+
+    let mut results = Vec::new();
+    for (idx, rec) in reader.records().enumerate() {
+        let rec = rec?;
+        let pt = match (
+            rec.get(lon_idx).and_then(|x| x.trim().parse::<f64>().ok()),
+            rec.get(lat_idx).and_then(|x| x.trim().parse::<f64>().ok()),
+        ) {
+            (Some(lon), Some(lat)) => LonLat::new(lon, lat),
+            _ => {
+                return Err(
+                    format!("Row {} of {} has malformed coordinates", idx + 1, path).into(),
+                );
+            }
+        };
+        let mut attributes = BTreeMap::new();
+        for (col, (k, v)) in headers.iter().zip(rec.iter()).enumerate() {
+            if col != lon_idx && col != lat_idx && !v.is_empty() {
+                attributes.insert(k.to_string(), v.to_string());
+            }
+        }
+        results.push(Feature {
+            idx,
+            parts: vec![vec![pt]],
+            attributes,
+        });
+    }
+    timer.stop(format!("read {}", path));
+
+    timer.note(format!(
+        "Got {} rows from {}",
+        prettyprint_usize(results.len()),
+        path
+    ));
+    Ok(results)
+}
+
 fn parse_pt(input: &str) -> Option<LonLat> {
     let coords: Vec<&str> = input.split(',').collect();
     if coords.len() != 2 {
@@ -120,3 +255,43 @@ fn parse_pt(input: &str) -> Option<LonLat> {
         _ => None,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_geometry_parts() {
+        let multi_pt = geometry_parts(geojson::Value::MultiPoint(vec![
+            vec![1.0, 2.0],
+            vec![3.0, 4.0],
+        ]));
+        assert_eq!(
+            multi_pt,
+            vec![vec![LonLat::new(1.0, 2.0)], vec![LonLat::new(3.0, 4.0)]]
+        );
+
+        let line = geometry_parts(geojson::Value::LineString(vec![
+            vec![1.0, 2.0],
+            vec![3.0, 4.0],
+        ]));
+        assert_eq!(
+            line,
+            vec![vec![LonLat::new(1.0, 2.0), LonLat::new(3.0, 4.0)]]
+        );
+
+        // Holes are dropped
+        let square = vec![
+            vec![0.0, 0.0],
+            vec![1.0, 0.0],
+            vec![1.0, 1.0],
+            vec![0.0, 0.0],
+        ];
+        let polygons = geometry_parts(geojson::Value::MultiPolygon(vec![
+            vec![square.clone(), square.clone()],
+            vec![square],
+        ]));
+        assert_eq!(polygons.len(), 2);
+        assert_eq!(polygons[0].len(), 4);
+    }
+}
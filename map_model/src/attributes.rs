@@ -0,0 +1,280 @@
+use crate::{BuildingID, IntersectionID, Map, RoadID};
+use abstutil::{deserialize_btreemap, serialize_btreemap, Timer};
+use geom::{Distance, FindClosest, PolyLine, Pt2D};
+use kml::Feature;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+
+// External data (traffic counts, crashes, land use, ...) joined onto map objects. Each table
+// belongs to one map and describes one kind of object.
+#[derive(Serialize, Deserialize)]
+pub struct AttributeTable {
+    pub name: String,
+    pub map_name: String,
+    pub columns: BTreeMap<String, AttributeType>,
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    pub values: BTreeMap<AttributeKey, BTreeMap<String, AttributeValue>>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum AttributeKey {
+    Road(RoadID),
+    Intersection(IntersectionID),
+    Building(BuildingID),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum AttributeType {
+    Number,
+    Text,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum AttributeValue {
+    Number(f64),
+    Text(String),
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum JoinTarget {
+    Roads,
+    Intersections,
+    Buildings,
+}
+
+// When multiple features snap to the same object, how to combine numeric values. Text always
+// keeps the first value.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Aggregation {
+    Sum,
+    Mean,
+    Max,
+    First,
+}
+
+pub struct JoinOptions {
+    pub target: JoinTarget,
+    // Features further away than this from any object are unmatched
+    pub max_dist: Distance,
+    // If empty, keep all attributes
+    pub columns: Vec<String>,
+    pub aggregation: Aggregation,
+}
+
+pub struct JoinReport {
+    pub num_features: usize,
+    pub matched: usize,
+    // (Index of the feature or row in the source file, why it didn't match)
+    pub unmatched: Vec<(usize, String)>,
+}
+
+impl AttributeTable {
+    pub fn join(
+        name: String,
+        map: &Map,
+        features: &[Feature],
+        opts: &JoinOptions,
+        timer: &mut Timer,
+    ) -> (AttributeTable, JoinReport) {
+        let mut closest: FindClosest<AttributeKey> = FindClosest::new(map.get_bounds());
+        match opts.target {
+            JoinTarget::Roads => {
+                for r in map.all_roads() {
+                    closest.add(AttributeKey::Road(r.id), r.center_pts.points());
+                }
+            }
+            JoinTarget::Intersections => {
+                for i in map.all_intersections() {
+                    closest.add(AttributeKey::Intersection(i.id), i.polygon.points());
+                }
+            }
+            JoinTarget::Buildings => {
+                for b in map.all_buildings() {
+                    closest.add(AttributeKey::Building(b.id), b.polygon.points());
+                }
+            }
+        }
+
+        let mut report = JoinReport {
+            num_features: features.len(),
+            matched: 0,
+            unmatched: Vec::new(),
+        };
+        // Group the raw values per object first, so the column types can be figured out
+        let mut raw: BTreeMap<AttributeKey, Vec<&BTreeMap<String, String>>> = BTreeMap::new();
+        let gps_bounds = map.get_gps_bounds();
+        timer.start_iter("snap features", features.len());
+        for feature in features {
+            timer.next();
+            // Each feature is joined to one object, even if it has many parts. Otherwise summing
+            // would count it multiple times.
+            let mut any_usable = false;
+            let mut any_in_bounds = false;
+            let mut best: Option<(AttributeKey, Distance)> = None;
+            for part in &feature.parts {
+                let query = match snap_point(&gps_bounds.convert(part)) {
+                    Some(pt) => pt,
+                    None => {
+                        continue;
+                    }
+                };
+                any_usable = true;
+                if !part.iter().any(|pt| gps_bounds.contains(*pt)) {
+                    continue;
+                }
+                any_in_bounds = true;
+                for (key, _, dist) in closest.all_close_pts(query, opts.max_dist) {
+                    if best.map(|(_, d)| dist < d).unwrap_or(true) {
+                        best = Some((key, dist));
+                    }
+                }
+            }
+
+            if let Some((key, _)) = best {
+                report.matched += 1;
+                raw.entry(key)
+                    .or_insert_with(Vec::new)
+                    .push(&feature.attributes);
+            } else if !any_usable {
+                report
+                    .unmatched
+                    .push((feature.idx, "geometry has no usable points".to_string()));
+            } else if !any_in_bounds {
+                report
+                    .unmatched
+                    .push((feature.idx, "outside the map".to_string()));
+            } else {
+                report
+                    .unmatched
+                    .push((feature.idx, format!("nothing within {}", opts.max_dist)));
+            }
+        }
+
+        let mut columns: BTreeMap<String, AttributeType> = BTreeMap::new();
+        for attribs in raw.values().flatten() {
+            for (k, v) in attribs.iter() {
+                if !opts.columns.is_empty() && !opts.columns.contains(k) {
+                    continue;
+                }
+                let is_number = v.parse::<f64>().is_ok();
+                let entry = columns.entry(k.clone()).or_insert(if is_number {
+                    AttributeType::Number
+                } else {
+                    AttributeType::Text
+                });
+                if !is_number {
+                    *entry = AttributeType::Text;
+                }
+            }
+        }
+        for c in &opts.columns {
+            if !columns.contains_key(c) {
+                timer.warn(format!("No matched feature has a {} attribute", c));
+            }
+        }
+
+        let mut values = BTreeMap::new();
+        for (key, list) in raw {
+            let mut row = BTreeMap::new();
+            for (col, col_type) in &columns {
+                let present: Vec<&String> = list.iter().filter_map(|a| a.get(col)).collect();
+                if present.is_empty() {
+                    continue;
+                }
+                let value = match col_type {
+                    AttributeType::Text => AttributeValue::Text(present[0].clone()),
+                    AttributeType::Number => {
+                        let nums: Vec<f64> =
+                            present.iter().map(|x| x.parse::<f64>().unwrap()).collect();
+                        AttributeValue::Number(match opts.aggregation {
+                            Aggregation::Sum => nums.iter().sum(),
+                            Aggregation::Mean => nums.iter().sum::<f64>() / (nums.len() as f64),
+                            Aggregation::Max => {
+                                nums.iter().cloned().fold(std::f64::NEG_INFINITY, f64::max)
+                            }
+                            Aggregation::First => nums[0],
+                        })
+                    }
+                };
+                row.insert(col.clone(), value);
+            }
+            values.insert(key, row);
+        }
+
+        (
+            AttributeTable {
+                name,
+                map_name: map.get_name().to_string(),
+                columns,
+                values,
+            },
+            report,
+        )
+    }
+
+    pub fn get(&self, key: AttributeKey, column: &str) -> Option<&AttributeValue> {
+        self.values.get(&key)?.get(column)
+    }
+
+    pub fn get_number(&self, key: AttributeKey, column: &str) -> Option<f64> {
+        match self.get(key, column)? {
+            AttributeValue::Number(x) => Some(*x),
+            AttributeValue::Text(_) => None,
+        }
+    }
+
+    pub fn load(map_name: &str, table_name: &str, timer: &mut Timer) -> AttributeTable {
+        abstutil::read_json(abstutil::path_attributes(map_name, table_name), timer)
+    }
+
+    // Every table joined onto this map
+    pub fn load_all(map_name: &str) -> Vec<AttributeTable> {
+        abstutil::load_all_objects(abstutil::path_all_attributes(map_name))
+            .into_iter()
+            .map(|(_, table)| table)
+            .collect()
+    }
+
+    pub fn save(&self) {
+        abstutil::write_json(abstutil::path_attributes(&self.map_name, &self.name), self);
+    }
+}
+
+impl fmt::Display for AttributeValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AttributeValue::Number(x) => write!(f, "{}", x),
+            AttributeValue::Text(x) => write!(f, "{}", x),
+        }
+    }
+}
+
+impl fmt::Display for JoinReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "Matched {} / {} features",
+            abstutil::prettyprint_usize(self.matched),
+            abstutil::prettyprint_usize(self.num_features)
+        )?;
+        for (idx, reason) in &self.unmatched {
+            writeln!(f, "- feature {} unmatched: {}", idx, reason)?;
+        }
+        Ok(())
+    }
+}
+
+// Where a feature gets snapped from. Endpoints of lines tend to be close to other objects, so like
+// the blockface matching, use the middle. Polygons use their center.
+fn snap_point(pts: &[Pt2D]) -> Option<Pt2D> {
+    match pts.len() {
+        0 => None,
+        1 => Some(pts[0]),
+        n if n > 3 && pts[0] == pts[n - 1] => Some(Pt2D::center(&pts.to_vec())),
+        _ => PolyLine::new(pts.to_vec()).ok().map(|pl| pl.middle()),
+    }
+}
@@ -25,6 +25,9 @@ pub fn upgrade(mut value: Value) -> Result<PermanentMapEdits, String> {
     if value["version"] == Value::Number(0.into()) {
         fix_road_direction(&mut value);
     }
+    if value["version"] == Value::Number(0.into()) || value["version"] == Value::Number(1.into()) {
+        fix_parking_rules(&mut value);
+    }
 
     abstutil::from_json(&value.to_string().into_bytes()).map_err(|x| x.to_string())
 }
@@ -107,3 +110,38 @@ fn fix_road_direction(value: &mut Value) {
         }
     });
 }
+
+// Parking prices became more general ParkingRules, so ChangeParkingLanePrice and
+// ChangeParkingLotPrice turned into ChangeLaneParkingRules and ChangeLotParkingRules.
+fn fix_parking_rules(value: &mut Value) {
+    walk(value, &|map| {
+        for (old_name, new_name) in vec![
+            ("ChangeParkingLanePrice", "ChangeLaneParkingRules"),
+            ("ChangeParkingLotPrice", "ChangeLotParkingRules"),
+        ] {
+            if map.len() == 1 && map.contains_key(old_name) {
+                let mut cmd = map.remove(old_name).unwrap();
+                let cmd_map = cmd.as_object_mut().unwrap();
+                for key in vec!["new", "old"] {
+                    let price = cmd_map[key].clone();
+                    cmd_map.insert(
+                        key.to_string(),
+                        serde_json::json!({
+                            "rules": {
+                                "price_per_hour": price,
+                                "metered_hours": [],
+                                "max_duration": null,
+                                "no_parking": [],
+                                "permit_zone": null,
+                            },
+                            "permit_zone": null,
+                        }),
+                    );
+                }
+                map.insert(new_name.to_string(), cmd);
+                return true;
+            }
+        }
+        false
+    });
+}
@@ -0,0 +1,789 @@
+use crate::edits::perma::{PermanentEditCmd, PermanentEditIntersection, PermanentParkingRules};
+use crate::edits::{EditCmd, EditIntersection, MapEdits};
+use crate::raw::OriginalRoad;
+use crate::{
+    osm, ControlStopSign, Direction, IntersectionID, LaneID, LaneType, Map, OriginalLane,
+    ParkingRules, PermanentMapEdits, RoadID,
+};
+use geom::{Distance, Line, Pt2D};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+// When the basemap is re-imported from newer OSM data, the roads and intersections that edits
+// refer to can be split, merged, renumbered, or restructured. Rather than throwing away all of the
+// edits when one reference breaks, match each reference against the new map as well as possible,
+// keep whatever survives, and report everything else.
+
+// A road or intersection in the new map farther than this from where the old one was isn't a
+// match.
+const MAX_MATCH_DIST: Distance = Distance::const_meters(15.0);
+// If the two best candidates score within this of each other, the match is ambiguous.
+const AMBIGUITY_DIST: Distance = Distance::const_meters(1.0);
+// Prefer candidates from the same OSM way by this much.
+const DIFFERENT_WAY_PENALTY: Distance = Distance::const_meters(5.0);
+
+pub struct MigrationReport {
+    pub num_commands: usize,
+    // Commands that referred to something that's changed, but were confidently matched up anyway
+    pub fuzzy: Vec<MigrationNote>,
+    // Commands with several plausible matches. The best one was used, but somebody should double
+    // check the result.
+    pub ambiguous: Vec<MigrationNote>,
+    // Commands that couldn't be matched up at all
+    pub dropped: Vec<MigrationNote>,
+}
+
+pub struct MigrationNote {
+    // Index into the original list of commands
+    pub idx: usize,
+    pub cmd: String,
+    pub details: Vec<String>,
+}
+
+impl MigrationReport {
+    pub fn is_clean(&self) -> bool {
+        self.fuzzy.is_empty() && self.ambiguous.is_empty() && self.dropped.is_empty()
+    }
+
+    pub fn num_kept(&self) -> usize {
+        self.num_commands - self.dropped.len()
+    }
+}
+
+impl fmt::Display for MigrationReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "Kept {} / {} commands. {} fuzzy matches, {} ambiguous, {} dropped",
+            self.num_kept(),
+            self.num_commands,
+            self.fuzzy.len(),
+            self.ambiguous.len(),
+            self.dropped.len()
+        )?;
+        for (label, notes) in vec![
+            ("Fuzzy", &self.fuzzy),
+            ("Ambiguous", &self.ambiguous),
+            ("Dropped", &self.dropped),
+        ] {
+            for note in notes {
+                writeln!(f, "- {} #{} ({}):", label, note.idx, note.cmd)?;
+                for line in &note.details {
+                    writeln!(f, "    {}", line)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl PermanentMapEdits {
+    // Unlike from_permanent, this never fails outright. Commands that can't be matched up against
+    // the current map are dropped and described in the report.
+    pub fn migrate(perma: PermanentMapEdits, map: &Map) -> (MapEdits, MigrationReport) {
+        let mut matcher = Matcher {
+            map,
+            road_hints: perma
+                .road_hints
+                .iter()
+                .map(|(r, pts)| (*r, map.get_gps_bounds().convert(pts)))
+                .collect(),
+            intersection_hints: perma
+                .intersection_hints
+                .iter()
+                .map(|(i, pt)| (*i, Pt2D::from_gps(*pt, map.get_gps_bounds())))
+                .collect(),
+            fuzzy: Vec::new(),
+            ambiguous: Vec::new(),
+        };
+
+        let mut report = MigrationReport {
+            num_commands: perma.commands.len(),
+            fuzzy: Vec::new(),
+            ambiguous: Vec::new(),
+            dropped: Vec::new(),
+        };
+        let mut edits = MapEdits::new();
+        edits.edits_name = perma.edits_name;
+        edits.proposal_description = perma.proposal_description;
+        edits.proposal_link = perma.proposal_link;
+
+        // compress() produces at most one of these per lane. If two old lanes wind up matching the
+        // same new lane, only the first wins.
+        let mut lanes_changed: BTreeSet<LaneID> = BTreeSet::new();
+
+        for (idx, cmd) in perma.commands.into_iter().enumerate() {
+            let describe = cmd.describe();
+            matcher.fuzzy.clear();
+            matcher.ambiguous.clear();
+
+            match matcher.migrate_cmd(cmd, &mut lanes_changed) {
+                Ok(cmds) => {
+                    edits.commands.extend(cmds);
+                    if !matcher.ambiguous.is_empty() {
+                        let mut details = matcher.ambiguous.clone();
+                        details.extend(matcher.fuzzy.clone());
+                        report.ambiguous.push(MigrationNote {
+                            idx,
+                            cmd: describe,
+                            details,
+                        });
+                    } else if !matcher.fuzzy.is_empty() {
+                        report.fuzzy.push(MigrationNote {
+                            idx,
+                            cmd: describe,
+                            details: matcher.fuzzy.clone(),
+                        });
+                    }
+                }
+                Err(err) => {
+                    report.dropped.push(MigrationNote {
+                        idx,
+                        cmd: describe,
+                        details: vec![err],
+                    });
+                }
+            }
+        }
+
+        edits.update_derived(map);
+        (edits, report)
+    }
+}
+
+impl PermanentEditCmd {
+    fn describe(&self) -> String {
+        match self {
+            PermanentEditCmd::ChangeLaneType { id, lt, .. } => {
+                format!("change {:?} to a {}", id, lt.short_name())
+            }
+            PermanentEditCmd::ReverseLane { l, .. } => format!("reverse {:?}", l),
+            PermanentEditCmd::ChangeSpeedLimit { id, new, .. } => {
+                format!("limit {} for {}", new, id)
+            }
+            PermanentEditCmd::ChangeIntersection { i, new, .. } => match new {
+                PermanentEditIntersection::StopSign { .. } => format!("stop sign at {}", i),
+                PermanentEditIntersection::TrafficSignal(_) => format!("traffic signal at {}", i),
+                PermanentEditIntersection::Closed => format!("close {}", i),
+            },
+            PermanentEditCmd::ChangeAccessRestrictions { id, .. } => {
+                format!("access restrictions for {}", id)
+            }
+            PermanentEditCmd::ChangeRouteSchedule { osm_rel_id, .. } => {
+                format!("reschedule route {}", osm_rel_id)
+            }
+            PermanentEditCmd::ChangeLaneParkingRules { id, .. } => {
+                format!("parking rules for {:?}", id)
+            }
+            PermanentEditCmd::ChangeLotParkingRules { osm_id, .. } => {
+                format!("parking rules for parking lot {}", osm_id)
+            }
+            PermanentEditCmd::ChangeLaneSchedule { id, .. } => {
+                format!("time-of-day use of {:?}", id)
+            }
+        }
+    }
+}
+
+// One road from the current map matching an old reference
+struct RoadMatch {
+    id: RoadID,
+    // The old and new road point in opposite directions
+    flipped: bool,
+}
+
+struct Matcher<'a> {
+    map: &'a Map,
+    road_hints: BTreeMap<OriginalRoad, Vec<Pt2D>>,
+    intersection_hints: BTreeMap<osm::NodeID, Pt2D>,
+
+    // Notes about the command currently being migrated
+    fuzzy: Vec<String>,
+    ambiguous: Vec<String>,
+}
+
+impl<'a> Matcher<'a> {
+    fn migrate_cmd(
+        &mut self,
+        cmd: PermanentEditCmd,
+        lanes_changed: &mut BTreeSet<LaneID>,
+    ) -> Result<Vec<EditCmd>, String> {
+        let map = self.map;
+        let mut cmds = Vec::new();
+        match cmd {
+            PermanentEditCmd::ChangeLaneType { id, lt, orig_lt } => {
+                for r in self.roads(id.parent)? {
+                    let l = self.lane(&id, &r, Some(orig_lt))?;
+                    let now = map.get_l(l).lane_type;
+                    if now == lt {
+                        self.fuzzy.push(format!(
+                            "the basemap already has a {} at {}",
+                            lt.short_name(),
+                            l
+                        ));
+                        continue;
+                    }
+                    if now != orig_lt {
+                        return Err(format!(
+                            "basemap lanetype of {} has changed from {:?} to {:?}",
+                            l, orig_lt, now
+                        ));
+                    }
+                    if !lanes_changed.insert(l) {
+                        self.ambiguous
+                            .push(format!("{} was already changed by another command", l));
+                        continue;
+                    }
+                    cmds.push(EditCmd::ChangeLaneType { id: l, lt, orig_lt });
+                }
+            }
+            PermanentEditCmd::ReverseLane { l, .. } => {
+                for r in self.roads(l.parent)? {
+                    let lane = self.lane(&l, &r, None)?;
+                    // OriginalLane was recorded after reversing, so its direction is the intended
+                    // one.
+                    let road = map.get_r(r.id);
+                    let dir = if r.flipped { l.dir.opposite() } else { l.dir };
+                    let dst_i = if dir == Direction::Fwd {
+                        road.dst_i
+                    } else {
+                        road.src_i
+                    };
+                    cmds.push(EditCmd::ReverseLane { l: lane, dst_i });
+                }
+            }
+            PermanentEditCmd::ChangeSpeedLimit { id, new, old } => {
+                for r in self.roads(id)? {
+                    cmds.push(EditCmd::ChangeSpeedLimit { id: r.id, new, old });
+                }
+            }
+            PermanentEditCmd::ChangeIntersection { i, new, old } => {
+                let id = self.intersection(i)?;
+                if map.get_i(id).is_border() {
+                    return Err(format!("{} is now a border", i));
+                }
+                let new = self.edit_intersection(new, id)?;
+                let old = self.edit_intersection(old, id)?;
+                cmds.push(EditCmd::ChangeIntersection { i: id, new, old });
+            }
+            PermanentEditCmd::ChangeAccessRestrictions { id, new, old } => {
+                for r in self.roads(id)? {
+                    cmds.push(EditCmd::ChangeAccessRestrictions {
+                        id: r.id,
+                        new: new.clone(),
+                        old: old.clone(),
+                    });
+                }
+            }
+            PermanentEditCmd::ChangeRouteSchedule {
+                osm_rel_id,
+                old,
+                new,
+            } => {
+                let id = map
+                    .find_br(osm_rel_id)
+                    .ok_or(format!("can't find {}", osm_rel_id))?;
+                cmds.push(EditCmd::ChangeRouteSchedule { id, old, new });
+            }
+            PermanentEditCmd::ChangeLaneParkingRules { id, new, old } => {
+                let new = self.parking_rules(new);
+                let old = self.parking_rules(old);
+                for r in self.roads(id.parent)? {
+                    let l = self.lane(&id, &r, None)?;
+                    cmds.push(EditCmd::ChangeLaneParkingRules {
+                        id: l,
+                        new: new.clone(),
+                        old: old.clone(),
+                    });
+                }
+            }
+            PermanentEditCmd::ChangeLotParkingRules { osm_id, new, old } => {
+                let id = map
+                    .find_pl_by_osm_id(osm_id)
+                    .ok_or(format!("can't find parking lot {}", osm_id))?;
+                cmds.push(EditCmd::ChangeLotParkingRules {
+                    id,
+                    new: self.parking_rules(new),
+                    old: self.parking_rules(old),
+                });
+            }
+            PermanentEditCmd::ChangeLaneSchedule { id, new, old } => {
+                for r in self.roads(id.parent)? {
+                    let l = self.lane(&id, &r, None)?;
+                    cmds.push(EditCmd::ChangeLaneSchedule {
+                        id: l,
+                        new: new.clone(),
+                        old: old.clone(),
+                    });
+                }
+            }
+        }
+        Ok(cmds)
+    }
+
+    // If the old road was split, this may return multiple roads.
+    fn roads(&mut self, id: OriginalRoad) -> Result<Vec<RoadMatch>, String> {
+        let map = self.map;
+        if let Ok(r) = map.find_r_by_osm_id(id) {
+            return Ok(vec![RoadMatch {
+                id: r,
+                flipped: false,
+            }]);
+        }
+
+        let same_way: Vec<RoadID> = map
+            .all_roads()
+            .iter()
+            .filter(|r| r.orig_id.osm_way_id == id.osm_way_id)
+            .map(|r| r.id)
+            .collect();
+
+        let hint = if let Some(pts) = self.road_hints.get(&id) {
+            Some(pts.clone())
+        } else {
+            // Older edits don't have hints, but the endpoints might still be around.
+            match (
+                map.find_i_by_osm_id(id.i1).ok(),
+                map.find_i_by_osm_id(id.i2).ok(),
+            ) {
+                (Some(i1), Some(i2)) => Some(vec![
+                    map.get_i(i1).polygon.center(),
+                    map.get_i(i2).polygon.center(),
+                ]),
+                _ => None,
+            }
+        };
+
+        let hint = match hint {
+            Some(pts) => pts,
+            None => {
+                // No geometry to go off of; only the OSM way is left.
+                return match same_way.len() {
+                    0 => Err(format!("{} is gone, and no geometry was recorded", id)),
+                    1 => {
+                        self.fuzzy.push(format!(
+                            "{} matched to {} from the same OSM way",
+                            id, same_way[0]
+                        ));
+                        Ok(vec![RoadMatch {
+                            id: same_way[0],
+                            flipped: false,
+                        }])
+                    }
+                    n => {
+                        self.ambiguous.push(format!(
+                            "{} could be any of {} roads from the same OSM way; using {}",
+                            id, n, same_way[0]
+                        ));
+                        Ok(vec![RoadMatch {
+                            id: same_way[0],
+                            flipped: false,
+                        }])
+                    }
+                };
+            }
+        };
+
+        // Was the way split into pieces that all lie along the old road?
+        let pieces: Vec<RoadMatch> = same_way
+            .iter()
+            .filter(|r| {
+                map.get_r(**r)
+                    .center_pts
+                    .points()
+                    .iter()
+                    .all(|pt| dist_to_pts(*pt, &hint) <= MAX_MATCH_DIST)
+            })
+            .map(|r| RoadMatch {
+                id: *r,
+                flipped: is_flipped(&hint, map.get_r(*r).center_pts.points()),
+            })
+            .collect();
+        if !pieces.is_empty() {
+            self.fuzzy.push(format!(
+                "{} matched to {}",
+                id,
+                pieces
+                    .iter()
+                    .map(|r| r.id.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+            return Ok(pieces);
+        }
+
+        // Otherwise, find whatever road is closest to the old geometry.
+        let mut scores: Vec<(Distance, RoadID)> = map
+            .all_roads()
+            .iter()
+            .map(|r| {
+                let mut score = mean_dist(&hint, r.center_pts.points());
+                if r.orig_id.osm_way_id != id.osm_way_id {
+                    score += DIFFERENT_WAY_PENALTY;
+                }
+                (score, r.id)
+            })
+            .filter(|(score, _)| *score <= MAX_MATCH_DIST)
+            .collect();
+        scores.sort();
+        if scores.is_empty() {
+            return Err(format!("nothing in the current map is close to {}", id));
+        }
+        let best = scores[0].1;
+        if scores.len() > 1 && scores[1].0 - scores[0].0 <= AMBIGUITY_DIST {
+            self.ambiguous.push(format!(
+                "{} is about equally close to {} and {}; using the first",
+                id, best, scores[1].1
+            ));
+        } else {
+            self.fuzzy
+                .push(format!("{} matched to {} by geometry", id, best));
+        }
+        Ok(vec![RoadMatch {
+            id: best,
+            flipped: is_flipped(&hint, map.get_r(best).center_pts.points()),
+        }])
+    }
+
+    // If a lane type is specified, only lanes that currently have that type are considered.
+    fn lane(
+        &mut self,
+        orig: &OriginalLane,
+        r: &RoadMatch,
+        lt: Option<LaneType>,
+    ) -> Result<LaneID, String> {
+        let road = self.map.get_r(r.id);
+        if !r.flipped {
+            if let Ok(l) = orig.clone().from_permanent(self.map) {
+                if road.all_lanes().contains(&l) {
+                    return Ok(l);
+                }
+            }
+        }
+
+        // Express the old lane as a position from the left side of the road, oriented like the
+        // new road.
+        let old_total = orig.num_fwd + orig.num_back;
+        let mut old_ltr = match orig.dir {
+            Direction::Back => orig.num_back.checked_sub(1 + orig.idx),
+            Direction::Fwd if orig.idx < orig.num_fwd => Some(orig.num_back + orig.idx),
+            Direction::Fwd => None,
+        }
+        .ok_or(format!(
+            "{:?} doesn't refer to a lane on the old road",
+            orig
+        ))?;
+        let mut dir = orig.dir;
+        if r.flipped {
+            old_ltr = old_total - 1 - old_ltr;
+            dir = dir.opposite();
+        }
+
+        let lanes = road.lanes_ltr();
+        if lanes.len() == old_total && lt.map(|lt| lanes[old_ltr].2 == lt).unwrap_or(true) {
+            // Same number of lanes, but the split between directions changed. Usually this is
+            // just a lane that's been reversed.
+            self.fuzzy.push(format!(
+                "{:?} matched to {} by position",
+                orig, lanes[old_ltr].0
+            ));
+            return Ok(lanes[old_ltr].0);
+        }
+
+        let old_pct = (old_ltr as f64 + 0.5) / (old_total as f64);
+        let mut candidates: Vec<(usize, LaneID)> = lanes
+            .iter()
+            .enumerate()
+            .filter(|(_, (_, lane_dir, lane_lt))| {
+                // Reversing a lane changes its direction, so only check direction when we know
+                // the type of lane to look for.
+                lt.map(|lt| *lane_dir == dir && *lane_lt == lt)
+                    .unwrap_or(true)
+            })
+            .map(|(idx, (l, _, _))| {
+                let pct = (idx as f64 + 0.5) / (lanes.len() as f64);
+                // Compare in thousandths to sort
+                (((pct - old_pct).abs() * 1000.0) as usize, *l)
+            })
+            .collect();
+        candidates.sort();
+        if candidates.is_empty() {
+            return Err(format!(
+                "{} has no {} lanes matching {:?}",
+                r.id,
+                lt.map(|lt| lt.short_name()).unwrap_or("usable"),
+                orig
+            ));
+        }
+        if candidates.len() > 1 && candidates[0].0 == candidates[1].0 {
+            self.ambiguous.push(format!(
+                "{:?} could be {} or {}; using the first",
+                orig, candidates[0].1, candidates[1].1
+            ));
+        } else {
+            self.fuzzy.push(format!(
+                "{:?} matched to {} by relative position",
+                orig, candidates[0].1
+            ));
+        }
+        Ok(candidates[0].1)
+    }
+
+    fn intersection(&mut self, id: osm::NodeID) -> Result<IntersectionID, String> {
+        let map = self.map;
+        if let Ok(i) = map.find_i_by_osm_id(id) {
+            return Ok(i);
+        }
+        let pt = *self
+            .intersection_hints
+            .get(&id)
+            .ok_or(format!("{} is gone, and no position was recorded", id))?;
+        let mut scores: Vec<(Distance, IntersectionID)> = map
+            .all_intersections()
+            .iter()
+            .map(|i| (i.polygon.center().dist_to(pt), i.id))
+            .filter(|(dist, _)| *dist <= MAX_MATCH_DIST)
+            .collect();
+        scores.sort();
+        if scores.is_empty() {
+            return Err(format!("nothing in the current map is close to {}", id));
+        }
+        let best = scores[0].1;
+        if scores.len() > 1 && scores[1].0 - scores[0].0 <= AMBIGUITY_DIST {
+            self.ambiguous.push(format!(
+                "{} is about equally close to {} and {}; using the first",
+                id, best, scores[1].1
+            ));
+        } else {
+            self.fuzzy.push(format!("{} matched to {} by position", id, best));
+        }
+        Ok(best)
+    }
+
+    fn edit_intersection(
+        &mut self,
+        edit: PermanentEditIntersection,
+        i: IntersectionID,
+    ) -> Result<EditIntersection, String> {
+        match edit {
+            PermanentEditIntersection::StopSign { must_stop } => {
+                let mut ss = ControlStopSign::new(self.map, i);
+                let mut unmatched = 0;
+                for (orig_r, stop) in must_stop {
+                    // Of all the pieces, only the ones touching this intersection matter.
+                    let matches = self
+                        .roads(orig_r)
+                        .unwrap_or_else(|_| Vec::new())
+                        .into_iter()
+                        .filter(|r| ss.roads.contains_key(&r.id))
+                        .collect::<Vec<_>>();
+                    if matches.is_empty() {
+                        unmatched += 1;
+                    }
+                    for r in matches {
+                        ss.roads.get_mut(&r.id).unwrap().must_stop = stop;
+                    }
+                }
+                if unmatched > 0 {
+                    self.fuzzy.push(format!(
+                        "{} roads at the stop sign couldn't be matched; they keep the default",
+                        unmatched
+                    ));
+                }
+                Ok(EditIntersection::StopSign(ss))
+            }
+            // The turn groups can only be validated after lane edits are applied, same as
+            // from_permanent.
+            PermanentEditIntersection::TrafficSignal(ts) => {
+                Ok(EditIntersection::TrafficSignal(ts))
+            }
+            PermanentEditIntersection::Closed => Ok(EditIntersection::Closed),
+        }
+    }
+
+    fn parking_rules(&mut self, rules: PermanentParkingRules) -> ParkingRules {
+        let (rules, missing) = rules.from_permanent(self.map);
+        if !missing.is_empty() {
+            self.fuzzy.push(format!(
+                "{} buildings in the permit zone don't exist anymore",
+                missing.len()
+            ));
+        }
+        rules
+    }
+}
+
+// Distance from the point to the polyline described by pts
+fn dist_to_pts(pt: Pt2D, pts: &Vec<Pt2D>) -> Distance {
+    if pts.len() == 1 {
+        return pt.dist_to(pts[0]);
+    }
+    pts.windows(2)
+        .filter_map(|pair| Line::new(pair[0], pair[1]))
+        .map(|line| pt.dist_to(line.project_pt(pt)))
+        .min()
+        .unwrap_or_else(|| pt.dist_to(pts[0]))
+}
+
+// How far, on average, the old geometry is from the new
+fn mean_dist(old: &Vec<Pt2D>, new: &Vec<Pt2D>) -> Distance {
+    old.iter().map(|pt| dist_to_pts(*pt, new)).sum::<Distance>() / (old.len() as f64)
+}
+
+fn is_flipped(old: &Vec<Pt2D>, new: &Vec<Pt2D>) -> bool {
+    let old_first = old[0];
+    let old_last = *old.last().unwrap();
+    let new_first = new[0];
+    let new_last = *new.last().unwrap();
+    old_first.dist_to(new_first) + old_last.dist_to(new_last)
+        > old_first.dist_to(new_last) + old_last.dist_to(new_first)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AccessRestrictions, Road};
+    use abstutil::Tags;
+    use geom::{PolyLine, Speed};
+
+    fn pt(x: f64, y: f64) -> Pt2D {
+        Pt2D::new(x, y)
+    }
+
+    // Adds a road with one driving lane in each direction
+    fn add_road(map: &mut Map, id: OriginalRoad, pts: Vec<Pt2D>) -> RoadID {
+        let r = RoadID(map.roads.len());
+        map.roads.push(Road {
+            id: r,
+            osm_tags: Tags::new(BTreeMap::new()),
+            turn_restrictions: Vec::new(),
+            complicated_turn_restrictions: Vec::new(),
+            orig_id: id,
+            speed_limit: Speed::miles_per_hour(25.0),
+            access_restrictions: AccessRestrictions::new(),
+            zorder: 0,
+            lanes_ltr: vec![
+                (LaneID(2 * r.0), Direction::Back, LaneType::Driving),
+                (LaneID(2 * r.0 + 1), Direction::Fwd, LaneType::Driving),
+            ],
+            center_pts: PolyLine::must_new(pts),
+            src_i: IntersectionID(0),
+            dst_i: IntersectionID(1),
+        });
+        r
+    }
+
+    fn matcher(map: &Map, road_hints: Vec<(OriginalRoad, Vec<Pt2D>)>) -> Matcher {
+        Matcher {
+            map,
+            road_hints: road_hints.into_iter().collect(),
+            intersection_hints: BTreeMap::new(),
+            fuzzy: Vec::new(),
+            ambiguous: Vec::new(),
+        }
+    }
+
+    fn fwd_lane(parent: OriginalRoad) -> OriginalLane {
+        OriginalLane {
+            parent,
+            num_fwd: 1,
+            num_back: 1,
+            dir: Direction::Fwd,
+            idx: 0,
+        }
+    }
+
+    #[test]
+    fn test_exact_match() {
+        let mut map = Map::blank();
+        let id = OriginalRoad::new(100, (1, 2));
+        let r = add_road(&mut map, id, vec![pt(0.0, 0.0), pt(100.0, 0.0)]);
+
+        let mut m = matcher(&map, Vec::new());
+        let matches = m.roads(id).unwrap();
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].id, r);
+        assert!(!matches[0].flipped);
+        assert_eq!(m.lane(&fwd_lane(id), &matches[0], None), Ok(LaneID(1)));
+        assert!(m.fuzzy.is_empty());
+        assert!(m.ambiguous.is_empty());
+    }
+
+    #[test]
+    fn test_renamed_road() {
+        // One endpoint was renumbered, and the way now points the other direction.
+        let mut map = Map::blank();
+        let old_id = OriginalRoad::new(100, (1, 2));
+        let r = add_road(
+            &mut map,
+            OriginalRoad::new(100, (2, 3)),
+            vec![pt(100.0, 0.0), pt(0.0, 0.0)],
+        );
+
+        let mut m = matcher(&map, vec![(old_id, vec![pt(0.0, 0.0), pt(100.0, 0.0)])]);
+        let matches = m.roads(old_id).unwrap();
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].id, r);
+        assert!(matches[0].flipped);
+        // The old forward lane is now the backwards lane on the flipped road.
+        assert_eq!(m.lane(&fwd_lane(old_id), &matches[0], None), Ok(LaneID(0)));
+        assert!(!m.fuzzy.is_empty());
+    }
+
+    #[test]
+    fn test_split_road() {
+        let mut map = Map::blank();
+        let old_id = OriginalRoad::new(100, (1, 2));
+        let r1 = add_road(
+            &mut map,
+            OriginalRoad::new(100, (1, 3)),
+            vec![pt(0.0, 0.0), pt(50.0, 0.0)],
+        );
+        let r2 = add_road(
+            &mut map,
+            OriginalRoad::new(100, (3, 2)),
+            vec![pt(50.0, 0.0), pt(100.0, 0.0)],
+        );
+        // Another way nearby shouldn't get picked up.
+        add_road(
+            &mut map,
+            OriginalRoad::new(200, (4, 5)),
+            vec![pt(0.0, 10.0), pt(100.0, 10.0)],
+        );
+
+        let mut m = matcher(&map, vec![(old_id, vec![pt(0.0, 0.0), pt(100.0, 0.0)])]);
+        let matches = m.roads(old_id).unwrap();
+        assert_eq!(
+            matches.iter().map(|r| r.id).collect::<Vec<_>>(),
+            vec![r1, r2]
+        );
+        assert!(matches.iter().all(|r| !r.flipped));
+        for (r, l) in matches.iter().zip(vec![LaneID(1), LaneID(3)]) {
+            assert_eq!(m.lane(&fwd_lane(old_id), r, None), Ok(l));
+        }
+    }
+
+    #[test]
+    fn test_invalid_old_lane() {
+        let mut map = Map::blank();
+        let old_id = OriginalRoad::new(100, (1, 2));
+        add_road(
+            &mut map,
+            OriginalRoad::new(100, (2, 3)),
+            vec![pt(100.0, 0.0), pt(0.0, 0.0)],
+        );
+
+        let mut m = matcher(&map, vec![(old_id, vec![pt(0.0, 0.0), pt(100.0, 0.0)])]);
+        let matches = m.roads(old_id).unwrap();
+        for (dir, idx) in vec![(Direction::Back, 0), (Direction::Fwd, 1)] {
+            let orig = OriginalLane {
+                parent: old_id,
+                num_fwd: 1,
+                num_back: 0,
+                dir,
+                idx,
+            };
+            assert!(m.lane(&orig, &matches[0], None).is_err());
+        }
+    }
+}
@@ -1,13 +1,17 @@
 mod compat;
+mod migrate;
+mod osm_tags;
 mod perma;
 
 use crate::{
     connectivity, AccessRestrictions, BusRouteID, ControlStopSign, ControlTrafficSignal, Direction,
-    IntersectionID, IntersectionType, LaneID, LaneType, Map, PathConstraints, Pathfinder, RoadID,
-    TurnID, Zone,
+    IntersectionID, IntersectionType, LaneID, LanePeriod, LaneType, Map, ParkingLotID,
+    ParkingRules, PathConstraints, Pathfinder, RoadID, TurnID, Zone,
 };
 use abstutil::{retain_btreemap, retain_btreeset, Timer};
 use geom::{Speed, Time};
+pub use migrate::{MigrationNote, MigrationReport};
+pub use osm_tags::OsmTagChanges;
 pub use perma::{OriginalLane, PermanentMapEdits};
 use std::collections::{BTreeMap, BTreeSet};
 
@@ -23,6 +27,9 @@ pub struct MapEdits {
     pub changed_speed_limits: BTreeSet<RoadID>,
     pub changed_access_restrictions: BTreeSet<RoadID>,
     pub changed_routes: BTreeSet<BusRouteID>,
+    pub original_lane_parking_rules: BTreeMap<LaneID, ParkingRules>,
+    pub original_lot_parking_rules: BTreeMap<ParkingLotID, ParkingRules>,
+    pub original_lane_schedules: BTreeMap<LaneID, Vec<LanePeriod>>,
 
     // Edits without these are player generated.
     pub proposal_description: Vec<String>,
@@ -71,6 +78,21 @@ pub enum EditCmd {
         old: Vec<Time>,
         new: Vec<Time>,
     },
+    ChangeLaneParkingRules {
+        id: LaneID,
+        new: ParkingRules,
+        old: ParkingRules,
+    },
+    ChangeLotParkingRules {
+        id: ParkingLotID,
+        new: ParkingRules,
+        old: ParkingRules,
+    },
+    ChangeLaneSchedule {
+        id: LaneID,
+        new: Vec<LanePeriod>,
+        old: Vec<LanePeriod>,
+    },
 }
 
 pub struct EditEffects {
@@ -95,18 +117,43 @@ impl MapEdits {
             changed_speed_limits: BTreeSet::new(),
             changed_access_restrictions: BTreeSet::new(),
             changed_routes: BTreeSet::new(),
+            original_lane_parking_rules: BTreeMap::new(),
+            original_lot_parking_rules: BTreeMap::new(),
+            original_lane_schedules: BTreeMap::new(),
         }
     }
 
     pub fn load(map: &Map, path: String, timer: &mut Timer) -> Result<MapEdits, String> {
+        PermanentMapEdits::from_permanent(MapEdits::load_permanent(path, timer)?, map)
+    }
+
+    // Like load, but if the basemap has changed since the edits were made, keeps whatever commands
+    // can still be matched up, instead of failing entirely.
+    pub fn load_and_migrate(
+        map: &Map,
+        path: String,
+        timer: &mut Timer,
+    ) -> Result<(MapEdits, MigrationReport), String> {
+        let perma = MapEdits::load_permanent(path, timer)?;
+        if &perma.map_name != map.get_name() {
+            return Err(format!(
+                "these edits are for {}, not {}",
+                perma.map_name,
+                map.get_name()
+            ));
+        }
+        Ok(PermanentMapEdits::migrate(perma, map))
+    }
+
+    // Upgrades edits saved in older formats
+    fn load_permanent(path: String, timer: &mut Timer) -> Result<PermanentMapEdits, String> {
         match abstutil::maybe_read_json(path.clone(), timer) {
-            Ok(perma) => PermanentMapEdits::from_permanent(perma, map),
+            Ok(perma) => Ok(perma),
             Err(_) => {
                 let bytes = abstutil::slurp_file(&path).map_err(|err| err.to_string())?;
                 let contents = std::str::from_utf8(&bytes).map_err(|err| err.to_string())?;
                 let value = serde_json::from_str(contents).map_err(|err| err.to_string())?;
-                let perma = compat::upgrade(value)?;
-                PermanentMapEdits::from_permanent(perma, map)
+                compat::upgrade(value)
             }
         }
     }
@@ -128,6 +175,9 @@ impl MapEdits {
         let mut changed_speed_limits = BTreeSet::new();
         let mut changed_access_restrictions = BTreeSet::new();
         let mut changed_routes = BTreeSet::new();
+        let mut orig_lane_parking_rules = BTreeMap::new();
+        let mut orig_lot_parking_rules = BTreeMap::new();
+        let mut orig_lane_schedules = BTreeMap::new();
 
         for cmd in &self.commands {
             match cmd {
@@ -157,6 +207,21 @@ impl MapEdits {
                 EditCmd::ChangeRouteSchedule { id, .. } => {
                     changed_routes.insert(*id);
                 }
+                EditCmd::ChangeLaneParkingRules { id, ref old, .. } => {
+                    if !orig_lane_parking_rules.contains_key(id) {
+                        orig_lane_parking_rules.insert(*id, old.clone());
+                    }
+                }
+                EditCmd::ChangeLotParkingRules { id, ref old, .. } => {
+                    if !orig_lot_parking_rules.contains_key(id) {
+                        orig_lot_parking_rules.insert(*id, old.clone());
+                    }
+                }
+                EditCmd::ChangeLaneSchedule { id, ref old, .. } => {
+                    if !orig_lane_schedules.contains_key(id) {
+                        orig_lane_schedules.insert(*id, old.clone());
+                    }
+                }
             }
         }
 
@@ -175,6 +240,15 @@ impl MapEdits {
             let r = map.get_br(*br);
             r.spawn_times != r.orig_spawn_times
         });
+        retain_btreemap(&mut orig_lane_parking_rules, |l, rules| {
+            &map.get_l(*l).parking_rules != rules
+        });
+        retain_btreemap(&mut orig_lot_parking_rules, |pl, rules| {
+            &map.get_pl(*pl).rules != rules
+        });
+        retain_btreemap(&mut orig_lane_schedules, |l, schedule| {
+            &map.get_l(*l).schedule != schedule
+        });
 
         self.original_lts = orig_lts;
         self.reversed_lanes = reversed_lanes;
@@ -182,6 +256,44 @@ impl MapEdits {
         self.changed_speed_limits = changed_speed_limits;
         self.changed_access_restrictions = changed_access_restrictions;
         self.changed_routes = changed_routes;
+        self.original_lane_parking_rules = orig_lane_parking_rules;
+        self.original_lot_parking_rules = orig_lot_parking_rules;
+        self.original_lane_schedules = orig_lane_schedules;
+    }
+
+    // Every road and intersection that some command refers to
+    fn referenced_objects(&self, map: &Map) -> (BTreeSet<RoadID>, BTreeSet<IntersectionID>) {
+        let mut roads = BTreeSet::new();
+        let mut intersections = BTreeSet::new();
+        for cmd in &self.commands {
+            match cmd {
+                EditCmd::ChangeLaneType { id, .. } => {
+                    roads.insert(map.get_l(*id).parent);
+                }
+                EditCmd::ReverseLane { l, dst_i } => {
+                    roads.insert(map.get_l(*l).parent);
+                    intersections.insert(*dst_i);
+                }
+                EditCmd::ChangeSpeedLimit { id, .. }
+                | EditCmd::ChangeAccessRestrictions { id, .. } => {
+                    roads.insert(*id);
+                }
+                EditCmd::ChangeIntersection { i, ref new, ref old } => {
+                    intersections.insert(*i);
+                    for edit in vec![new, old] {
+                        if let EditIntersection::StopSign(ref ss) = edit {
+                            roads.extend(ss.roads.keys().cloned());
+                        }
+                    }
+                }
+                EditCmd::ChangeLaneParkingRules { id, .. }
+                | EditCmd::ChangeLaneSchedule { id, .. } => {
+                    roads.insert(map.get_l(*id).parent);
+                }
+                EditCmd::ChangeRouteSchedule { .. } | EditCmd::ChangeLotParkingRules { .. } => {}
+            }
+        }
+        (roads, intersections)
     }
 
     // Assumes update_derived has been called.
@@ -228,6 +340,27 @@ impl MapEdits {
                 old: r.orig_spawn_times.clone(),
             });
         }
+        for (l, old) in &self.original_lane_parking_rules {
+            self.commands.push(EditCmd::ChangeLaneParkingRules {
+                id: *l,
+                new: map.get_l(*l).parking_rules.clone(),
+                old: old.clone(),
+            });
+        }
+        for (pl, old) in &self.original_lot_parking_rules {
+            self.commands.push(EditCmd::ChangeLotParkingRules {
+                id: *pl,
+                new: map.get_pl(*pl).rules.clone(),
+                old: old.clone(),
+            });
+        }
+        for (l, old) in &self.original_lane_schedules {
+            self.commands.push(EditCmd::ChangeLaneSchedule {
+                id: *l,
+                new: map.get_l(*l).schedule.clone(),
+                old: old.clone(),
+            });
+        }
     }
 }
 
@@ -266,6 +399,9 @@ impl EditCmd {
             EditCmd::ChangeRouteSchedule { id, .. } => {
                 format!("reschedule route {}", map.get_br(*id).short_name)
             }
+            EditCmd::ChangeLaneParkingRules { id, .. } => format!("parking rules for {}", id),
+            EditCmd::ChangeLotParkingRules { id, .. } => format!("parking rules for {}", id),
+            EditCmd::ChangeLaneSchedule { id, .. } => format!("time-of-day use of {}", id),
         }
     }
 
@@ -395,6 +531,36 @@ impl EditCmd {
                 map.bus_routes[id.0].spawn_times = new.clone();
                 true
             }
+            EditCmd::ChangeLaneParkingRules { id, new, .. } => {
+                if &map.lanes[id.0].parking_rules == new {
+                    return false;
+                }
+                map.lanes[id.0].parking_rules = new.clone();
+                true
+            }
+            EditCmd::ChangeLotParkingRules { id, new, .. } => {
+                if &map.parking_lots[id.0].rules == new {
+                    return false;
+                }
+                map.parking_lots[id.0].rules = new.clone();
+                true
+            }
+            EditCmd::ChangeLaneSchedule { id, new, .. } => {
+                let lane = &mut map.lanes[id.0];
+                if &lane.schedule == new {
+                    return false;
+                }
+                lane.schedule = new.clone();
+
+                // The lane might carry traffic at different times now, so it might need turns
+                effects.changed_roads.insert(lane.parent);
+                effects.changed_intersections.insert(lane.src_i);
+                effects.changed_intersections.insert(lane.dst_i);
+                let (src_i, dst_i) = (lane.src_i, lane.dst_i);
+                recalculate_turns(src_i, map, effects, timer);
+                recalculate_turns(dst_i, map, effects, timer);
+                true
+            }
         }
     }
 
@@ -453,6 +619,24 @@ impl EditCmd {
                 new: old.clone(),
             }
             .apply(effects, map, timer),
+            EditCmd::ChangeLaneParkingRules { id, old, new } => EditCmd::ChangeLaneParkingRules {
+                id: *id,
+                old: new.clone(),
+                new: old.clone(),
+            }
+            .apply(effects, map, timer),
+            EditCmd::ChangeLotParkingRules { id, old, new } => EditCmd::ChangeLotParkingRules {
+                id: *id,
+                old: new.clone(),
+                new: old.clone(),
+            }
+            .apply(effects, map, timer),
+            EditCmd::ChangeLaneSchedule { id, old, new } => EditCmd::ChangeLaneSchedule {
+                id: *id,
+                old: new.clone(),
+                new: old.clone(),
+            }
+            .apply(effects, map, timer),
         }
     }
 }
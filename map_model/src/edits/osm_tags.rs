@@ -0,0 +1,571 @@
+use crate::edits::{EditIntersection, MapEdits};
+use crate::make::initial::lane_specs::get_lane_specs_ltr;
+use crate::raw::{DrivingSide, OriginalRoad};
+use crate::{osm, Direction, LaneType, Map, PathConstraints, RoadID};
+use abstutil::Tags;
+use std::collections::{BTreeMap, BTreeSet};
+
+// Many edits correspond directly to OSM tags. This figures out how to retag the original OSM ways
+// and nodes so that they'd produce the edited map, so proposals can be reviewed and uploaded with
+// normal OSM tools. Only the attributes that the edits actually changed are retagged.
+//
+// Importing rewrites some tags (like sidewalk=separate, once the separate sidewalks are found), so
+// the changes are relative to the ways' tags in the original OSM input, not Road::osm_tags.
+
+pub struct OsmTagChanges {
+    // Keyed by pieces of ways, since different pieces of one way may need different tags. A value
+    // of None means to remove the key.
+    pub roads: BTreeMap<OriginalRoad, BTreeMap<String, Option<String>>>,
+    pub nodes: BTreeMap<osm::NodeID, BTreeMap<String, Option<String>>>,
+    // Edits that have no OSM equivalent
+    pub unsupported: Vec<String>,
+}
+
+impl MapEdits {
+    // The map must have these edits applied. original_tags has the tags of the affected ways in the
+    // OSM input; ways missing from it fall back to the tags the map was imported with.
+    pub fn to_osm_tag_changes(
+        &self,
+        map: &Map,
+        original_tags: &BTreeMap<osm::WayID, Tags>,
+    ) -> OsmTagChanges {
+        let mut changes = OsmTagChanges {
+            roads: BTreeMap::new(),
+            nodes: BTreeMap::new(),
+            unsupported: Vec::new(),
+        };
+
+        let mut lanes_changed: BTreeSet<RoadID> = BTreeSet::new();
+        for l in self.original_lts.keys().chain(self.reversed_lanes.iter()) {
+            lanes_changed.insert(map.get_l(*l).parent);
+        }
+        for r in lanes_changed {
+            let r = map.get_r(r);
+            let orig = original_tags
+                .get(&r.orig_id.osm_way_id)
+                .unwrap_or(&r.osm_tags);
+            let tags = lane_tags(
+                r.orig_id,
+                &r.osm_tags,
+                r.lanes_ltr()
+                    .into_iter()
+                    .map(|(_, dir, lt)| (dir, lt))
+                    .collect(),
+                orig,
+                map.get_driving_side(),
+                &mut changes.unsupported,
+            );
+            if r.lanes_ltr()
+                .iter()
+                .any(|(_, _, lt)| *lt == LaneType::Construction)
+            {
+                changes.unsupported.push(format!(
+                    "{} has a lane closed for construction, which OSM can't express per lane",
+                    r.orig_id
+                ));
+            }
+            changes
+                .roads
+                .entry(r.orig_id)
+                .or_insert_with(BTreeMap::new)
+                .extend(tags);
+        }
+
+        for r in &self.changed_speed_limits {
+            let r = map.get_r(*r);
+            let mph = original_tags
+                .get(&r.orig_id.osm_way_id)
+                .unwrap_or(&r.osm_tags)
+                .get(osm::MAXSPEED)
+                .map(|x| x.ends_with("mph"))
+                .unwrap_or(false);
+            let value = if mph {
+                format!("{} mph", r.speed_limit.inner_meters_per_second() * 2.23694)
+            } else {
+                format!("{}", r.speed_limit.inner_meters_per_second() * 3.6)
+            };
+            // Speeds in OSM are whole numbers
+            let value = round_numbers(&value);
+            changes
+                .roads
+                .entry(r.orig_id)
+                .or_insert_with(BTreeMap::new)
+                .insert(osm::MAXSPEED.to_string(), Some(value));
+        }
+
+        for r in &self.changed_access_restrictions {
+            let r = map.get_r(*r);
+            let restrictions = &r.access_restrictions;
+            let mut tags = BTreeMap::new();
+            if restrictions.allow_through_traffic.is_empty() {
+                tags.insert("access".to_string(), Some("private".to_string()));
+            } else {
+                tags.insert("access".to_string(), None);
+                for (constraint, key) in vec![
+                    (PathConstraints::Car, "motor_vehicle"),
+                    (PathConstraints::Bike, "bicycle"),
+                    (PathConstraints::Pedestrian, "foot"),
+                ] {
+                    tags.insert(
+                        key.to_string(),
+                        if restrictions.allow_through_traffic.contains(constraint) {
+                            None
+                        } else {
+                            Some("destination".to_string())
+                        },
+                    );
+                }
+                if restrictions
+                    .allow_through_traffic
+                    .contains(PathConstraints::Bus)
+                    && !restrictions
+                        .allow_through_traffic
+                        .contains(PathConstraints::Car)
+                {
+                    tags.insert("psv".to_string(), Some("yes".to_string()));
+                }
+            }
+            if restrictions.cap_vehicles_per_hour.is_some() {
+                changes.unsupported.push(format!(
+                    "{} has a vehicle cap, which OSM can't express",
+                    r.orig_id
+                ));
+            }
+            // TODO Could use the conditional restrictions syntax
+            if !restrictions.hours.is_empty() {
+                changes.unsupported.push(format!(
+                    "{} only restricts access at some hours, which isn't exported",
+                    r.orig_id
+                ));
+            }
+            if !restrictions.entry_charges.is_empty() {
+                changes.unsupported.push(format!(
+                    "{} has entry charges, which OSM can't express",
+                    r.orig_id
+                ));
+            }
+            changes
+                .roads
+                .entry(r.orig_id)
+                .or_insert_with(BTreeMap::new)
+                .extend(tags);
+        }
+
+        for (i, orig) in &self.original_intersections {
+            let node = map.get_i(*i).orig_id;
+            let mut tags = BTreeMap::new();
+            match map.get_i_edit(*i) {
+                EditIntersection::TrafficSignal(_) => {
+                    tags.insert(
+                        osm::HIGHWAY.to_string(),
+                        Some("traffic_signals".to_string()),
+                    );
+                    tags.insert("stop".to_string(), None);
+                }
+                EditIntersection::StopSign(ss) => {
+                    let num_stop = ss.roads.values().filter(|r| r.must_stop).count();
+                    if num_stop == 0 {
+                        tags.insert(osm::HIGHWAY.to_string(), None);
+                        tags.insert("stop".to_string(), None);
+                    } else {
+                        tags.insert(osm::HIGHWAY.to_string(), Some("stop".to_string()));
+                        if num_stop == ss.roads.len() {
+                            tags.insert("stop".to_string(), Some("all".to_string()));
+                        } else {
+                            tags.insert("stop".to_string(), Some("minor".to_string()));
+                            if let EditIntersection::StopSign(_) = orig {
+                                changes.unsupported.push(format!(
+                                    "{} only has stop signs on some roads; which ones can't be \
+                                     expressed on the node",
+                                    node
+                                ));
+                            }
+                        }
+                    }
+                }
+                EditIntersection::Closed => {
+                    changes
+                        .unsupported
+                        .push(format!("{} is closed, which OSM can't express", node));
+                    continue;
+                }
+            }
+            changes
+                .nodes
+                .entry(node)
+                .or_insert_with(BTreeMap::new)
+                .extend(tags);
+        }
+
+        for br in &self.changed_routes {
+            changes.unsupported.push(format!(
+                "the schedule of {} changed, which belongs in GTFS, not OSM",
+                map.get_br(*br).osm_rel_id
+            ));
+        }
+        for l in self.original_lane_parking_rules.keys() {
+            changes.unsupported.push(format!(
+                "the parking rules along {} changed, which aren't retagged",
+                map.get_parent(*l).orig_id
+            ));
+        }
+        for l in self.original_lane_schedules.keys() {
+            changes.unsupported.push(format!(
+                "the time-of-day use of a lane along {} changed, which isn't retagged",
+                map.get_parent(*l).orig_id
+            ));
+        }
+        for pl in self.original_lot_parking_rules.keys() {
+            changes.unsupported.push(format!(
+                "the rules of parking lot {} changed, which aren't retagged",
+                map.get_pl(*pl).osm_id
+            ));
+        }
+
+        changes
+    }
+}
+
+// The lanes on each side of the road, from the center going outwards
+struct Sides {
+    fwd: Vec<LaneType>,
+    back: Vec<LaneType>,
+}
+
+impl Sides {
+    fn new(lanes_ltr: Vec<(Direction, LaneType)>) -> Sides {
+        let mut fwd = Vec::new();
+        let mut back = Vec::new();
+        for (dir, lt) in lanes_ltr {
+            if dir == Direction::Fwd {
+                fwd.push(lt);
+            } else {
+                back.push(lt);
+            }
+        }
+        back.reverse();
+        Sides { fwd, back }
+    }
+
+    fn travel(side: &[LaneType]) -> Vec<LaneType> {
+        side.iter()
+            .filter(|lt| **lt == LaneType::Driving || **lt == LaneType::Bus)
+            .cloned()
+            .collect()
+    }
+
+    fn has(side: &[LaneType], lt: LaneType) -> bool {
+        side.contains(&lt)
+    }
+}
+
+// Only produces tags for the aspects of the road that differ from what the original tags describe.
+// imported_tags and lanes_ltr describe the road as imported and as edited.
+fn lane_tags(
+    orig_id: OriginalRoad,
+    imported_tags: &Tags,
+    lanes_ltr: Vec<(Direction, LaneType)>,
+    orig_tags: &Tags,
+    driving_side: DrivingSide,
+    unsupported: &mut Vec<String>,
+) -> BTreeMap<String, Option<String>> {
+    let lanes = |tags: &Tags| {
+        Sides::new(
+            get_lane_specs_ltr(tags)
+                .into_iter()
+                .map(|spec| (spec.dir, spec.lt))
+                .collect(),
+        )
+    };
+    let before = lanes(orig_tags);
+    // Sidewalks are often inferred or snapped from separate ways during import, so the original
+    // tags don't say where they are. Compare those against what was imported instead.
+    let imported = lanes(imported_tags);
+    let after = Sides::new(lanes_ltr);
+    // Which side of the road the forward lanes are on
+    let (right, left) = match driving_side {
+        DrivingSide::Right => (&after.fwd, &after.back),
+        DrivingSide::Left => (&after.back, &after.fwd),
+    };
+    let (before_right, before_left) = match driving_side {
+        DrivingSide::Right => (&before.fwd, &before.back),
+        DrivingSide::Left => (&before.back, &before.fwd),
+    };
+
+    let mut tags: BTreeMap<String, Option<String>> = BTreeMap::new();
+    let set = |tags: &mut BTreeMap<String, Option<String>>, k: &str, v: Option<String>| {
+        tags.insert(k.to_string(), v);
+    };
+
+    // Travel lanes and direction
+    let fwd_travel = Sides::travel(&after.fwd);
+    let back_travel = Sides::travel(&after.back);
+    if fwd_travel != Sides::travel(&before.fwd) || back_travel != Sides::travel(&before.back) {
+        set(
+            &mut tags,
+            "lanes",
+            Some((fwd_travel.len() + back_travel.len()).to_string()),
+        );
+        if !fwd_travel.is_empty() && !back_travel.is_empty() {
+            set(
+                &mut tags,
+                "lanes:forward",
+                Some(fwd_travel.len().to_string()),
+            );
+            set(
+                &mut tags,
+                "lanes:backward",
+                Some(back_travel.len().to_string()),
+            );
+            set(&mut tags, "oneway", None);
+        } else {
+            set(&mut tags, "lanes:forward", None);
+            set(&mut tags, "lanes:backward", None);
+            if back_travel.is_empty() {
+                set(&mut tags, "oneway", Some("yes".to_string()));
+            } else {
+                set(&mut tags, "oneway", Some("-1".to_string()));
+            }
+        }
+
+        for (key, travel) in vec![
+            ("bus:lanes:forward", &fwd_travel),
+            ("bus:lanes:backward", &back_travel),
+        ] {
+            if travel.contains(&LaneType::Bus) {
+                set(
+                    &mut tags,
+                    key,
+                    Some(
+                        travel
+                            .iter()
+                            .map(|lt| {
+                                if *lt == LaneType::Bus {
+                                    "designated"
+                                } else {
+                                    ""
+                                }
+                            })
+                            .collect::<Vec<_>>()
+                            .join("|"),
+                    ),
+                );
+            } else {
+                set(&mut tags, key, None);
+            }
+        }
+        for key in vec![
+            "bus:lanes",
+            "psv:lanes",
+            "psv:lanes:forward",
+            "psv:lanes:backward",
+        ] {
+            set(&mut tags, key, None);
+        }
+    }
+
+    let turn_lane = Sides::has(&after.fwd, LaneType::SharedLeftTurn);
+    if turn_lane != Sides::has(&before.fwd, LaneType::SharedLeftTurn) {
+        set(
+            &mut tags,
+            "centre_turn_lane",
+            if turn_lane {
+                Some("yes".to_string())
+            } else {
+                None
+            },
+        );
+        set(&mut tags, "lanes:both_ways", None);
+    }
+
+    // Bike lanes
+    if Sides::has(right, LaneType::Biking) != Sides::has(before_right, LaneType::Biking)
+        || Sides::has(left, LaneType::Biking) != Sides::has(before_left, LaneType::Biking)
+    {
+        for key in vec!["cycleway", "cycleway:both"] {
+            set(&mut tags, key, None);
+        }
+        for (key, side) in vec![("cycleway:right", right), ("cycleway:left", left)] {
+            set(
+                &mut tags,
+                key,
+                Some(if Sides::has(side, LaneType::Biking) {
+                    "lane".to_string()
+                } else {
+                    "no".to_string()
+                }),
+            );
+        }
+    }
+
+    // Parking
+    if Sides::has(right, LaneType::Parking) != Sides::has(before_right, LaneType::Parking)
+        || Sides::has(left, LaneType::Parking) != Sides::has(before_left, LaneType::Parking)
+    {
+        let both = orig_tags.get(osm::PARKING_BOTH).cloned();
+        set(&mut tags, osm::PARKING_BOTH, None);
+        for (key, side) in vec![(osm::PARKING_RIGHT, right), (osm::PARKING_LEFT, left)] {
+            let value = if Sides::has(side, LaneType::Parking) {
+                // Keep the original style of parking, if there was one
+                orig_tags
+                    .get(key)
+                    .cloned()
+                    .or_else(|| both.clone())
+                    .filter(|x| x == "diagonal" || x == "perpendicular")
+                    .unwrap_or_else(|| "parallel".to_string())
+            } else {
+                "no_parking".to_string()
+            };
+            set(&mut tags, key, Some(value));
+        }
+    }
+
+    // Sidewalks
+    let (imported_right, imported_left) = match driving_side {
+        DrivingSide::Right => (&imported.fwd, &imported.back),
+        DrivingSide::Left => (&imported.back, &imported.fwd),
+    };
+    let sidewalk_right = Sides::has(right, LaneType::Sidewalk);
+    let sidewalk_left = Sides::has(left, LaneType::Sidewalk);
+    let sidewalks_changed = sidewalk_right != Sides::has(imported_right, LaneType::Sidewalk)
+        || sidewalk_left != Sides::has(imported_left, LaneType::Sidewalk);
+    if sidewalks_changed && orig_tags.is(osm::SIDEWALK, "separate") {
+        unsupported.push(format!(
+            "the sidewalks along {} changed, but they're mapped as separate ways",
+            orig_id
+        ));
+    } else if sidewalks_changed {
+        set(
+            &mut tags,
+            osm::SIDEWALK,
+            Some(
+                match (sidewalk_left, sidewalk_right) {
+                    (true, true) => "both",
+                    (false, true) => "right",
+                    (true, false) => "left",
+                    (false, false) => "no",
+                }
+                .to_string(),
+            ),
+        );
+    }
+
+    tags
+}
+
+// "25.000001 mph" -> "25 mph"
+fn round_numbers(value: &str) -> String {
+    value
+        .split(' ')
+        .map(|part| {
+            if let Ok(x) = part.parse::<f64>() {
+                format!("{}", x.round())
+            } else {
+                part.to_string()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tags(kv: Vec<&str>) -> Tags {
+        let mut tags = Tags::new(BTreeMap::new());
+        for pair in kv {
+            let parts = pair.split('=').collect::<Vec<_>>();
+            tags.insert(parts[0], parts[1]);
+        }
+        tags
+    }
+
+    fn lanes_ltr(tags: &Tags) -> Vec<(Direction, LaneType)> {
+        get_lane_specs_ltr(tags)
+            .into_iter()
+            .map(|spec| (spec.dir, spec.lt))
+            .collect()
+    }
+
+    fn changes(kv: Vec<(&str, Option<&str>)>) -> BTreeMap<String, Option<String>> {
+        kv.into_iter()
+            .map(|(k, v)| (k.to_string(), v.map(|v| v.to_string())))
+            .collect()
+    }
+
+    fn retag(
+        orig: &Tags,
+        lanes_ltr: Vec<(Direction, LaneType)>,
+    ) -> BTreeMap<String, Option<String>> {
+        let id = OriginalRoad {
+            osm_way_id: osm::WayID(1),
+            i1: osm::NodeID(1),
+            i2: osm::NodeID(2),
+        };
+        let mut unsupported = Vec::new();
+        let tags = lane_tags(
+            id,
+            orig,
+            lanes_ltr,
+            orig,
+            DrivingSide::Right,
+            &mut unsupported,
+        );
+        assert!(unsupported.is_empty());
+        tags
+    }
+
+    #[test]
+    fn test_unchanged() {
+        let orig = tags(vec!["highway=primary", "lanes=2", "sidewalk=both"]);
+        assert!(retag(&orig, lanes_ltr(&orig)).is_empty());
+    }
+
+    #[test]
+    fn test_bus_lane() {
+        let orig = tags(vec!["highway=primary", "lanes=2", "sidewalk=both"]);
+        let mut lanes = lanes_ltr(&orig);
+        let idx = lanes
+            .iter()
+            .position(|(dir, lt)| *dir == Direction::Fwd && *lt == LaneType::Driving)
+            .unwrap();
+        lanes[idx].1 = LaneType::Bus;
+        assert_eq!(
+            retag(&orig, lanes),
+            changes(vec![
+                ("bus:lanes", None),
+                ("bus:lanes:backward", None),
+                ("bus:lanes:forward", Some("designated")),
+                ("lanes", Some("2")),
+                ("lanes:backward", Some("1")),
+                ("lanes:forward", Some("1")),
+                ("oneway", None),
+                ("psv:lanes", None),
+                ("psv:lanes:backward", None),
+                ("psv:lanes:forward", None),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_oneway() {
+        let orig = tags(vec!["highway=primary", "lanes=2", "sidewalk=both"]);
+        // Both travel lanes go forwards now
+        let lanes = lanes_ltr(&orig)
+            .into_iter()
+            .map(|(dir, lt)| {
+                if lt == LaneType::Driving {
+                    (Direction::Fwd, lt)
+                } else {
+                    (dir, lt)
+                }
+            })
+            .collect();
+        let tags = retag(&orig, lanes);
+        assert_eq!(tags["lanes"], Some("2".to_string()));
+        assert_eq!(tags["oneway"], Some("yes".to_string()));
+        assert_eq!(tags["lanes:forward"], None);
+        assert_eq!(tags["lanes:backward"], None);
+    }
+}
@@ -1,10 +1,11 @@
 use crate::edits::{EditCmd, EditIntersection, MapEdits};
 use crate::raw::OriginalRoad;
 use crate::{
-    osm, AccessRestrictions, ControlStopSign, Direction, IntersectionID, LaneID, LaneType, Map,
+    osm, AccessRestrictions, BuildingID, ControlStopSign, Direction, IntersectionID, LaneID,
+    LanePeriod, LaneType, Map, ParkingRules,
 };
 use abstutil::{deserialize_btreemap, serialize_btreemap};
-use geom::{Speed, Time};
+use geom::{LonLat, Speed, Time};
 use serde::{Deserialize, Serialize};
 use std::collections::{BTreeMap, BTreeSet};
 
@@ -15,16 +16,31 @@ pub struct PermanentMapEdits {
     pub map_name: String,
     pub edits_name: String,
     pub version: usize,
-    commands: Vec<PermanentEditCmd>,
+    pub(crate) commands: Vec<PermanentEditCmd>,
 
     // Edits without these are player generated.
     pub proposal_description: Vec<String>,
     // The link is optional even for proposals
     pub proposal_link: Option<String>,
+
+    // Where the referenced roads and intersections were when these edits were saved. If the
+    // basemap changes later, this is used to find them again. Older edits don't have this.
+    #[serde(
+        default,
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    pub(crate) road_hints: BTreeMap<OriginalRoad, Vec<LonLat>>,
+    #[serde(
+        default,
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    pub(crate) intersection_hints: BTreeMap<osm::NodeID, LonLat>,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
-enum PermanentEditIntersection {
+pub(crate) enum PermanentEditIntersection {
     StopSign {
         #[serde(
             serialize_with = "serialize_btreemap",
@@ -47,7 +63,7 @@ pub struct OriginalLane {
 }
 
 #[derive(Serialize, Deserialize, Clone)]
-enum PermanentEditCmd {
+pub(crate) enum PermanentEditCmd {
     ChangeLaneType {
         id: OriginalLane,
         lt: LaneType,
@@ -78,15 +94,38 @@ enum PermanentEditCmd {
         old: Vec<Time>,
         new: Vec<Time>,
     },
+    ChangeLaneParkingRules {
+        id: OriginalLane,
+        new: PermanentParkingRules,
+        old: PermanentParkingRules,
+    },
+    ChangeLotParkingRules {
+        osm_id: osm::OsmID,
+        new: PermanentParkingRules,
+        old: PermanentParkingRules,
+    },
+    ChangeLaneSchedule {
+        id: OriginalLane,
+        new: Vec<LanePeriod>,
+        old: Vec<LanePeriod>,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub(crate) struct PermanentParkingRules {
+    // The permit_zone here is always None; it's stored by OSM ID instead.
+    rules: ParkingRules,
+    permit_zone: Option<Vec<osm::OsmID>>,
 }
 
 impl PermanentMapEdits {
     pub fn to_permanent(edits: &MapEdits, map: &Map) -> PermanentMapEdits {
+        let (roads, intersections) = edits.referenced_objects(map);
         PermanentMapEdits {
             map_name: map.get_name().to_string(),
             edits_name: edits.edits_name.clone(),
             // Increase this every time there's a schema change
-            version: 1,
+            version: 2,
             proposal_description: edits.proposal_description.clone(),
             proposal_link: edits.proposal_link.clone(),
             commands: edits
@@ -132,6 +171,44 @@ impl PermanentMapEdits {
                             new: new.clone(),
                         }
                     }
+                    EditCmd::ChangeLaneParkingRules { id, new, old } => {
+                        PermanentEditCmd::ChangeLaneParkingRules {
+                            id: OriginalLane::to_permanent(*id, map),
+                            new: new.to_permanent(map),
+                            old: old.to_permanent(map),
+                        }
+                    }
+                    EditCmd::ChangeLotParkingRules { id, new, old } => {
+                        PermanentEditCmd::ChangeLotParkingRules {
+                            osm_id: map.get_pl(*id).osm_id,
+                            new: new.to_permanent(map),
+                            old: old.to_permanent(map),
+                        }
+                    }
+                    EditCmd::ChangeLaneSchedule { id, new, old } => {
+                        PermanentEditCmd::ChangeLaneSchedule {
+                            id: OriginalLane::to_permanent(*id, map),
+                            new: new.clone(),
+                            old: old.clone(),
+                        }
+                    }
+                })
+                .collect(),
+            road_hints: roads
+                .into_iter()
+                .map(|r| {
+                    let r = map.get_r(r);
+                    (
+                        r.orig_id,
+                        map.get_gps_bounds().convert_back(r.center_pts.points()),
+                    )
+                })
+                .collect(),
+            intersection_hints: intersections
+                .into_iter()
+                .map(|i| {
+                    let i = map.get_i(i);
+                    (i.orig_id, i.polygon.center().to_gps(map.get_gps_bounds()))
                 })
                 .collect(),
         }
@@ -194,6 +271,28 @@ impl PermanentMapEdits {
                             .ok_or(format!("can't find {}", osm_rel_id))?;
                         Ok(EditCmd::ChangeRouteSchedule { id, old, new })
                     }
+                    PermanentEditCmd::ChangeLaneParkingRules { id, new, old } => {
+                        let id = id.from_permanent(map)?;
+                        Ok(EditCmd::ChangeLaneParkingRules {
+                            id,
+                            new: new.from_permanent_strict(map)?,
+                            old: old.from_permanent_strict(map)?,
+                        })
+                    }
+                    PermanentEditCmd::ChangeLotParkingRules { osm_id, new, old } => {
+                        let id = map
+                            .find_pl_by_osm_id(osm_id)
+                            .ok_or(format!("can't find parking lot {}", osm_id))?;
+                        Ok(EditCmd::ChangeLotParkingRules {
+                            id,
+                            new: new.from_permanent_strict(map)?,
+                            old: old.from_permanent_strict(map)?,
+                        })
+                    }
+                    PermanentEditCmd::ChangeLaneSchedule { id, new, old } => {
+                        let id = id.from_permanent(map)?;
+                        Ok(EditCmd::ChangeLaneSchedule { id, new, old })
+                    }
                 })
                 .collect::<Result<Vec<EditCmd>, String>>()?,
 
@@ -203,6 +302,9 @@ impl PermanentMapEdits {
             changed_speed_limits: BTreeSet::new(),
             changed_access_restrictions: BTreeSet::new(),
             changed_routes: BTreeSet::new(),
+            original_lane_parking_rules: BTreeMap::new(),
+            original_lot_parking_rules: BTreeMap::new(),
+            original_lane_schedules: BTreeMap::new(),
         };
         edits.update_derived(map);
         Ok(edits)
@@ -255,6 +357,50 @@ impl PermanentEditIntersection {
     }
 }
 
+impl ParkingRules {
+    fn to_permanent(&self, map: &Map) -> PermanentParkingRules {
+        let mut rules = self.clone();
+        let permit_zone = rules
+            .permit_zone
+            .take()
+            .map(|zone| zone.into_iter().map(|b| map.get_b(b).orig_id).collect());
+        PermanentParkingRules { rules, permit_zone }
+    }
+}
+
+impl PermanentParkingRules {
+    // Also returns buildings in the permit zone that no longer exist
+    pub(crate) fn from_permanent(self, map: &Map) -> (ParkingRules, Vec<osm::OsmID>) {
+        let mut rules = self.rules;
+        let mut missing = Vec::new();
+        if let Some(zone) = self.permit_zone {
+            let lookup: BTreeMap<osm::OsmID, BuildingID> = map
+                .all_buildings()
+                .iter()
+                .map(|b| (b.orig_id, b.id))
+                .collect();
+            let mut translated = BTreeSet::new();
+            for id in zone {
+                if let Some(b) = lookup.get(&id) {
+                    translated.insert(*b);
+                } else {
+                    missing.push(id);
+                }
+            }
+            rules.permit_zone = Some(translated);
+        }
+        (rules, missing)
+    }
+
+    fn from_permanent_strict(self, map: &Map) -> Result<ParkingRules, String> {
+        let (rules, missing) = self.from_permanent(map);
+        if let Some(id) = missing.get(0) {
+            return Err(format!("can't find building {} in a permit zone", id));
+        }
+        Ok(rules)
+    }
+}
+
 impl OriginalLane {
     pub fn to_permanent(l: LaneID, map: &Map) -> OriginalLane {
         let r = map.get_parent(l);
@@ -0,0 +1,372 @@
+use crate::{BuildingType, Direction, IntersectionType, Map, OffstreetParking, PathConstraints};
+use abstutil::Timer;
+use geojson::feature::Id;
+use geojson::{Feature, FeatureCollection, Geometry, Value};
+use geom::{GPSBounds, Polygon, Pt2D, Time};
+use serde_json::Map as JsonMap;
+use serde_json::Value as JsonValue;
+
+// Exports the map to GeoJSON in WGS84, with one FeatureCollection per layer, for use in GIS tools.
+// Feature IDs are the map's internal IDs, which are only stable for one version of the map, so
+// properties also include the OSM IDs that the objects came from. Bus stops are keyed by their
+// sidewalk and index, and zones by the OSM way and nodes of their first road.
+impl Map {
+    pub fn export_geojson(&self, timer: &mut Timer) -> Vec<(&'static str, FeatureCollection)> {
+        let gps = self.get_gps_bounds();
+        let mut layers = Vec::new();
+
+        timer.start("export lanes and roads");
+        let mut lanes = Vec::new();
+        let mut roads = Vec::new();
+        for r in self.all_roads() {
+            for (l, dir, lt) in r.lanes_ltr() {
+                let lane = self.get_l(l);
+                let mut props = JsonMap::new();
+                props.insert("road".to_string(), r.id.0.into());
+                props.insert("type".to_string(), format!("{:?}", lt).into());
+                props.insert(
+                    "direction".to_string(),
+                    if dir == Direction::Fwd {
+                        "forward".into()
+                    } else {
+                        "backward".into()
+                    },
+                );
+                props.insert("width_meters".to_string(), lane.width.inner_meters().into());
+                props.insert("src_i".to_string(), lane.src_i.0.into());
+                props.insert("dst_i".to_string(), lane.dst_i.0.into());
+                lanes.push(feature(
+                    l.0,
+                    linestring(lane.lane_center_pts.points(), gps),
+                    props,
+                ));
+            }
+
+            let mut props = JsonMap::new();
+            props.insert("osm_way_id".to_string(), r.orig_id.osm_way_id.0.into());
+            props.insert("osm_node1".to_string(), r.orig_id.i1.0.into());
+            props.insert("osm_node2".to_string(), r.orig_id.i2.0.into());
+            props.insert("name".to_string(), r.get_name(None).into());
+            props.insert(
+                "speed_limit_mph".to_string(),
+                (r.speed_limit.inner_meters_per_second() * 2.23694).into(),
+            );
+            props.insert("src_i".to_string(), r.src_i.0.into());
+            props.insert("dst_i".to_string(), r.dst_i.0.into());
+            props.insert("num_lanes".to_string(), r.lanes_ltr().len().into());
+            props.insert(
+                "length_meters".to_string(),
+                r.center_pts.length().inner_meters().into(),
+            );
+            props.insert("private".to_string(), r.is_private().into());
+            roads.push(feature(
+                r.id.0,
+                linestring(r.center_pts.points(), gps),
+                props,
+            ));
+        }
+        layers.push(("lanes", collection(lanes)));
+        layers.push(("roads", collection(roads)));
+        timer.stop("export lanes and roads");
+
+        let mut intersections = Vec::new();
+        for i in self.all_intersections() {
+            let mut props = JsonMap::new();
+            props.insert("osm_node_id".to_string(), i.orig_id.0.into());
+            props.insert(
+                "type".to_string(),
+                format!("{:?}", i.intersection_type).into(),
+            );
+            match i.intersection_type {
+                IntersectionType::StopSign => {
+                    let ss = self.get_stop_sign(i.id);
+                    props.insert(
+                        "roads_with_stop_signs".to_string(),
+                        ss.roads.values().filter(|r| r.must_stop).count().into(),
+                    );
+                }
+                IntersectionType::TrafficSignal => {
+                    let ts = self.get_traffic_signal(i.id);
+                    props.insert("num_phases".to_string(), ts.phases.len().into());
+                    props.insert(
+                        "cycle_length_seconds".to_string(),
+                        ts.phases
+                            .iter()
+                            .map(|p| p.phase_type.simple_duration().inner_seconds())
+                            .sum::<f64>()
+                            .into(),
+                    );
+                    props.insert(
+                        "offset_seconds".to_string(),
+                        ts.offset.inner_seconds().into(),
+                    );
+                }
+                IntersectionType::Border | IntersectionType::Construction => {}
+            }
+            props.insert(
+                "elevation_meters".to_string(),
+                i.elevation.inner_meters().into(),
+            );
+            intersections.push(feature(i.id.0, polygon(&i.polygon, gps), props));
+        }
+        layers.push(("intersections", collection(intersections)));
+
+        let mut buildings = Vec::new();
+        for b in self.all_buildings() {
+            let mut props = JsonMap::new();
+            props.insert("osm_id".to_string(), b.orig_id.to_string().into());
+            props.insert("address".to_string(), b.address.clone().into());
+            if let Some(ref name) = b.name {
+                props.insert("name".to_string(), name.get(None).clone().into());
+            }
+            let (bldg_type, residents) = match b.bldg_type {
+                BuildingType::Residential(n) => ("residential", n),
+                BuildingType::ResidentialCommercial(n) => ("residential_commercial", n),
+                BuildingType::Commercial => ("commercial", 0),
+                BuildingType::Empty => ("empty", 0),
+            };
+            props.insert("type".to_string(), bldg_type.into());
+            props.insert("residents".to_string(), residents.into());
+            props.insert(
+                "amenities".to_string(),
+                JsonValue::Array(
+                    b.amenities
+                        .iter()
+                        .map(|(name, amenity)| {
+                            let mut a = JsonMap::new();
+                            a.insert("name".to_string(), name.get(None).clone().into());
+                            a.insert("type".to_string(), amenity.clone().into());
+                            JsonValue::Object(a)
+                        })
+                        .collect(),
+                ),
+            );
+            let (public, spots) = match b.parking {
+                OffstreetParking::PublicGarage(_, n) => (true, n),
+                OffstreetParking::Private(n, _) => (false, n),
+            };
+            props.insert("parking_spots".to_string(), spots.into());
+            props.insert("public_parking".to_string(), public.into());
+            props.insert("sidewalk".to_string(), b.sidewalk().0.into());
+            buildings.push(feature(b.id.0, polygon(&b.polygon, gps), props));
+        }
+        layers.push(("buildings", collection(buildings)));
+
+        let mut bus_stops = Vec::new();
+        for bs in self.all_bus_stops().values() {
+            let mut props = JsonMap::new();
+            props.insert("name".to_string(), bs.name.clone().into());
+            props.insert("sidewalk".to_string(), bs.sidewalk_pos.lane().0.into());
+            props.insert("driving_lane".to_string(), bs.driving_pos.lane().0.into());
+            props.insert("is_train_stop".to_string(), bs.is_train_stop.into());
+            props.insert(
+                "routes".to_string(),
+                JsonValue::Array(
+                    self.get_routes_serving_stop(bs.id)
+                        .into_iter()
+                        .map(|r| r.id.0.into())
+                        .collect(),
+                ),
+            );
+            bus_stops.push(feature_with_id(
+                Id::String(format!("{}_{}", bs.id.sidewalk.0, bs.id.idx)),
+                point(bs.sidewalk_pos.pt(self), gps),
+                props,
+            ));
+        }
+        layers.push(("bus_stops", collection(bus_stops)));
+
+        timer.start("export bus routes");
+        let mut bus_routes = Vec::new();
+        for route in self.all_bus_routes() {
+            // Trace the path the vehicle actually takes between stops
+            let mut lines = Vec::new();
+            for req in route.all_steps(self) {
+                let start_dist = req.start.dist_along();
+                match self
+                    .pathfind(req)
+                    .and_then(|path| path.trace(self, start_dist, None))
+                {
+                    Some(pl) => {
+                        lines.push(pts_to_gps(pl.points(), gps));
+                    }
+                    None => {
+                        timer.warn(format!(
+                            "Can't trace part of {} ({}) for GeoJSON",
+                            route.id, route.full_name
+                        ));
+                    }
+                }
+            }
+            let mut props = JsonMap::new();
+            props.insert("full_name".to_string(), route.full_name.clone().into());
+            props.insert("short_name".to_string(), route.short_name.clone().into());
+            props.insert("osm_rel_id".to_string(), route.osm_rel_id.0.into());
+            props.insert(
+                "type".to_string(),
+                if route.route_type == PathConstraints::Bus {
+                    "bus".into()
+                } else {
+                    "train".into()
+                },
+            );
+            props.insert("num_stops".to_string(), route.stops.len().into());
+            props.insert("trips_per_day".to_string(), route.spawn_times.len().into());
+            bus_routes.push(feature(
+                route.id.0,
+                Geometry::new(Value::MultiLineString(lines)),
+                props,
+            ));
+        }
+        layers.push(("bus_routes", collection(bus_routes)));
+        timer.stop("export bus routes");
+
+        let mut parking_lots = Vec::new();
+        for pl in self.all_parking_lots() {
+            let mut props = JsonMap::new();
+            props.insert("osm_id".to_string(), pl.osm_id.to_string().into());
+            props.insert("capacity".to_string(), pl.capacity().into());
+            parking_lots.push(feature(pl.id.0, polygon(&pl.polygon, gps), props));
+        }
+        layers.push(("parking_lots", collection(parking_lots)));
+
+        let mut areas = Vec::new();
+        for a in self.all_areas() {
+            let mut props = JsonMap::new();
+            props.insert("osm_id".to_string(), a.osm_id.to_string().into());
+            props.insert("type".to_string(), format!("{:?}", a.area_type).into());
+            if let Some(name) = a.osm_tags.get(crate::osm::NAME) {
+                props.insert("name".to_string(), name.clone().into());
+            }
+            areas.push(feature(a.id.0, polygon(&a.polygon, gps), props));
+        }
+        layers.push(("areas", collection(areas)));
+
+        // Zones don't have their own geometry, so use their roads
+        let mut zones = Vec::new();
+        for zone in self.all_zones() {
+            let mut props = JsonMap::new();
+            props.insert(
+                "allow_through_traffic".to_string(),
+                JsonValue::Array(
+                    zone.restrictions
+                        .allow_through_traffic
+                        .iter()
+                        .map(|c| format!("{:?}", c).into())
+                        .collect(),
+                ),
+            );
+            if let Some(cap) = zone.restrictions.cap_vehicles_per_hour {
+                props.insert("cap_vehicles_per_hour".to_string(), cap.into());
+            }
+            if !zone.restrictions.hours.is_empty() {
+                props.insert(
+                    "hours".to_string(),
+                    JsonValue::Array(
+                        zone.restrictions
+                            .hours
+                            .iter()
+                            .map(|(start, end)| {
+                                format!(
+                                    "{}-{}",
+                                    Time::START_OF_DAY + *start,
+                                    Time::START_OF_DAY + *end
+                                )
+                                .into()
+                            })
+                            .collect(),
+                    ),
+                );
+            }
+            for (c, charge) in &zone.restrictions.entry_charges {
+                props.insert(format!("entry_charge_{:?}", c), (*charge).into());
+            }
+            props.insert(
+                "roads".to_string(),
+                JsonValue::Array(zone.members.iter().map(|r| r.0.into()).collect()),
+            );
+            props.insert(
+                "borders".to_string(),
+                JsonValue::Array(zone.borders.iter().map(|i| i.0.into()).collect()),
+            );
+            let key = zone
+                .members
+                .iter()
+                .map(|r| self.get_r(*r).orig_id)
+                .min()
+                .unwrap();
+            zones.push(feature_with_id(
+                Id::String(format!("{}_{}_{}", key.osm_way_id.0, key.i1.0, key.i2.0)),
+                Geometry::new(Value::MultiLineString(
+                    zone.members
+                        .iter()
+                        .map(|r| pts_to_gps(self.get_r(*r).center_pts.points(), gps))
+                        .collect(),
+                )),
+                props,
+            ));
+        }
+        layers.push(("zones", collection(zones)));
+
+        layers
+    }
+}
+
+fn feature(id: usize, geometry: Geometry, properties: JsonMap<String, JsonValue>) -> Feature {
+    feature_with_id(Id::Number(id.into()), geometry, properties)
+}
+
+fn feature_with_id(id: Id, geometry: Geometry, properties: JsonMap<String, JsonValue>) -> Feature {
+    Feature {
+        bbox: None,
+        geometry: Some(geometry),
+        id: Some(id),
+        properties: Some(properties),
+        foreign_members: None,
+    }
+}
+
+fn collection(features: Vec<Feature>) -> FeatureCollection {
+    FeatureCollection {
+        bbox: None,
+        features,
+        foreign_members: None,
+    }
+}
+
+fn pts_to_gps(pts: &[Pt2D], gps: &GPSBounds) -> Vec<Vec<f64>> {
+    pts.iter()
+        .map(|pt| {
+            let pt = pt.to_gps(gps);
+            vec![pt.x(), pt.y()]
+        })
+        .collect()
+}
+
+fn point(pt: Pt2D, gps: &GPSBounds) -> Geometry {
+    let pt = pt.to_gps(gps);
+    Geometry::new(Value::Point(vec![pt.x(), pt.y()]))
+}
+
+fn linestring(pts: &[Pt2D], gps: &GPSBounds) -> Geometry {
+    Geometry::new(Value::LineString(pts_to_gps(pts, gps)))
+}
+
+fn polygon(polygon: &Polygon, gps: &GPSBounds) -> Geometry {
+    // The outer ring, then one per hole
+    let rings = if let Some(rings) = polygon.get_rings() {
+        rings.iter().map(|r| ring(r.points(), gps)).collect()
+    } else {
+        vec![ring(polygon.points(), gps)]
+    };
+    Geometry::new(Value::Polygon(rings))
+}
+
+fn ring(pts: &[Pt2D], gps: &GPSBounds) -> Vec<Vec<f64>> {
+    let mut ring = pts_to_gps(pts, gps);
+    // GeoJSON rings must be closed
+    if ring.len() > 1 && ring[0] != ring[ring.len() - 1] {
+        ring.push(ring[0].clone());
+    }
+    ring
+}
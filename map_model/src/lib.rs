@@ -1,6 +1,8 @@
+mod attributes;
 mod city;
 pub mod connectivity;
 mod edits;
+mod geojson_export;
 mod make;
 mod map;
 mod objects;
@@ -9,9 +11,14 @@ mod pathfind;
 pub mod raw;
 mod traversable;
 
+pub use crate::attributes::{
+    Aggregation, AttributeKey, AttributeTable, AttributeType, AttributeValue, JoinOptions,
+    JoinReport, JoinTarget,
+};
 pub use crate::city::City;
 pub use crate::edits::{
-    EditCmd, EditEffects, EditIntersection, MapEdits, OriginalLane, PermanentMapEdits,
+    EditCmd, EditEffects, EditIntersection, MapEdits, MigrationNote, MigrationReport,
+    OriginalLane, OsmTagChanges, PermanentMapEdits,
 };
 pub use crate::map::MapConfig;
 pub use crate::objects::area::{Area, AreaID, AreaType};
@@ -21,9 +28,10 @@ pub use crate::objects::building::{
 pub use crate::objects::bus_stop::{BusRoute, BusRouteID, BusStop, BusStopID};
 pub use crate::objects::intersection::{Intersection, IntersectionID, IntersectionType};
 pub use crate::objects::lane::{
-    Lane, LaneID, LaneType, PARKING_LOT_SPOT_LENGTH, PARKING_SPOT_LENGTH,
+    Lane, LaneID, LanePeriod, LaneType, PARKING_LOT_SPOT_LENGTH, PARKING_SPOT_LENGTH,
 };
 pub use crate::objects::parking_lot::{ParkingLot, ParkingLotID};
+pub use crate::objects::parking_rules::ParkingRules;
 pub use crate::objects::road::{DirectedRoadID, Direction, Road, RoadID};
 pub use crate::objects::stop_signs::{ControlStopSign, RoadWithStopSign};
 pub use crate::objects::traffic_signals::{ControlTrafficSignal, Phase, PhaseType};
@@ -33,7 +41,7 @@ pub use crate::objects::turn::{
 pub use crate::objects::zone::{AccessRestrictions, Zone};
 pub use crate::pathfind::uber_turns::{IntersectionCluster, UberTurn, UberTurnGroup};
 use crate::pathfind::Pathfinder;
-pub use crate::pathfind::{Path, PathConstraints, PathRequest, PathStep};
+pub use crate::pathfind::{Path, PathConstraints, PathRequest, PathStep, RoutingParams};
 pub use crate::traversable::{Position, Traversable};
 use abstutil::{deserialize_btreemap, serialize_btreemap};
 use geom::{Bounds, Distance, GPSBounds, Polygon};
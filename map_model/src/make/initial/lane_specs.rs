@@ -1,8 +1,11 @@
+use crate::raw::DrivingSide;
 use crate::{
-    osm, Direction, LaneType, NORMAL_LANE_THICKNESS, SHOULDER_THICKNESS, SIDEWALK_THICKNESS,
+    osm, Direction, LaneType, TurnType, NORMAL_LANE_THICKNESS, SHOULDER_THICKNESS,
+    SIDEWALK_THICKNESS,
 };
 use abstutil::Tags;
 use geom::Distance;
+use std::collections::BTreeSet;
 use std::iter;
 
 #[derive(PartialEq)]
@@ -10,6 +13,8 @@ pub struct LaneSpec {
     pub lt: LaneType,
     pub dir: Direction,
     pub width: Distance,
+    // From turn:lanes. If None, turns are inferred from geometry.
+    pub turns: Option<BTreeSet<TurnType>>,
 }
 
 fn fwd(lt: LaneType) -> LaneSpec {
@@ -21,6 +26,7 @@ fn fwd(lt: LaneType) -> LaneSpec {
             LaneType::Shoulder => SHOULDER_THICKNESS,
             _ => NORMAL_LANE_THICKNESS,
         },
+        turns: None,
     }
 }
 
@@ -33,6 +39,7 @@ fn back(lt: LaneType) -> LaneSpec {
             LaneType::Shoulder => SHOULDER_THICKNESS,
             _ => NORMAL_LANE_THICKNESS,
         },
+        turns: None,
     }
 }
 
@@ -223,7 +230,8 @@ pub fn get_lane_specs_ltr(tags: &Tags) -> Vec<LaneSpec> {
         fwd_side.push(fwd(LaneType::Sidewalk));
         back_side.push(back(LaneType::Sidewalk));
     } else if tags.is(osm::SIDEWALK, "separate") {
-        // TODO Need to snap separate sidewalks to ways. Until then, just do this.
+        // convert_osm replaces this when it finds the separate sidewalks. Otherwise, assume
+        // they're on both sides.
         fwd_side.push(fwd(LaneType::Sidewalk));
         if !back_side.is_empty() {
             back_side.push(back(LaneType::Sidewalk));
@@ -270,6 +278,126 @@ pub fn get_lane_specs_ltr(tags: &Tags) -> Vec<LaneSpec> {
     back_side
 }
 
+// Fills in LaneSpec::turns from turn:lanes, turn:lanes:forward, and turn:lanes:backward. These list
+// every lane for motor vehicles, left-to-right in the direction of travel. The restrictions only
+// apply where the OSM way ends, which split_ways marks with ENDPT_FWD and ENDPT_BACK. Returns
+// problems with the tags; all lanes in a direction with a problem keep inferring turns from
+// geometry.
+pub fn apply_turn_lanes(
+    tags: &Tags,
+    specs: &mut Vec<LaneSpec>,
+    driving_side: DrivingSide,
+) -> Vec<String> {
+    let mut problems = Vec::new();
+    let is_vehicle_lane =
+        |spec: &LaneSpec| spec.lt == LaneType::Driving || spec.lt == LaneType::Bus;
+
+    // Indices into specs, from the center of the road going out
+    let fwd_lanes: Vec<usize> = (0..specs.len())
+        .filter(|idx| specs[*idx].dir == Direction::Fwd && is_vehicle_lane(&specs[*idx]))
+        .collect();
+    let back_lanes: Vec<usize> = (0..specs.len())
+        .rev()
+        .filter(|idx| specs[*idx].dir == Direction::Back && is_vehicle_lane(&specs[*idx]))
+        .collect();
+
+    let fwd_spec = if !tags.contains_key(osm::ENDPT_FWD) {
+        None
+    } else if let Some(x) = tags.get("turn:lanes:forward") {
+        Some(("turn:lanes:forward", x))
+    } else if back_lanes.is_empty() {
+        tags.get("turn:lanes").map(|x| ("turn:lanes", x))
+    } else {
+        None
+    };
+    let back_spec = if tags.contains_key(osm::ENDPT_BACK) {
+        tags.get("turn:lanes:backward")
+            .map(|x| ("turn:lanes:backward", x))
+    } else {
+        None
+    };
+
+    for (spec, lanes) in vec![(fwd_spec, fwd_lanes), (back_spec, back_lanes)] {
+        let (key, value) = match spec {
+            Some(pair) => pair,
+            None => continue,
+        };
+        let mut parts: Vec<&str> = value.split('|').collect();
+        if parts.len() != lanes.len() {
+            problems.push(format!(
+                "{}={} describes {} lanes, but there are {}",
+                key,
+                value,
+                parts.len(),
+                lanes.len()
+            ));
+            continue;
+        }
+        // Lanes are ordered from the center, which is on the left when driving on the right
+        if driving_side == DrivingSide::Left {
+            parts.reverse();
+        }
+        let mut turns = Vec::new();
+        for part in parts {
+            match parse_turn_lane(part) {
+                Ok(x) => {
+                    turns.push(x);
+                }
+                Err(err) => {
+                    problems.push(format!("{}={}: {}", key, value, err));
+                    break;
+                }
+            }
+        }
+        if turns.len() == lanes.len() {
+            for (idx, x) in lanes.into_iter().zip(turns) {
+                specs[idx].turns = x;
+            }
+        }
+    }
+    problems
+}
+
+// None means there's no restriction
+fn parse_turn_lane(part: &str) -> Result<Option<BTreeSet<TurnType>>, String> {
+    // TODO Probably the target lane should get marked as LaneType::Bus
+    if part == "no" || part == "none" || part == "yes" || part == "psv" || part == "bus" {
+        return Ok(None);
+    }
+    let mut turns = BTreeSet::new();
+    for s in part.split(';') {
+        match s {
+            "left" | "left\\left" => {
+                turns.insert(TurnType::Left);
+            }
+            "right" => {
+                turns.insert(TurnType::Right);
+            }
+            // TODO What is blank supposed to mean? From few observed cases, same as through
+            "through" | "" => {
+                turns.insert(TurnType::Straight);
+            }
+            // TODO Check this more carefully
+            "slight_right" | "slight right" | "merge_to_right" | "sharp_right" => {
+                turns.insert(TurnType::Straight);
+                turns.insert(TurnType::Right);
+            }
+            "slight_left" | "slight left" | "merge_to_left" | "sharp_left" => {
+                turns.insert(TurnType::Straight);
+                turns.insert(TurnType::Left);
+            }
+            "reverse" => {
+                // TODO We need TurnType::UTurn. Until then, u-turns usually show up as left turns.
+                turns.insert(TurnType::Left);
+            }
+            s => {
+                return Err(format!("unknown turn {}", s));
+            }
+        }
+    }
+    Ok(Some(turns))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -392,4 +520,39 @@ mod tests {
         }
         assert!(ok);
     }
+
+    #[test]
+    fn test_turn_lanes() {
+        let mut input = tags(vec![
+            "lanes=3",
+            "oneway=yes",
+            "sidewalk=right",
+            "turn:lanes=left|through|through;right",
+        ]);
+        input.insert(osm::ENDPT_FWD, "true");
+        let mut specs = get_lane_specs_ltr(&input);
+        assert!(apply_turn_lanes(&input, &mut specs, DrivingSide::Right).is_empty());
+        let turns: Vec<Option<Vec<TurnType>>> = specs
+            .iter()
+            .map(|s| s.turns.clone().map(|x| x.into_iter().collect()))
+            .collect();
+        assert_eq!(
+            turns,
+            vec![
+                Some(vec![TurnType::Left]),
+                Some(vec![TurnType::Straight]),
+                Some(vec![TurnType::Straight, TurnType::Right]),
+                None,
+            ]
+        );
+
+        // Mismatched lane counts are reported and ignored
+        input.insert("turn:lanes", "left|through");
+        let mut specs = get_lane_specs_ltr(&input);
+        assert_eq!(
+            apply_turn_lanes(&input, &mut specs, DrivingSide::Right).len(),
+            1
+        );
+        assert!(specs.iter().all(|s| s.turns.is_none()));
+    }
 }
@@ -89,8 +89,15 @@ impl InitialMap {
             m.intersections.get_mut(&id.i1).unwrap().roads.insert(*id);
             m.intersections.get_mut(&id.i2).unwrap().roads.insert(*id);
 
-            m.roads
-                .insert(*id, Road::new(*id, r, raw.config.driving_side));
+            let mut road = Road::new(*id, r, raw.config.driving_side);
+            for problem in lane_specs::apply_turn_lanes(
+                &r.osm_tags,
+                &mut road.lane_specs_ltr,
+                raw.config.driving_side,
+            ) {
+                timer.warn(format!("Ignoring turn lanes on {}: {}", id, problem));
+            }
+            m.roads.insert(*id, road);
         }
 
         timer.start_iter("find each intersection polygon", m.intersections.len());
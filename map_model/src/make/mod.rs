@@ -13,7 +13,7 @@ use crate::raw::{OriginalRoad, RawMap};
 use crate::{
     connectivity, osm, AccessRestrictions, Area, AreaID, ControlStopSign, ControlTrafficSignal,
     Direction, Intersection, IntersectionID, IntersectionType, Lane, LaneID, Map, MapEdits,
-    PathConstraints, Position, Road, RoadID, TurnGroup, Zone,
+    ParkingRules, PathConstraints, Position, Road, RoadID, TurnGroup, Zone,
 };
 use abstutil::{Parallelism, Timer};
 use geom::{Bounds, Distance, FindClosest, HashablePt2D, Speed, EPSILON_DIST};
@@ -101,13 +101,14 @@ impl Map {
                     .complicated_turn_restrictions
                     .iter()
                     .filter_map(|(via, to)| {
-                        if let (Some(via), Some(to)) =
-                            (road_id_mapping.get(via), road_id_mapping.get(to))
-                        {
-                            Some((*via, *to))
+                        let via_ids: Option<Vec<RoadID>> =
+                            via.iter().map(|r| road_id_mapping.get(r).cloned()).collect();
+                        if let (Some(via_ids), Some(to)) = (via_ids, road_id_mapping.get(to)) {
+                            Some((via_ids, *to))
                         } else {
                             timer.warn(format!(
-                                "Complicated turn restriction from {} has invalid via {} or dst {}",
+                                "Complicated turn restriction from {} has invalid via {:?} or dst \
+                                 {}",
                                 r.id, via, to
                             ));
                             None
@@ -182,6 +183,9 @@ impl Map {
                     bus_stops: BTreeSet::new(),
                     driving_blackhole: false,
                     biking_blackhole: false,
+                    turn_restrictions: lane.turns.clone().map(|turns| (dst_i, turns)),
+                    parking_rules: ParkingRules::new(),
+                    schedule: Vec::new(),
                 });
             }
             if road.get_name(None) == "???" {
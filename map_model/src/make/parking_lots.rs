@@ -1,11 +1,13 @@
 use crate::make::match_points_to_lanes;
 use crate::raw::RawParkingLot;
 use crate::{
-    osm, Map, ParkingLot, ParkingLotID, PathConstraints, Position, NORMAL_LANE_THICKNESS,
-    PARKING_LOT_SPOT_LENGTH,
+    osm, Map, ParkingLot, ParkingLotID, ParkingRules, PathConstraints, Position,
+    NORMAL_LANE_THICKNESS, PARKING_LOT_SPOT_LENGTH,
+};
+use abstutil::{Tags, Timer};
+use geom::{
+    Angle, Distance, Duration, FindClosest, HashablePt2D, Line, PolyLine, Polygon, Pt2D, Ring,
 };
-use abstutil::Timer;
-use geom::{Angle, Distance, FindClosest, HashablePt2D, Line, PolyLine, Polygon, Pt2D, Ring};
 use std::collections::HashSet;
 
 pub fn make_all_parking_lots(
@@ -87,6 +89,7 @@ pub fn make_all_parking_lots(
                     driving_pos,
                     sidewalk_line,
                     sidewalk_pos: *sidewalk_pos,
+                    rules: parking_rules(&orig.osm_tags),
                 });
             } else {
                 timer.warn(format!(
@@ -255,3 +258,74 @@ fn line_valid(
 
     true
 }
+
+fn parking_rules(tags: &Tags) -> ParkingRules {
+    let mut rules = ParkingRules::new();
+    rules.price_per_hour = price_per_hour(tags);
+    rules.max_duration = tags.get("maxstay").and_then(|x| max_stay(x));
+    rules
+}
+
+// Understands simple charges like "2.50 USD/hour" or "EUR 1/h". Anything else, like daily or
+// time-dependent charges, is treated as free.
+fn price_per_hour(tags: &Tags) -> f64 {
+    if tags.is("fee", "no") {
+        return 0.0;
+    }
+    if let Some(charge) = tags.get("charge") {
+        if charge.ends_with("/hour") || charge.ends_with("/h") {
+            for word in charge.split(|c: char| c.is_whitespace() || c == '/') {
+                if let Ok(x) = word.parse::<f64>() {
+                    return x;
+                }
+            }
+        }
+    }
+    0.0
+}
+
+// Understands "2 hours", "90 minutes", "1 h", etc
+fn max_stay(value: &str) -> Option<Duration> {
+    let mut parts = value.split_whitespace();
+    let x = parts.next()?.parse::<f64>().ok()?;
+    match parts.next()? {
+        "h" | "hr" | "hour" | "hours" => Some(Duration::hours(1) * x),
+        "min" | "mins" | "minute" | "minutes" => Some(Duration::minutes(1) * x),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tags(kv: Vec<&str>) -> Tags {
+        let mut tags = Tags::new(std::collections::BTreeMap::new());
+        for pair in kv {
+            let parts = pair.split('=').collect::<Vec<_>>();
+            tags.insert(parts[0], parts[1]);
+        }
+        tags
+    }
+
+    #[test]
+    fn test_price_per_hour() {
+        for (input, price) in vec![
+            (vec![], 0.0),
+            (vec!["fee=yes", "charge=2.50 USD/hour"], 2.5),
+            (vec!["charge=EUR 1/h"], 1.0),
+            (vec!["fee=no", "charge=3 USD/hour"], 0.0),
+            (vec!["charge=10 USD/day"], 0.0),
+        ] {
+            assert_eq!(price_per_hour(&tags(input.clone())), price, "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn test_max_stay() {
+        assert_eq!(max_stay("2 hours"), Some(Duration::hours(2)));
+        assert_eq!(max_stay("90 minutes"), Some(Duration::minutes(90)));
+        assert_eq!(max_stay("unlimited"), None);
+        assert_eq!(max_stay("1 day"), None);
+    }
+}
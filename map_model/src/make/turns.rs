@@ -1,7 +1,7 @@
 use crate::raw::RestrictionType;
 use crate::{Intersection, Lane, LaneID, Map, Turn, TurnID, TurnType};
 use abstutil::Timer;
-use geom::{Distance, PolyLine, Pt2D};
+use geom::{Distance, Line, PolyLine, Pt2D};
 use nbez::{Bez3o, BezCurve, Point2d};
 use std::collections::{BTreeSet, HashMap, HashSet};
 
@@ -15,6 +15,23 @@ pub fn make_all_turns(map: &Map, i: &Intersection, timer: &mut Timer) -> Vec<Tur
     ));
     let unique_turns = ensure_unique(raw_turns);
 
+    // Report turn:lanes asking for movements that the geometry doesn't have
+    for l in &i.incoming_lanes {
+        if let Some(types) = map.get_l(*l).get_turn_restrictions(i.id) {
+            for turn_type in types {
+                if !unique_turns
+                    .iter()
+                    .any(|t| t.id.src == *l && t.turn_type == *turn_type)
+                {
+                    timer.warn(format!(
+                        "turn:lanes on {} allows {:?} turns at {}, but there are none",
+                        l, turn_type, i.id
+                    ));
+                }
+            }
+        }
+    }
+
     let mut final_turns: Vec<Turn> = Vec::new();
     let mut filtered_turns: HashMap<LaneID, Vec<Turn>> = HashMap::new();
     for turn in unique_turns {
@@ -126,11 +143,8 @@ fn ensure_unique(turns: Vec<Turn>) -> Vec<Turn> {
 }
 
 fn is_turn_allowed(turn: &Turn, map: &Map) -> bool {
-    if let Some(mut types) = map
-        .get_l(turn.id.src)
-        .get_turn_restrictions(map.get_parent(turn.id.src))
-    {
-        types.any(|turn_type| turn_type == turn.turn_type)
+    if let Some(types) = map.get_l(turn.id.src).get_turn_restrictions(turn.id.parent) {
+        types.contains(&turn.turn_type)
     } else {
         true
     }
@@ -169,16 +183,33 @@ fn does_turn_pass_restrictions(turn: &Turn, i: &Intersection, map: &Map) -> bool
 fn make_vehicle_turns(i: &Intersection, map: &Map, timer: &mut Timer) -> Vec<Turn> {
     let mut turns = Vec::new();
 
+    // Where vehicles leave and enter lanes here, with a line pointing in the direction of travel.
+    // Reversible lanes are also used the other way, from their other end.
+    let mut sources: Vec<(&Lane, Line)> = Vec::new();
+    let mut destinations: Vec<(&Lane, Line)> = Vec::new();
+    for l in &i.incoming_lanes {
+        let l = map.get_l(*l);
+        sources.push((l, l.last_line()));
+        if l.is_ever_reversed() {
+            destinations.push((l, l.last_line().reverse()));
+        }
+    }
+    for l in &i.outgoing_lanes {
+        let l = map.get_l(*l);
+        destinations.push((l, l.first_line()));
+        if l.is_ever_reversed() {
+            sources.push((l, l.first_line().reverse()));
+        }
+    }
+
     // Just generate every possible combination of turns between incoming and outgoing lanes.
     let is_deadend = i.roads.len() == 1;
-    for src in &i.incoming_lanes {
-        let src = map.get_l(*src);
-        if !src.lane_type.is_for_moving_vehicles() {
+    for (src, src_line) in &sources {
+        if !src.is_ever_for_moving_vehicles() {
             continue;
         }
-        for dst in &i.outgoing_lanes {
-            let dst = map.get_l(*dst);
-            if !dst.lane_type.is_for_moving_vehicles() {
+        for (dst, dst_line) in &destinations {
+            if !dst.is_ever_for_moving_vehicles() {
                 continue;
             }
             // Only allow U-turns at deadends
@@ -189,7 +220,7 @@ fn make_vehicle_turns(i: &Intersection, map: &Map, timer: &mut Timer) -> Vec<Tur
             if src.is_light_rail() != dst.is_light_rail() {
                 continue;
             }
-            if src.last_pt() == dst.first_pt() {
+            if src_line.pt2() == dst_line.pt1() {
                 timer.warn(format!(
                     "No turn from {} to {}; the endpoints are the same",
                     src.id, dst.id
@@ -197,13 +228,12 @@ fn make_vehicle_turns(i: &Intersection, map: &Map, timer: &mut Timer) -> Vec<Tur
                 continue;
             }
 
-            let turn_type =
-                TurnType::from_angles(src.last_line().angle(), dst.first_line().angle());
+            let turn_type = TurnType::from_angles(src_line.angle(), dst_line.angle());
             let geom = if turn_type == TurnType::Straight {
-                PolyLine::must_new(vec![src.last_pt(), dst.first_pt()])
+                PolyLine::must_new(vec![src_line.pt2(), dst_line.pt1()])
             } else {
-                curvey_turn(src, dst)
-                    .unwrap_or_else(|_| PolyLine::must_new(vec![src.last_pt(), dst.first_pt()]))
+                curvey_turn(src_line, dst_line)
+                    .unwrap_or_else(|_| PolyLine::must_new(vec![src_line.pt2(), dst_line.pt1()]))
             };
 
             turns.push(Turn {
@@ -222,18 +252,17 @@ fn make_vehicle_turns(i: &Intersection, map: &Map, timer: &mut Timer) -> Vec<Tur
     turns
 }
 
-fn curvey_turn(src: &Lane, dst: &Lane) -> Result<PolyLine, String> {
+fn curvey_turn(src_line: &Line, dst_line: &Line) -> Result<PolyLine, String> {
     // The control points are straight out/in from the source/destination lanes, so
     // that the car exits and enters at the same angle as the road.
-    let src_line = src.last_line();
-    let dst_line = dst.first_line().reverse();
+    let dst_line = dst_line.reverse();
 
     // TODO Tune the 5.0 and pieces
     let curve = Bez3o::new(
-        to_pt(src.last_pt()),
+        to_pt(src_line.pt2()),
         to_pt(src_line.unbounded_dist_along(src_line.length() + Distance::meters(5.0))),
         to_pt(dst_line.unbounded_dist_along(dst_line.length() + Distance::meters(5.0))),
-        to_pt(dst.first_pt()),
+        to_pt(dst_line.pt2()),
     );
     let pieces = 5;
     let mut curve: Vec<Pt2D> = (0..=pieces)
@@ -1,9 +1,9 @@
 use crate::raw::DrivingSide;
 use crate::{
-    Direction, Intersection, IntersectionID, Lane, LaneID, LaneType, Map, Road, Turn, TurnID,
+    osm, Direction, Intersection, IntersectionID, Lane, LaneID, LaneType, Map, Road, Turn, TurnID,
     TurnType,
 };
-use abstutil::{wraparound_get, Timer};
+use abstutil::{wraparound_get, Tags, Timer};
 use geom::{Distance, Line, PolyLine, Pt2D, Ring};
 use std::collections::BTreeSet;
 
@@ -83,7 +83,9 @@ pub fn make_walking_turns(map: &Map, i: &Intersection, timer: &mut Timer) -> Vec
         if let Some(l1) = get_sidewalk(lanes, roads[idx1].incoming_lanes(i.id)) {
             // Make the crosswalk to the other side
             if let Some(l2) = get_sidewalk(lanes, roads[idx1].outgoing_lanes(i.id)) {
-                result.extend(make_crosswalks(i.id, l1, l2).into_iter().flatten());
+                if crosswalk_allowed(&roads[idx1].osm_tags, roads[idx1].src_i == i.id) {
+                    result.extend(make_crosswalks(i.id, l1, l2).into_iter().flatten());
+                }
             }
 
             // Find the shared corner
@@ -143,6 +145,17 @@ pub fn make_walking_turns(map: &Map, i: &Intersection, timer: &mut Timer) -> Vec
     result
 }
 
+// Assume every road can be crossed, unless OSM maps a crossing=no node near this end of it.
+// Unmarked crossings often aren't mapped at all, so a missing tag doesn't mean anything.
+fn crosswalk_allowed(tags: &Tags, at_start: bool) -> bool {
+    let key = if at_start {
+        osm::CROSSING_BACK
+    } else {
+        osm::CROSSING_FWD
+    };
+    !tags.is(key, "no")
+}
+
 // TODO Need to filter out extraneous crosswalks. Why weren't they being created before?
 fn _new_make_walking_turns(
     driving_side: DrivingSide,
@@ -451,3 +464,24 @@ fn get_sidewalk<'a>(lanes: &'a Vec<Lane>, children: Vec<(LaneID, LaneType)>) ->
     }
     None
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_crosswalk_allowed() {
+        let mut tags = Tags::new(BTreeMap::new());
+        assert!(crosswalk_allowed(&tags, true));
+        assert!(crosswalk_allowed(&tags, false));
+
+        // Only the tagged end loses its crosswalk
+        tags.insert(osm::CROSSING_BACK, "no");
+        assert!(!crosswalk_allowed(&tags, true));
+        assert!(crosswalk_allowed(&tags, false));
+
+        tags.insert(osm::CROSSING_FWD, "uncontrolled");
+        assert!(crosswalk_allowed(&tags, false));
+    }
+}
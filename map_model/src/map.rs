@@ -1,9 +1,10 @@
 use crate::raw::{DrivingSide, OriginalRoad, RawMap};
 use crate::{
-    osm, Area, AreaID, Building, BuildingID, BuildingType, BusRoute, BusRouteID, BusStop,
-    BusStopID, ControlStopSign, ControlTrafficSignal, Intersection, IntersectionID, Lane, LaneID,
-    LaneType, Map, MapEdits, OffstreetParking, ParkingLot, ParkingLotID, Path, PathConstraints,
-    PathRequest, Pathfinder, Position, Road, RoadID, Turn, TurnGroupID, TurnID, TurnType, Zone,
+    osm, AccessRestrictions, Area, AreaID, Building, BuildingID, BuildingType, BusRoute,
+    BusRouteID, BusStop, BusStopID, ControlStopSign, ControlTrafficSignal, Intersection,
+    IntersectionID, Lane, LaneID, LaneType, Map, MapEdits, OffstreetParking, ParkingLot,
+    ParkingLotID, Path, PathConstraints, PathRequest, Pathfinder, Position, Road, RoadID,
+    RoutingParams, Turn, TurnGroupID, TurnID, TurnType, Zone,
 };
 use abstutil::Timer;
 use geom::{Angle, Bounds, Distance, GPSBounds, Line, PolyLine, Polygon, Pt2D, Ring, Time};
@@ -276,6 +277,7 @@ impl Map {
     }
 
     // The turns may belong to two different intersections!
+    // Only in the usual direction of reversible lanes
     pub fn get_turns_from_lane(&self, l: LaneID) -> Vec<&Turn> {
         let lane = self.get_l(l);
         let mut turns: Vec<&Turn> = self
@@ -283,7 +285,7 @@ impl Map {
             .turns
             .iter()
             .map(|t| self.get_t(*t))
-            .filter(|t| t.id.src == l)
+            .filter(|t| t.id.src == l && !t.enters_contraflow(self))
             .collect();
         // Sidewalks/shoulders are bidirectional
         if lane.is_walkable() {
@@ -296,6 +298,7 @@ impl Map {
         turns
     }
 
+    // Only in the usual direction of reversible lanes
     pub fn get_turns_to_lane(&self, l: LaneID) -> Vec<&Turn> {
         let lane = self.get_l(l);
         let mut turns: Vec<&Turn> = self
@@ -303,7 +306,7 @@ impl Map {
             .turns
             .iter()
             .map(|t| self.get_t(*t))
-            .filter(|t| t.id.dst == l)
+            .filter(|t| t.id.dst == l && !t.leaves_contraflow(self))
             .collect();
         // Sidewalks/shoulders are bidirectional
         if lane.is_walkable() {
@@ -341,11 +344,13 @@ impl Map {
             .map(move |t| (self.get_t(*t), self.get_l(t.dst)))
     }
 
+    // Reversible lanes are only used in their usual direction here; only pathfinding for a
+    // particular time of day knows when they run the other way.
     pub fn get_turns_for(&self, from: LaneID, constraints: PathConstraints) -> Vec<&Turn> {
         let mut turns: Vec<&Turn> = self
             .get_next_turns_and_lanes(from, self.get_l(from).dst_i)
             .into_iter()
-            .filter(|(_, l)| constraints.can_use(l, self))
+            .filter(|(t, l)| constraints.can_use(l, self) && !t.enters_contraflow(self))
             .map(|(t, _)| t)
             .collect();
         // Sidewalks are bidirectional
@@ -377,6 +382,17 @@ impl Map {
         self.get_r(l.parent)
     }
 
+    // If this turn crosses into a zone that charges vehicles to enter, returns that zone's
+    // restrictions. Neighboring roads with the same restrictions are always in the same zone.
+    pub fn entering_priced_zone(&self, t: TurnID) -> Option<&AccessRestrictions> {
+        let dst = &self.get_parent(t.dst).access_restrictions;
+        if !dst.entry_charges.is_empty() && self.get_parent(t.src).access_restrictions != *dst {
+            Some(dst)
+        } else {
+            None
+        }
+    }
+
     pub fn get_gps_bounds(&self) -> &GPSBounds {
         &self.gps_bounds
     }
@@ -525,6 +541,19 @@ impl Map {
         self.pathfinder.pathfind(req, self)
     }
 
+    // Respects lanes that change type during the day, zones that restrict access at some hours,
+    // and entry charges. Also returns true if entry charges or a zone closed right now pushed the
+    // path off of the usual route.
+    pub fn pathfind_at(
+        &self,
+        req: PathRequest,
+        now: Time,
+        params: &RoutingParams,
+    ) -> Option<(Path, bool)> {
+        assert!(!self.pathfinder_dirty);
+        self.pathfinder.pathfind_at(req, now, params, self)
+    }
+
     pub fn should_use_transit(
         &self,
         start: Position,
@@ -576,6 +605,15 @@ impl Map {
         None
     }
 
+    pub fn find_pl_by_osm_id(&self, id: osm::OsmID) -> Option<ParkingLotID> {
+        for pl in self.all_parking_lots() {
+            if pl.osm_id == id {
+                return Some(pl.id);
+            }
+        }
+        None
+    }
+
     pub fn find_br(&self, id: osm::RelationID) -> Option<BusRouteID> {
         for br in self.all_bus_routes() {
             if br.osm_rel_id == id {
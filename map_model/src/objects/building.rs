@@ -184,7 +184,7 @@ impl Building {
     }
 }
 
-fn sidewalk_to_bike(sidewalk_pos: Position, map: &Map) -> Option<(Position, Position)> {
+pub(crate) fn sidewalk_to_bike(sidewalk_pos: Position, map: &Map) -> Option<(Position, Position)> {
     let lane = map.get_parent(sidewalk_pos.lane()).find_closest_lane(
         sidewalk_pos.lane(),
         |l| !l.biking_blackhole && PathConstraints::Bike.can_use(l, map),
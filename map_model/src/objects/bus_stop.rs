@@ -1,3 +1,4 @@
+use crate::objects::building::sidewalk_to_bike;
 use crate::{osm, LaneID, Map, PathConstraints, PathRequest, Position};
 use abstutil::{deserialize_usize, serialize_usize};
 use geom::Time;
@@ -44,6 +45,14 @@ pub struct BusStop {
     pub is_train_stop: bool,
 }
 
+impl BusStop {
+    // Returns (biking position, sidewalk position), for people who ride here and continue on
+    // transit. Unlike buildings, only looks at the stop's own road.
+    pub fn biking_connection(&self, map: &Map) -> Option<(Position, Position)> {
+        sidewalk_to_bike(self.sidewalk_pos, map)
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct BusRoute {
     pub id: BusRouteID,
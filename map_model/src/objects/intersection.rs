@@ -75,6 +75,11 @@ impl Intersection {
         self.roads.iter().all(|r| map.get_r(*r).is_light_rail())
     }
 
+    // One of the intersections around the ring of a roundabout
+    pub fn is_roundabout(&self, map: &Map) -> bool {
+        self.roads.iter().any(|r| map.get_r(*r).is_roundabout())
+    }
+
     pub fn is_private(&self, map: &Map) -> bool {
         self.roads.iter().all(|r| map.get_r(*r).is_private())
     }
@@ -1,10 +1,10 @@
+use crate::objects::parking_rules::in_window;
 use crate::pathfind;
 use crate::{
-    osm, BusStopID, DirectedRoadID, Direction, IntersectionID, Map, PathConstraints, Road, RoadID,
-    TurnType,
+    BusStopID, DirectedRoadID, IntersectionID, Map, ParkingRules, PathConstraints, RoadID, TurnType,
 };
 use abstutil::{deserialize_usize, serialize_usize};
-use geom::{Distance, Line, PolyLine, Pt2D};
+use geom::{Distance, Duration, Line, PolyLine, Pt2D, Time};
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeSet;
 use std::fmt;
@@ -101,6 +101,25 @@ impl LaneType {
     }
 }
 
+// Part of the day when a lane is used differently, like a peak-hour bus lane, a parking lane that
+// opens to traffic during rush hour, or a reversible center lane.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct LanePeriod {
+    // Times of day. A window ending before it starts wraps past midnight.
+    pub start: Duration,
+    pub end: Duration,
+    pub lt: LaneType,
+    // Traffic runs from dst_i to src_i during this period.
+    #[serde(default)]
+    pub reversed: bool,
+}
+
+impl LanePeriod {
+    pub fn contains(&self, now: Time) -> bool {
+        in_window(&[(self.start, self.end)], now)
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Lane {
     pub id: LaneID,
@@ -119,6 +138,15 @@ pub struct Lane {
     // graph, because this is near a border.
     pub driving_blackhole: bool,
     pub biking_blackhole: bool,
+
+    // From turn:lanes, the only turns allowed at one intersection. The intersection is where the
+    // lane originally ended, so the restrictions stop applying if the lane gets reversed.
+    pub turn_restrictions: Option<(IntersectionID, BTreeSet<TurnType>)>,
+
+    // Only meaningful for parking lanes; changed by map edits.
+    pub parking_rules: ParkingRules,
+    // Outside of these periods, the lane is just lane_type. Changed by map edits.
+    pub schedule: Vec<LanePeriod>,
 }
 
 impl Lane {
@@ -217,6 +245,45 @@ impl Lane {
         self.lane_type == LaneType::LightRail
     }
 
+    // The first matching period wins
+    pub fn lane_type_at(&self, now: Time) -> LaneType {
+        self.schedule
+            .iter()
+            .find(|p| p.contains(now))
+            .map(|p| p.lt)
+            .unwrap_or(self.lane_type)
+    }
+
+    // Every type the lane takes on at some point in the day
+    pub fn all_lane_types(&self) -> BTreeSet<LaneType> {
+        let mut types: BTreeSet<LaneType> = self.schedule.iter().map(|p| p.lt).collect();
+        types.insert(self.lane_type);
+        types
+    }
+
+    // The first matching period wins, like lane_type_at
+    pub fn reversed_at(&self, now: Time) -> bool {
+        self.is_ever_reversed()
+            && self
+                .schedule
+                .iter()
+                .find(|p| p.contains(now))
+                .map(|p| p.reversed)
+                .unwrap_or(false)
+    }
+
+    // Loop lanes start and end at the same intersection, so there's no other way to run them.
+    pub fn is_ever_reversed(&self) -> bool {
+        self.src_i != self.dst_i && self.schedule.iter().any(|p| p.reversed)
+    }
+
+    // Turns have to exist for lanes that only carry traffic during part of the day.
+    pub fn is_ever_for_moving_vehicles(&self) -> bool {
+        self.all_lane_types()
+            .into_iter()
+            .any(|lt| lt.is_for_moving_vehicles())
+    }
+
     // TODO Store this natively if this winds up being useful.
     pub(crate) fn get_directed_parent(&self, map: &Map) -> DirectedRoadID {
         let r = map.get_r(self.parent);
@@ -226,53 +293,21 @@ impl Lane {
         }
     }
 
-    pub fn get_turn_restrictions<'a>(
-        &'a self,
-        road: &'a Road,
-    ) -> Option<impl Iterator<Item = TurnType> + 'a> {
-        if !self.is_driving() {
-            return None;
+    // If None, any turn type is allowed
+    pub fn get_turn_restrictions(&self, i: IntersectionID) -> Option<&BTreeSet<TurnType>> {
+        match self.turn_restrictions {
+            Some((at, ref turns)) if at == i && self.dst_i == i => Some(turns),
+            _ => None,
         }
+    }
 
-        let (dir, offset) = road.dir_and_offset(self.id);
-        let all = if dir == Direction::Fwd && road.osm_tags.contains_key(osm::ENDPT_FWD) {
-            road.osm_tags
-                .get("turn:lanes:forward")
-                .or_else(|| road.osm_tags.get("turn:lanes"))?
-        } else if dir == Direction::Back && road.osm_tags.contains_key(osm::ENDPT_BACK) {
-            road.osm_tags.get("turn:lanes:backward")?
-        } else {
-            return None;
-        };
-        let parts: Vec<&str> = all.split('|').collect();
-        // TODO Verify the number of lanes matches up
-        let part = parts.get(offset)?;
-        // TODO Probably the target lane should get marked as LaneType::Bus
-        if part == &"no" || part == &"none" || part == &"yes" || part == &"psv" || part == &"bus" {
-            return None;
+    // In the direction of the lane, from src_i to dst_i. Returns [-1.0, 1.0]; positive is uphill.
+    pub fn percent_grade(&self, map: &Map) -> f64 {
+        if self.length() == Distance::ZERO {
+            return 0.0;
         }
-        Some(part.split(';').flat_map(|s| match s {
-            "left" | "left\\left" => vec![TurnType::Left],
-            "right" => vec![TurnType::Right],
-            // TODO What is blank supposed to mean? From few observed cases, same as through
-            "through" | "" => vec![TurnType::Straight],
-            // TODO Check this more carefully
-            "slight_right" | "slight right" | "merge_to_right" | "sharp_right" => {
-                vec![TurnType::Straight, TurnType::Right]
-            }
-            "slight_left" | "slight left" | "merge_to_left" | "sharp_left" => {
-                vec![TurnType::Straight, TurnType::Left]
-            }
-            "reverse" => {
-                // TODO We need TurnType::UTurn. Until then, u-turns usually show up as
-                // left turns.
-                vec![TurnType::Left]
-            }
-            s => {
-                println!("Unknown turn restriction {}", s);
-                vec![]
-            }
-        }))
+        let rise = map.get_i(self.dst_i).elevation - map.get_i(self.src_i).elevation;
+        (rise / self.length()).max(-1.0).min(1.0)
     }
 
     pub fn get_max_cost(&self, constraints: PathConstraints, map: &Map) -> usize {
@@ -287,3 +322,60 @@ impl Lane {
             })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lane(src_i: usize, dst_i: usize, schedule: Vec<LanePeriod>) -> Lane {
+        Lane {
+            id: LaneID(0),
+            parent: RoadID(0),
+            lane_type: LaneType::Driving,
+            lane_center_pts: PolyLine::must_new(vec![Pt2D::new(0.0, 0.0), Pt2D::new(100.0, 0.0)]),
+            width: Distance::meters(3.0),
+            src_i: IntersectionID(src_i),
+            dst_i: IntersectionID(dst_i),
+            bus_stops: BTreeSet::new(),
+            driving_blackhole: false,
+            biking_blackhole: false,
+            turn_restrictions: None,
+            parking_rules: ParkingRules::new(),
+            schedule,
+        }
+    }
+
+    fn at(hours: usize) -> Time {
+        Time::START_OF_DAY + Duration::hours(hours)
+    }
+
+    #[test]
+    fn test_reversed_at() {
+        let schedule = vec![
+            LanePeriod {
+                start: Duration::hours(7),
+                end: Duration::hours(9),
+                lt: LaneType::Driving,
+                reversed: true,
+            },
+            LanePeriod {
+                start: Duration::hours(16),
+                end: Duration::hours(18),
+                lt: LaneType::Bus,
+                reversed: false,
+            },
+        ];
+        let l = lane(0, 1, schedule.clone());
+        assert!(l.is_ever_reversed());
+        assert!(l.reversed_at(at(8)));
+        assert_eq!(l.lane_type_at(at(8)), LaneType::Driving);
+        assert!(!l.reversed_at(at(12)));
+        assert!(!l.reversed_at(at(17)));
+        assert_eq!(l.lane_type_at(at(17)), LaneType::Bus);
+
+        // A loop lane has no other way to run
+        let l = lane(0, 0, schedule);
+        assert!(!l.is_ever_reversed());
+        assert!(!l.reversed_at(at(8)));
+    }
+}
@@ -4,6 +4,7 @@ pub mod bus_stop;
 pub mod intersection;
 pub mod lane;
 pub mod parking_lot;
+pub mod parking_rules;
 pub mod road;
 pub mod stop_signs;
 pub mod traffic_signals;
@@ -1,4 +1,4 @@
-use crate::{osm, Position};
+use crate::{osm, ParkingRules, Position};
 use abstutil::{deserialize_usize, serialize_usize};
 use geom::{Angle, Line, PolyLine, Polygon, Pt2D};
 use serde::{Deserialize, Serialize};
@@ -49,6 +49,9 @@ pub struct ParkingLot {
     // Lot to sidewalk
     pub sidewalk_line: Line,
     pub sidewalk_pos: Position,
+
+    // Initially from OSM tags, changed by map edits.
+    pub rules: ParkingRules,
 }
 
 impl ParkingLot {
@@ -0,0 +1,151 @@
+use crate::BuildingID;
+use geom::{Duration, Time};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+
+// Regulations for parking along a lane or in a lot. The defaults don't restrict anything.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct ParkingRules {
+    // In dollars, charged only during metered hours
+    pub price_per_hour: f64,
+    // Times of day when the price and max_duration apply. Empty means always. A window ending
+    // before it starts wraps past midnight.
+    pub metered_hours: Vec<(Duration, Duration)>,
+    // How long somebody can stay during metered hours
+    pub max_duration: Option<Duration>,
+    // Times of day when nobody can park, like overnight restrictions or street cleaning
+    pub no_parking: Vec<(Duration, Duration)>,
+    // If set, only residents of these buildings can park here. They don't pay or have a time
+    // limit.
+    pub permit_zone: Option<BTreeSet<BuildingID>>,
+}
+
+impl ParkingRules {
+    pub fn new() -> ParkingRules {
+        ParkingRules {
+            price_per_hour: 0.0,
+            metered_hours: Vec::new(),
+            max_duration: None,
+            no_parking: Vec::new(),
+            permit_zone: None,
+        }
+    }
+
+    pub fn is_unrestricted(&self) -> bool {
+        self == &ParkingRules::new()
+    }
+
+    pub fn has_permit(&self, home: Option<BuildingID>) -> bool {
+        match (&self.permit_zone, home) {
+            (Some(zone), Some(b)) => zone.contains(&b),
+            _ => false,
+        }
+    }
+
+    // Can a car whose owner lives at home start parking now?
+    pub fn allows(&self, now: Time, home: Option<BuildingID>) -> bool {
+        if in_window(&self.no_parking, now) {
+            return false;
+        }
+        self.permit_zone.is_none() || self.has_permit(home)
+    }
+
+    // In dollars per hour, for somebody starting to park now
+    pub fn current_price(&self, now: Time, home: Option<BuildingID>) -> f64 {
+        if self.has_permit(home) || !self.is_metered(now) {
+            0.0
+        } else {
+            self.price_per_hour
+        }
+    }
+
+    pub fn is_metered(&self, now: Time) -> bool {
+        self.metered_hours.is_empty() || in_window(&self.metered_hours, now)
+    }
+
+    // How much time between start and end was metered?
+    pub fn metered_time(&self, start: Time, end: Time) -> Duration {
+        if self.metered_hours.is_empty() {
+            end - start
+        } else {
+            time_within(&self.metered_hours, start, end)
+        }
+    }
+
+    // How much time between start and end was parking banned?
+    pub fn banned_time(&self, start: Time, end: Time) -> Duration {
+        time_within(&self.no_parking, start, end)
+    }
+}
+
+const DAY: f64 = 24.0 * 3600.0;
+
+// Split windows that wrap past midnight, so every window has start < end.
+fn normalize(windows: &[(Duration, Duration)]) -> Vec<(f64, f64)> {
+    let mut result = Vec::new();
+    for (start, end) in windows {
+        let (start, end) = (start.inner_seconds(), end.inner_seconds());
+        if start < end {
+            result.push((start, end));
+        } else {
+            result.push((start, DAY));
+            result.push((0.0, end));
+        }
+    }
+    result
+}
+
+pub(crate) fn in_window(windows: &[(Duration, Duration)], t: Time) -> bool {
+    let time_of_day = t.inner_seconds() % DAY;
+    normalize(windows)
+        .into_iter()
+        .any(|(start, end)| time_of_day >= start && time_of_day < end)
+}
+
+// How much of the interval falls inside the windows, repeated every day?
+fn time_within(windows: &[(Duration, Duration)], start: Time, end: Time) -> Duration {
+    let (start, end) = (start.inner_seconds(), end.inner_seconds());
+    let mut total = 0.0;
+    for (w1, w2) in normalize(windows) {
+        let mut day = (start / DAY).floor() * DAY;
+        while day < end {
+            total += (end.min(day + w2) - start.max(day + w1)).max(0.0);
+            day += DAY;
+        }
+    }
+    Duration::seconds(total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hours(x: f64) -> Duration {
+        Duration::seconds(x * 3600.0)
+    }
+
+    fn at(x: f64) -> Time {
+        Time::START_OF_DAY + hours(x)
+    }
+
+    #[test]
+    fn test_windows() {
+        let daytime = vec![(hours(8.0), hours(18.0))];
+        let overnight = vec![(hours(22.0), hours(6.0))];
+
+        assert!(in_window(&daytime, at(8.0)));
+        assert!(!in_window(&daytime, at(18.0)));
+        assert!(in_window(&daytime, at(24.0 + 12.0)));
+        assert!(in_window(&overnight, at(23.0)));
+        assert!(in_window(&overnight, at(24.0 + 5.0)));
+        assert!(!in_window(&overnight, at(12.0)));
+
+        assert_eq!(time_within(&daytime, at(7.0), at(9.0)), hours(1.0));
+        assert_eq!(time_within(&daytime, at(12.0), at(24.0 + 10.0)), hours(8.0));
+        assert_eq!(
+            time_within(&overnight, at(20.0), at(24.0 + 8.0)),
+            hours(8.0)
+        );
+        assert_eq!(time_within(&overnight, at(0.0), at(1.0)), hours(1.0));
+    }
+}
@@ -93,8 +93,8 @@ pub struct Road {
     pub osm_tags: Tags,
     // self is 'from'
     pub turn_restrictions: Vec<(RestrictionType, RoadID)>,
-    // self is 'from'. (via, to). Only BanTurns.
-    pub complicated_turn_restrictions: Vec<(RoadID, RoadID)>,
+    // self is 'from'. (via, to), where via is one or more consecutive roads. Only BanTurns.
+    pub complicated_turn_restrictions: Vec<(Vec<RoadID>, RoadID)>,
     pub orig_id: OriginalRoad,
     pub speed_limit: Speed,
     pub access_restrictions: AccessRestrictions,
@@ -322,6 +322,12 @@ impl Road {
         self.lanes_ltr().len() == 1 && self.lanes_ltr()[0].2 == LaneType::LightRail
     }
 
+    // Part of the ring of a roundabout, where circulating traffic has priority
+    pub fn is_roundabout(&self) -> bool {
+        self.osm_tags
+            .is_any("junction", vec!["roundabout", "circular"])
+    }
+
     pub fn is_footway(&self) -> bool {
         self.lanes_ltr().len() == 1 && self.lanes_ltr()[0].2 == LaneType::Sidewalk
     }
@@ -354,7 +360,7 @@ impl Road {
         };
         AccessRestrictions {
             allow_through_traffic,
-            cap_vehicles_per_hour: None,
+            ..AccessRestrictions::new()
         }
     }
 
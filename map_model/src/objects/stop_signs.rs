@@ -3,29 +3,23 @@ use abstutil::{deserialize_btreemap, serialize_btreemap};
 use serde::{Deserialize, Serialize};
 use std::collections::{BTreeMap, HashMap};
 
-// TODO These are old notes, they don't reflect current reality. But some of the ideas here should
-// be implemented, so keeping them...
-// 1) Pedestrians always have right-of-way. (for now -- should be toggleable later)
-// 2) Incoming roads without a stop sign have priority over roads with a sign.
+// How the simulation treats stop signs (see IntersectionSimState::stop_sign_policy):
+// 1) Pedestrians have right-of-way. Vehicles don't start a turn that conflicts with a pedestrian
+//    waiting to cross.
+// 2) Incoming roads without a stop sign have priority over roads with a sign. A vehicle with a
+//    stop sign won't start a turn if a conflicting vehicle without one will arrive before the turn
+//    could be finished.
 // 3) Agents with a stop sign have to actually wait some amount of time before starting the turn.
-// 4) Before starting any turn, an agent should make sure it can complete the turn without making a
-//    higher-priority agent have to wait.
-//    - "Complete" the turn just means the optimistic "length / max_speed" calculation -- if they
-//      queue behind slow cars upstream a bit, blocking the intersection a little bit is nice and
-//      realistic.
-//    - The higher priority agent might not even be at the intersection yet! This'll be a little
-//      harder to implement.
-//    - "Higher priority" has two cases -- stop sign road always yields to a non-stop sign road. But
-//      also a non-stop sign road yields to another non-stop sign road. In other words, left turns
-//      yield to straight and ideally, lane-changing yields to straight too.
-//    - So there still is a notion of turn priorities -- priority (can never conflict with another
-//      priority), yield (can't impede a priority turn), stop (has to pause and can't impede a
-//      priority or yield turn). But I don't think we want to really depict this...
-// 5) Rule 4 gives us a notion of roads that conflict -- or actually, do we even need it? No! An
-//    intersection with no stop signs at all means everyone yields. An intersection with all stop
-//    signs means everyone pauses before proceeding.
-// 6) Additionally, individual turns can be banned completely.
-//    - Even though letting players manipulate this could make parts of the map unreachable?
+//    This is configurable through SimOptions.
+// 4) Among equal priorities, it's first-come, first-served.
+//
+// Some ideas not implemented yet:
+// - Left turns yield to straight, and lane-changing yields to straight too.
+// - Individual turns can be banned completely. Even though letting players manipulate this could
+//   make parts of the map unreachable?
+//
+// Which roads have a stop sign comes from highway=stop nodes in OSM if possible, and is otherwise
+// guessed from the rank of the roads.
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct ControlStopSign {
@@ -73,11 +67,40 @@ impl ControlStopSign {
             }
         }
 
+        // Use stop signs mapped in OSM, if there are any
+        let mut tagged: BTreeMap<RoadID, String> = BTreeMap::new();
+        for r in ss.roads.keys() {
+            let road = map.get_r(*r);
+            let key = if road.dst_i == id {
+                osm::STOP_SIGN_FWD
+            } else {
+                osm::STOP_SIGN_BACK
+            };
+            if let Some(value) = road.osm_tags.get(key) {
+                tagged.insert(*r, value.clone());
+            }
+        }
+        if !tagged.is_empty() {
+            let all_way = tagged.values().any(|x| x == "all");
+            for (r, cfg) in ss.roads.iter_mut() {
+                cfg.must_stop = all_way || tagged.contains_key(r);
+            }
+            return ss;
+        }
+
         if ss.roads.len() <= 2 {
             // Degenerate roads and deadends don't need any stop signs.
             return ss;
         }
 
+        // Traffic entering a roundabout yields to traffic already circulating, no matter the rank.
+        if map.get_i(id).is_roundabout(map) {
+            for (r, cfg) in ss.roads.iter_mut() {
+                cfg.must_stop = !map.get_r(*r).is_roundabout();
+            }
+            return ss;
+        }
+
         // What's the rank of each road?
         let mut rank: HashMap<RoadID, osm::RoadRank> = HashMap::new();
         for r in ss.roads.keys() {
@@ -108,6 +108,25 @@ impl Turn {
         self.turn_type == TurnType::SharedSidewalkCorner || self.turn_type == TurnType::Crosswalk
     }
 
+    // Reversible lanes also have turns for the hours they run the other way. These only work
+    // while the lane is reversed.
+    pub fn leaves_contraflow(&self, map: &Map) -> bool {
+        !self.between_sidewalks() && map.get_l(self.id.src).dst_i != self.id.parent
+    }
+    pub fn enters_contraflow(&self, map: &Map) -> bool {
+        !self.between_sidewalks() && map.get_l(self.id.dst).src_i != self.id.parent
+    }
+    pub fn is_contraflow(&self, map: &Map) -> bool {
+        self.leaves_contraflow(map) || self.enters_contraflow(map)
+    }
+
+    // From outside a roundabout onto its ring, yielding to the traffic already circulating
+    pub fn is_roundabout_entry(&self, map: &Map) -> bool {
+        !self.between_sidewalks()
+            && !map.get_parent(self.id.src).is_roundabout()
+            && map.get_parent(self.id.dst).is_roundabout()
+    }
+
     // TODO Maybe precompute this.
     // penalties for (lane types, lane-changing, rightmost)
     pub fn penalty(&self, map: &Map) -> (usize, usize, usize) {
@@ -211,8 +230,15 @@ impl TurnGroup {
         let mut results = BTreeMap::new();
         let mut groups: MultiMap<(DirectedRoadID, DirectedRoadID), TurnID> = MultiMap::new();
         for turn in map.get_turns_in_intersection(i) {
-            let from = map.get_l(turn.id.src).get_directed_parent(map);
-            let to = map.get_l(turn.id.dst).get_directed_parent(map);
+            let mut from = map.get_l(turn.id.src).get_directed_parent(map);
+            let mut to = map.get_l(turn.id.dst).get_directed_parent(map);
+            // Reversible lanes carry traffic the other way for part of the day.
+            if turn.leaves_contraflow(map) {
+                from.dir = from.dir.opposite();
+            }
+            if turn.enters_contraflow(map) {
+                to.dir = to.dir.opposite();
+            }
             match turn.turn_type {
                 TurnType::SharedSidewalkCorner => {}
                 TurnType::Crosswalk => {
@@ -1,16 +1,26 @@
+use crate::objects::parking_rules::in_window;
 use crate::pathfind::{driving_cost, walking_cost, WalkingNode};
 use crate::{
     IntersectionID, LaneID, Map, Path, PathConstraints, PathRequest, PathStep, RoadID, TurnID,
 };
 use enumset::EnumSet;
+use geom::{Duration, Time};
 use petgraph::graphmap::DiGraphMap;
 use serde::{Deserialize, Serialize};
-use std::collections::BTreeSet;
+use std::collections::{BTreeMap, BTreeSet};
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub struct AccessRestrictions {
     pub allow_through_traffic: EnumSet<PathConstraints>,
     pub cap_vehicles_per_hour: Option<usize>,
+    // Times of day when through traffic is restricted and entry charges apply, like a school
+    // street closed during drop-off. Empty means all day. Older edits don't have these fields.
+    #[serde(default)]
+    pub hours: Vec<(Duration, Duration)>,
+    // Dollars charged every time a vehicle crosses into the zone. This is cordon pricing; there's
+    // no charge for trips that stay inside.
+    #[serde(default)]
+    pub entry_charges: BTreeMap<PathConstraints, f64>,
 }
 
 impl AccessRestrictions {
@@ -18,6 +28,34 @@ impl AccessRestrictions {
         AccessRestrictions {
             allow_through_traffic: EnumSet::all(),
             cap_vehicles_per_hour: None,
+            hours: Vec::new(),
+            entry_charges: BTreeMap::new(),
+        }
+    }
+
+    pub fn is_active(&self, now: Time) -> bool {
+        self.hours.is_empty() || in_window(&self.hours, now)
+    }
+
+    // The contraction hierarchy doesn't know the time of day, so it only excludes zones that
+    // restrict through traffic all day.
+    pub fn blocks_through_traffic(&self, constraints: PathConstraints) -> bool {
+        self.hours.is_empty() && !self.allow_through_traffic.contains(constraints)
+    }
+
+    pub fn blocks_through_traffic_at(&self, constraints: PathConstraints, now: Time) -> bool {
+        self.is_active(now) && !self.allow_through_traffic.contains(constraints)
+    }
+
+    pub fn entry_charge(&self, constraints: PathConstraints) -> Option<f64> {
+        self.entry_charges.get(&constraints).cloned()
+    }
+
+    pub fn entry_charge_at(&self, constraints: PathConstraints, now: Time) -> Option<f64> {
+        if self.is_active(now) {
+            self.entry_charge(constraints)
+        } else {
+            None
         }
     }
 }
@@ -23,6 +23,12 @@ pub const OSM_REL_ID: &str = "abst:osm_rel_id";
 // for interpreting turn restrictions.
 pub const ENDPT_FWD: &str = "abst:endpt_fwd";
 pub const ENDPT_BACK: &str = "abst:endpt_back";
+// The type of crossing (from crossing=*, or just "yes") near the start or end of a road
+pub const CROSSING_FWD: &str = "abst:crossing_fwd";
+pub const CROSSING_BACK: &str = "abst:crossing_back";
+// From highway=stop nodes near the start or end of a road. "all" for an all-way stop, or "yes".
+pub const STOP_SIGN_FWD: &str = "abst:stop_sign_fwd";
+pub const STOP_SIGN_BACK: &str = "abst:stop_sign_back";
 
 // Any roads might have these.
 pub const INFERRED_PARKING: &str = "abst:parking_inferred";
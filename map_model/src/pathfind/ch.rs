@@ -1,3 +1,4 @@
+use crate::pathfind::dijkstra::TimeOfDayGraphs;
 use crate::pathfind::driving::VehiclePathfinder;
 use crate::pathfind::walking::{
     one_step_walking_path, walking_path_to_steps, SidewalkPathfinder, WalkingNode,
@@ -7,6 +8,7 @@ use crate::{
     Zone,
 };
 use abstutil::Timer;
+use geom::Time;
 use serde::{Deserialize, Serialize};
 
 #[derive(Serialize, Deserialize)]
@@ -17,6 +19,9 @@ pub struct ContractionHierarchyPathfinder {
     train_graph: VehiclePathfinder,
     walking_graph: SidewalkPathfinder,
     walking_with_transit_graph: SidewalkPathfinder,
+    // The contraction hierarchies ignore the time of day
+    #[serde(skip_serializing, skip_deserializing)]
+    time_of_day_graphs: TimeOfDayGraphs,
 }
 
 impl ContractionHierarchyPathfinder {
@@ -55,6 +60,7 @@ impl ContractionHierarchyPathfinder {
             train_graph,
             walking_graph,
             walking_with_transit_graph,
+            time_of_day_graphs: TimeOfDayGraphs::default(),
         }
     }
 
@@ -71,11 +77,7 @@ impl ContractionHierarchyPathfinder {
         match (start_r.get_zone(map), end_r.get_zone(map)) {
             (Some(z1), Some(z2)) => {
                 if z1 == z2 {
-                    if !z1
-                        .restrictions
-                        .allow_through_traffic
-                        .contains(req.constraints)
-                    {
+                    if z1.restrictions.blocks_through_traffic(req.constraints) {
                         if req.constraints == PathConstraints::Pedestrian {
                             let steps =
                                 walking_path_to_steps(z1.pathfind_walking(req.clone(), map)?, map);
@@ -89,11 +91,7 @@ impl ContractionHierarchyPathfinder {
                 }
             }
             (Some(zone), None) => {
-                if !zone
-                    .restrictions
-                    .allow_through_traffic
-                    .contains(req.constraints)
-                {
+                if zone.restrictions.blocks_through_traffic(req.constraints) {
                     let mut borders: Vec<&Intersection> =
                         zone.borders.iter().map(|i| map.get_i(*i)).collect();
                     // TODO Use the CH to pick the lowest overall cost?
@@ -109,11 +107,7 @@ impl ContractionHierarchyPathfinder {
                 }
             }
             (None, Some(zone)) => {
-                if !zone
-                    .restrictions
-                    .allow_through_traffic
-                    .contains(req.constraints)
-                {
+                if zone.restrictions.blocks_through_traffic(req.constraints) {
                     let mut borders: Vec<&Intersection> =
                         zone.borders.iter().map(|i| map.get_i(*i)).collect();
                     // TODO Use the CH to pick the lowest overall cost?
@@ -312,6 +306,17 @@ impl ContractionHierarchyPathfinder {
         Some(main_path)
     }
 
+    pub fn pathfind_at(
+        &self,
+        req: PathRequest,
+        now: Time,
+        avoid_charges: bool,
+        map: &Map,
+    ) -> Option<Path> {
+        self.time_of_day_graphs
+            .pathfind_at(req, now, avoid_charges, map)
+    }
+
     pub fn should_use_transit(
         &self,
         map: &Map,
@@ -346,5 +351,7 @@ impl ContractionHierarchyPathfinder {
         self.walking_with_transit_graph
             .apply_edits(map, &self.bus_graph, &self.train_graph);
         timer.stop("apply edits to pedestrian using transit pathfinding");
+
+        self.time_of_day_graphs = TimeOfDayGraphs::default();
     }
 }
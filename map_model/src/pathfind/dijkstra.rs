@@ -3,9 +3,11 @@ use crate::pathfind::walking::{
     one_step_walking_path, walking_cost, walking_path_to_steps, WalkingNode,
 };
 use crate::{LaneID, Map, Path, PathConstraints, PathRequest, PathStep, TurnID};
+use geom::Time;
 use petgraph::graphmap::DiGraphMap;
-
-// TODO These should maybe keep the DiGraphMaps as state. It's cheap to recalculate it for edits.
+use petgraph::visit::EdgeFiltered;
+use std::collections::BTreeMap;
+use std::sync::RwLock;
 
 pub fn pathfind(req: PathRequest, map: &Map) -> Option<Path> {
     if req.constraints == PathConstraints::Pedestrian {
@@ -25,27 +27,131 @@ pub fn pathfind(req: PathRequest, map: &Map) -> Option<Path> {
             }
         }
     }
-
-    let (_, path) = petgraph::algo::astar(
+    let (_, lanes) = petgraph::algo::astar(
         &graph,
         req.start.lane(),
         |l| l == req.end.lane(),
         |(_, _, turn)| driving_cost(map.get_l(turn.src), map.get_t(*turn), req.constraints, map),
         |_| 0,
     )?;
+    let nodes = lanes.into_iter().map(|l| (l, false)).collect();
+    Some(lanes_to_path(req, nodes, map))
+}
+
+// A lane, and whether it's used against its usual direction
+type DirectedLane = (LaneID, bool);
+
+// Every turn between lanes that a vehicle could use at some time of day, including reversible
+// lanes in both directions. Which lanes are open depends on the time and the trip, so searches
+// check that as they go, and one graph per vehicle type serves all of them.
+pub fn time_of_day_graph(
+    constraints: PathConstraints,
+    map: &Map,
+) -> DiGraphMap<DirectedLane, TurnID> {
+    let mut graph: DiGraphMap<DirectedLane, TurnID> = DiGraphMap::new();
+    for l in map.all_lanes() {
+        if !constraints.can_ever_use(l, map) {
+            continue;
+        }
+        let mut exits = vec![(false, l.dst_i)];
+        if l.is_ever_reversed() {
+            exits.push((true, l.src_i));
+        }
+        for (contraflow, i) in exits {
+            for (turn, dst) in map.get_next_turns_and_lanes(l.id, i) {
+                if constraints.can_ever_use(dst, map) {
+                    graph.add_edge(
+                        (l.id, contraflow),
+                        (dst.id, turn.enters_contraflow(map)),
+                        turn.id,
+                    );
+                }
+            }
+        }
+    }
+    graph
+}
+
+// Lazily builds and keeps a time_of_day_graph per vehicle type, until the map is edited.
+#[derive(Default)]
+pub struct TimeOfDayGraphs {
+    graphs: RwLock<BTreeMap<PathConstraints, DiGraphMap<DirectedLane, TurnID>>>,
+}
+
+impl TimeOfDayGraphs {
+    pub fn pathfind_at(
+        &self,
+        req: PathRequest,
+        now: Time,
+        avoid_charges: bool,
+        map: &Map,
+    ) -> Option<Path> {
+        let constraints = req.constraints;
+        if !self.graphs.read().unwrap().contains_key(&constraints) {
+            let graph = time_of_day_graph(constraints, map);
+            self.graphs
+                .write()
+                .unwrap()
+                .entry(constraints)
+                .or_insert(graph);
+        }
+        let graphs = self.graphs.read().unwrap();
+        pathfind_at(req, now, avoid_charges, &graphs[&constraints], map)
+    }
+}
+
+// Only use lanes open to this trip at this time of day, in the direction they run right now.
+// Optionally avoid zones that charge to enter.
+pub fn pathfind_at(
+    req: PathRequest,
+    now: Time,
+    avoid_charges: bool,
+    graph: &DiGraphMap<DirectedLane, TurnID>,
+    map: &Map,
+) -> Option<Path> {
+    assert_ne!(req.constraints, PathConstraints::Pedestrian);
+    // The search starts from a lane that's always allowed, so it's enough to check where each
+    // turn leads.
+    let open = EdgeFiltered::from_fn(
+        graph,
+        |(_, (dst, contraflow), _): (DirectedLane, DirectedLane, &TurnID)| {
+            let l = map.get_l(dst);
+            req.allows_lane_at(l, now, map)
+                && req.allows_direction_at(l, contraflow, now)
+                && !(avoid_charges && req.pays_to_use(l, now, map))
+        },
+    );
+    let (_, lanes) = petgraph::algo::astar(
+        &open,
+        (req.start.lane(), false),
+        |l| l == (req.end.lane(), false),
+        |(_, _, turn)| driving_cost(map.get_l(turn.src), map.get_t(*turn), req.constraints, map),
+        |_| 0,
+    )?;
+    Some(lanes_to_path(req, lanes, map))
+}
+
+fn lanes_to_path(req: PathRequest, lanes: Vec<DirectedLane>, map: &Map) -> Path {
     let mut steps = Vec::new();
-    for pair in path.windows(2) {
-        steps.push(PathStep::Lane(pair[0]));
+    for pair in lanes.windows(2) {
+        let (l, contraflow) = pair[0];
         // We don't need to look for this turn in the map; we know it exists.
+        let parent = if contraflow {
+            steps.push(PathStep::ContraflowLane(l));
+            map.get_l(l).src_i
+        } else {
+            steps.push(PathStep::Lane(l));
+            map.get_l(l).dst_i
+        };
         steps.push(PathStep::Turn(TurnID {
-            parent: map.get_l(pair[0]).dst_i,
-            src: pair[0],
-            dst: pair[1],
+            parent,
+            src: l,
+            dst: pair[1].0,
         }));
     }
     steps.push(PathStep::Lane(req.end.lane()));
     assert_eq!(steps[0], PathStep::Lane(req.start.lane()));
-    Some(Path::new(map, steps, req.end.dist_along(), Vec::new()))
+    Path::new(map, steps, req.end.dist_along(), Vec::new())
 }
 
 // TODO Not happy this works so differently
@@ -165,11 +165,10 @@ fn make_input_graph(
         let from = nodes.get(Node::Lane(l.id));
         let mut any = false;
         if constraints.can_use(l, map)
-            && map
+            && !map
                 .get_r(l.parent)
                 .access_restrictions
-                .allow_through_traffic
-                .contains(constraints)
+                .blocks_through_traffic(constraints)
         {
             let indices = uber_turn_entrances.get(l.id);
             if indices.is_empty() {
@@ -214,6 +213,10 @@ fn make_input_graph(
     input_graph
 }
 
+// Climbing one meter is as bad as biking this many meters on flat ground. Roughly matches how much
+// the simulation slows bikes down on hills.
+const BIKE_CLIMB_PENALTY: f64 = 15.0;
+
 pub fn driving_cost(lane: &Lane, turn: &Turn, constraints: PathConstraints, map: &Map) -> usize {
     // TODO Could cost turns differently.
 
@@ -227,7 +230,8 @@ pub fn driving_cost(lane: &Lane, turn: &Turn, constraints: PathConstraints, map:
         PathConstraints::Bike => {
             // Speed limits don't matter, bikes are usually constrained by their own speed limit.
             let dist = lane.length() + turn.geom.length();
-            // TODO Elevation gain is bad, loss is good.
+            // Elevation gain is bad. Going downhill doesn't make up for it, so don't reward it.
+            let climb = lane.length() * lane.percent_grade(map).max(0.0);
             // TODO If we're on a driving lane, higher speed limit is worse.
             // TODO Bike lanes next to parking is dangerous.
 
@@ -238,12 +242,14 @@ pub fn driving_cost(lane: &Lane, turn: &Turn, constraints: PathConstraints, map:
             } else if lane.is_bus() {
                 1.1
             } else {
-                assert!(lane.is_driving());
+                // A driving lane, or one that only opens to traffic at some hours
                 1.5
             };
 
             // 1m resolution is fine
-            (lt_penalty * dist).inner_meters().round() as usize
+            (lt_penalty * dist + BIKE_CLIMB_PENALTY * climb)
+                .inner_meters()
+                .round() as usize
         }
         PathConstraints::Bus => {
             // Like Car, but prefer bus lanes.
@@ -252,7 +258,7 @@ pub fn driving_cost(lane: &Lane, turn: &Turn, constraints: PathConstraints, map:
             let lt_penalty = if lane.is_bus() {
                 1.0
             } else {
-                assert!(lane.is_driving());
+                // A driving lane, or one that only opens to traffic at some hours
                 1.1
             };
             (lt_penalty * (t1 + t2)).inner_seconds().round() as usize
@@ -10,21 +10,25 @@ pub use self::ch::ContractionHierarchyPathfinder;
 pub use self::driving::driving_cost;
 pub use self::walking::{walking_cost, WalkingNode};
 use crate::{
-    osm, BusRouteID, BusStopID, Lane, LaneID, LaneType, Map, Position, Traversable, TurnID,
-    UberTurn,
+    osm, BusRouteID, BusStopID, Lane, LaneID, LaneType, Map, Position, Road, RoadID, Traversable,
+    TurnID, UberTurn,
 };
 use abstutil::Timer;
 use enumset::EnumSetType;
-use geom::{Distance, PolyLine, EPSILON_DIST};
+use geom::{Distance, Duration, PolyLine, Speed, Time, EPSILON_DIST};
 use serde::{Deserialize, Serialize};
 use std::collections::VecDeque;
 use std::fmt;
 
+// Bike path costs are roughly distances. Assume about 9mph, the middle of the usual bike speeds,
+// to express them as time.
+const BIKE_SPEED_FOR_CHARGES: Speed = Speed::const_meters_per_second(4.0);
+
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub enum PathStep {
     // Original direction
     Lane(LaneID),
-    // Sidewalks only!
+    // Sidewalks, or reversible lanes while they're reversed
     ContraflowLane(LaneID),
     Turn(TurnID),
 }
@@ -46,6 +50,16 @@ impl PathStep {
         self.as_traversable().as_turn()
     }
 
+    // The geometry in the direction of travel. Vehicles measure distances along this, so from the
+    // lane's end for ContraflowLane.
+    pub fn geom(&self, map: &Map) -> PolyLine {
+        match self {
+            PathStep::Lane(id) => map.get_l(*id).lane_center_pts.clone(),
+            PathStep::ContraflowLane(id) => map.get_l(*id).lane_center_pts.reversed(),
+            PathStep::Turn(id) => map.get_t(*id).geom.clone(),
+        }
+    }
+
     // Returns dist_remaining. start is relative to the start of the actual geometry -- so from the
     // lane's real start for ContraflowLane.
     fn slice(
@@ -112,6 +126,19 @@ pub struct Path {
 }
 
 impl Path {
+    // Dollars charged for crossing into zones along this path, if it starts at this time
+    pub fn entry_charges(&self, constraints: PathConstraints, now: Time, map: &Map) -> f64 {
+        self.steps
+            .iter()
+            .filter_map(|step| match step {
+                PathStep::Turn(t) => map
+                    .entering_priced_zone(*t)
+                    .and_then(|r| r.entry_charge_at(constraints, now)),
+                _ => None,
+            })
+            .sum()
+    }
+
     pub(crate) fn new(
         map: &Map,
         steps: Vec<PathStep>,
@@ -182,6 +209,10 @@ impl Path {
         self.total_lanes - remaining
     }
 
+    pub fn end_dist(&self) -> Distance {
+        self.end_dist
+    }
+
     pub fn crossed_so_far(&self) -> Distance {
         self.crossed_so_far
     }
@@ -462,13 +493,32 @@ impl PathConstraints {
 
     // TODO Handle private zones here?
     pub fn can_use(self, l: &Lane, map: &Map) -> bool {
+        self.can_use_lt(l.lane_type, l, map)
+    }
+
+    // Some lanes change type during the day. Outside of pathfinding, most callers just want
+    // can_use, the lane's normal type.
+    pub fn can_use_at(self, l: &Lane, map: &Map, now: Time) -> bool {
+        self.can_use_lt(l.lane_type_at(now), l, map)
+    }
+
+    // Could this lane be used at some time of day?
+    pub fn can_ever_use(self, l: &Lane, map: &Map) -> bool {
+        l.all_lane_types()
+            .into_iter()
+            .any(|lt| self.can_use_lt(lt, l, map))
+    }
+
+    fn can_use_lt(self, lt: LaneType, l: &Lane, map: &Map) -> bool {
         match self {
-            PathConstraints::Pedestrian => l.is_walkable(),
-            PathConstraints::Car => l.is_driving(),
+            PathConstraints::Pedestrian => lt == LaneType::Sidewalk || lt == LaneType::Shoulder,
+            PathConstraints::Car => lt == LaneType::Driving,
             PathConstraints::Bike => {
-                if l.is_biking() {
+                if lt == LaneType::Biking {
                     true
-                } else if l.is_driving() || (l.is_bus() && map.config.bikes_can_use_bus_lanes) {
+                } else if lt == LaneType::Driving
+                    || (lt == LaneType::Bus && map.config.bikes_can_use_bus_lanes)
+                {
                     let road = map.get_r(l.parent);
                     !road.osm_tags.is("bicycle", "no")
                         && !road
@@ -478,8 +528,8 @@ impl PathConstraints {
                     false
                 }
             }
-            PathConstraints::Bus => l.is_driving() || l.is_bus(),
-            PathConstraints::Train => l.is_light_rail(),
+            PathConstraints::Bus => lt == LaneType::Driving || lt == LaneType::Bus,
+            PathConstraints::Train => lt == LaneType::LightRail,
         }
     }
 
@@ -507,6 +557,50 @@ pub struct PathRequest {
     pub constraints: PathConstraints,
 }
 
+impl PathRequest {
+    // Can this trip use the lane at this time of day? The start and end lanes are always allowed,
+    // since the vehicle is already there or has to go there.
+    pub(crate) fn allows_lane_at(&self, l: &Lane, now: Time, map: &Map) -> bool {
+        if l.id == self.start.lane() || l.id == self.end.lane() {
+            return true;
+        }
+        self.constraints.can_use_at(l, map, now) && !self.blocked_by_zone_at(l, now, map)
+    }
+
+    // Reversible lanes can only be used the way they run right now. Like lane types, the start
+    // and end lanes are exempt, but they're always used in their usual direction.
+    pub(crate) fn allows_direction_at(&self, l: &Lane, contraflow: bool, now: Time) -> bool {
+        if l.id == self.start.lane() || l.id == self.end.lane() {
+            return !contraflow;
+        }
+        l.reversed_at(now) == contraflow
+    }
+
+    // Zones closed to through traffic right now can still be used if the trip starts or ends
+    // inside.
+    fn blocked_by_zone_at(&self, l: &Lane, now: Time, map: &Map) -> bool {
+        let r = map.get_r(l.parent);
+        r.access_restrictions
+            .blocks_through_traffic_at(self.constraints, now)
+            && !self.starts_or_ends_in_zone(r, map)
+    }
+
+    // Would using this lane mean paying to enter a zone right now?
+    pub(crate) fn pays_to_use(&self, l: &Lane, now: Time, map: &Map) -> bool {
+        let r = map.get_r(l.parent);
+        r.access_restrictions
+            .entry_charge_at(self.constraints, now)
+            .is_some()
+            && !self.starts_or_ends_in_zone(r, map)
+    }
+
+    fn starts_or_ends_in_zone(&self, r: &Road, map: &Map) -> bool {
+        let zone = r.get_zone(map).unwrap();
+        zone.members.contains(&map.get_l(self.start.lane()).parent)
+            || zone.members.contains(&map.get_l(self.end.lane()).parent)
+    }
+}
+
 impl fmt::Display for PathRequest {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(
@@ -565,26 +659,45 @@ fn validate_continuity(map: &Map, steps: &Vec<PathStep>) {
 }
 
 fn validate_restrictions(map: &Map, steps: &Vec<PathStep>) {
-    for triple in steps.windows(5) {
-        if let (PathStep::Lane(l1), PathStep::Lane(l2), PathStep::Lane(l3)) =
-            (triple[0], triple[2], triple[4])
-        {
-            let from = map.get_parent(l1);
-            let via = map.get_l(l2).parent;
-            let to = map.get_l(l3).parent;
-
-            for (dont_via, dont_to) in &from.complicated_turn_restrictions {
-                if via == *dont_via && to == *dont_to {
-                    panic!(
-                        "Some path does illegal uber-turn: {} -> {} -> {}",
-                        l1, l2, l3
-                    );
-                }
+    let roads: Vec<RoadID> = steps
+        .iter()
+        .filter_map(|step| match step {
+            PathStep::Lane(l) | PathStep::ContraflowLane(l) => Some(map.get_l(*l).parent),
+            _ => None,
+        })
+        .collect();
+    for (idx, from) in roads.iter().enumerate() {
+        for (dont_via, dont_to) in &map.get_r(*from).complicated_turn_restrictions {
+            let rest = &roads[idx + 1..];
+            if rest.len() > dont_via.len()
+                && rest[..dont_via.len()] == dont_via[..]
+                && rest[dont_via.len()] == *dont_to
+            {
+                panic!(
+                    "Some path does illegal uber-turn: {} -> {:?} -> {}",
+                    from, dont_via, dont_to
+                );
             }
         }
     }
 }
 
+// How trips trade off time and money when choosing a route. This depends on the people using the
+// map, not the map itself.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RoutingParams {
+    // Dollars per hour. Trips pay to enter a zone if the time saved is worth more than this.
+    pub value_of_time_per_hour: f64,
+}
+
+impl RoutingParams {
+    pub fn new() -> RoutingParams {
+        RoutingParams {
+            value_of_time_per_hour: 20.0,
+        }
+    }
+}
+
 // Most of the time, prefer using the faster contraction hierarchies. But sometimes, callers can
 // explicitly opt into a slower (but preparation-free) pathfinder that just uses Dijkstra's
 // maneuever.
@@ -602,6 +715,88 @@ impl Pathfinder {
         }
     }
 
+    pub fn pathfind_at(
+        &self,
+        req: PathRequest,
+        now: Time,
+        params: &RoutingParams,
+        map: &Map,
+    ) -> Option<(Path, bool)> {
+        // TODO Pedestrians ignore the time of day.
+        if req.constraints == PathConstraints::Pedestrian {
+            return self.pathfind(req, map).map(|path| (path, false));
+        }
+
+        // The usual pathfinder ignores lanes that change type or direction during the day and
+        // zones that only restrict through traffic at some hours. If its path uses a lane that's
+        // closed right now, or there's no path at all, fall back to Dijkstra's over the lanes
+        // that're open right now.
+        let usual = self.pathfind(req.clone(), map);
+        let usual_is_open = usual
+            .as_ref()
+            .map(|path| {
+                path.get_steps().iter().all(|step| match step {
+                    PathStep::Lane(l) => {
+                        let l = map.get_l(*l);
+                        req.allows_lane_at(l, now, map) && req.allows_direction_at(l, false, now)
+                    }
+                    _ => true,
+                })
+            })
+            .unwrap_or(false);
+        let (path, diverted) = if usual_is_open {
+            (usual.unwrap(), false)
+        } else {
+            let path = self.pathfind_open_lanes_at(req.clone(), now, false, map)?;
+            // Lanes closed for part of the day don't count as a diversion; zones do.
+            let diverted = usual
+                .map(|usual| {
+                    usual.get_steps().iter().any(|step| match step {
+                        PathStep::Lane(l) => req.blocked_by_zone_at(map.get_l(*l), now, map),
+                        _ => false,
+                    })
+                })
+                .unwrap_or(false);
+            (path, diverted)
+        };
+
+        // The usual pathfinder doesn't know about entry charges either, since they often only
+        // apply at some hours. If this route pays any, see if the time saved is worth it.
+        let charges = path.entry_charges(req.constraints, now, map);
+        if charges == 0.0 {
+            return Some((path, diverted));
+        }
+        // The detour still pays to enter a zone where the trip starts or ends.
+        if let Some(detour) = self.pathfind_open_lanes_at(req.clone(), now, true, map) {
+            let saved = charges - detour.entry_charges(req.constraints, now, map);
+            let extra_time =
+                path_time(&detour, req.constraints, map) - path_time(&path, req.constraints, map);
+            if saved > 0.0
+                && extra_time.inner_seconds() < 3600.0 * saved / params.value_of_time_per_hour
+            {
+                return Some((detour, true));
+            }
+        }
+        Some((path, diverted))
+    }
+
+    // Dijkstra's over only the lanes open right now. Optionally avoid zones that charge to enter.
+    fn pathfind_open_lanes_at(
+        &self,
+        req: PathRequest,
+        now: Time,
+        avoid_charges: bool,
+        map: &Map,
+    ) -> Option<Path> {
+        match self {
+            Pathfinder::Dijkstra => {
+                let graph = dijkstra::time_of_day_graph(req.constraints, map);
+                dijkstra::pathfind_at(req, now, avoid_charges, &graph, map)
+            }
+            Pathfinder::CH(ref p) => p.pathfind_at(req, now, avoid_charges, map),
+        }
+    }
+
     pub fn should_use_transit(
         &self,
         map: &Map,
@@ -622,3 +817,22 @@ impl Pathfinder {
         }
     }
 }
+
+// Roughly how long the path takes, to weigh against entry charges
+fn path_time(path: &Path, constraints: PathConstraints, map: &Map) -> Duration {
+    let cost: usize = path
+        .get_steps()
+        .iter()
+        .map(|step| match step {
+            PathStep::Turn(t) => driving_cost(map.get_l(t.src), map.get_t(*t), constraints, map),
+            _ => 0,
+        })
+        .sum();
+    if constraints == PathConstraints::Bike {
+        // Bike costs are roughly in meters
+        Distance::meters(cost as f64) / BIKE_SPEED_FOR_CHARGES
+    } else {
+        // Everything else costs seconds
+        Duration::seconds(cost as f64)
+    }
+}
@@ -227,11 +227,10 @@ fn make_input_graph(
 
     for l in map.all_lanes() {
         if l.is_walkable()
-            && map
+            && !map
                 .get_r(l.parent)
                 .access_restrictions
-                .allow_through_traffic
-                .contains(PathConstraints::Pedestrian)
+                .blocks_through_traffic(PathConstraints::Pedestrian)
         {
             let mut cost = walking_cost(l.length());
             // TODO Tune this penalty, along with many others.
@@ -240,8 +240,9 @@ pub struct RawRoad {
     pub center_points: Vec<Pt2D>,
     pub osm_tags: Tags,
     pub turn_restrictions: Vec<(RestrictionType, OriginalRoad)>,
-    // (via, to). For turn restrictions where 'via' is an entire road. Only BanTurns.
-    pub complicated_turn_restrictions: Vec<(OriginalRoad, OriginalRoad)>,
+    // (via, to). For turn restrictions where 'via' is one or more entire roads, in order. Only
+    // BanTurns.
+    pub complicated_turn_restrictions: Vec<(Vec<OriginalRoad>, OriginalRoad)>,
 }
 
 impl RawRoad {
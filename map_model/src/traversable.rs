@@ -203,6 +203,14 @@ impl Traversable {
         }
     }
 
+    // Turns are treated as flat.
+    pub fn percent_grade(&self, map: &Map) -> f64 {
+        match *self {
+            Traversable::Lane(id) => map.get_l(id).percent_grade(map),
+            Traversable::Turn(_) => 0.0,
+        }
+    }
+
     pub fn get_zorder(&self, map: &Map) -> isize {
         match *self {
             Traversable::Lane(id) => map.get_parent(id).zorder,
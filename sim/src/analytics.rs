@@ -1,9 +1,12 @@
-use crate::{AgentType, AlertLocation, CarID, Event, ParkingSpot, TripID, TripMode, TripPhaseType};
+use crate::{
+    AgentType, AlertLocation, CarID, Event, ParkingSpot, PersonID, TripID, TripMode, TripPhaseType,
+    VehicleType,
+};
 use abstutil::Counter;
 use geom::{Distance, Duration, Time};
 use map_model::{
-    BusRouteID, BusStopID, CompressedTurnGroupID, IntersectionID, LaneID, Map, ParkingLotID, Path,
-    PathRequest, RoadID, Traversable, TurnGroupID,
+    BuildingID, BusRouteID, BusStopID, CompressedTurnGroupID, IntersectionID, LaneID, Map,
+    ParkingLotID, Path, PathRequest, RoadID, Traversable, TurnGroupID,
 };
 use serde::{Deserialize, Serialize};
 use std::collections::{BTreeMap, VecDeque};
@@ -40,6 +43,36 @@ pub struct Analytics {
     // Per parking lane or lot, when does a spot become filled (true) or free (false)
     pub parking_lane_changes: BTreeMap<LaneID, Vec<(Time, bool)>>,
     pub parking_lot_changes: BTreeMap<ParkingLotID, Vec<(Time, bool)>>,
+    // For trips that parked a car: how long the driver spent looking for a spot, and the
+    // straight-line distance from the spot to where they walked next
+    pub parking_overhead: BTreeMap<TripID, (Duration, Distance)>,
+    // When each driver still looking for parking started
+    parking_searches: BTreeMap<TripID, Time>,
+    // Every time a car leaves a spot with parking rules: the fee paid in dollars, and whether
+    // the car followed the rules
+    pub parking_sessions: Vec<(Time, ParkingSpot, f64, bool)>,
+
+    // For each ride-hail pickup, how long did the passenger wait?
+    pub ridehail_waits: Vec<(Time, PersonID, Duration)>,
+    // Every path a ride-hail vehicle finished driving, and whether a passenger was aboard
+    pub ridehail_legs: Vec<(Time, CarID, Distance, bool)>,
+
+    // Per bike-share station, how many vehicles are parked there after each change
+    pub bikeshare_changes: BTreeMap<BuildingID, Vec<(Time, usize)>>,
+    // Per park-and-ride lot, how many cars are left there after each change
+    pub park_and_ride_changes: BTreeMap<ParkingLotID, Vec<(Time, usize)>>,
+
+    // Every time an electric vehicle leaves a charger: how long it was there, and the kWh it got
+    pub charging_sessions: Vec<(Time, ParkingSpot, Duration, f64)>,
+    // How many electric vehicles are waiting for a free charger after each change
+    pub charger_queue_changes: Vec<(Time, usize)>,
+    // How long each vehicle waited for a charger before getting one
+    pub charger_waits: Vec<(Time, CarID, Duration)>,
+
+    // Every time a vehicle paid to enter a zone: where it entered and the charge in dollars
+    pub zone_entry_charges: Vec<(Time, IntersectionID, VehicleType, f64)>,
+    // Trips that entry charges or a zone closed at that time pushed off of their usual route
+    pub diverted_trips: Vec<(Time, TripID)>,
 
     pub(crate) alerts: Vec<(Time, AlertLocation, String)>,
 
@@ -65,6 +98,18 @@ impl Analytics {
             intersection_delays: BTreeMap::new(),
             parking_lane_changes: BTreeMap::new(),
             parking_lot_changes: BTreeMap::new(),
+            parking_overhead: BTreeMap::new(),
+            parking_searches: BTreeMap::new(),
+            parking_sessions: Vec::new(),
+            ridehail_waits: Vec::new(),
+            ridehail_legs: Vec::new(),
+            bikeshare_changes: BTreeMap::new(),
+            park_and_ride_changes: BTreeMap::new(),
+            charging_sessions: Vec::new(),
+            charger_queue_changes: Vec::new(),
+            charger_waits: Vec::new(),
+            zone_entry_charges: Vec::new(),
+            diverted_trips: Vec::new(),
             alerts: Vec::new(),
             record_anything: true,
         }
@@ -207,6 +252,79 @@ impl Analytics {
             }
         }
 
+        if let Event::ParkingSessionEnded {
+            spot,
+            fee,
+            compliant,
+            ..
+        } = ev
+        {
+            self.parking_sessions.push((time, spot, fee, compliant));
+        }
+
+        if let Event::RideHailPickup(person, _, waiting) = ev {
+            self.ridehail_waits.push((time, person, waiting));
+        }
+        if let Event::RideHailLegFinished {
+            car,
+            dist,
+            occupied,
+        } = ev
+        {
+            self.ridehail_legs.push((time, car, dist, occupied));
+        }
+
+        if let Event::BikeShareAvailability(b, cnt) = ev {
+            self.bikeshare_changes
+                .entry(b)
+                .or_insert_with(Vec::new)
+                .push((time, cnt));
+        }
+        if let Event::ParkAndRideOccupancy(pl, cnt) = ev {
+            self.park_and_ride_changes
+                .entry(pl)
+                .or_insert_with(Vec::new)
+                .push((time, cnt));
+        }
+
+        if let Event::ChargingSessionEnded {
+            spot,
+            duration,
+            energy,
+            ..
+        } = ev
+        {
+            self.charging_sessions.push((time, spot, duration, energy));
+        }
+        if let Event::ChargerQueueLength(cnt) = ev {
+            self.charger_queue_changes.push((time, cnt));
+        }
+        if let Event::ChargerWaitEnded(car, waited) = ev {
+            self.charger_waits.push((time, car, waited));
+        }
+
+        if let Event::ZoneEntryCharged { car, i, charge } = ev {
+            self.zone_entry_charges.push((time, i, car.1, charge));
+        }
+        if let Event::TripDiverted(trip) = ev {
+            self.diverted_trips.push((time, trip));
+        }
+
+        // Parking overhead. The search might restart if a spot gets taken; count from the first
+        // attempt.
+        if let Event::TripPhaseStarting(id, _, _, TripPhaseType::Parking) = ev {
+            self.parking_searches.entry(id).or_insert(time);
+        }
+        if let Event::TripParkedCar {
+            trip, walking_dist, ..
+        } = ev
+        {
+            if let Some(started) = self.parking_searches.remove(&trip) {
+                self.parking_overhead
+                    .insert(trip, (time - started, walking_dist));
+            }
+        }
+
         // TODO Kinda hacky, but these all consume the event, so kinda bundle em.
         match ev {
             Event::TripPhaseStarting(id, _, maybe_req, phase_type) => {
@@ -413,6 +531,95 @@ impl Analytics {
         pts
     }
 
+    // (Total distance driven by ride-hail vehicles, the part of that driven empty)
+    pub fn ridehail_vmt(&self, now: Time) -> (Distance, Distance) {
+        let mut total = Distance::ZERO;
+        let mut empty = Distance::ZERO;
+        for (t, _, dist, occupied) in &self.ridehail_legs {
+            if *t > now {
+                break;
+            }
+            total += *dist;
+            if !occupied {
+                empty += *dist;
+            }
+        }
+        (total, empty)
+    }
+
+    // Returns the vehicles parked at a bike-share station over time
+    pub fn bikeshare_availability(&self, now: Time, b: BuildingID) -> Vec<(Time, usize)> {
+        Analytics::step_function(now, self.bikeshare_changes.get(&b))
+    }
+
+    // Returns the park-and-ride cars left in a lot over time
+    pub fn park_and_ride_occupancy(&self, now: Time, pl: ParkingLotID) -> Vec<(Time, usize)> {
+        Analytics::step_function(now, self.park_and_ride_changes.get(&pl))
+    }
+
+    // What fraction of the time so far did a vehicle spend charging at this spot? Only counts
+    // finished sessions.
+    pub fn charger_utilization(&self, now: Time, spot: ParkingSpot) -> f64 {
+        let mut total = Duration::ZERO;
+        for (t, s, duration, _) in &self.charging_sessions {
+            if *t > now {
+                break;
+            }
+            if *s == spot {
+                total += *duration;
+            }
+        }
+        if now == Time::START_OF_DAY {
+            0.0
+        } else {
+            total / (now - Time::START_OF_DAY)
+        }
+    }
+
+    // Returns the number of vehicles waiting for a charger over time
+    pub fn charger_queue(&self, now: Time) -> Vec<(Time, usize)> {
+        Analytics::step_function(now, Some(&self.charger_queue_changes))
+    }
+
+    // Dollars collected from zone entry charges so far, per vehicle type
+    pub fn zone_revenue(&self, now: Time) -> BTreeMap<VehicleType, f64> {
+        let mut revenue = BTreeMap::new();
+        for (t, _, vt, charge) in &self.zone_entry_charges {
+            if *t > now {
+                break;
+            }
+            *revenue.entry(*vt).or_insert(0.0) += *charge;
+        }
+        revenue
+    }
+
+    pub fn num_diverted_trips(&self, now: Time) -> usize {
+        self.diverted_trips
+            .iter()
+            .take_while(|(t, _)| *t <= now)
+            .count()
+    }
+
+    fn step_function(now: Time, changes: Option<&Vec<(Time, usize)>>) -> Vec<(Time, usize)> {
+        let mut pts: Vec<(Time, usize)> = Vec::new();
+        if let Some(changes) = changes {
+            for (t, cnt) in changes {
+                if *t > now {
+                    break;
+                }
+                // Step functions. Don't interpolate.
+                if let Some(prev) = pts.last().map(|(_, cnt)| *cnt) {
+                    pts.push((*t, prev));
+                }
+                pts.push((*t, *cnt));
+            }
+        }
+        if let Some(cnt) = pts.last().map(|(_, cnt)| *cnt) {
+            pts.push((now, cnt));
+        }
+        pts
+    }
+
     // Returns the free spots over time
     pub fn parking_lane_availability(
         &self,
@@ -0,0 +1,403 @@
+use crate::{
+    CarID, Event, SidewalkSpot, TripManager, Vehicle, VehicleSpec, VehicleType, BIKE_LENGTH,
+};
+use abstutil::{deserialize_btreemap, serialize_btreemap};
+use geom::{Distance, Speed};
+use map_model::{BuildingID, Map};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+// How the shared bikes or scooters are set up. By default there are none, so bike-share trips
+// just walk.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct BikeShareParams {
+    // Each docking station, how many docks it has, and how many vehicles start there
+    pub docks: Vec<(BuildingID, usize, usize)>,
+    // Dockless vehicles start outside these buildings. Riders leave them right at their
+    // destination.
+    pub dockless: Vec<(BuildingID, usize)>,
+    // Scooters are just slower bikes
+    pub max_speed: Option<Speed>,
+    // How far (in a straight line) somebody will walk to pick up or return a vehicle
+    pub max_walk: Distance,
+}
+
+impl BikeShareParams {
+    pub fn new() -> BikeShareParams {
+        BikeShareParams {
+            docks: Vec::new(),
+            dockless: Vec::new(),
+            max_speed: None,
+            max_walk: Distance::meters(500.0),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Clone)]
+struct Station {
+    // 0 if there's no dock here, just dockless vehicles left outside
+    capacity: usize,
+    docked: Vec<CarID>,
+    // Docks promised to riders on their way here
+    reserved: usize,
+    dockless: Vec<CarID>,
+}
+
+impl Station {
+    fn new(capacity: usize) -> Station {
+        Station {
+            capacity,
+            docked: Vec::new(),
+            reserved: 0,
+            dockless: Vec::new(),
+        }
+    }
+
+    fn available(&self) -> usize {
+        self.docked.len() + self.dockless.len()
+    }
+
+    fn free_docks(&self) -> usize {
+        self.capacity - self.docked.len() - self.reserved
+    }
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Clone)]
+pub struct BikeShareSimState {
+    // Docking stations, and buildings with dockless vehicles outside
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    stations: BTreeMap<BuildingID, Station>,
+    // Every shared vehicle, whether it's parked or not
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    vehicles: BTreeMap<CarID, Vehicle>,
+    // Vehicles checked out, where they'll be returned, and if they're going to a dock there
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    in_use: BTreeMap<CarID, (BuildingID, bool)>,
+    max_walk: Distance,
+
+    events: Vec<Event>,
+}
+
+impl BikeShareSimState {
+    pub fn new(params: BikeShareParams, trips: &mut TripManager, map: &Map) -> BikeShareSimState {
+        let mut state = BikeShareSimState {
+            stations: BTreeMap::new(),
+            vehicles: BTreeMap::new(),
+            in_use: BTreeMap::new(),
+            max_walk: params.max_walk,
+            events: Vec::new(),
+        };
+        for (b, capacity, count) in params.docks {
+            if SidewalkSpot::bike_rack(b, map).is_none() {
+                println!("WARNING: bike-share dock at {} can't be reached by bike", b);
+                continue;
+            }
+            if count > capacity {
+                println!(
+                    "WARNING: bike-share dock at {} only has {} docks, not {}",
+                    b, capacity, count
+                );
+            }
+            let mut station = Station::new(capacity);
+            for _ in 0..count.min(capacity) {
+                station
+                    .docked
+                    .push(state.new_vehicle(params.max_speed, trips));
+            }
+            state.stations.insert(b, station);
+        }
+        for (b, count) in params.dockless {
+            if SidewalkSpot::bike_rack(b, map).is_none() {
+                println!(
+                    "WARNING: dockless vehicles at {} can't be reached by bike",
+                    b
+                );
+                continue;
+            }
+            for _ in 0..count {
+                let id = state.new_vehicle(params.max_speed, trips);
+                state
+                    .stations
+                    .entry(b)
+                    .or_insert_with(|| Station::new(0))
+                    .dockless
+                    .push(id);
+            }
+        }
+        for (b, station) in &state.stations {
+            state
+                .events
+                .push(Event::BikeShareAvailability(*b, station.available()));
+        }
+        state
+    }
+
+    fn new_vehicle(&mut self, max_speed: Option<Speed>, trips: &mut TripManager) -> CarID {
+        let id = CarID(trips.new_car_id(), VehicleType::Bike);
+        let vehicle = VehicleSpec {
+            vehicle_type: VehicleType::Bike,
+            length: BIKE_LENGTH,
+            max_speed,
+            battery: None,
+        }
+        .make(id, None);
+        self.vehicles.insert(id, vehicle);
+        id
+    }
+
+    // Reserve the nearest available vehicle, and somewhere to leave it near the goal. Returns the
+    // vehicle, where to pick it up, and where to return it. If there's nothing within walking
+    // distance, the rider should just walk.
+    pub fn checkout(
+        &mut self,
+        start: BuildingID,
+        goal: BuildingID,
+        map: &Map,
+    ) -> Option<(CarID, BuildingID, BuildingID)> {
+        let pickup = self.nearest(|b| dist_between(start, b, map), |s| s.available() > 0)?;
+        let use_dock = !self.stations[&pickup].docked.is_empty();
+        let dropoff = if use_dock {
+            self.nearest(|b| dist_between(goal, b, map), |s| s.free_docks() > 0)?
+        } else {
+            SidewalkSpot::bike_rack(goal, map)?;
+            goal
+        };
+        // Like UsingBike, don't bother riding along the same sidewalk
+        if SidewalkSpot::bike_rack(pickup, map)?.sidewalk_pos.lane()
+            == SidewalkSpot::bike_rack(dropoff, map)?.sidewalk_pos.lane()
+        {
+            return None;
+        }
+
+        Some((self.claim(pickup, dropoff, use_dock), pickup, dropoff))
+    }
+
+    // Take a vehicle from the pickup station and hold a dock for it at the dropoff, if needed.
+    fn claim(&mut self, pickup: BuildingID, dropoff: BuildingID, use_dock: bool) -> CarID {
+        let station = self.stations.get_mut(&pickup).unwrap();
+        let id = if use_dock {
+            station.docked.pop().unwrap()
+        } else {
+            station.dockless.pop().unwrap()
+        };
+        self.availability_changed(pickup);
+        if use_dock {
+            self.stations.get_mut(&dropoff).unwrap().reserved += 1;
+        }
+        self.in_use.insert(id, (dropoff, use_dock));
+        id
+    }
+
+    // Also used when a trip is aborted; the vehicle just warps to wherever it was headed.
+    pub fn vehicle_returned(&mut self, id: CarID) {
+        let (b, use_dock) = self.in_use.remove(&id).unwrap();
+        let station = self.stations.entry(b).or_insert_with(|| Station::new(0));
+        if use_dock {
+            station.reserved -= 1;
+            station.docked.push(id);
+        } else {
+            station.dockless.push(id);
+        }
+        self.availability_changed(b);
+    }
+
+    pub fn is_in_use(&self, id: CarID) -> bool {
+        self.in_use.contains_key(&id)
+    }
+
+    pub fn get_vehicle(&self, id: CarID) -> Option<&Vehicle> {
+        self.vehicles.get(&id)
+    }
+
+    // Move up to some number of parked vehicles into free docks at another station, or just leave
+    // them outside if there's no dock there. Returns how many were moved.
+    pub fn rebalance(&mut self, from: BuildingID, to: BuildingID, count: usize) -> usize {
+        if from == to {
+            return 0;
+        }
+        let room = match self.stations.get(&to) {
+            Some(s) if s.capacity > 0 => s.free_docks(),
+            _ => count,
+        };
+        let mut moved = Vec::new();
+        if let Some(src) = self.stations.get_mut(&from) {
+            while moved.len() < count.min(room) {
+                if let Some(id) = src.docked.pop().or_else(|| src.dockless.pop()) {
+                    moved.push(id);
+                } else {
+                    break;
+                }
+            }
+        }
+        if moved.is_empty() {
+            return 0;
+        }
+
+        let num_moved = moved.len();
+        let dst = self.stations.entry(to).or_insert_with(|| Station::new(0));
+        if dst.capacity == 0 {
+            dst.dockless.extend(moved);
+        } else {
+            dst.docked.extend(moved);
+        }
+        self.availability_changed(from);
+        self.availability_changed(to);
+        num_moved
+    }
+
+    // (building, vehicles available, docks)
+    pub fn get_stations(&self) -> Vec<(BuildingID, usize, usize)> {
+        self.stations
+            .iter()
+            .map(|(b, s)| (*b, s.available(), s.capacity))
+            .collect()
+    }
+
+    pub fn collect_events(&mut self) -> Vec<Event> {
+        self.events.drain(..).collect()
+    }
+
+    // The closest station within walking distance passing the filter
+    fn nearest<D: Fn(BuildingID) -> Distance, F: Fn(&Station) -> bool>(
+        &self,
+        dist_to: D,
+        ok: F,
+    ) -> Option<BuildingID> {
+        self.stations
+            .iter()
+            .filter(|(_, s)| ok(s))
+            .map(|(b, _)| (dist_to(*b), *b))
+            .filter(|(dist, _)| *dist <= self.max_walk)
+            .min_by_key(|(dist, _)| *dist)
+            .map(|(_, b)| b)
+    }
+
+    fn availability_changed(&mut self, b: BuildingID) {
+        let cnt = self.stations[&b].available();
+        self.events.push(Event::BikeShareAvailability(b, cnt));
+    }
+}
+
+fn dist_between(b1: BuildingID, b2: BuildingID, map: &Map) -> Distance {
+    map.get_b(b1)
+        .label_center
+        .dist_to(map.get_b(b2).label_center)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bike(idx: usize) -> CarID {
+        CarID(idx, VehicleType::Bike)
+    }
+
+    // Stations at buildings 0, 1, 2..., each (capacity, docked bikes, dockless bikes). Building
+    // IDs double as distances in meters.
+    fn state(stations: Vec<(usize, usize, usize)>) -> BikeShareSimState {
+        let mut state = BikeShareSimState {
+            stations: BTreeMap::new(),
+            vehicles: BTreeMap::new(),
+            in_use: BTreeMap::new(),
+            max_walk: Distance::meters(10.0),
+            events: Vec::new(),
+        };
+        let mut next_id = 0;
+        for (idx, (capacity, docked, dockless)) in stations.into_iter().enumerate() {
+            let mut station = Station::new(capacity);
+            for _ in 0..docked {
+                station.docked.push(bike(next_id));
+                next_id += 1;
+            }
+            for _ in 0..dockless {
+                station.dockless.push(bike(next_id));
+                next_id += 1;
+            }
+            state.stations.insert(BuildingID(idx), station);
+        }
+        state
+    }
+
+    fn dist(from: usize) -> impl Fn(BuildingID) -> Distance {
+        move |b| Distance::meters((b.0 as f64 - from as f64).abs())
+    }
+
+    #[test]
+    fn test_nearest_within_walking_distance() {
+        let mut state = state(vec![(2, 0, 0), (2, 1, 0), (2, 2, 0)]);
+        // Station 0 is closest to itself, but empty
+        assert_eq!(
+            state.nearest(dist(0), |s| s.available() > 0),
+            Some(BuildingID(1))
+        );
+        assert_eq!(
+            state.nearest(dist(0), |s| s.free_docks() > 0),
+            Some(BuildingID(0))
+        );
+        // Too far to walk
+        state.max_walk = Distance::meters(0.5);
+        assert_eq!(state.nearest(dist(0), |s| s.available() > 0), None);
+    }
+
+    #[test]
+    fn test_checkout_reserves_dock_until_returned() {
+        let mut state = state(vec![(1, 1, 0), (1, 0, 0)]);
+        let id = state.claim(BuildingID(0), BuildingID(1), true);
+        assert!(state.is_in_use(id));
+        assert_eq!(state.stations[&BuildingID(0)].available(), 0);
+        // The only dock at the destination is promised to this rider
+        assert_eq!(state.stations[&BuildingID(1)].free_docks(), 0);
+        assert_eq!(
+            state.nearest(dist(1), |s| s.free_docks() > 0),
+            Some(BuildingID(0))
+        );
+
+        state.vehicle_returned(id);
+        assert!(!state.is_in_use(id));
+        assert_eq!(state.stations[&BuildingID(1)].docked, vec![id]);
+        assert_eq!(state.stations[&BuildingID(1)].free_docks(), 0);
+        assert_eq!(state.stations[&BuildingID(1)].reserved, 0);
+        assert_eq!(
+            state.collect_events(),
+            vec![
+                Event::BikeShareAvailability(BuildingID(0), 0),
+                Event::BikeShareAvailability(BuildingID(1), 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_dockless_left_at_destination() {
+        let mut state = state(vec![(0, 0, 1)]);
+        let id = state.claim(BuildingID(0), BuildingID(5), false);
+        state.vehicle_returned(id);
+        assert_eq!(state.stations[&BuildingID(0)].available(), 0);
+        assert_eq!(state.stations[&BuildingID(5)].dockless, vec![id]);
+        assert_eq!(state.stations[&BuildingID(5)].capacity, 0);
+    }
+
+    #[test]
+    fn test_rebalance_respects_free_docks() {
+        let mut state = state(vec![(3, 3, 0), (2, 1, 0)]);
+        assert_eq!(state.rebalance(BuildingID(0), BuildingID(0), 1), 0);
+        // Only one free dock at the destination
+        assert_eq!(state.rebalance(BuildingID(0), BuildingID(1), 2), 1);
+        assert_eq!(
+            state.get_stations(),
+            vec![(BuildingID(0), 2, 3), (BuildingID(1), 2, 2)]
+        );
+        // No dock at all, so leave them all outside
+        assert_eq!(state.rebalance(BuildingID(0), BuildingID(7), 5), 2);
+        assert_eq!(state.stations[&BuildingID(7)].dockless.len(), 2);
+        assert_eq!(state.rebalance(BuildingID(0), BuildingID(1), 1), 0);
+    }
+}
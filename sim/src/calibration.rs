@@ -0,0 +1,237 @@
+use crate::{AgentType, Analytics};
+use map_model::raw::OriginalRoad;
+use map_model::{osm, Map};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+
+// Compare simulated throughput against real-world traffic counts, to judge how well a scenario
+// reproduces reality.
+
+#[derive(Serialize, Deserialize)]
+pub struct ObservedCounts {
+    pub map_name: String,
+    // Which agents the real counts include. Usually counters only see vehicles.
+    #[serde(default = "default_agent_types")]
+    pub agent_types: BTreeSet<AgentType>,
+    pub counts: Vec<ObservedCount>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ObservedCount {
+    pub location: CountLocation,
+    // Counts are per hour, with 0 meaning midnight to 1am of the first day
+    pub hour: usize,
+    pub count: usize,
+}
+
+// Refer to places by OSM IDs, so counts survive map rebuilds
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum CountLocation {
+    Road(OriginalRoad),
+    Intersection(osm::NodeID),
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct CalibrationReport {
+    // Pairs of (observed, simulated) counts, for a scatter plot
+    pub comparisons: Vec<CountComparison>,
+    pub unmatched: Vec<(CountLocation, String)>,
+
+    pub rmse: f64,
+    // RMSE as a percent of the mean observed count
+    pub pct_rmse: f64,
+    // The common acceptance target is 85% of counts having GEH under 5
+    pub pct_geh_under_5: f64,
+    // Multiplying the number of people by this would match the total observed count
+    pub suggested_scale: f64,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct CountComparison {
+    pub location: CountLocation,
+    pub hour: usize,
+    pub observed: usize,
+    pub simulated: usize,
+    pub geh: f64,
+}
+
+impl CalibrationReport {
+    pub fn new(
+        observed: &ObservedCounts,
+        analytics: &Analytics,
+        map: &Map,
+    ) -> Result<CalibrationReport, String> {
+        if &observed.map_name != map.get_name() {
+            return Err(format!(
+                "these counts are for {}, not {}",
+                observed.map_name,
+                map.get_name()
+            ));
+        }
+
+        let mut comparisons = Vec::new();
+        let mut unmatched = Vec::new();
+        for obs in &observed.counts {
+            let simulated = match obs.location {
+                CountLocation::Road(id) => match map.find_r_by_osm_id(id) {
+                    Ok(r) => observed
+                        .agent_types
+                        .iter()
+                        .map(|agent_type| {
+                            analytics
+                                .road_thruput
+                                .counts
+                                .get(&(r, *agent_type, obs.hour))
+                                .cloned()
+                                .unwrap_or(0)
+                        })
+                        .sum(),
+                    Err(err) => {
+                        unmatched.push((obs.location, err));
+                        continue;
+                    }
+                },
+                CountLocation::Intersection(id) => match map.find_i_by_osm_id(id) {
+                    Ok(i) => observed
+                        .agent_types
+                        .iter()
+                        .map(|agent_type| {
+                            analytics
+                                .intersection_thruput
+                                .counts
+                                .get(&(i, *agent_type, obs.hour))
+                                .cloned()
+                                .unwrap_or(0)
+                        })
+                        .sum(),
+                    Err(err) => {
+                        unmatched.push((obs.location, err));
+                        continue;
+                    }
+                },
+            };
+            comparisons.push(CountComparison {
+                location: obs.location,
+                hour: obs.hour,
+                observed: obs.count,
+                simulated,
+                geh: geh(obs.count, simulated),
+            });
+        }
+        Ok(CalibrationReport::from_comparisons(comparisons, unmatched))
+    }
+
+    fn from_comparisons(
+        comparisons: Vec<CountComparison>,
+        unmatched: Vec<(CountLocation, String)>,
+    ) -> CalibrationReport {
+        let n = comparisons.len() as f64;
+        let (rmse, pct_rmse, pct_geh_under_5, suggested_scale) = if comparisons.is_empty() {
+            (0.0, 0.0, 0.0, 1.0)
+        } else {
+            let sum_sq: f64 = comparisons
+                .iter()
+                .map(|c| (c.simulated as f64 - c.observed as f64).powi(2))
+                .sum();
+            let rmse = (sum_sq / n).sqrt();
+            let total_observed: usize = comparisons.iter().map(|c| c.observed).sum();
+            let total_simulated: usize = comparisons.iter().map(|c| c.simulated).sum();
+            let mean_observed = (total_observed as f64) / n;
+            let pct_rmse = if mean_observed == 0.0 {
+                0.0
+            } else {
+                100.0 * rmse / mean_observed
+            };
+            let pct_geh = 100.0 * (comparisons.iter().filter(|c| c.geh < 5.0).count() as f64) / n;
+            let scale = if total_simulated == 0 {
+                1.0
+            } else {
+                (total_observed as f64) / (total_simulated as f64)
+            };
+            (rmse, pct_rmse, pct_geh, scale)
+        };
+
+        CalibrationReport {
+            comparisons,
+            unmatched,
+            rmse,
+            pct_rmse,
+            pct_geh_under_5,
+            suggested_scale,
+        }
+    }
+
+    pub fn describe(&self) -> Vec<String> {
+        let mut lines = vec![
+            format!("{} counts compared", self.comparisons.len()),
+            format!("RMSE: {:.1} ({:.1}%)", self.rmse, self.pct_rmse),
+            format!("{:.1}% of counts have GEH < 5", self.pct_geh_under_5),
+            format!(
+                "Scaling the scenario by {:.2}x would match the total count",
+                self.suggested_scale
+            ),
+        ];
+        for (loc, err) in &self.unmatched {
+            lines.push(format!("Skipped {:?}: {}", loc, err));
+        }
+        lines
+    }
+}
+
+// The GEH statistic (https://en.wikipedia.org/wiki/GEH_statistic) for hourly counts
+fn geh(observed: usize, simulated: usize) -> f64 {
+    let m = simulated as f64;
+    let c = observed as f64;
+    if m + c == 0.0 {
+        return 0.0;
+    }
+    (2.0 * (m - c).powi(2) / (m + c)).sqrt()
+}
+
+fn default_agent_types() -> BTreeSet<AgentType> {
+    vec![AgentType::Car, AgentType::Bus].into_iter().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn comparison(observed: usize, simulated: usize) -> CountComparison {
+        CountComparison {
+            location: CountLocation::Intersection(osm::NodeID(1)),
+            hour: 0,
+            observed,
+            simulated,
+            geh: geh(observed, simulated),
+        }
+    }
+
+    #[test]
+    fn test_geh() {
+        assert_eq!(geh(0, 0), 0.0);
+        assert_eq!(geh(100, 100), 0.0);
+        // sqrt(2 * 50^2 / 150)
+        assert!((geh(100, 50) - 5.7735).abs() < 0.0001);
+        assert_eq!(geh(100, 50), geh(50, 100));
+        assert_eq!(geh(0, 8), 4.0);
+    }
+
+    #[test]
+    fn test_summary() {
+        let report = CalibrationReport::from_comparisons(
+            vec![comparison(100, 100), comparison(100, 50), comparison(0, 8)],
+            Vec::new(),
+        );
+        // sqrt((0 + 50^2 + 8^2) / 3)
+        assert!((report.rmse - 29.2347).abs() < 0.0001);
+        // The mean observed count is 200 / 3
+        assert!((report.pct_rmse - 43.8520).abs() < 0.0001);
+        // Only the 100 vs 50 count misses
+        assert!((report.pct_geh_under_5 - 200.0 / 3.0).abs() < 0.0001);
+        assert!((report.suggested_scale - 200.0 / 158.0).abs() < 0.0001);
+
+        let empty = CalibrationReport::from_comparisons(Vec::new(), Vec::new());
+        assert_eq!(empty.rmse, 0.0);
+        assert_eq!(empty.suggested_scale, 1.0);
+    }
+}
@@ -1,8 +1,8 @@
-use crate::{CarID, VehicleType};
+use crate::{CarID, Event, VehicleType};
 use geom::{Duration, Time};
-use map_model::{LaneID, Map, Path, PathConstraints, PathStep};
+use map_model::{AccessRestrictions, LaneID, Map, Path, PathConstraints, PathStep, TurnID};
 use serde::{Deserialize, Serialize};
-use std::collections::{BTreeMap, BTreeSet};
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
 
 // TODO When do we increase the counter for a zone, and when do we enforce the check? If we check
 // when the trip starts and increase when the car enters the zone, then lots of cars starting at
@@ -14,19 +14,44 @@ const RESERVE_WHEN_STARTING_TRIP: bool = true;
 // Note this only indexes into the zones we track here, not all of them in the map.
 type ZoneIdx = usize;
 
+// Handles the parts of zone access restrictions that need state: caps on the number of cars
+// entering, and charging vehicles to enter. The pathfinder handles everything else.
 // This only caps driving trips.
 #[derive(Serialize, Deserialize, PartialEq, Clone)]
 pub struct CapSimState {
     lane_to_zone: BTreeMap<LaneID, ZoneIdx>,
     zones: Vec<Zone>,
+
+    events: Vec<Event>,
 }
 
 #[derive(Serialize, Deserialize, PartialEq, Clone)]
 struct Zone {
     cap: usize,
-    entered_in_last_hour: BTreeSet<CarID>,
-    // TODO Maybe want sliding windows or something else
-    hour_started: Time,
+    // The cap only applies during the restrictions' hours
+    restrictions: AccessRestrictions,
+    // Cars counted against the cap in the last hour, oldest first
+    entered: VecDeque<(Time, CarID)>,
+}
+
+impl Zone {
+    // A sliding window over the last hour
+    fn expire(&mut self, now: Time) {
+        while let Some((t, _)) = self.entered.front() {
+            if now - *t >= Duration::hours(1) {
+                self.entered.pop_front();
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn count(&self, now: Time) -> usize {
+        self.entered
+            .iter()
+            .filter(|(t, _)| now - *t < Duration::hours(1))
+            .count()
+    }
 }
 
 impl CapSimState {
@@ -34,6 +59,7 @@ impl CapSimState {
         let mut sim = CapSimState {
             lane_to_zone: BTreeMap::new(),
             zones: Vec::new(),
+            events: Vec::new(),
         };
         for z in map.all_zones() {
             if let Some(cap) = z.restrictions.cap_vehicles_per_hour {
@@ -47,8 +73,8 @@ impl CapSimState {
                 }
                 sim.zones.push(Zone {
                     cap,
-                    entered_in_last_hour: BTreeSet::new(),
-                    hour_started: Time::START_OF_DAY,
+                    restrictions: z.restrictions.clone(),
+                    entered: VecDeque::new(),
                 });
             }
         }
@@ -67,39 +93,70 @@ impl CapSimState {
         } else {
             return;
         };
+        if !zone.restrictions.is_active(now) {
+            return;
+        }
 
-        if now - zone.hour_started >= Duration::hours(1) {
-            zone.hour_started = Time::START_OF_DAY + Duration::hours(now.get_parts().0);
-            zone.entered_in_last_hour.clear();
+        zone.expire(now);
+        if zone.entered.iter().all(|(_, c)| *c != car) {
+            zone.entered.push_back((now, car));
         }
-        zone.entered_in_last_hour.insert(car);
     }
 
-    pub fn allow_trip(&mut self, car: CarID, path: &Path) -> bool {
+    // Any vehicle crossing into a priced zone pays when it starts the turn.
+    pub fn car_entering_turn(&mut self, now: Time, car: CarID, turn: TurnID, map: &Map) {
+        if let Some(charge) = map
+            .entering_priced_zone(turn)
+            .and_then(|r| r.entry_charge_at(car.1.to_constraints(), now))
+        {
+            self.events.push(Event::ZoneEntryCharged {
+                car,
+                i: turn.parent,
+                charge,
+            });
+        }
+    }
+
+    pub fn allow_trip(&mut self, now: Time, car: CarID, path: &Path) -> bool {
         if car.1 != VehicleType::Car {
             return true;
         }
+        let mut zones = BTreeSet::new();
         for step in path.get_steps() {
             if let PathStep::Lane(l) = step {
                 if let Some(idx) = self.lane_to_zone.get(l) {
-                    let zone = &mut self.zones[*idx];
-                    if zone.entered_in_last_hour.len() >= zone.cap {
-                        return false;
-                    }
-                    if RESERVE_WHEN_STARTING_TRIP {
-                        zone.entered_in_last_hour.insert(car);
-                    }
+                    zones.insert(*idx);
+                }
+            }
+        }
+        // Check every zone before reserving anything
+        for idx in &zones {
+            let zone = &mut self.zones[*idx];
+            zone.expire(now);
+            if zone.restrictions.is_active(now) && zone.entered.len() >= zone.cap {
+                return false;
+            }
+        }
+        if RESERVE_WHEN_STARTING_TRIP {
+            for idx in zones {
+                let zone = &mut self.zones[idx];
+                if zone.restrictions.is_active(now) {
+                    zone.entered.push_back((now, car));
                 }
             }
         }
         true
     }
 
-    pub fn get_cap_counter(&self, l: LaneID) -> usize {
+    pub fn get_cap_counter(&self, l: LaneID, now: Time) -> usize {
         if let Some(idx) = self.lane_to_zone.get(&l) {
-            self.zones[*idx].entered_in_last_hour.len()
+            self.zones[*idx].count(now)
         } else {
             0
         }
     }
+
+    pub fn collect_events(&mut self) -> Vec<Event> {
+        self.events.drain(..).collect()
+    }
 }
@@ -0,0 +1,428 @@
+use crate::{CarID, Event, ParkingSimState, ParkingSpot, Vehicle, VehicleSpec, VehicleType};
+use abstutil::{deserialize_btreemap, serialize_btreemap};
+use geom::{Distance, Time};
+use map_model::{BuildingID, Map};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+// How much energy a battery-electric vehicle carries and how quickly it uses it up
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct BatterySpec {
+    pub capacity_kwh: f64,
+    pub kwh_per_km: f64,
+}
+
+impl BatterySpec {
+    pub fn energy_for(&self, dist: Distance) -> f64 {
+        self.kwh_per_km * dist.inner_meters() / 1000.0
+    }
+}
+
+// By default, nobody drives an electric vehicle.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ChargingParams {
+    // Percent of the cars people own that are electric
+    pub pct_electric: usize,
+    pub battery: BatterySpec,
+    // Parking spots (on-street or in lots) with a charger, and how fast it charges in kW. Only
+    // electric vehicles can park there.
+    pub chargers: Vec<(ParkingSpot, f64)>,
+    // Drivers detour to a charger when they'd otherwise arrive with less than this fraction of a
+    // full battery
+    pub low_charge: f64,
+    // How far (in a straight line) somebody will walk from a charger to their destination
+    pub max_walk: Distance,
+}
+
+impl ChargingParams {
+    pub fn new() -> ChargingParams {
+        ChargingParams {
+            pct_electric: 0,
+            battery: BatterySpec {
+                capacity_kwh: 60.0,
+                kwh_per_km: 0.2,
+            },
+            chargers: Vec::new(),
+            low_charge: 0.2,
+            max_walk: Distance::meters(500.0),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Clone)]
+pub struct ChargingSimState {
+    pct_electric: usize,
+    battery: BatterySpec,
+    low_charge: f64,
+    max_walk: Distance,
+    cars_seen: usize,
+
+    // In kW
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    chargers: BTreeMap<ParkingSpot, f64>,
+    // Every electric vehicle that's driven so far, and how many kWh are left. Vehicles start
+    // fully charged.
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    state_of_charge: BTreeMap<CarID, (BatterySpec, f64)>,
+    // Where and since when each vehicle has been plugged in
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    plugged_in: BTreeMap<CarID, (ParkingSpot, Time)>,
+    // Vehicles that needed a charger, but couldn't find a free one. They stop waiting when they
+    // park anywhere, and try again the next time they start driving.
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    waiting: BTreeMap<CarID, Time>,
+
+    events: Vec<Event>,
+}
+
+impl ChargingSimState {
+    pub fn new(params: ChargingParams, parking: &mut ParkingSimState) -> ChargingSimState {
+        let mut state = ChargingSimState {
+            pct_electric: params.pct_electric,
+            battery: params.battery,
+            low_charge: params.low_charge,
+            max_walk: params.max_walk,
+            cars_seen: 0,
+            chargers: BTreeMap::new(),
+            state_of_charge: BTreeMap::new(),
+            plugged_in: BTreeMap::new(),
+            waiting: BTreeMap::new(),
+            events: Vec::new(),
+        };
+        let all_spots: BTreeSet<ParkingSpot> = {
+            let (filled, free) = parking.get_all_parking_spots();
+            filled.into_iter().chain(free.into_iter()).collect()
+        };
+        for (spot, kw) in params.chargers {
+            if let ParkingSpot::Offstreet(_, _) = spot {
+                println!(
+                    "WARNING: chargers in private garages aren't supported, skipping {:?}",
+                    spot
+                );
+                continue;
+            }
+            if !all_spots.contains(&spot) {
+                println!("WARNING: charger at {:?} isn't a usable parking spot", spot);
+                continue;
+            }
+            parking.add_charger(spot);
+            state.chargers.insert(spot, kw);
+        }
+        if !state.chargers.is_empty() {
+            println!(
+                "{} chargers ({} kW total)",
+                state.chargers.len(),
+                state.chargers.values().sum::<f64>()
+            );
+        }
+        state
+    }
+
+    // Make some of a new person's cars electric. Deterministically spreads them out, so this
+    // doesn't disturb any RNG.
+    pub fn electrify(&mut self, mut specs: Vec<VehicleSpec>) -> Vec<VehicleSpec> {
+        for spec in &mut specs {
+            if spec.vehicle_type != VehicleType::Car {
+                continue;
+            }
+            let before = self.cars_seen * self.pct_electric / 100;
+            self.cars_seen += 1;
+            if self.cars_seen * self.pct_electric / 100 > before {
+                spec.battery = Some(self.battery);
+            }
+        }
+        specs
+    }
+
+    // In kWh
+    pub fn state_of_charge(&self, vehicle: &Vehicle) -> Option<f64> {
+        let battery = vehicle.battery?;
+        Some(
+            self.state_of_charge
+                .get(&vehicle.id)
+                .map(|(_, soc)| *soc)
+                .unwrap_or(battery.capacity_kwh),
+        )
+    }
+
+    // In kWh, including whatever a plugged-in vehicle has picked up so far
+    fn charge_at(&self, vehicle: &Vehicle, now: Time) -> Option<f64> {
+        let soc = self.state_of_charge(vehicle)?;
+        Some(soc + self.pending_charge(vehicle.id, now))
+    }
+
+    fn pending_charge(&self, car: CarID, now: Time) -> f64 {
+        if let Some((spot, since)) = self.plugged_in.get(&car) {
+            let (battery, soc) = self.state_of_charge[&car];
+            (self.chargers[spot] * (now - *since).inner_seconds() / 3600.0)
+                .min(battery.capacity_kwh - soc)
+        } else {
+            0.0
+        }
+    }
+
+    // Would driving this far leave the vehicle too low?
+    pub fn needs_charge(&self, vehicle: &Vehicle, dist: Distance, now: Time) -> bool {
+        if let Some(battery) = vehicle.battery {
+            self.charge_at(vehicle, now).unwrap() - battery.energy_for(dist)
+                < self.low_charge * battery.capacity_kwh
+        } else {
+            false
+        }
+    }
+
+    // Can the vehicle drive this far without running out?
+    pub fn has_range(&self, vehicle: &Vehicle, dist: Distance, now: Time) -> bool {
+        if let Some(battery) = vehicle.battery {
+            self.charge_at(vehicle, now).unwrap() >= battery.energy_for(dist)
+        } else {
+            true
+        }
+    }
+
+    // Trips check has_range before starting, so this never runs the battery below empty.
+    pub fn car_departed(&mut self, vehicle: &Vehicle, dist: Distance, now: Time) {
+        if let Some(battery) = vehicle.battery {
+            // The CarLeftParkingSpot event isn't handled until later, so finish charging first.
+            self.unplug(vehicle.id, now);
+            let soc = self.state_of_charge(vehicle).unwrap() - battery.energy_for(dist);
+            self.state_of_charge
+                .insert(vehicle.id, (battery, soc.max(0.0)));
+        }
+    }
+
+    fn unplug(&mut self, car: CarID, now: Time) {
+        let energy = self.pending_charge(car, now);
+        if let Some((spot, since)) = self.plugged_in.remove(&car) {
+            let (battery, soc) = self.state_of_charge[&car];
+            self.state_of_charge.insert(car, (battery, soc + energy));
+            self.events.push(Event::ChargingSessionEnded {
+                car,
+                spot,
+                duration: now - since,
+                energy,
+            });
+        }
+    }
+
+    // The free charger closest to the target that the vehicle can use right now. If there isn't
+    // one, the vehicle starts waiting for one.
+    pub fn find_charger(
+        &mut self,
+        vehicle: &Vehicle,
+        target: BuildingID,
+        now: Time,
+        parking: &ParkingSimState,
+        map: &Map,
+    ) -> Option<ParkingSpot> {
+        let pt = map.get_b(target).sidewalk_pos.pt(map);
+        let best = self
+            .chargers
+            .keys()
+            .filter(|spot| parking.is_free(**spot) && parking.is_allowed(**spot, vehicle, now, map))
+            .map(|spot| {
+                (
+                    parking.spot_to_sidewalk_pos(*spot, map).pt(map).dist_to(pt),
+                    *spot,
+                )
+            })
+            .filter(|(dist, _)| *dist <= self.max_walk)
+            .min_by_key(|(dist, _)| *dist)
+            .map(|(_, spot)| spot);
+        if best.is_none() && !self.waiting.contains_key(&vehicle.id) {
+            self.waiting.insert(vehicle.id, now);
+            self.events
+                .push(Event::ChargerQueueLength(self.waiting.len()));
+        }
+        best
+    }
+
+    pub fn handle_event(&mut self, now: Time, ev: &Event) {
+        match ev {
+            Event::CarReachedParkingSpot(car, spot) => {
+                if self.chargers.contains_key(spot) && self.state_of_charge.contains_key(car) {
+                    self.plugged_in.insert(*car, (*spot, now));
+                    if let Some(since) = self.waiting.remove(car) {
+                        self.events.push(Event::ChargerWaitEnded(*car, now - since));
+                        self.events
+                            .push(Event::ChargerQueueLength(self.waiting.len()));
+                    }
+                } else if self.waiting.remove(car).is_some() {
+                    // Parked without a charger, so stop counting them in the queue
+                    self.events
+                        .push(Event::ChargerQueueLength(self.waiting.len()));
+                }
+            }
+            Event::CarLeftParkingSpot(car, _) => {
+                self.unplug(*car, now);
+            }
+            _ => {}
+        }
+    }
+
+    // (spot, kW, who's plugged in there)
+    pub fn get_chargers(&self) -> Vec<(ParkingSpot, f64, Option<CarID>)> {
+        self.chargers
+            .iter()
+            .map(|(spot, kw)| {
+                (
+                    *spot,
+                    *kw,
+                    self.plugged_in
+                        .iter()
+                        .find(|(_, (s, _))| s == spot)
+                        .map(|(car, _)| *car),
+                )
+            })
+            .collect()
+    }
+
+    pub fn collect_events(&mut self) -> Vec<Event> {
+        self.events.drain(..).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MIN_CAR_LENGTH;
+    use geom::Duration;
+    use map_model::LaneID;
+
+    const CHARGER: ParkingSpot = ParkingSpot::Onstreet(LaneID(0), 0);
+
+    fn state() -> ChargingSimState {
+        let mut params = ChargingParams::new();
+        params.pct_electric = 50;
+        let mut chargers = BTreeMap::new();
+        chargers.insert(CHARGER, 10.0);
+        ChargingSimState {
+            pct_electric: params.pct_electric,
+            battery: params.battery,
+            low_charge: params.low_charge,
+            max_walk: params.max_walk,
+            cars_seen: 0,
+            chargers,
+            state_of_charge: BTreeMap::new(),
+            plugged_in: BTreeMap::new(),
+            waiting: BTreeMap::new(),
+            events: Vec::new(),
+        }
+    }
+
+    fn spec(vehicle_type: VehicleType) -> VehicleSpec {
+        VehicleSpec {
+            vehicle_type,
+            length: MIN_CAR_LENGTH,
+            max_speed: None,
+            battery: None,
+        }
+    }
+
+    fn electric_car(idx: usize, state: &ChargingSimState) -> Vehicle {
+        let mut spec = spec(VehicleType::Car);
+        spec.battery = Some(state.battery);
+        spec.make(CarID(idx, VehicleType::Car), None)
+    }
+
+    fn hours(h: usize) -> Time {
+        Time::START_OF_DAY + Duration::hours(h)
+    }
+
+    #[test]
+    fn test_electrify() {
+        let mut state = state();
+        let specs = state.electrify(vec![
+            spec(VehicleType::Car),
+            spec(VehicleType::Bike),
+            spec(VehicleType::Car),
+            spec(VehicleType::Car),
+            spec(VehicleType::Car),
+        ]);
+        let electric: Vec<bool> = specs.iter().map(|s| s.battery.is_some()).collect();
+        assert_eq!(electric, vec![false, false, true, false, true]);
+    }
+
+    #[test]
+    fn test_range() {
+        let mut state = state();
+        let car = electric_car(0, &state);
+        let gas_car = spec(VehicleType::Car).make(CarID(1, VehicleType::Car), None);
+        assert_eq!(state.state_of_charge(&car), Some(60.0));
+        assert_eq!(state.state_of_charge(&gas_car), None);
+
+        // 20 kWh used, 40 left
+        state.car_departed(&car, Distance::meters(100_000.0), hours(0));
+        assert_eq!(state.state_of_charge(&car), Some(40.0));
+        // 30 kWh would leave less than 20% of the battery
+        assert!(state.needs_charge(&car, Distance::meters(150_000.0), hours(1)));
+        assert!(!state.needs_charge(&car, Distance::meters(100_000.0), hours(1)));
+        assert!(state.has_range(&car, Distance::meters(150_000.0), hours(1)));
+        assert!(!state.has_range(&car, Distance::meters(250_000.0), hours(1)));
+        assert!(!state.needs_charge(&gas_car, Distance::meters(1_000_000.0), hours(1)));
+        assert!(state.has_range(&gas_car, Distance::meters(1_000_000.0), hours(1)));
+    }
+
+    #[test]
+    fn test_charging_session() {
+        let mut state = state();
+        let car = electric_car(0, &state);
+        state.car_departed(&car, Distance::meters(100_000.0), hours(0));
+        state.handle_event(hours(1), &Event::CarReachedParkingSpot(car.id, CHARGER));
+        assert_eq!(state.get_chargers(), vec![(CHARGER, 10.0, Some(car.id))]);
+        // 10 kW for an hour
+        assert!(state.has_range(&car, Distance::meters(250_000.0), hours(2)));
+        // Charging stops once the battery is full
+        assert!(!state.has_range(&car, Distance::meters(350_000.0), hours(10)));
+
+        state.handle_event(hours(2), &Event::CarLeftParkingSpot(car.id, CHARGER));
+        assert_eq!(state.state_of_charge(&car), Some(50.0));
+        assert_eq!(state.get_chargers(), vec![(CHARGER, 10.0, None)]);
+        assert_eq!(
+            state.collect_events(),
+            vec![Event::ChargingSessionEnded {
+                car: car.id,
+                spot: CHARGER,
+                duration: Duration::hours(1),
+                energy: 10.0,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_waiting_for_charger() {
+        let mut state = state();
+        let car1 = electric_car(0, &state);
+        let car2 = electric_car(1, &state);
+        for car in vec![&car1, &car2] {
+            state.car_departed(car, Distance::meters(100_000.0), hours(0));
+            state.waiting.insert(car.id, hours(0));
+        }
+
+        // Parking somewhere else gives up on charging
+        let elsewhere = ParkingSpot::Onstreet(LaneID(0), 1);
+        state.handle_event(hours(1), &Event::CarReachedParkingSpot(car1.id, elsewhere));
+        state.handle_event(hours(2), &Event::CarReachedParkingSpot(car2.id, CHARGER));
+        assert!(state.waiting.is_empty());
+        assert_eq!(
+            state.collect_events(),
+            vec![
+                Event::ChargerQueueLength(1),
+                Event::ChargerWaitEnded(car2.id, Duration::hours(2)),
+                Event::ChargerQueueLength(0),
+            ]
+        );
+    }
+}
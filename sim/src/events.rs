@@ -1,10 +1,10 @@
 use crate::{
     AgentID, CarID, OffMapLocation, ParkingSpot, PedestrianID, PersonID, TripID, TripMode,
 };
-use geom::Duration;
+use geom::{Distance, Duration};
 use map_model::{
-    BuildingID, BusRouteID, BusStopID, CompressedTurnGroupID, IntersectionID, LaneID, Map, Path,
-    PathRequest, Traversable,
+    BuildingID, BusRouteID, BusStopID, CompressedTurnGroupID, IntersectionID, LaneID, Map,
+    ParkingLotID, Path, PathRequest, Traversable,
 };
 use serde::{Deserialize, Serialize};
 
@@ -15,6 +15,33 @@ use serde::{Deserialize, Serialize};
 pub enum Event {
     CarReachedParkingSpot(CarID, ParkingSpot),
     CarLeftParkingSpot(CarID, ParkingSpot),
+    // A car left a spot with parking rules. The fee is in dollars. Overstaying the time limit or
+    // still being parked when parking is banned isn't compliant.
+    ParkingSessionEnded {
+        car: CarID,
+        spot: ParkingSpot,
+        duration: Duration,
+        fee: f64,
+        compliant: bool,
+    },
+    // An electric vehicle left a charger. Energy is in kWh.
+    ChargingSessionEnded {
+        car: CarID,
+        spot: ParkingSpot,
+        duration: Duration,
+        energy: f64,
+    },
+    // How many electric vehicles need a charger but haven't found a free one yet
+    ChargerQueueLength(usize),
+    ChargerWaitEnded(CarID, Duration),
+    // A vehicle crossed into a zone at this intersection and paid the charge, in dollars
+    ZoneEntryCharged {
+        car: CarID,
+        i: IntersectionID,
+        charge: f64,
+    },
+    // Entry charges or a zone closed at this time of day pushed a trip off of its usual route
+    TripDiverted(TripID),
 
     BusArrivedAtStop(CarID, BusRouteID, BusStopID),
     BusDepartedFromStop(CarID, BusRouteID, BusStopID),
@@ -22,6 +49,19 @@ pub enum Event {
     PassengerBoardsTransit(PersonID, CarID, BusRouteID, BusStopID, Duration),
     PassengerAlightsTransit(PersonID, CarID, BusRouteID, BusStopID),
 
+    // How long did the passenger wait for pickup?
+    RideHailPickup(PersonID, CarID, Duration),
+    // A ride-hail vehicle reached the end of its path. Without a passenger, it was deadheading.
+    RideHailLegFinished {
+        car: CarID,
+        dist: Distance,
+        occupied: bool,
+    },
+    // How many shared bikes or scooters are parked at a station now
+    BikeShareAvailability(BuildingID, usize),
+    // How many park-and-ride cars are left in a lot now
+    ParkAndRideOccupancy(ParkingLotID, usize),
+
     PersonEntersBuilding(PersonID, BuildingID),
     PersonLeavesBuilding(PersonID, BuildingID),
     // None if aborted
@@ -51,6 +91,13 @@ pub enum Event {
         blocked_time: Duration,
     },
     TripAborted(TripID),
+    // The driver just parked and will walk the rest of the way. The distance is a straight line
+    // from the spot to where they're walking.
+    TripParkedCar {
+        trip: TripID,
+        spot: ParkingSpot,
+        walking_dist: Distance,
+    },
     TripPhaseStarting(TripID, PersonID, Option<PathRequest>, TripPhaseType),
 
     // Just use for parking replanning. Not happy about copying the full path in here, but the way
@@ -77,6 +124,8 @@ pub enum TripPhaseType {
     WaitingForBus(BusRouteID, BusStopID),
     // What stop did they board at?
     RidingBus(BusRouteID, BusStopID, CarID),
+    WaitingForRideHail,
+    RidingRideHail(CarID),
     Aborted,
     Finished,
     DelayedStart,
@@ -94,6 +143,8 @@ impl TripPhaseType {
                 format!("waiting for bus {}", map.get_br(r).full_name)
             }
             TripPhaseType::RidingBus(r, _, _) => format!("riding bus {}", map.get_br(r).full_name),
+            TripPhaseType::WaitingForRideHail => "waiting for a ride-hail pickup".to_string(),
+            TripPhaseType::RidingRideHail(car) => format!("riding in {}", car),
             TripPhaseType::Aborted => "trip aborted due to some bug".to_string(),
             TripPhaseType::Finished => "trip finished".to_string(),
             TripPhaseType::DelayedStart => "delayed by previous trip taking too long".to_string(),
@@ -1,10 +1,14 @@
 mod analytics;
+mod bikeshare;
+mod calibration;
 mod cap;
+mod charging;
 mod events;
 mod make;
 mod mechanics;
 mod pandemic;
 mod render;
+mod ridehail;
 mod router;
 mod scheduler;
 mod sim;
@@ -12,7 +16,14 @@ mod transit;
 mod trips;
 
 pub use self::analytics::{Analytics, TripPhase};
+pub use self::bikeshare::BikeShareParams;
+pub(crate) use self::bikeshare::BikeShareSimState;
+pub use self::calibration::{
+    CalibrationReport, CountComparison, CountLocation, ObservedCount, ObservedCounts,
+};
 pub(crate) use self::cap::CapSimState;
+pub(crate) use self::charging::ChargingSimState;
+pub use self::charging::{BatterySpec, ChargingParams};
 pub(crate) use self::events::Event;
 pub use self::events::{AlertLocation, TripPhaseType};
 pub use self::make::{
@@ -23,8 +34,11 @@ pub(crate) use self::mechanics::{
     DrivingSimState, IntersectionSimState, ParkingSimState, WalkingSimState,
 };
 pub(crate) use self::pandemic::PandemicModel;
+pub use self::pandemic::{PandemicCounts, PandemicParams, PandemicPolicies};
+pub use self::ridehail::RideHailParams;
+pub(crate) use self::ridehail::{RideHailRequest, RideHailSimState};
 pub(crate) use self::router::{ActionAtEnd, Router};
-pub(crate) use self::scheduler::{Command, Scheduler};
+pub(crate) use self::scheduler::{Command, CommandType, Scheduler};
 pub use self::sim::{AgentProperties, AlertHandler, Sim, SimCallback, SimOptions};
 pub(crate) use self::transit::TransitSimState;
 pub use self::trips::{Person, PersonState, TripInfo, TripResult};
@@ -38,7 +52,7 @@ use abstutil::{deserialize_usize, serialize_usize};
 use geom::{Distance, Pt2D, Speed, Time};
 use map_model::{
     BuildingID, BusRouteID, BusStopID, DirectedRoadID, IntersectionID, LaneID, Map, ParkingLotID,
-    Path, PathConstraints, PathRequest, Position,
+    ParkingRules, Path, PathConstraints, PathRequest, Position,
 };
 use serde::{Deserialize, Serialize};
 use std::fmt;
@@ -273,6 +287,8 @@ pub struct Vehicle {
     pub vehicle_type: VehicleType,
     pub length: Distance,
     pub max_speed: Option<Speed>,
+    // Only for electric vehicles
+    pub battery: Option<BatterySpec>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
@@ -280,6 +296,7 @@ pub struct VehicleSpec {
     pub vehicle_type: VehicleType,
     pub length: Distance,
     pub max_speed: Option<Speed>,
+    pub battery: Option<BatterySpec>,
 }
 
 impl VehicleSpec {
@@ -291,6 +308,7 @@ impl VehicleSpec {
             vehicle_type: self.vehicle_type,
             length: self.length,
             max_speed: self.max_speed,
+            battery: self.battery,
         }
     }
 }
@@ -304,6 +322,17 @@ pub enum ParkingSpot {
     Lot(ParkingLotID, usize),
 }
 
+impl ParkingSpot {
+    // TODO Garages in buildings don't have any rules yet.
+    pub fn rules<'a>(&self, map: &'a Map) -> Option<&'a ParkingRules> {
+        match self {
+            ParkingSpot::Onstreet(l, _) => Some(&map.get_l(*l).parking_rules),
+            ParkingSpot::Offstreet(_, _) => None,
+            ParkingSpot::Lot(pl, _) => Some(&map.get_pl(*pl).rules),
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub struct ParkedCar {
     pub vehicle: Vehicle,
@@ -316,6 +345,10 @@ pub struct ParkedCar {
 pub enum DrivingGoal {
     ParkNear(BuildingID),
     Border(IntersectionID, LaneID, Option<OffMapLocation>),
+    // Leave the car in a lot, then continue on transit
+    ParkAndRide(ParkingLotID),
+    // Leave the bike by a stop, then continue on transit
+    BikeAndRide(BusStopID),
 }
 
 impl DrivingGoal {
@@ -346,6 +379,8 @@ impl DrivingGoal {
                 }
             },
             DrivingGoal::Border(_, l, _) => Some(Position::end(*l, map)),
+            DrivingGoal::ParkAndRide(pl) => Some(map.get_pl(*pl).driving_pos),
+            DrivingGoal::BikeAndRide(stop) => Some(map.get_bs(*stop).biking_connection(map)?.0),
         }
     }
 
@@ -361,6 +396,12 @@ impl DrivingGoal {
             DrivingGoal::Border(i, last_lane, _) => {
                 Router::end_at_border(owner, path, map.get_l(*last_lane).length(), *i)
             }
+            DrivingGoal::ParkAndRide(pl) => Router::park_in_lot(owner, path, *pl, map),
+            DrivingGoal::BikeAndRide(stop) => Router::bike_then_stop(
+                owner,
+                path,
+                SidewalkSpot::bike_rack_at_stop(*stop, map).unwrap(),
+            ),
         }
     }
 
@@ -368,6 +409,8 @@ impl DrivingGoal {
         match self {
             DrivingGoal::ParkNear(b) => map.get_b(*b).polygon.center(),
             DrivingGoal::Border(i, _, _) => map.get_i(*i).polygon.center(),
+            DrivingGoal::ParkAndRide(pl) => map.get_pl(*pl).polygon.center(),
+            DrivingGoal::BikeAndRide(stop) => map.get_bs(*stop).sidewalk_pos.pt(map),
         }
     }
 }
@@ -431,6 +474,14 @@ impl SidewalkSpot {
         })
     }
 
+    pub fn bike_rack_at_stop(stop: BusStopID, map: &Map) -> Option<SidewalkSpot> {
+        let (bike_pos, sidewalk_pos) = map.get_bs(stop).biking_connection(map)?;
+        Some(SidewalkSpot {
+            connection: SidewalkPOI::BikeRack(bike_pos),
+            sidewalk_pos,
+        })
+    }
+
     pub fn bus_stop(stop: BusStopID, map: &Map) -> SidewalkSpot {
         SidewalkSpot {
             sidewalk_pos: map.get_bs(stop).sidewalk_pos,
@@ -584,7 +635,7 @@ pub struct CreateCar {
     pub req: PathRequest,
     pub start_dist: Distance,
     pub maybe_parked_car: Option<ParkedCar>,
-    // None for buses
+    // None for buses and ride-hail vehicles
     pub trip_and_person: Option<(TripID, PersonID)>,
     pub maybe_route: Option<BusRouteID>,
 }
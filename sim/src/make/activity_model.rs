@@ -1,6 +1,6 @@
 use crate::{
-    IndividTrip, PersonID, PersonSpec, Scenario, ScenarioGenerator, SpawnTrip, TripEndpoint,
-    TripMode,
+    IndividTrip, PersonID, PersonSpec, Scenario, ScenarioGenerator, SidewalkSpot, SpawnTrip,
+    TripEndpoint, TripMode,
 };
 use abstutil::{Parallelism, Timer};
 use geom::{Distance, Duration, Time};
@@ -139,19 +139,38 @@ fn robot_person(
         );
     }
 
-    // Skip the person if either trip can't be created.
-    let goto_work = SpawnTrip::new(
-        TripEndpoint::Bldg(home),
-        TripEndpoint::Bldg(work),
-        mode,
-        map,
-    )?;
-    let return_home = SpawnTrip::new(
-        TripEndpoint::Bldg(work),
-        TripEndpoint::Bldg(home),
-        mode,
-        map,
-    )?;
+    // Some longer transit commutes start by driving or biking to a transfer point.
+    let transfer = if mode == TripMode::Transit && dist > Distance::miles(3.0) {
+        if rng.gen_bool(0.3) {
+            park_and_ride(home, work, map)
+        } else if rng.gen_bool(0.1) {
+            bike_and_ride(home, work, map)
+        } else {
+            None
+        }
+    } else {
+        None
+    };
+
+    let (goto_work, return_home) = if let Some(pair) = transfer {
+        pair
+    } else {
+        // Skip the person if either trip can't be created.
+        (
+            SpawnTrip::new(
+                TripEndpoint::Bldg(home),
+                TripEndpoint::Bldg(work),
+                mode,
+                map,
+            )?,
+            SpawnTrip::new(
+                TripEndpoint::Bldg(work),
+                TripEndpoint::Bldg(home),
+                mode,
+                map,
+            )?,
+        )
+    };
 
     Some(PersonSpec {
         // Fix this outside the parallelism
@@ -164,6 +183,31 @@ fn robot_person(
     })
 }
 
+// Drive to a park-and-ride lot and take transit to work, then pick the car back up on the way
+// home.
+fn park_and_ride(home: BuildingID, work: BuildingID, map: &Map) -> Option<(SpawnTrip, SpawnTrip)> {
+    let goto_work = SpawnTrip::drive_then_transit(home, SidewalkSpot::building(work, map), map)?;
+    let lot = match goto_work {
+        SpawnTrip::DriveThenTransit { lot, .. } => lot,
+        _ => unreachable!(),
+    };
+    let return_home =
+        SpawnTrip::transit_then_drive(SidewalkSpot::building(work, map), lot, home, map)?;
+    Some((goto_work, return_home))
+}
+
+// Same idea, but leave a bike at a stop.
+fn bike_and_ride(home: BuildingID, work: BuildingID, map: &Map) -> Option<(SpawnTrip, SpawnTrip)> {
+    let goto_work = SpawnTrip::bike_then_transit(home, SidewalkSpot::building(work, map), map)?;
+    let transfer = match goto_work {
+        SpawnTrip::BikeThenTransit { transfer, .. } => transfer,
+        _ => unreachable!(),
+    };
+    let return_home =
+        SpawnTrip::transit_then_bike(SidewalkSpot::building(work, map), transfer, home, map)?;
+    Some((goto_work, return_home))
+}
+
 fn border_person(
     incoming_connections: &Vec<&Intersection>,
     outgoing_connections: &Vec<&Intersection>,
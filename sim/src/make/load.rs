@@ -1,6 +1,10 @@
-use crate::{AlertHandler, Scenario, Sim, SimOptions};
+use crate::{
+    AlertHandler, BikeShareParams, ChargingParams, PandemicParams, PandemicPolicies,
+    RideHailParams, Scenario, Sim, SimOptions,
+};
 use abstutil::CmdArgs;
-use map_model::{Map, MapEdits};
+use geom::Duration;
+use map_model::{Map, MapEdits, RoutingParams};
 use rand::SeedableRng;
 use rand_xorshift::XorShiftRng;
 
@@ -38,6 +42,30 @@ impl SimFlags {
                 } else {
                     None
                 },
+                pandemic_params: args
+                    .optional("--pandemic_params")
+                    .map(|path| abstutil::read_json(path, &mut abstutil::Timer::throwaway()))
+                    .unwrap_or_else(PandemicParams::new),
+                pandemic_policies: args
+                    .optional("--pandemic_policies")
+                    .map(|path| abstutil::read_json(path, &mut abstutil::Timer::throwaway()))
+                    .unwrap_or_else(PandemicPolicies::new),
+                ridehail_params: args
+                    .optional("--ridehail_params")
+                    .map(|path| abstutil::read_json(path, &mut abstutil::Timer::throwaway()))
+                    .unwrap_or_else(RideHailParams::new),
+                bikeshare_params: args
+                    .optional("--bikeshare_params")
+                    .map(|path| abstutil::read_json(path, &mut abstutil::Timer::throwaway()))
+                    .unwrap_or_else(BikeShareParams::new),
+                charging_params: args
+                    .optional("--charging_params")
+                    .map(|path| abstutil::read_json(path, &mut abstutil::Timer::throwaway()))
+                    .unwrap_or_else(ChargingParams::new),
+                routing_params: args
+                    .optional("--routing_params")
+                    .map(|path| abstutil::read_json(path, &mut abstutil::Timer::throwaway()))
+                    .unwrap_or_else(RoutingParams::new),
                 alerts: args
                     .optional("--alerts")
                     .map(|x| match x.as_ref() {
@@ -48,6 +76,11 @@ impl SimFlags {
                     })
                     .unwrap_or(AlertHandler::Print),
                 pathfinding_upfront: args.enabled("--pathfinding_upfront"),
+                stop_sign_wait: args
+                    .optional_parse("--stop_sign_wait", |s| {
+                        s.parse::<f64>().map(Duration::seconds)
+                    })
+                    .unwrap_or_else(|| Duration::seconds(0.5)),
             },
         }
     }
@@ -82,15 +115,19 @@ impl SimFlags {
 
             let mut map = Map::new(abstutil::path_map(&sim.map_name), timer);
             if sim.edits_name != "untitled edits" {
-                map.must_apply_edits(
-                    MapEdits::load(
-                        &map,
-                        abstutil::path_edits(map.get_name(), &sim.edits_name),
-                        timer,
-                    )
-                    .unwrap(),
+                let (edits, report) = MapEdits::load_and_migrate(
+                    &map,
+                    abstutil::path_edits(map.get_name(), &sim.edits_name),
                     timer,
-                );
+                )
+                .unwrap();
+                if !report.is_clean() {
+                    timer.error(format!(
+                        "The basemap changed since {} was saved. {}",
+                        sim.edits_name, report
+                    ));
+                }
+                map.must_apply_edits(edits, timer);
                 map.recalculate_pathfinding_after_edits(timer);
             }
             sim.restore_paths(&map, timer);
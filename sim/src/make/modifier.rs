@@ -10,6 +10,8 @@ use std::collections::BTreeSet;
 pub enum ScenarioModifier {
     RepeatDays(usize),
     CancelPeople(usize),
+    // Percent of the original number of people. Above 100, randomly chosen people are duplicated.
+    ScalePeople(usize),
     ChangeMode {
         to_mode: TripMode,
         pct_ppl: usize,
@@ -27,6 +29,7 @@ impl ScenarioModifier {
         match self {
             ScenarioModifier::RepeatDays(n) => repeat_days(s, *n),
             ScenarioModifier::CancelPeople(pct) => cancel_people(s, *pct, rng),
+            ScenarioModifier::ScalePeople(pct) => scale_people(s, *pct, rng),
             ScenarioModifier::ChangeMode {
                 to_mode,
                 pct_ppl,
@@ -78,6 +81,9 @@ impl ScenarioModifier {
             ScenarioModifier::CancelPeople(pct) => {
                 format!("cancel all trips for {}% of people", pct)
             }
+            ScenarioModifier::ScalePeople(pct) => {
+                format!("scale the number of people to {}%", pct)
+            }
             ScenarioModifier::ChangeMode {
                 pct_ppl,
                 to_mode,
@@ -136,3 +142,29 @@ fn cancel_people(mut s: Scenario, pct: usize, rng: &mut XorShiftRng) -> Scenario
     }
     s
 }
+
+fn scale_people(mut s: Scenario, pct: usize, rng: &mut XorShiftRng) -> Scenario {
+    if pct <= 100 {
+        return cancel_people(s, 100 - pct, rng);
+    }
+    let extra = (pct as f64) / 100.0 - 1.0;
+    let mut copies = Vec::new();
+    for person in &s.people {
+        let mut num = extra.floor() as usize;
+        if rng.gen_bool(extra.fract()) {
+            num += 1;
+        }
+        for _ in 0..num {
+            let mut copy = person.clone();
+            for trip in &mut copy.trips {
+                trip.modified = true;
+            }
+            copies.push(copy);
+        }
+    }
+    for mut p in copies {
+        p.id = PersonID(s.people.len());
+        s.people.push(p);
+    }
+    s
+}
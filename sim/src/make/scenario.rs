@@ -4,10 +4,10 @@ use crate::{
     MAX_CAR_LENGTH, MIN_CAR_LENGTH, SPAWN_DIST,
 };
 use abstutil::{prettyprint_usize, Counter, Timer};
-use geom::{Distance, Duration, LonLat, Speed, Time};
+use geom::{Distance, Duration, LonLat, Pt2D, Speed, Time};
 use map_model::{
-    BuildingID, BusRouteID, BusStopID, DirectedRoadID, Map, OffstreetParking, PathConstraints,
-    Position, RoadID,
+    BuildingID, BusRouteID, BusStopID, DirectedRoadID, Map, OffstreetParking, ParkingLotID,
+    PathConstraints, Position, RoadID,
 };
 use rand::seq::SliceRandom;
 use rand::{Rng, SeedableRng};
@@ -79,6 +79,45 @@ pub enum SpawnTrip {
         BusStopID,
         Option<BusStopID>,
     ),
+    UsingRideHail(BuildingID, BuildingID),
+    // Using a shared bike or scooter instead of one parked at home
+    UsingBikeShare(BuildingID, BuildingID),
+    // Drive to a park-and-ride lot, then finish the trip like UsingTransit
+    DriveThenTransit {
+        start: BuildingID,
+        lot: ParkingLotID,
+        goal: SidewalkSpot,
+        route: BusRouteID,
+        stop1: BusStopID,
+        maybe_stop2: Option<BusStopID>,
+    },
+    // Bike to a stop, leave the bike there, then finish the trip like UsingTransit
+    BikeThenTransit {
+        start: BuildingID,
+        transfer: BusStopID,
+        goal: SidewalkSpot,
+        route: BusRouteID,
+        stop1: BusStopID,
+        maybe_stop2: Option<BusStopID>,
+    },
+    // The return trip for DriveThenTransit: ride transit back to the lot, then drive home
+    TransitThenDrive {
+        start: SidewalkSpot,
+        route: BusRouteID,
+        stop1: BusStopID,
+        stop2: BusStopID,
+        lot: ParkingLotID,
+        goal: BuildingID,
+    },
+    // The return trip for BikeThenTransit: ride transit back to the stop, then bike home
+    TransitThenBike {
+        start: SidewalkSpot,
+        route: BusRouteID,
+        stop1: BusStopID,
+        stop2: BusStopID,
+        transfer: BusStopID,
+        goal: BuildingID,
+    },
     // Completely off-map trip. Don't really simulate much of it.
     Remote {
         from: OffMapLocation,
@@ -187,6 +226,7 @@ impl Scenario {
             vehicle_type: VehicleType::Car,
             length,
             max_speed: None,
+            battery: None,
         }
     }
 
@@ -200,6 +240,7 @@ impl Scenario {
             vehicle_type: VehicleType::Bike,
             length: BIKE_LENGTH,
             max_speed,
+            battery: None,
         }
     }
 
@@ -265,7 +306,16 @@ fn seed_parked_cars(
 ) {
     let mut open_spots_per_road: BTreeMap<RoadID, Vec<(ParkingSpot, Option<BuildingID>)>> =
         BTreeMap::new();
+    // Keep chargers free, even for electric vehicles
+    let chargers: BTreeSet<ParkingSpot> = sim
+        .get_chargers()
+        .into_iter()
+        .map(|(spot, _, _)| spot)
+        .collect();
     for spot in sim.get_all_parking_spots().1 {
+        if chargers.contains(&spot) {
+            continue;
+        }
         let (r, restriction) = match spot {
             ParkingSpot::Onstreet(l, _) => (map.get_l(l).parent, None),
             ParkingSpot::Offstreet(b, _) => (
@@ -299,7 +349,14 @@ fn seed_parked_cars(
         if !ok {
             continue;
         }
-        if let Some(spot) = find_spot_near_building(b, &mut open_spots_per_road, map) {
+        // Grant the permit first, so spots in the owner's permit zone are allowed
+        sim.grant_parking_permit(vehicle.id, b);
+        if let Some(spot) = find_spot_near_building(
+            b,
+            |spot| sim.is_parking_allowed(spot, &vehicle, map),
+            &mut open_spots_per_road,
+            map,
+        ) {
             seeded += 1;
             sim.seed_parked_car(vehicle, spot);
         } else {
@@ -315,9 +372,10 @@ fn seed_parked_cars(
 
 // Pick a parking spot for this building. If the building's road has a free spot, use it. If not,
 // start BFSing out from the road in a deterministic way until finding a nearby road with an open
-// spot.
-fn find_spot_near_building(
+// spot that the car is allowed to use.
+fn find_spot_near_building<F: Fn(ParkingSpot) -> bool>(
     b: BuildingID,
+    is_allowed: F,
     open_spots_per_road: &mut BTreeMap<RoadID, Vec<(ParkingSpot, Option<BuildingID>)>>,
     map: &Map,
 ) -> Option<ParkingSpot> {
@@ -336,13 +394,13 @@ fn find_spot_near_building(
             // TODO With some probability, skip this available spot and park farther away
             if let Some(idx) = spots
                 .iter()
-                .position(|(_, restriction)| restriction == &Some(b))
+                .position(|(spot, restriction)| restriction == &Some(b) && is_allowed(*spot))
             {
                 return Some(spots.remove(idx).0);
             }
             if let Some(idx) = spots
                 .iter()
-                .position(|(_, restriction)| restriction.is_none())
+                .position(|(spot, restriction)| restriction.is_none() && is_allowed(*spot))
             {
                 return Some(spots.remove(idx).0);
             }
@@ -421,6 +479,97 @@ impl SpawnTrip {
                     maybe_stop2,
                 }
             }
+            SpawnTrip::UsingRideHail(start, goal) => TripSpec::UsingRideHail { start, goal },
+            SpawnTrip::UsingBikeShare(start, goal) => TripSpec::UsingBikeShare { start, goal },
+            SpawnTrip::DriveThenTransit {
+                start,
+                lot,
+                goal,
+                route,
+                stop1,
+                maybe_stop2,
+            } => TripSpec::DriveThenTransit {
+                start_bldg: start,
+                car: use_vehicle.unwrap(),
+                lot,
+                goal,
+                route,
+                stop1,
+                maybe_stop2,
+            },
+            SpawnTrip::BikeThenTransit {
+                start,
+                transfer,
+                goal,
+                route,
+                stop1,
+                maybe_stop2,
+            } => TripSpec::BikeThenTransit {
+                start,
+                bike: use_vehicle.unwrap(),
+                transfer,
+                goal,
+                route,
+                stop1,
+                maybe_stop2,
+            },
+            SpawnTrip::TransitThenDrive {
+                start,
+                route,
+                stop1,
+                stop2,
+                goal,
+                ..
+            }
+            | SpawnTrip::TransitThenBike {
+                start,
+                route,
+                stop1,
+                stop2,
+                goal,
+                ..
+            } if use_vehicle.is_none() => {
+                // The vehicle wasn't left at the transfer point (maybe the first trip was
+                // cancelled), so just walk the rest of the way.
+                TripSpec::UsingTransit {
+                    start,
+                    goal: SidewalkSpot::building(goal, map),
+                    route,
+                    stop1,
+                    maybe_stop2: Some(stop2),
+                }
+            }
+            SpawnTrip::TransitThenDrive {
+                start,
+                route,
+                stop1,
+                stop2,
+                goal,
+                ..
+            } => TripSpec::TransitThenDrive {
+                start,
+                route,
+                stop1,
+                stop2,
+                car: use_vehicle.unwrap(),
+                goal,
+            },
+            SpawnTrip::TransitThenBike {
+                start,
+                route,
+                stop1,
+                stop2,
+                transfer,
+                goal,
+            } => TripSpec::TransitThenBike {
+                start,
+                route,
+                stop1,
+                stop2,
+                bike: use_vehicle.unwrap(),
+                transfer,
+                goal,
+            },
             SpawnTrip::Remote {
                 from,
                 to,
@@ -456,6 +605,12 @@ impl SpawnTrip {
             SpawnTrip::UsingBike(_, _) => TripMode::Bike,
             SpawnTrip::JustWalking(_, _) => TripMode::Walk,
             SpawnTrip::UsingTransit(_, _, _, _, _) => TripMode::Transit,
+            SpawnTrip::UsingRideHail(_, _) => TripMode::RideHail,
+            SpawnTrip::UsingBikeShare(_, _) => TripMode::Bike,
+            SpawnTrip::DriveThenTransit { .. }
+            | SpawnTrip::BikeThenTransit { .. }
+            | SpawnTrip::TransitThenDrive { .. }
+            | SpawnTrip::TransitThenBike { .. } => TripMode::Transit,
             // TODO Uh...
             SpawnTrip::Remote { .. } => TripMode::Drive,
         }
@@ -471,16 +626,25 @@ impl SpawnTrip {
             }
             SpawnTrip::UsingParkedCar(b, _) => TripEndpoint::Bldg(*b),
             SpawnTrip::UsingBike(b, _) => TripEndpoint::Bldg(*b),
-            SpawnTrip::JustWalking(ref spot, _) | SpawnTrip::UsingTransit(ref spot, _, _, _, _) => {
-                match spot.connection {
-                    SidewalkPOI::Building(b) => TripEndpoint::Bldg(b),
-                    SidewalkPOI::Border(i, ref loc) => TripEndpoint::Border(i, loc.clone()),
-                    SidewalkPOI::SuddenlyAppear => {
-                        TripEndpoint::Border(map.get_l(spot.sidewalk_pos.lane()).src_i, None)
-                    }
-                    _ => unreachable!(),
-                }
+            SpawnTrip::UsingRideHail(b, _) => TripEndpoint::Bldg(*b),
+            SpawnTrip::UsingBikeShare(b, _) => TripEndpoint::Bldg(*b),
+            SpawnTrip::DriveThenTransit { start, .. }
+            | SpawnTrip::BikeThenTransit { start, .. } => TripEndpoint::Bldg(*start),
+            SpawnTrip::JustWalking(ref spot, _)
+            | SpawnTrip::UsingTransit(ref spot, _, _, _, _)
+            | SpawnTrip::TransitThenDrive {
+                start: ref spot, ..
             }
+            | SpawnTrip::TransitThenBike {
+                start: ref spot, ..
+            } => match spot.connection {
+                SidewalkPOI::Building(b) => TripEndpoint::Bldg(b),
+                SidewalkPOI::Border(i, ref loc) => TripEndpoint::Border(i, loc.clone()),
+                SidewalkPOI::SuddenlyAppear => {
+                    TripEndpoint::Border(map.get_l(spot.sidewalk_pos.lane()).src_i, None)
+                }
+                _ => unreachable!(),
+            },
             // Pick an arbitrary border
             SpawnTrip::Remote { ref from, .. } => {
                 TripEndpoint::Border(map.all_outgoing_borders()[0].id, Some(from.clone()))
@@ -496,13 +660,21 @@ impl SpawnTrip {
             | SpawnTrip::UsingBike(_, ref goal) => match goal {
                 DrivingGoal::ParkNear(b) => TripEndpoint::Bldg(*b),
                 DrivingGoal::Border(i, _, ref loc) => TripEndpoint::Border(*i, loc.clone()),
+                // Only used for the first leg of DriveThenTransit and BikeThenTransit
+                DrivingGoal::ParkAndRide(_) | DrivingGoal::BikeAndRide(_) => unreachable!(),
             },
-            SpawnTrip::JustWalking(_, ref spot) | SpawnTrip::UsingTransit(_, ref spot, _, _, _) => {
-                match spot.connection {
-                    SidewalkPOI::Building(b) => TripEndpoint::Bldg(b),
-                    SidewalkPOI::Border(i, ref loc) => TripEndpoint::Border(i, loc.clone()),
-                    _ => unreachable!(),
-                }
+            SpawnTrip::JustWalking(_, ref spot)
+            | SpawnTrip::UsingTransit(_, ref spot, _, _, _)
+            | SpawnTrip::DriveThenTransit { goal: ref spot, .. }
+            | SpawnTrip::BikeThenTransit { goal: ref spot, .. } => match spot.connection {
+                SidewalkPOI::Building(b) => TripEndpoint::Bldg(b),
+                SidewalkPOI::Border(i, ref loc) => TripEndpoint::Border(i, loc.clone()),
+                _ => unreachable!(),
+            },
+            SpawnTrip::UsingRideHail(_, b) => TripEndpoint::Bldg(*b),
+            SpawnTrip::UsingBikeShare(_, b) => TripEndpoint::Bldg(*b),
+            SpawnTrip::TransitThenDrive { goal, .. } | SpawnTrip::TransitThenBike { goal, .. } => {
+                TripEndpoint::Bldg(*goal)
             }
             // Pick an arbitrary border
             SpawnTrip::Remote { ref to, .. } => {
@@ -556,10 +728,139 @@ impl SpawnTrip {
                     SpawnTrip::JustWalking(start, goal)
                 }
             }
+            // Ride-hail vehicles only pick up and drop off at buildings
+            TripMode::RideHail => match (from, to) {
+                (TripEndpoint::Bldg(b1), TripEndpoint::Bldg(b2)) => {
+                    SpawnTrip::UsingRideHail(b1, b2)
+                }
+                _ => {
+                    return None;
+                }
+            },
+        })
+    }
+
+    // Drive to the nearest park-and-ride lot that's on the way and has a useful transit
+    // connection to the goal.
+    pub fn drive_then_transit(
+        start: BuildingID,
+        goal: SidewalkSpot,
+        map: &Map,
+    ) -> Option<SpawnTrip> {
+        let lots = transfers_on_the_way(
+            map.get_b(start).label_center,
+            goal.sidewalk_pos.pt(map),
+            map.all_parking_lots()
+                .iter()
+                .map(|pl| (pl.sidewalk_pos.pt(map), pl.id))
+                .collect(),
+        );
+        for lot in lots {
+            if let Some((stop1, maybe_stop2, route)) =
+                map.should_use_transit(map.get_pl(lot).sidewalk_pos, goal.sidewalk_pos)
+            {
+                return Some(SpawnTrip::DriveThenTransit {
+                    start,
+                    lot,
+                    goal,
+                    route,
+                    stop1,
+                    maybe_stop2,
+                });
+            }
+        }
+        None
+    }
+
+    // Same idea as drive_then_transit, but leave the bike at a stop instead.
+    pub fn bike_then_transit(
+        start: BuildingID,
+        goal: SidewalkSpot,
+        map: &Map,
+    ) -> Option<SpawnTrip> {
+        let stops = transfers_on_the_way(
+            map.get_b(start).label_center,
+            goal.sidewalk_pos.pt(map),
+            map.all_bus_stops()
+                .values()
+                .filter(|bs| bs.biking_connection(map).is_some())
+                .map(|bs| (bs.sidewalk_pos.pt(map), bs.id))
+                .collect(),
+        );
+        for transfer in stops {
+            if let Some((stop1, maybe_stop2, route)) =
+                map.should_use_transit(map.get_bs(transfer).sidewalk_pos, goal.sidewalk_pos)
+            {
+                return Some(SpawnTrip::BikeThenTransit {
+                    start,
+                    transfer,
+                    goal,
+                    route,
+                    stop1,
+                    maybe_stop2,
+                });
+            }
+        }
+        None
+    }
+
+    // The return trip for drive_then_transit, picking up the car left at the lot.
+    pub fn transit_then_drive(
+        start: SidewalkSpot,
+        lot: ParkingLotID,
+        goal: BuildingID,
+        map: &Map,
+    ) -> Option<SpawnTrip> {
+        let (stop1, stop2, route) =
+            map.should_use_transit(start.sidewalk_pos, map.get_pl(lot).sidewalk_pos)?;
+        Some(SpawnTrip::TransitThenDrive {
+            start,
+            route,
+            stop1,
+            stop2: stop2?,
+            lot,
+            goal,
+        })
+    }
+
+    // The return trip for bike_then_transit, picking up the bike left at the stop.
+    pub fn transit_then_bike(
+        start: SidewalkSpot,
+        transfer: BusStopID,
+        goal: BuildingID,
+        map: &Map,
+    ) -> Option<SpawnTrip> {
+        let (stop1, stop2, route) =
+            map.should_use_transit(start.sidewalk_pos, map.get_bs(transfer).sidewalk_pos)?;
+        Some(SpawnTrip::TransitThenBike {
+            start,
+            route,
+            stop1,
+            stop2: stop2?,
+            transfer,
+            goal,
         })
     }
 }
 
+// Checking for a transit connection is expensive, so only look at the closest few lots or stops.
+const MAX_TRANSFERS_TO_TRY: usize = 5;
+
+// The transfer points closer to the goal than the start is, nearest to the start first
+fn transfers_on_the_way<T>(start: Pt2D, goal: Pt2D, candidates: Vec<(Pt2D, T)>) -> Vec<T> {
+    let mut candidates: Vec<(Distance, T)> = candidates
+        .into_iter()
+        .filter(|(pt, _)| pt.dist_to(goal) < start.dist_to(goal))
+        .map(|(pt, x)| (pt.dist_to(start), x))
+        .collect();
+    candidates.sort_by_key(|(dist, _)| *dist);
+    candidates
+        .into_iter()
+        .take(MAX_TRANSFERS_TO_TRY)
+        .map(|(_, x)| x)
+        .collect()
+}
+
 impl PersonSpec {
     // Verify that the trip start/endpoints of the person match up
     fn check_schedule(&self, map: &Map) -> Result<(), String> {
@@ -618,6 +919,8 @@ impl PersonSpec {
         let mut bike_idx = None;
         // For each indexed car, is it parked somewhere, or off-map?
         let mut car_locations: Vec<(usize, Option<BuildingID>)> = Vec::new();
+        // Cars left at a park-and-ride lot, waiting for the return trip
+        let mut cars_at_lots: Vec<(usize, ParkingLotID)> = Vec::new();
 
         // TODO If the trip is cancelled, this should be affected...
         for trip in &self.trips {
@@ -658,6 +961,8 @@ impl PersonSpec {
                             DrivingGoal::Border(_, _, _) => {
                                 car_locations.push((idx, None));
                             }
+                            // Left somewhere the person can't reuse it from
+                            DrivingGoal::ParkAndRide(_) | DrivingGoal::BikeAndRide(_) => {}
                         }
 
                         Some(idx)
@@ -688,11 +993,42 @@ impl PersonSpec {
                         DrivingGoal::Border(_, _, _) => {
                             car_locations.push((idx, None));
                         }
+                        DrivingGoal::ParkAndRide(_) | DrivingGoal::BikeAndRide(_) => {}
                     }
 
                     Some(idx)
                 }
-                SpawnTrip::UsingBike(_, _) => {
+                SpawnTrip::DriveThenTransit { start: b, lot, .. } => {
+                    let idx = if let Some(idx) = car_locations
+                        .iter()
+                        .find(|(_, parked_at)| *parked_at == Some(b))
+                        .map(|(idx, _)| *idx)
+                    {
+                        idx
+                    } else {
+                        let idx = vehicle_specs.len();
+                        vehicle_specs.push(Scenario::rand_car(rng));
+                        cars_initially_parked_at.push((idx, b));
+                        idx
+                    };
+                    car_locations.retain(|(i, _)| idx != *i);
+                    cars_at_lots.push((idx, lot));
+
+                    Some(idx)
+                }
+                SpawnTrip::TransitThenDrive { lot, goal, .. } => {
+                    // If there's no car waiting at the lot, to_trip_spec falls back to walking.
+                    if let Some(pos) = cars_at_lots.iter().position(|(_, pl)| *pl == lot) {
+                        let (idx, _) = cars_at_lots.remove(pos);
+                        car_locations.push((idx, Some(goal)));
+                        Some(idx)
+                    } else {
+                        None
+                    }
+                }
+                SpawnTrip::UsingBike(_, _)
+                | SpawnTrip::BikeThenTransit { .. }
+                | SpawnTrip::TransitThenBike { .. } => {
                     if bike_idx.is_none() {
                         bike_idx = Some(vehicle_specs.len());
                         vehicle_specs.push(Scenario::rand_bike(rng));
@@ -700,6 +1036,8 @@ impl PersonSpec {
                     bike_idx
                 }
                 SpawnTrip::JustWalking(_, _) | SpawnTrip::UsingTransit(_, _, _, _, _) => None,
+                SpawnTrip::UsingRideHail(_, _) => None,
+                SpawnTrip::UsingBikeShare(_, _) => None,
                 SpawnTrip::Remote { .. } => None,
             };
             vehicle_foreach_trip.push(use_for_trip);
@@ -723,3 +1061,88 @@ impl PersonSpec {
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use map_model::LaneID;
+
+    fn spot() -> SidewalkSpot {
+        SidewalkSpot {
+            connection: SidewalkPOI::SuddenlyAppear,
+            sidewalk_pos: Position::start(LaneID(0)),
+        }
+    }
+
+    // BusStopID can't be built outside of map_model
+    fn stop() -> BusStopID {
+        abstutil::from_json(&br#"{"sidewalk": 0, "idx": 0}"#.to_vec()).unwrap()
+    }
+
+    fn there(b: usize, lot: usize) -> SpawnTrip {
+        SpawnTrip::DriveThenTransit {
+            start: BuildingID(b),
+            lot: ParkingLotID(lot),
+            goal: spot(),
+            route: BusRouteID(0),
+            stop1: stop(),
+            maybe_stop2: None,
+        }
+    }
+
+    fn back(lot: usize, b: usize) -> SpawnTrip {
+        SpawnTrip::TransitThenDrive {
+            start: spot(),
+            route: BusRouteID(0),
+            stop1: stop(),
+            stop2: stop(),
+            lot: ParkingLotID(lot),
+            goal: BuildingID(b),
+        }
+    }
+
+    fn person(trips: Vec<SpawnTrip>) -> PersonSpec {
+        PersonSpec {
+            id: PersonID(0),
+            orig_id: None,
+            trips: trips
+                .into_iter()
+                .enumerate()
+                .map(|(idx, trip)| {
+                    IndividTrip::new(Time::START_OF_DAY + Duration::hours(idx), trip)
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_park_and_ride_picks_car_back_up() {
+        let person = person(vec![
+            there(0, 0),
+            // Nobody left a car at this lot
+            back(1, 5),
+            back(0, 5),
+            // The car was driven home to building 5 and gets reused from there
+            there(5, 1),
+            back(1, 0),
+        ]);
+        let (specs, parked_at, foreach_trip) =
+            person.get_vehicles(&mut XorShiftRng::from_seed([0; 16]));
+        assert_eq!(specs.len(), 1);
+        assert_eq!(parked_at, vec![(0, BuildingID(0))]);
+        assert_eq!(foreach_trip, vec![Some(0), None, Some(0), Some(0), Some(0)]);
+    }
+
+    #[test]
+    fn test_transfers_on_the_way() {
+        let pt = |x: f64| Pt2D::new(x, 0.0);
+        let candidates = (0..10).map(|x| (pt(x as f64), x)).collect();
+        // Start at 3, heading to 10
+        assert_eq!(
+            transfers_on_the_way(pt(3.0), pt(10.0), candidates),
+            vec![4, 5, 6, 7, 8]
+        );
+        // Nothing's closer to the goal than the start
+        assert!(transfers_on_the_way(pt(3.0), pt(3.0), vec![(pt(3.0), 0)]).is_empty());
+    }
+}
@@ -1,3 +1,4 @@
+use crate::ridehail::curb_pos;
 use crate::{
     CarID, Command, DrivingGoal, OffMapLocation, Person, PersonID, Scheduler, SidewalkSpot,
     TripEndpoint, TripLeg, TripManager, TripMode, VehicleType,
@@ -5,7 +6,8 @@ use crate::{
 use abstutil::{Parallelism, Timer};
 use geom::{Duration, Time};
 use map_model::{
-    BuildingID, BusRouteID, BusStopID, IntersectionID, Map, PathConstraints, PathRequest, Position,
+    BuildingID, BusRouteID, BusStopID, IntersectionID, Map, ParkingLotID, PathConstraints,
+    PathRequest, Position,
 };
 use serde::{Deserialize, Serialize};
 
@@ -52,6 +54,56 @@ pub enum TripSpec {
         stop1: BusStopID,
         maybe_stop2: Option<BusStopID>,
     },
+    UsingRideHail {
+        start: BuildingID,
+        goal: BuildingID,
+    },
+    // Walk to the nearest shared bike or scooter, ride it, and leave it near the goal.
+    UsingBikeShare {
+        start: BuildingID,
+        goal: BuildingID,
+    },
+    // Drive to a park-and-ride lot, leave the car there, and continue on transit.
+    DriveThenTransit {
+        // This must be a currently parked vehicle owned by the person.
+        car: CarID,
+        start_bldg: BuildingID,
+        lot: ParkingLotID,
+        goal: SidewalkSpot,
+        route: BusRouteID,
+        stop1: BusStopID,
+        maybe_stop2: Option<BusStopID>,
+    },
+    // Bike to a stop, leave the bike there, and continue on transit.
+    BikeThenTransit {
+        bike: CarID,
+        start: BuildingID,
+        transfer: BusStopID,
+        goal: SidewalkSpot,
+        route: BusRouteID,
+        stop1: BusStopID,
+        maybe_stop2: Option<BusStopID>,
+    },
+    // Ride transit back to a park-and-ride lot, then drive home in the car left there.
+    TransitThenDrive {
+        start: SidewalkSpot,
+        route: BusRouteID,
+        stop1: BusStopID,
+        stop2: BusStopID,
+        // This must be a vehicle owned by the person, left at the lot by DriveThenTransit.
+        car: CarID,
+        goal: BuildingID,
+    },
+    // Ride transit back to the stop where a bike was left, then bike home.
+    TransitThenBike {
+        start: SidewalkSpot,
+        route: BusRouteID,
+        stop1: BusStopID,
+        stop2: BusStopID,
+        bike: CarID,
+        transfer: BusStopID,
+        goal: BuildingID,
+    },
     // Completely off-map trip. Don't really simulate much of it.
     Remote {
         from: OffMapLocation,
@@ -144,6 +196,8 @@ impl TripSpawner {
                             }
                         })
                     }
+                    // Only used by BikeThenTransit
+                    DrivingGoal::ParkAndRide(_) | DrivingGoal::BikeAndRide(_) => unreachable!(),
                 };
 
                 if let Some(start_spot) = SidewalkSpot::bike_rack(*start, map) {
@@ -176,6 +230,74 @@ impl TripSpawner {
                 }
             }
             TripSpec::UsingTransit { .. } => {}
+            TripSpec::UsingRideHail { .. } => {}
+            TripSpec::UsingBikeShare { .. } => {}
+            TripSpec::DriveThenTransit { .. } => {}
+            TripSpec::BikeThenTransit {
+                start,
+                transfer,
+                goal,
+                route,
+                stop1,
+                maybe_stop2,
+                ..
+            } => {
+                let ok = match (
+                    SidewalkSpot::bike_rack(*start, map),
+                    SidewalkSpot::bike_rack_at_stop(*transfer, map),
+                ) {
+                    (Some(start_spot), Some(transfer_spot)) => {
+                        start_spot.sidewalk_pos.lane() != transfer_spot.sidewalk_pos.lane()
+                    }
+                    _ => false,
+                };
+                if !ok {
+                    println!(
+                        "Can't bike from {} to {}, just taking transit instead",
+                        start, transfer
+                    );
+                    spec = TripSpec::UsingTransit {
+                        start: SidewalkSpot::building(*start, map),
+                        goal: goal.clone(),
+                        route: *route,
+                        stop1: *stop1,
+                        maybe_stop2: *maybe_stop2,
+                    };
+                }
+            }
+            TripSpec::TransitThenDrive { .. } => {}
+            TripSpec::TransitThenBike {
+                start,
+                route,
+                stop1,
+                stop2,
+                transfer,
+                goal,
+                ..
+            } => {
+                let ok = match (
+                    SidewalkSpot::bike_rack_at_stop(*transfer, map),
+                    SidewalkSpot::bike_rack(*goal, map),
+                ) {
+                    (Some(transfer_spot), Some(goal_spot)) => {
+                        transfer_spot.sidewalk_pos.lane() != goal_spot.sidewalk_pos.lane()
+                    }
+                    _ => false,
+                };
+                if !ok {
+                    println!(
+                        "Can't bike from {} to {}, just walking from the stop instead",
+                        transfer, goal
+                    );
+                    spec = TripSpec::UsingTransit {
+                        start: start.clone(),
+                        goal: SidewalkSpot::building(*goal, map),
+                        route: *route,
+                        stop1: *stop1,
+                        maybe_stop2: Some(*stop2),
+                    };
+                }
+            }
             TripSpec::Remote { .. } => {}
         };
 
@@ -191,6 +313,7 @@ impl TripSpawner {
         timer: &mut Timer,
     ) {
         let pathfinding_upfront = trips.pathfinding_upfront;
+        let routing_params = trips.routing_params.clone();
         let profile = false;
         if profile {
             abstutil::start_profiler();
@@ -201,15 +324,13 @@ impl TripSpawner {
             std::mem::replace(&mut self.trips, Vec::new()),
             |tuple| {
                 let req = tuple.2.get_pathfinding_request(map);
-                (
-                    tuple,
-                    req.clone(),
-                    if pathfinding_upfront {
-                        req.and_then(|r| map.pathfind(r))
-                    } else {
-                        None
-                    },
-                )
+                let path = if pathfinding_upfront {
+                    req.clone()
+                        .and_then(|r| map.pathfind_at(r, tuple.1, &routing_params))
+                } else {
+                    None
+                };
+                (tuple, req, path)
             },
         );
         if profile {
@@ -279,6 +400,7 @@ impl TripSpawner {
                             legs.push(TripLeg::Walk(SidewalkSpot::building(b, map)));
                         }
                         DrivingGoal::Border(_, _, _) => {}
+                        DrivingGoal::ParkAndRide(_) | DrivingGoal::BikeAndRide(_) => unreachable!(),
                     }
                     trips.new_trip(
                         person.id,
@@ -310,6 +432,7 @@ impl TripSpawner {
                             legs.push(TripLeg::Walk(SidewalkSpot::building(b, map)));
                         }
                         DrivingGoal::Border(_, _, _) => {}
+                        DrivingGoal::ParkAndRide(_) | DrivingGoal::BikeAndRide(_) => unreachable!(),
                     };
                     trips.new_trip(
                         person.id,
@@ -351,6 +474,126 @@ impl TripSpawner {
                         map,
                     )
                 }
+                TripSpec::DriveThenTransit {
+                    car,
+                    lot,
+                    goal,
+                    route,
+                    stop1,
+                    maybe_stop2,
+                    ..
+                } => {
+                    let mut legs = vec![
+                        TripLeg::Walk(SidewalkSpot::deferred_parking_spot()),
+                        TripLeg::Drive(car, DrivingGoal::ParkAndRide(lot)),
+                        TripLeg::Walk(SidewalkSpot::bus_stop(stop1, map)),
+                        TripLeg::RideBus(route, maybe_stop2),
+                    ];
+                    if maybe_stop2.is_some() {
+                        legs.push(TripLeg::Walk(goal));
+                    }
+                    trips.new_trip(
+                        person.id,
+                        start_time,
+                        trip_start,
+                        TripMode::Transit,
+                        modified,
+                        legs,
+                        map,
+                    )
+                }
+                TripSpec::BikeThenTransit {
+                    bike,
+                    start,
+                    transfer,
+                    goal,
+                    route,
+                    stop1,
+                    maybe_stop2,
+                } => {
+                    let mut legs = vec![
+                        TripLeg::Walk(SidewalkSpot::bike_rack(start, map).unwrap()),
+                        TripLeg::Drive(bike, DrivingGoal::BikeAndRide(transfer)),
+                        TripLeg::Walk(SidewalkSpot::bus_stop(stop1, map)),
+                        TripLeg::RideBus(route, maybe_stop2),
+                    ];
+                    if maybe_stop2.is_some() {
+                        legs.push(TripLeg::Walk(goal));
+                    }
+                    trips.new_trip(
+                        person.id,
+                        start_time,
+                        trip_start,
+                        TripMode::Transit,
+                        modified,
+                        legs,
+                        map,
+                    )
+                }
+                TripSpec::TransitThenDrive {
+                    route,
+                    stop1,
+                    stop2,
+                    car,
+                    goal,
+                    ..
+                } => trips.new_trip(
+                    person.id,
+                    start_time,
+                    trip_start,
+                    TripMode::Transit,
+                    modified,
+                    vec![
+                        TripLeg::Walk(SidewalkSpot::bus_stop(stop1, map)),
+                        TripLeg::RideBus(route, Some(stop2)),
+                        TripLeg::Walk(SidewalkSpot::deferred_parking_spot()),
+                        TripLeg::Drive(car, DrivingGoal::ParkNear(goal)),
+                        TripLeg::Walk(SidewalkSpot::building(goal, map)),
+                    ],
+                    map,
+                ),
+                TripSpec::TransitThenBike {
+                    route,
+                    stop1,
+                    stop2,
+                    bike,
+                    transfer,
+                    goal,
+                    ..
+                } => trips.new_trip(
+                    person.id,
+                    start_time,
+                    trip_start,
+                    TripMode::Transit,
+                    modified,
+                    vec![
+                        TripLeg::Walk(SidewalkSpot::bus_stop(stop1, map)),
+                        TripLeg::RideBus(route, Some(stop2)),
+                        TripLeg::Walk(SidewalkSpot::bike_rack_at_stop(transfer, map).unwrap()),
+                        TripLeg::Drive(bike, DrivingGoal::ParkNear(goal)),
+                        TripLeg::Walk(SidewalkSpot::building(goal, map)),
+                    ],
+                    map,
+                ),
+                TripSpec::UsingRideHail { goal, .. } => trips.new_trip(
+                    person.id,
+                    start_time,
+                    trip_start,
+                    TripMode::RideHail,
+                    modified,
+                    vec![TripLeg::RideHail(goal)],
+                    map,
+                ),
+                // The legs to pick up and return the vehicle get filled out when the trip starts.
+                TripSpec::UsingBikeShare { goal, .. } => trips.new_trip(
+                    person.id,
+                    start_time,
+                    trip_start,
+                    TripMode::Bike,
+                    modified,
+                    vec![TripLeg::Walk(SidewalkSpot::building(goal, map))],
+                    map,
+                ),
                 TripSpec::Remote { to, mode, .. } => trips.new_trip(
                     person.id,
                     start_time,
@@ -396,22 +639,34 @@ impl TripSpec {
             }
             TripSpec::NoRoomToSpawn { .. } => None,
             // We don't know where the parked car will be
-            TripSpec::UsingParkedCar { .. } => None,
+            TripSpec::UsingParkedCar { .. } | TripSpec::DriveThenTransit { .. } => None,
             TripSpec::JustWalking { start, goal, .. } => Some(PathRequest {
                 start: start.sidewalk_pos,
                 end: goal.sidewalk_pos,
                 constraints: PathConstraints::Pedestrian,
             }),
-            TripSpec::UsingBike { start, .. } => Some(PathRequest {
-                start: map.get_b(*start).sidewalk_pos,
-                end: SidewalkSpot::bike_rack(*start, map).unwrap().sidewalk_pos,
-                constraints: PathConstraints::Pedestrian,
-            }),
-            TripSpec::UsingTransit { start, stop1, .. } => Some(PathRequest {
+            TripSpec::UsingBike { start, .. } | TripSpec::BikeThenTransit { start, .. } => {
+                Some(PathRequest {
+                    start: map.get_b(*start).sidewalk_pos,
+                    end: SidewalkSpot::bike_rack(*start, map).unwrap().sidewalk_pos,
+                    constraints: PathConstraints::Pedestrian,
+                })
+            }
+            TripSpec::UsingTransit { start, stop1, .. }
+            | TripSpec::TransitThenDrive { start, stop1, .. }
+            | TripSpec::TransitThenBike { start, stop1, .. } => Some(PathRequest {
                 start: start.sidewalk_pos,
                 end: SidewalkSpot::bus_stop(*stop1, map).sidewalk_pos,
                 constraints: PathConstraints::Pedestrian,
             }),
+            // Just to check the ride is possible
+            TripSpec::UsingRideHail { start, goal } => Some(PathRequest {
+                start: curb_pos(*start, map)?,
+                end: curb_pos(*goal, map)?,
+                constraints: PathConstraints::Car,
+            }),
+            // We don't know where the vehicle will be yet
+            TripSpec::UsingBikeShare { .. } => None,
             TripSpec::Remote { .. } => None,
         }
     }
@@ -2,8 +2,8 @@ use crate::{
     CarStatus, DistanceInterval, DrawCarInput, ParkingSpot, PersonID, Router, TimeInterval,
     TransitSimState, TripID, Vehicle, VehicleType,
 };
-use geom::{Distance, Duration, PolyLine, Time};
-use map_model::{Direction, Map, Traversable};
+use geom::{Distance, Duration, PolyLine, Speed, Time};
+use map_model::{Direction, Map, PathStep, Traversable};
 use serde::{Deserialize, Serialize};
 use std::collections::VecDeque;
 
@@ -20,7 +20,7 @@ pub struct Car {
 
     // In reverse order -- most recently left is first. The sum length of these must be >=
     // vehicle.length.
-    pub last_steps: VecDeque<Traversable>,
+    pub last_steps: VecDeque<PathStep>,
 }
 
 impl Car {
@@ -46,7 +46,15 @@ impl Car {
         let on = self.router.head();
         let mut speed = on.speed_limit(map);
         if let Some(s) = self.vehicle.max_speed {
-            speed = speed.min(s);
+            if self.vehicle.vehicle_type == VehicleType::Bike {
+                let mut grade = on.percent_grade(map);
+                if let PathStep::ContraflowLane(_) = self.router.get_path().current_step() {
+                    grade *= -1.0;
+                }
+                speed = speed.min(bike_speed_on_incline(s, grade));
+            } else {
+                speed = speed.min(s);
+            }
         }
         let dt = (dist_int.end - dist_int.start) / speed;
         CarState::Crossing(TimeInterval::new(start_time, start_time + dt), dist_int)
@@ -62,16 +70,15 @@ impl Car {
         assert!(front >= Distance::ZERO);
         // This goes from back to front
         let mut partly_on = Vec::new();
+        let head = self.router.get_path().current_step().geom(map);
+        // Reversing a lane's points can change its length slightly
+        let front = front.min(head.length());
         let raw_body = if front >= self.vehicle.length {
-            self.router
-                .head()
-                .exact_slice(front - self.vehicle.length, front, map)
+            head.exact_slice(front - self.vehicle.length, front)
         } else {
             // TODO This is redoing some of the Path::trace work...
-            let mut result = self
-                .router
-                .head()
-                .slice(Distance::ZERO, front, map)
+            let mut result = head
+                .slice(Distance::ZERO, front)
                 .map(|(pl, _)| pl.into_points())
                 .ok()
                 .unwrap_or_else(Vec::new);
@@ -83,11 +90,12 @@ impl Car {
                     // a truncated body.
                     break;
                 }
-                partly_on.push(self.last_steps[i]);
-                let len = self.last_steps[i].length(map);
+                partly_on.push(self.last_steps[i].as_traversable());
+                let geom = self.last_steps[i].geom(map);
+                let len = geom.length();
                 let start = (len - leftover).max(Distance::ZERO);
-                let piece = self.last_steps[i]
-                    .slice(start, len, map)
+                let piece = geom
+                    .slice(start, len)
                     .map(|(pl, _)| pl.into_points())
                     .ok()
                     .unwrap_or_else(Vec::new);
@@ -265,3 +273,15 @@ impl CarState {
         }
     }
 }
+
+// A bike's max speed is on flat ground. Riders slow down a lot going uphill, and speed up a little
+// going downhill, still limited by the speed limit.
+fn bike_speed_on_incline(max_speed: Speed, percent_grade: f64) -> Speed {
+    let factor = if percent_grade >= 0.0 {
+        // 5% is about 60% of normal speed
+        (1.0 - 8.0 * percent_grade).max(0.3)
+    } else {
+        (1.0 - 3.0 * percent_grade).min(1.5)
+    };
+    max_speed * factor
+}
@@ -4,12 +4,12 @@ use crate::sim::Ctx;
 use crate::{
     ActionAtEnd, AgentID, AgentProperties, CarID, Command, CreateCar, DistanceInterval,
     DrawCarInput, Event, IntersectionSimState, ParkedCar, ParkingSimState, ParkingSpot, PersonID,
-    Scheduler, TimeInterval, TransitSimState, TripManager, UnzoomedAgent, Vehicle, WalkingSimState,
-    FOLLOWING_DISTANCE,
+    RideHailSimState, Scheduler, TimeInterval, TransitSimState, TripManager, UnzoomedAgent,
+    Vehicle, WalkingSimState, FOLLOWING_DISTANCE,
 };
 use abstutil::{deserialize_btreemap, serialize_btreemap};
 use geom::{Distance, Duration, PolyLine, Time};
-use map_model::{LaneID, Map, Path, PathStep, Traversable};
+use map_model::{IntersectionID, LaneID, Map, Path, PathStep, Position, Traversable};
 use serde::{Deserialize, Serialize};
 use std::collections::{BTreeMap, HashSet, VecDeque};
 
@@ -18,6 +18,7 @@ const TIME_TO_PARK_ONSTREET: Duration = Duration::const_seconds(15.0);
 const TIME_TO_UNPARK_OFFSTREET: Duration = Duration::const_seconds(5.0);
 const TIME_TO_PARK_OFFSTREET: Duration = Duration::const_seconds(5.0);
 const TIME_TO_WAIT_AT_STOP: Duration = Duration::const_seconds(10.0);
+const TIME_TO_RECHECK_CLOSED_LANES: Duration = Duration::const_seconds(60.0);
 
 // TODO Do something else.
 pub(crate) const BLIND_RETRY_TO_CREEP_FORWARDS: Duration = Duration::const_seconds(0.1);
@@ -52,7 +53,7 @@ impl DrivingSimState {
         };
 
         for l in map.all_lanes() {
-            if l.lane_type.is_for_moving_vehicles() {
+            if l.is_ever_for_moving_vehicles() {
                 let q = Queue::new(Traversable::Lane(l.id), map);
                 sim.queues.insert(q.id, q);
             }
@@ -79,7 +80,9 @@ impl DrivingSimState {
     ) -> bool {
         let first_lane = params.router.head().as_lane();
 
-        if !intersections.nobody_headed_towards(first_lane, map.get_l(first_lane).src_i) {
+        if !intersections.nobody_headed_towards(first_lane, map.get_l(first_lane).src_i)
+            || !self.queues[&Traversable::Lane(first_lane)].is_clear_for(false)
+        {
             return false;
         }
         if let Some(idx) = self.queues[&Traversable::Lane(first_lane)].get_idx_to_insert_car(
@@ -115,6 +118,7 @@ impl DrivingSimState {
                 // Have to do this early
                 if car.router.last_step() {
                     match car.router.maybe_handle_end(
+                        now,
                         params.start_dist,
                         &car.vehicle,
                         parking,
@@ -123,6 +127,9 @@ impl DrivingSimState {
                         &mut self.events,
                     ) {
                         None | Some(ActionAtEnd::GotoLaneEnd) => {}
+                        // A ride-hail vehicle might be sent to pick up right outside its depot.
+                        // It'll notice again after crossing nothing.
+                        Some(ActionAtEnd::RideHailStop) => {}
                         x => {
                             panic!(
                                 "Car with one-step route {:?} had unexpected result from \
@@ -155,6 +162,7 @@ impl DrivingSimState {
                 // Don't use try_to_reserve_entry -- it's overly conservative.
                 // get_idx_to_insert_car does a more detailed check of the current space usage.
                 queue.reserved_length += car.vehicle.length + FOLLOWING_DISTANCE;
+                queue.contraflow = false;
             }
             self.cars.insert(car.vehicle.id, car);
             return true;
@@ -169,6 +177,7 @@ impl DrivingSimState {
         ctx: &mut Ctx,
         trips: &mut TripManager,
         transit: &mut TransitSimState,
+        ridehail: &mut RideHailSimState,
         walking: &mut WalkingSimState,
     ) {
         // State transitions for this car:
@@ -205,7 +214,8 @@ impl DrivingSimState {
             // checker, temporarily move one of them out of the BTreeMap.
             let mut car = self.cars.remove(&id).unwrap();
             // Responsibility of update_car to manage scheduling stuff!
-            need_distances = self.update_car_without_distances(&mut car, now, ctx, transit);
+            need_distances =
+                self.update_car_without_distances(&mut car, now, ctx, trips, transit, ridehail);
             self.cars.insert(id, car);
         }
 
@@ -222,9 +232,9 @@ impl DrivingSimState {
             // checker, temporarily move one of them out of the BTreeMap.
             let mut car = self.cars.remove(&id).unwrap();
             // Responsibility of update_car_with_distances to manage scheduling stuff!
-            if self
-                .update_car_with_distances(&mut car, &dists, idx, now, ctx, trips, transit, walking)
-            {
+            if self.update_car_with_distances(
+                &mut car, &dists, idx, now, ctx, trips, transit, ridehail, walking,
+            ) {
                 self.cars.insert(id, car);
             } else {
                 self.delete_car(
@@ -248,10 +258,34 @@ impl DrivingSimState {
         car: &mut Car,
         now: Time,
         ctx: &mut Ctx,
+        trips: &mut TripManager,
         transit: &mut TransitSimState,
+        ridehail: &mut RideHailSimState,
     ) -> bool {
         match car.state {
-            CarState::Crossing(_, _) => {
+            CarState::Crossing(_, ref dist_int) => {
+                // A ride-hail vehicle heading back to its depot might've been sent somewhere else.
+                // Only turn around at the end of a lane, never in the middle of an uber-turn or on
+                // a reversible lane running backwards.
+                if car.trip_and_person.is_none() && !car.vehicle.vehicle_type.is_transit() {
+                    if let PathStep::Lane(l) = car.router.get_path().current_step() {
+                        if car.router.get_path().currently_inside_ut().is_none() {
+                            if let Some(router) = ridehail.vehicle_reached_lane_end(
+                                now,
+                                car.vehicle.id,
+                                Position::new(l, dist_int.end),
+                                car.router.get_path(),
+                                trips,
+                                ctx,
+                            ) {
+                                car.router = router;
+                                self.events
+                                    .push(Event::PathAmended(car.router.get_path().clone()));
+                            }
+                        }
+                    }
+                }
+
                 car.state = CarState::Queued { blocked_since: now };
                 if car.router.last_step() {
                     // Immediately run update_car_with_distances.
@@ -264,6 +298,7 @@ impl DrivingSimState {
                     if self.recalc_lanechanging {
                         car.router.opportunistically_lanechange(
                             &self.queues,
+                            now,
                             ctx.map,
                             self.handle_uber_turns,
                         );
@@ -278,6 +313,7 @@ impl DrivingSimState {
                     // (quite unlikely), the next loop will pick that up. Just trigger the
                     // side effect of choosing an end_dist.
                     car.router.maybe_handle_end(
+                        now,
                         front,
                         &car.vehicle,
                         ctx.parking,
@@ -291,7 +327,17 @@ impl DrivingSimState {
                     .push(car.state.get_end_time(), Command::UpdateCar(car.vehicle.id));
             }
             CarState::IdlingAtStop(dist, _) => {
-                car.router = transit.bus_departed_from_stop(car.vehicle.id, ctx.map);
+                car.router = if car.vehicle.vehicle_type.is_transit() {
+                    transit.bus_departed_from_stop(car.vehicle.id, ctx.map)
+                } else {
+                    ridehail.vehicle_departed(
+                        now,
+                        car.vehicle.id,
+                        Position::new(car.router.head().as_lane(), dist),
+                        trips,
+                        ctx,
+                    )
+                };
                 self.events
                     .push(Event::PathAmended(car.router.get_path().clone()));
                 car.state = car.crossing_state(dist, now, ctx.map);
@@ -339,6 +385,30 @@ impl DrivingSimState {
             CarState::Queued { .. } => unreachable!(),
             CarState::WaitingToAdvance { blocked_since } => {
                 // 'car' is the leader.
+                if let Traversable::Lane(_) = car.router.head() {
+                    let old_next = car.router.next();
+                    let rerouted = car.router.avoid_closed_lanes(
+                        now,
+                        &car.vehicle,
+                        &trips.routing_params,
+                        ctx.map,
+                    );
+                    // A previous attempt might've asked to start a turn we're no longer taking.
+                    if let Traversable::Turn(t) = old_next {
+                        if !rerouted || car.router.next() != old_next {
+                            ctx.intersections
+                                .cancel_request(AgentID::Car(car.vehicle.id), t);
+                        }
+                    }
+                    if !rerouted {
+                        // Every way onwards is closed right now. Wait for the lane to open again.
+                        ctx.scheduler.update(
+                            now + TIME_TO_RECHECK_CLOSED_LANES,
+                            Command::UpdateCar(car.vehicle.id),
+                        );
+                        return false;
+                    }
+                }
                 let from = car.router.head();
                 let goto = car.router.next();
                 assert!(from != goto);
@@ -361,6 +431,7 @@ impl DrivingSimState {
                             // Don't schedule a retry here.
                             return false;
                         }
+                        ctx.cap.car_entering_turn(now, car.vehicle.id, t, ctx.map);
                     }
                     Traversable::Lane(l) => {
                         ctx.cap.car_entering_lane(now, car.vehicle.id, l);
@@ -377,6 +448,7 @@ impl DrivingSimState {
                 // way, until laggy_head is None.
 
                 let last_step = car.router.advance(
+                    now,
                     &car.vehicle,
                     ctx.parking,
                     ctx.map,
@@ -438,6 +510,7 @@ impl DrivingSimState {
         ctx: &mut Ctx,
         trips: &mut TripManager,
         transit: &mut TransitSimState,
+        ridehail: &mut RideHailSimState,
         walking: &mut WalkingSimState,
     ) -> bool {
         let our_dist = dists[idx].1;
@@ -449,6 +522,7 @@ impl DrivingSimState {
             | CarState::WaitingToAdvance { .. } => unreachable!(),
             CarState::Queued { blocked_since } => {
                 match car.router.maybe_handle_end(
+                    now,
                     our_dist,
                     &car.vehicle,
                     ctx.parking,
@@ -514,8 +588,7 @@ impl DrivingSimState {
                             car.vehicle.id,
                             bike_rack,
                             car.total_blocked_time,
-                            ctx.map,
-                            ctx.scheduler,
+                            ctx,
                         );
                         false
                     }
@@ -534,6 +607,27 @@ impl DrivingSimState {
                             false
                         }
                     }
+                    Some(ActionAtEnd::RideHailStop) => {
+                        car.total_blocked_time += now - blocked_since;
+                        if ridehail.vehicle_arrived(
+                            now,
+                            car.vehicle.id,
+                            Position::new(car.router.head().as_lane(), our_dist),
+                            trips,
+                            ctx,
+                        ) {
+                            car.state = CarState::IdlingAtStop(
+                                our_dist,
+                                TimeInterval::new(now, now + TIME_TO_WAIT_AT_STOP),
+                            );
+                            ctx.scheduler
+                                .push(car.state.get_end_time(), Command::UpdateCar(car.vehicle.id));
+                            true
+                        } else {
+                            // Back at the depot
+                            false
+                        }
+                    }
                     None => {
                         ctx.scheduler.push(
                             now + BLIND_RETRY_TO_REACH_END_DIST,
@@ -564,10 +658,13 @@ impl DrivingSimState {
                 }
             }
             CarState::Parking(_, spot, _) => {
-                ctx.parking.add_parked_car(ParkedCar {
-                    vehicle: car.vehicle.clone(),
-                    spot,
-                });
+                ctx.parking.add_parked_car(
+                    ParkedCar {
+                        vehicle: car.vehicle.clone(),
+                        spot,
+                    },
+                    now,
+                );
                 trips.car_reached_parking_spot(
                     now,
                     car.vehicle.id,
@@ -626,11 +723,13 @@ impl DrivingSimState {
             assert_eq!(queue.cars.remove(idx).unwrap(), car.vehicle.id);
             // trim_last_steps doesn't actually include the current queue!
             queue.free_reserved_space(car);
-            let i = match queue.id {
-                Traversable::Lane(l) => map.get_l(l).src_i,
-                Traversable::Turn(t) => t.parent,
+            let entrances = match queue.id {
+                Traversable::Lane(l) => lane_entrances(l, map),
+                Traversable::Turn(t) => vec![t.parent],
             };
-            intersections.space_freed(now, i, scheduler, map);
+            for i in entrances {
+                intersections.space_freed(now, i, scheduler, map);
+            }
         }
 
         intersections.vehicle_gone(car.vehicle.id);
@@ -716,7 +815,7 @@ impl DrivingSimState {
                 num_to_trim = Some(self.cars[&id].last_steps.len() - idx);
                 break;
             }
-            dist_left_to_cleanup -= step.length(map);
+            dist_left_to_cleanup -= step.as_traversable().length(map);
         }
 
         if let Some(n) = num_to_trim {
@@ -770,10 +869,10 @@ impl DrivingSimState {
     ) {
         for i in 0..n {
             let on = car.last_steps.pop_back().unwrap();
-            let old_queue = self.queues.get_mut(&on).unwrap();
+            let old_queue = self.queues.get_mut(&on.as_traversable()).unwrap();
             assert_eq!(old_queue.laggy_head, Some(car.vehicle.id));
             old_queue.laggy_head = None;
-            match on {
+            match on.as_traversable() {
                 Traversable::Turn(t) => {
                     intersections.turn_finished(
                         now,
@@ -785,7 +884,9 @@ impl DrivingSimState {
                 }
                 Traversable::Lane(l) => {
                     old_queue.free_reserved_space(car);
-                    intersections.space_freed(now, map.get_l(l).src_i, scheduler, map);
+                    for i in lane_entrances(l, map) {
+                        intersections.space_freed(now, i, scheduler, map);
+                    }
                 }
             }
 
@@ -806,6 +907,7 @@ impl DrivingSimState {
                                 if self.recalc_lanechanging {
                                     follower.router.opportunistically_lanechange(
                                         &self.queues,
+                                        now,
                                         map,
                                         self.handle_uber_turns,
                                     );
@@ -839,8 +941,11 @@ impl DrivingSimState {
                 continue;
             }
 
-            for (c, dist) in queue.get_car_positions(now, &self.cars, &self.queues) {
+            for (c, mut dist) in queue.get_car_positions(now, &self.cars, &self.queues) {
                 let car = &self.cars[&c];
+                if queue.contraflow {
+                    dist = queue.geom_len - dist;
+                }
                 result.push(UnzoomedAgent {
                     vehicle_type: Some(car.vehicle.vehicle_type),
                     pos: match queue.id.dist_along(dist, map) {
@@ -974,7 +1079,13 @@ impl DrivingSimState {
             .find(|(c, _)| *c == id)
             .unwrap()
             .1;
-        car.router.get_path().trace(map, front, dist_ahead)
+        // Path::trace measures along the lane's real direction.
+        let start = if let PathStep::ContraflowLane(l) = car.router.get_path().current_step() {
+            map.get_l(l).length() - front
+        } else {
+            front
+        };
+        car.router.get_path().trace(map, start, dist_ahead)
     }
 
     pub fn percent_along_route(&self, id: CarID) -> f64 {
@@ -1056,3 +1167,14 @@ impl DrivingSimState {
         self.queues[&Traversable::Lane(l)].target_lane_penalty()
     }
 }
+
+// Where vehicles wait to enter a lane. Freeing space on a reversible lane might let traffic in from
+// either end.
+fn lane_entrances(l: LaneID, map: &Map) -> Vec<IntersectionID> {
+    let lane = map.get_l(l);
+    if lane.is_ever_reversed() {
+        vec![lane.src_i, lane.dst_i]
+    } else {
+        vec![lane.src_i]
+    }
+}
@@ -1,4 +1,4 @@
-use crate::mechanics::car::Car;
+use crate::mechanics::car::{Car, CarState};
 use crate::mechanics::Queue;
 use crate::{AgentID, AlertLocation, CarID, Command, Event, Scheduler, Speed};
 use abstutil::{deserialize_btreemap, retain_btreeset, serialize_btreemap};
@@ -10,7 +10,6 @@ use map_model::{
 use serde::{Deserialize, Serialize};
 use std::collections::{BTreeMap, BTreeSet, HashSet};
 
-const WAIT_AT_STOP_SIGN: Duration = Duration::const_seconds(0.5);
 const WAIT_BEFORE_YIELD_AT_TRAFFIC_SIGNAL: Duration = Duration::const_seconds(0.2);
 
 #[derive(Serialize, Deserialize, PartialEq, Clone)]
@@ -20,6 +19,7 @@ pub struct IntersectionSimState {
     dont_block_the_box: bool,
     break_turn_conflict_cycles: bool,
     handle_uber_turns: bool,
+    stop_sign_wait: Duration,
     // (x, y) means x is blocked by y. It's a many-to-many relationship. TODO Better data
     // structure.
     blocked_by: BTreeSet<(CarID, CarID)>,
@@ -60,6 +60,7 @@ impl IntersectionSimState {
         dont_block_the_box: bool,
         break_turn_conflict_cycles: bool,
         handle_uber_turns: bool,
+        stop_sign_wait: Duration,
     ) -> IntersectionSimState {
         let mut sim = IntersectionSimState {
             state: BTreeMap::new(),
@@ -67,6 +68,7 @@ impl IntersectionSimState {
             dont_block_the_box,
             break_turn_conflict_cycles,
             handle_uber_turns,
+            stop_sign_wait,
             blocked_by: BTreeSet::new(),
             events: Vec::new(),
         };
@@ -338,7 +340,7 @@ impl IntersectionSimState {
         } else if let Some(ref signal) = map.maybe_get_traffic_signal(turn.parent) {
             self.traffic_signal_policy(&req, map, signal, speed, now, Some(scheduler))
         } else if let Some(ref sign) = map.maybe_get_stop_sign(turn.parent) {
-            self.stop_sign_policy(&req, map, sign, now, scheduler)
+            self.stop_sign_policy(&req, map, sign, speed, now, scheduler, readonly_pair)
         } else {
             unreachable!()
         };
@@ -347,6 +349,7 @@ impl IntersectionSimState {
         }
 
         // Lock the entire uber-turn.
+        let mut lock_uber_turn = None;
         if self.handle_uber_turns {
             if let Some(ut) = maybe_cars_and_queues
                 .as_ref()
@@ -359,14 +362,7 @@ impl IntersectionSimState {
                         return false;
                     }
                 }
-                // If the way is clear, make sure it stays that way.
-                for t in &ut.path {
-                    self.state
-                        .get_mut(&t.parent)
-                        .unwrap()
-                        .reserved
-                        .insert(Request { agent, turn: *t });
-                }
+                lock_uber_turn = Some(ut.path.clone());
             }
         }
 
@@ -379,6 +375,7 @@ impl IntersectionSimState {
             let queue = queues.get_mut(&Traversable::Lane(turn.dst)).unwrap();
             if !queue.try_to_reserve_entry(
                 car,
+                map.get_t(turn).enters_contraflow(map),
                 !self.dont_block_the_box
                     || allow_block_the_box(map.get_i(turn.parent).orig_id.0)
                     || inside_ut,
@@ -389,29 +386,38 @@ impl IntersectionSimState {
                         self.blocked_by.insert((car.vehicle.id, c));
                     } else if let Some(c) = queue.cars.get(0) {
                         self.blocked_by.insert((car.vehicle.id, *c));
-                    } else {
+                    } else if let Some(r) = self.state[&turn.parent]
+                        .accepted
+                        .iter()
+                        .find(|r| r.turn.dst == turn.dst)
+                    {
                         // Nobody's in the target lane, but there's somebody already in the
                         // intersection headed there, taking up all of the space.
                         // I guess we shouldn't count reservations for uber-turns here, because
                         // we're not going to do block-the-box resolution in the interior at
                         // all?
-                        self.blocked_by.insert((
-                            car.vehicle.id,
-                            self.state[&turn.parent]
-                                .accepted
-                                .iter()
-                                .find(|r| r.turn.dst == turn.dst)
-                                .unwrap()
-                                .agent
-                                .as_car(),
-                        ));
+                        self.blocked_by.insert((car.vehicle.id, r.agent.as_car()));
                     }
+                    // Otherwise somebody's headed into a reversible lane from its other end.
                 }
 
                 return false;
             }
         }
 
+        // If the way is clear, make sure it stays that way. Only do this once the vehicle is
+        // definitely starting; a vehicle still waiting for room up ahead mustn't hold onto the
+        // rest of the uber-turn.
+        if let Some(path) = lock_uber_turn {
+            for t in path {
+                self.state
+                    .get_mut(&t.parent)
+                    .unwrap()
+                    .reserved
+                    .insert(Request { agent, turn: t });
+            }
+        }
+
         // TODO For now, we're only interested in signals, and there's too much raw data to store
         // for stop signs too.
         let state = self.state.get_mut(&turn.parent).unwrap();
@@ -561,23 +567,81 @@ impl IntersectionSimState {
         req: &Request,
         map: &Map,
         sign: &ControlStopSign,
+        speed: Speed,
         now: Time,
         scheduler: &mut Scheduler,
+        maybe_cars_and_queues: Option<(&BTreeMap<CarID, Car>, &BTreeMap<Traversable, Queue>)>,
     ) -> bool {
         let our_priority = sign.get_priority(req.turn, map);
         assert!(our_priority != TurnPriority::Banned);
         let our_time = self.state[&req.turn.parent].waiting[req];
+        let turn = map.get_t(req.turn);
 
-        if our_priority == TurnPriority::Yield && now < our_time + WAIT_AT_STOP_SIGN {
+        // Entering a roundabout means yielding to circulating traffic, not coming to a full stop.
+        if our_priority == TurnPriority::Yield
+            && now < our_time + self.stop_sign_wait
+            && !turn.is_roundabout_entry(map)
+        {
             // Since we have "ownership" of scheduling for req.agent, don't need to use
             // scheduler.update.
             scheduler.push(
-                our_time + WAIT_AT_STOP_SIGN,
+                our_time + self.stop_sign_wait,
                 Command::update_agent(req.agent),
             );
             return false;
         }
 
+        if turn.between_sidewalks() {
+            return true;
+        }
+
+        // Vehicles yield to pedestrians waiting to cross. Once the pedestrian finishes, everybody
+        // waiting here gets woken up to try again.
+        if self.state[&req.turn.parent].waiting.keys().any(|other| {
+            matches!(other.agent, AgentID::Pedestrian(_))
+                && map.get_t(other.turn).conflicts_with(turn)
+        }) {
+            return false;
+        }
+
+        // Don't pull out in front of a vehicle on a road without a stop sign, if it'll reach the
+        // intersection before we can finish the turn.
+        if our_priority == TurnPriority::Yield {
+            if let Some((cars, queues)) = maybe_cars_and_queues {
+                let finish_turn = now + turn.geom.length() / speed;
+                for l in &map.get_i(req.turn.parent).incoming_lanes {
+                    if sign
+                        .roads
+                        .get(&map.get_l(*l).parent)
+                        .map(|cfg| cfg.must_stop)
+                        .unwrap_or(true)
+                    {
+                        continue;
+                    }
+                    let car = match queues
+                        .get(&Traversable::Lane(*l))
+                        .and_then(|q| q.cars.get(0))
+                    {
+                        Some(c) => &cars[c],
+                        None => continue,
+                    };
+                    let arrival = match car.state {
+                        CarState::Crossing(ref time_int, _) => time_int.end,
+                        _ => continue,
+                    };
+                    if arrival <= now || arrival >= finish_turn || car.router.last_step() {
+                        continue;
+                    }
+                    if let Traversable::Turn(t) = car.router.next() {
+                        if map.get_t(t).conflicts_with(turn) {
+                            scheduler.push(arrival, Command::update_agent(req.agent));
+                            return false;
+                        }
+                    }
+                }
+            }
+        }
+
         // Once upon a time, we'd make sure that this request doesn't conflict with another in
         // self.waiting:
         // 1) Higher-ranking turns get to go first.
@@ -586,13 +650,10 @@ impl IntersectionSimState {
         // even conflict, then allow it. Except determining if the other agent is blocked or not is
         // tough and kind of recursive.
         //
-        // So instead, don't do any of that! The WAIT_AT_STOP_SIGN scheduling above and the fact
+        // So instead, don't do any of that! The stop_sign_wait scheduling above and the fact
         // that events are processed in time order mean that case #2 is magically handled anyway.
         // If a case #1 could've started by now, then they would have. Since they didn't, they must
-        // be blocked.
-
-        // TODO Make sure we can optimistically finish this turn before an approaching
-        // higher-priority vehicle wants to begin.
+        // be blocked. Higher-ranking vehicles still approaching are handled above.
 
         true
     }
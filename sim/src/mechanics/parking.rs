@@ -1,15 +1,25 @@
-use crate::{CarID, CarStatus, DrawCarInput, Event, ParkedCar, ParkingSpot, PersonID, Vehicle};
+use crate::{
+    AlertLocation, CarID, CarStatus, DrawCarInput, Event, ParkedCar, ParkingSpot, PersonID, Vehicle,
+};
 use abstutil::{
     deserialize_btreemap, deserialize_multimap, serialize_btreemap, serialize_multimap, MultiMap,
     Timer,
 };
-use geom::{Distance, PolyLine, Pt2D};
+use geom::{Distance, Duration, PolyLine, Pt2D, Time};
 use map_model::{
     BuildingID, Lane, LaneID, LaneType, Map, OffstreetParking, ParkingLotID, PathConstraints,
     PathStep, Position, Traversable, TurnID,
 };
 use serde::{Deserialize, Serialize};
-use std::collections::{BTreeMap, BTreeSet, BinaryHeap, HashMap};
+use std::collections::{BTreeMap, BTreeSet, BinaryHeap, HashMap, VecDeque};
+
+// How drivers trade off walking, paying, and circling around to find a spot. Paying a dollar is as
+// bad as walking this far.
+const WALK_METERS_PER_DOLLAR: f64 = 100.0;
+// Drivers don't know how long they'll stay, so assume this when weighing the price.
+const EXPECTED_PARKING_DURATION: Duration = Duration::const_seconds(2.0 * 3600.0);
+// Driving one more meter to look for parking is as bad as walking this fraction of a meter.
+const CIRCLING_COST: f64 = 0.25;
 
 #[derive(Serialize, Deserialize, PartialEq, Clone)]
 pub struct ParkingSimState {
@@ -51,6 +61,21 @@ pub struct ParkingSimState {
     )]
     driving_to_lots: MultiMap<LaneID, ParkingLotID>,
 
+    // Cars whose owners live in these buildings, for permit zones
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    permits: BTreeMap<CarID, BuildingID>,
+    // Only electric vehicles can park at these
+    chargers: BTreeSet<ParkingSpot>,
+    // When each parked car arrived, for enforcing time limits and charging
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    arrivals: BTreeMap<CarID, Time>,
+
     events: Vec<Event>,
 }
 
@@ -70,6 +95,10 @@ impl ParkingSimState {
             num_spots_per_lot: BTreeMap::new(),
             driving_to_lots: MultiMap::new(),
 
+            permits: BTreeMap::new(),
+            chargers: BTreeSet::new(),
+            arrivals: BTreeMap::new(),
+
             events: Vec::new(),
         };
         for l in map.all_lanes() {
@@ -151,7 +180,7 @@ impl ParkingSimState {
         }
     }
 
-    pub fn remove_parked_car(&mut self, p: ParkedCar) {
+    pub fn remove_parked_car(&mut self, p: ParkedCar, now: Time, map: &Map) {
         self.parked_cars
             .remove(&p.vehicle.id)
             .expect("remove_parked_car missing from parked_cars");
@@ -160,11 +189,38 @@ impl ParkingSimState {
             .expect("remove_parked_car missing from occupants");
         self.events
             .push(Event::CarLeftParkingSpot(p.vehicle.id, p.spot));
+
+        let arrived = self
+            .arrivals
+            .remove(&p.vehicle.id)
+            .expect("remove_parked_car missing from arrivals");
+        if let Some(rules) = p.spot.rules(map) {
+            if rules.is_unrestricted() {
+                return;
+            }
+            let permit = rules.has_permit(self.permits.get(&p.vehicle.id).cloned());
+            let metered = rules.metered_time(arrived, now);
+            let fee = if permit {
+                0.0
+            } else {
+                rules.price_per_hour * metered.inner_seconds() / 3600.0
+            };
+            let overstayed =
+                !permit && rules.max_duration.map(|max| metered > max).unwrap_or(false);
+            self.events.push(Event::ParkingSessionEnded {
+                car: p.vehicle.id,
+                spot: p.spot,
+                duration: now - arrived,
+                fee,
+                compliant: !overstayed && rules.banned_time(arrived, now) == Duration::ZERO,
+            });
+        }
     }
 
-    pub fn add_parked_car(&mut self, p: ParkedCar) {
+    pub fn add_parked_car(&mut self, p: ParkedCar, now: Time) {
         self.events
             .push(Event::CarReachedParkingSpot(p.vehicle.id, p.spot));
+        self.arrivals.insert(p.vehicle.id, now);
 
         assert!(self.reserved_spots.remove(&p.spot));
 
@@ -175,6 +231,121 @@ impl ParkingSimState {
         self.parked_cars.insert(p.vehicle.id, p);
     }
 
+    // Lets the car park in the building's permit zones
+    pub fn grant_permit(&mut self, car: CarID, home: BuildingID) {
+        self.permits.insert(car, home);
+    }
+
+    pub fn add_charger(&mut self, spot: ParkingSpot) {
+        self.chargers.insert(spot);
+    }
+
+    pub fn is_charger(&self, spot: ParkingSpot) -> bool {
+        self.chargers.contains(&spot)
+    }
+
+    // Can this vehicle start parking in the spot right now? Ignores whether it's free.
+    pub fn is_allowed(&self, spot: ParkingSpot, vehicle: &Vehicle, now: Time, map: &Map) -> bool {
+        if self.chargers.contains(&spot) && vehicle.battery.is_none() {
+            return false;
+        }
+        // Some parking lanes open to traffic during part of the day
+        if let ParkingSpot::Onstreet(l, _) = spot {
+            if map.get_l(l).lane_type_at(now) != LaneType::Parking {
+                return false;
+            }
+        }
+        spot.rules(map)
+            .map(|rules| rules.allows(now, self.permits.get(&vehicle.id).cloned()))
+            .unwrap_or(true)
+    }
+
+    // The parking lane is opening to traffic, so tow everything parked there to the nearest spot
+    // each car is allowed to use. Cars in `exempt` are about to be driven away by their owners.
+    pub fn tow_cars(&mut self, now: Time, l: LaneID, exempt: &BTreeSet<CarID>, map: &Map) {
+        let (spots, driving_lane) = if let Some(lane) = self.onstreet_lanes.get(&l) {
+            (lane.spots(), lane.driving_lane)
+        } else {
+            return;
+        };
+        // The schedule might've changed since this was scheduled
+        if map.get_l(l).lane_type_at(now) == LaneType::Parking {
+            return;
+        }
+        for spot in spots {
+            let car = match self.occupants.get(&spot) {
+                Some(car) if !exempt.contains(car) => *car,
+                _ => continue,
+            };
+            let parked_car = self.parked_cars[&car].clone();
+            self.remove_parked_car(parked_car.clone(), now, map);
+            let location = parked_car
+                .vehicle
+                .owner
+                .map(AlertLocation::Person)
+                .unwrap_or(AlertLocation::Nil);
+            if let Some(new_spot) =
+                self.nearest_allowed_spot(driving_lane, &parked_car.vehicle, now, map)
+            {
+                self.events.push(Event::Alert(
+                    location,
+                    format!("{} towed from {} to {:?}", car, l, new_spot),
+                ));
+                self.reserve_spot(new_spot);
+                self.add_parked_car(
+                    ParkedCar {
+                        vehicle: parked_car.vehicle,
+                        spot: new_spot,
+                    },
+                    now,
+                );
+            } else {
+                self.events.push(Event::Alert(
+                    location,
+                    format!("{} towed from {}, but there's nowhere to put it", car, l),
+                ));
+            }
+        }
+    }
+
+    // Searching outwards from a driving lane, the closest free spot on-street or in a lot that
+    // the vehicle can park in right now
+    fn nearest_allowed_spot(
+        &self,
+        start: LaneID,
+        vehicle: &Vehicle,
+        now: Time,
+        map: &Map,
+    ) -> Option<ParkingSpot> {
+        let mut visited = BTreeSet::new();
+        let mut queue = VecDeque::new();
+        visited.insert(start);
+        queue.push_back(start);
+        while let Some(current) = queue.pop_front() {
+            let mut spots = Vec::new();
+            for l in self.driving_to_parking_lanes.get(current) {
+                spots.extend(self.onstreet_lanes[l].spots());
+            }
+            for pl in self.driving_to_lots.get(current) {
+                for idx in 0..self.num_spots_per_lot[pl] {
+                    spots.push(ParkingSpot::Lot(*pl, idx));
+                }
+            }
+            if let Some(spot) = spots
+                .into_iter()
+                .find(|spot| self.is_free(*spot) && self.is_allowed(*spot, vehicle, now, map))
+            {
+                return Some(spot);
+            }
+            for turn in map.get_turns_for(current, PathConstraints::Car) {
+                if visited.insert(turn.id.dst) {
+                    queue.push_back(turn.id.dst);
+                }
+            }
+        }
+        None
+    }
+
     pub fn get_draw_cars(&self, id: LaneID, map: &Map) -> Vec<DrawCarInput> {
         let mut cars = Vec::new();
         if let Some(ref lane) = self.onstreet_lanes.get(&id) {
@@ -285,6 +456,7 @@ impl ParkingSimState {
         // Either the building where a seeded car starts or the target of a trip. For filtering
         // private spots.
         target: BuildingID,
+        now: Time,
         map: &Map,
     ) -> Vec<(ParkingSpot, Position)> {
         let mut candidates = Vec::new();
@@ -292,6 +464,7 @@ impl ParkingSimState {
         for l in self.driving_to_parking_lanes.get(driving_pos.lane()) {
             for spot in self.onstreet_lanes[l].spots() {
                 if self.is_free(spot)
+                    && self.is_allowed(spot, vehicle, now, map)
                     && driving_pos.dist_along()
                         < self.spot_to_driving_pos(spot, vehicle, map).dist_along()
                 {
@@ -318,10 +491,17 @@ impl ParkingSimState {
 
         for pl in self.driving_to_lots.get(driving_pos.lane()) {
             let lot_dist = map.get_pl(*pl).driving_pos.dist_along();
-            if driving_pos.dist_along() < lot_dist {
+            if driving_pos.dist_along() < lot_dist
+                && map
+                    .get_pl(*pl)
+                    .rules
+                    .allows(now, self.permits.get(&vehicle.id).cloned())
+            {
                 for idx in 0..self.num_spots_per_lot[&pl] {
                     let spot = ParkingSpot::Lot(*pl, idx);
-                    if self.is_free(spot) {
+                    if self.is_free(spot)
+                        && (vehicle.battery.is_some() || !self.chargers.contains(&spot))
+                    {
                         candidates.push(spot);
                     }
                 }
@@ -401,54 +581,57 @@ impl ParkingSimState {
         (filled, available)
     }
 
-    // Unrealistically assumes the driver has knowledge of currently free parking spots, even if
-    // they're far away. Since they don't reserve the spot in advance, somebody else can still beat
-    // them there, producing some nice, realistic churn if there's too much contention.
-    // The first PathStep is the turn after start, NOT PathStep::Lane(start).
+    // Finds the best free spot reachable from the driving position that the car is allowed to use
+    // now. Spots are scored by the cost of walking from them to the target, the price of parking
+    // there, and the distance driven to reach them. Returns the steps to get to the spot (empty if
+    // it's further along the current lane), the spot, and its driving position.
     pub fn path_to_free_parking_spot(
         &self,
-        start: LaneID,
+        start: Position,
         vehicle: &Vehicle,
         target: BuildingID,
+        now: Time,
         map: &Map,
     ) -> Option<(Vec<PathStep>, ParkingSpot, Position)> {
+        let start_lane = start.lane();
+        let mut best: Option<(Distance, LaneID, ParkingSpot, Position)> = None;
+        for (spot, pos) in self.get_all_free_spots(start, vehicle, target, now, map) {
+            let cost = self.parking_cost(spot, vehicle.id, target, now, map)
+                + (pos.dist_along() - start.dist_along()) * CIRCLING_COST;
+            if best.map(|(c, _, _, _)| cost < c).unwrap_or(true) {
+                best = Some((cost, start_lane, spot, pos));
+            }
+        }
+
         let mut backrefs: HashMap<LaneID, TurnID> = HashMap::new();
-        // Don't travel far.
         // This is a max-heap, so negate all distances. Tie breaker is lane ID, arbitrary but
         // deterministic.
         let mut queue: BinaryHeap<(Distance, LaneID)> = BinaryHeap::new();
-        queue.push((Distance::ZERO, start));
-
-        while !queue.is_empty() {
-            let (dist_so_far, current) = queue.pop().unwrap();
-            // If the current lane has a spot open, we wouldn't be asking. This can happen if a spot
-            // opens up on the 'start' lane, but behind the car.
-            if current != start {
-                // Pick the closest to the start of the lane, since that's closest to where we came
-                // from
-                if let Some((spot, pos)) = self
-                    .get_all_free_spots(Position::start(current), vehicle, target, map)
-                    .into_iter()
-                    .min_by_key(|(_, pos)| pos.dist_along())
+        queue.push((Distance::ZERO, start_lane));
+
+        while let Some((dist_so_far, current)) = queue.pop() {
+            // Anything further away costs more than the best spot so far just to drive to, so
+            // stop circling.
+            if let Some((cost, _, _, _)) = best {
+                if -dist_so_far * CIRCLING_COST >= cost {
+                    break;
+                }
+            }
+            // Spots on the start lane were already considered. Reaching spots behind the car by
+            // looping around would make a weird path.
+            if current != start_lane {
+                for (spot, pos) in
+                    self.get_all_free_spots(Position::start(current), vehicle, target, now, map)
                 {
-                    let mut steps = vec![PathStep::Lane(current)];
-                    let mut current = current;
-                    loop {
-                        if current == start {
-                            // Don't include PathStep::Lane(start)
-                            steps.pop();
-                            steps.reverse();
-                            return Some((steps, spot, pos));
-                        }
-                        let turn = backrefs[&current];
-                        steps.push(PathStep::Turn(turn));
-                        steps.push(PathStep::Lane(turn.src));
-                        current = turn.src;
+                    let cost = self.parking_cost(spot, vehicle.id, target, now, map)
+                        + (pos.dist_along() - dist_so_far) * CIRCLING_COST;
+                    if best.map(|(c, _, _, _)| cost < c).unwrap_or(true) {
+                        best = Some((cost, current, spot, pos));
                     }
                 }
             }
             for turn in map.get_turns_for(current, PathConstraints::Car) {
-                if !backrefs.contains_key(&turn.id.dst) {
+                if turn.id.dst != start_lane && !backrefs.contains_key(&turn.id.dst) {
                     let dist_this_step = turn.geom.length() + map.get_l(current).length();
                     backrefs.insert(turn.id.dst, turn.id);
                     // Remember, keep things negative
@@ -457,7 +640,44 @@ impl ParkingSimState {
             }
         }
 
-        None
+        let (_, lane, spot, pos) = best?;
+        let mut steps = Vec::new();
+        let mut current = lane;
+        while current != start_lane {
+            steps.push(PathStep::Lane(current));
+            let turn = backrefs[&current];
+            steps.push(PathStep::Turn(turn));
+            current = turn.src;
+        }
+        steps.reverse();
+        Some((steps, spot, pos))
+    }
+
+    // Lower is better. Walking from the spot to the target and the price of parking there are
+    // both expressed as an equivalent distance walked.
+    fn parking_cost(
+        &self,
+        spot: ParkingSpot,
+        car: CarID,
+        target: BuildingID,
+        now: Time,
+        map: &Map,
+    ) -> Distance {
+        let walk = self
+            .spot_to_sidewalk_pos(spot, map)
+            .pt(map)
+            .dist_to(map.get_b(target).sidewalk_pos.pt(map));
+        let price = self.spot_price(spot, car, now, map)
+            * (EXPECTED_PARKING_DURATION.inner_seconds() / 3600.0)
+            * WALK_METERS_PER_DOLLAR;
+        walk + Distance::meters(price)
+    }
+
+    // In dollars per hour, for this car starting to park now
+    pub fn spot_price(&self, spot: ParkingSpot, car: CarID, now: Time, map: &Map) -> f64 {
+        spot.rules(map)
+            .map(|rules| rules.current_price(now, self.permits.get(&car).cloned()))
+            .unwrap_or(0.0)
     }
 
     pub fn collect_events(&mut self) -> Vec<Event> {
@@ -19,6 +19,9 @@ pub struct Queue {
     // length first. This is unused for turns themselves. This value can exceed geom_len (for the
     // edge case of ONE long car on a short queue).
     pub reserved_length: Distance,
+    // Are vehicles on this lane going against its usual direction? Reversible lanes only carry
+    // traffic one way at a time.
+    pub contraflow: bool,
 }
 
 impl Queue {
@@ -29,9 +32,18 @@ impl Queue {
             laggy_head: None,
             geom_len: id.length(map),
             reserved_length: Distance::ZERO,
+            contraflow: false,
         }
     }
 
+    // Traffic can only start going the other way once the lane is totally clear.
+    pub fn is_clear_for(&self, contraflow: bool) -> bool {
+        self.contraflow == contraflow
+            || (self.cars.is_empty()
+                && self.laggy_head.is_none()
+                && self.reserved_length == Distance::ZERO)
+    }
+
     // Farthest along (greatest distance) is first.
     pub fn get_car_positions(
         &self,
@@ -96,11 +108,12 @@ impl Queue {
                             assert_eq!(head, id);
 
                             let mut dist_away_from_this_queue = head_dist;
-                            for on in &leader.last_steps {
-                                if *on == self.id {
+                            for step in &leader.last_steps {
+                                let on = step.as_traversable();
+                                if on == self.id {
                                     break;
                                 }
-                                dist_away_from_this_queue += queues[on].geom_len;
+                                dist_away_from_this_queue += queues[&on].geom_len;
                             }
                             // They might actually be out of the way, but laggy_head hasn't been
                             // updated yet.
@@ -199,7 +212,12 @@ impl Queue {
 
     // If true, there's room and the car must actually start the turn (because the space is
     // reserved).
-    pub fn try_to_reserve_entry(&mut self, car: &Car, force_entry: bool) -> bool {
+    pub fn try_to_reserve_entry(&mut self, car: &Car, contraflow: bool, force_entry: bool) -> bool {
+        // Nothing overrides this; vehicles going opposite ways can't share a lane.
+        if !self.is_clear_for(contraflow) {
+            return false;
+        }
+
         // Sometimes a car + FOLLOWING_DISTANCE might be longer than the geom_len entirely. In that
         // case, it just means the car won't totally fit on the queue at once, which is fine.
         // Reserve the normal amount of space; the next car trying to enter will get rejected.
@@ -210,6 +228,7 @@ impl Queue {
             || force_entry
         {
             self.reserved_length += dist;
+            self.contraflow = contraflow;
             return true;
         }
         false
@@ -301,3 +320,38 @@ fn dump_cars(
     }
     println!();
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use map_model::LaneID;
+
+    #[test]
+    fn test_is_clear_for() {
+        let mut queue = Queue {
+            id: Traversable::Lane(LaneID(0)),
+            cars: VecDeque::new(),
+            laggy_head: None,
+            geom_len: Distance::meters(100.0),
+            reserved_length: Distance::ZERO,
+            contraflow: false,
+        };
+        // An empty lane can flow either way
+        assert!(queue.is_clear_for(false));
+        assert!(queue.is_clear_for(true));
+
+        // Somebody's headed in against the usual direction
+        queue.reserved_length = Distance::meters(10.0);
+        queue.contraflow = true;
+        assert!(queue.is_clear_for(true));
+        assert!(!queue.is_clear_for(false));
+
+        // Their back is still on the lane
+        queue.reserved_length = Distance::ZERO;
+        queue.laggy_head = Some(CarID(0, VehicleType::Car));
+        assert!(!queue.is_clear_for(false));
+
+        queue.laggy_head = None;
+        assert!(queue.is_clear_for(false));
+    }
+}
@@ -511,7 +511,13 @@ impl Pedestrian {
             }
         };
         let dist_int = DistanceInterval::new_walking(start_dist, end_dist);
-        let time_int = TimeInterval::new(start_time, start_time + dist_int.length() / self.speed);
+        let percent_grade = match self.path.current_step() {
+            PathStep::Lane(l) => map.get_l(l).percent_grade(map),
+            PathStep::ContraflowLane(l) => -map.get_l(l).percent_grade(map),
+            PathStep::Turn(_) => 0.0,
+        };
+        let speed = ped_speed_on_incline(self.speed, percent_grade);
+        let time_int = TimeInterval::new(start_time, start_time + dist_int.length() / speed);
         PedState::Crossing(dist_int, time_int)
     }
 
@@ -759,3 +765,11 @@ fn find_crowds(
 
     (loners, crowds)
 }
+
+// The pedestrian's speed is on flat ground. Scale it using Tobler's hiking function
+// (https://en.wikipedia.org/wiki/Tobler%27s_hiking_function), which is fastest on a slight
+// downhill.
+fn ped_speed_on_incline(speed: Speed, percent_grade: f64) -> Speed {
+    let tobler = |grade: f64| (-3.5 * (grade + 0.05).abs()).exp();
+    speed * (tobler(percent_grade) / tobler(0.0))
+}
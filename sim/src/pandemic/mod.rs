@@ -1,13 +1,18 @@
 mod pandemic;
+mod params;
+mod policies;
 
 use geom::{Duration, Time};
-pub use pandemic::{Cmd, PandemicModel};
+pub use pandemic::{Cmd, PandemicCounts, PandemicModel};
+pub use params::PandemicParams;
+pub use policies::PandemicPolicies;
 use rand::Rng;
 use rand_distr::{Distribution, Exp, Normal};
 use rand_xorshift::XorShiftRng;
+use serde::{Deserialize, Serialize};
 use std::ops;
 
-#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd, Serialize, Deserialize)]
 pub struct AnyTime(f64);
 
 impl AnyTime {
@@ -68,7 +73,7 @@ impl From<f64> for AnyTime {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum StateEvent {
     Exposition,
     Incubation,
@@ -77,7 +82,7 @@ pub enum StateEvent {
     Death,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Event {
     s: StateEvent,
     p_hosp: f64,  // probability of people being hospitalized after infection
@@ -86,14 +91,16 @@ pub struct Event {
 }
 
 impl Event {
-    fn next(&self, now: AnyTime, rng: &mut XorShiftRng) -> State {
+    fn next(&self, now: AnyTime, params: &PandemicParams, rng: &mut XorShiftRng) -> State {
+        let t_inc = params.incubation.inner_seconds();
+        let t_inf = params.infectious.inner_seconds();
         match self.s {
             StateEvent::Exposition => State::Exposed((
                 Event {
                     s: StateEvent::Incubation,
                     p_hosp: self.p_hosp,
                     p_death: self.p_death,
-                    t: now + State::get_time_normal(State::T_INC, State::T_INC / 2.0, rng),
+                    t: now + State::get_time_normal(t_inc, t_inc / 2.0, rng),
                 },
                 now.into(),
             )),
@@ -104,7 +111,7 @@ impl Event {
                             s: StateEvent::Recovery,
                             p_hosp: self.p_hosp,
                             p_death: self.p_death,
-                            t: now + State::get_time_normal(State::T_INF, State::T_INF / 2.0, rng),
+                            t: now + State::get_time_normal(t_inf, t_inf / 2.0, rng),
                         },
                         now.into(),
                     ))
@@ -114,7 +121,7 @@ impl Event {
                             s: StateEvent::Hospitalization,
                             p_hosp: self.p_hosp,
                             p_death: self.p_death,
-                            t: now + State::get_time_normal(State::T_INF, State::T_INF / 2.0, rng),
+                            t: now + State::get_time_normal(t_inf, t_inf / 2.0, rng),
                         },
                         now.into(),
                     ))
@@ -127,7 +134,7 @@ impl Event {
                             s: StateEvent::Recovery,
                             p_hosp: self.p_hosp,
                             p_death: self.p_death,
-                            t: now + State::get_time_normal(State::T_INF, State::T_INF / 2.0, rng),
+                            t: now + State::get_time_normal(t_inf, t_inf / 2.0, rng),
                         },
                         now.into(),
                     ))
@@ -137,7 +144,7 @@ impl Event {
                             s: StateEvent::Death,
                             p_hosp: self.p_hosp,
                             p_death: self.p_death,
-                            t: now + State::get_time_normal(State::T_INF, State::T_INF / 2.0, rng),
+                            t: now + State::get_time_normal(t_inf, t_inf / 2.0, rng),
                         },
                         now.into(),
                     ))
@@ -149,7 +156,7 @@ impl Event {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum State {
     Sane((Event, Time)),
     Exposed((Event, Time)),
@@ -160,22 +167,6 @@ pub enum State {
 }
 
 impl State {
-    const T_INF: f64 = 360.0 * 10.0; // TODO dummy values
-    const T_INC: f64 = 3600.0; // TODO dummy values
-    const R_0: f64 = 2.5;
-    // const S_RATIO: f64 = 0.985;
-    const E_RATIO: f64 = 0.01;
-    const I_RATIO: f64 = 0.05;
-    // const R_RATIO: f64 = 0.0;
-
-    pub fn ini_infectious_ratio() -> f64 {
-        Self::I_RATIO
-    }
-
-    pub fn ini_exposed_ratio() -> f64 {
-        Self::E_RATIO
-    }
-
     fn new(p_hosp: f64, p_death: f64) -> Self {
         Self::Sane((
             Event {
@@ -219,6 +210,13 @@ impl State {
         }
     }
 
+    fn is_hospitalized(&self) -> bool {
+        match self {
+            State::Hospitalized(_) => true,
+            _ => false,
+        }
+    }
+
     fn is_recovered(&self) -> bool {
         match self {
             State::Recovered(_) => true,
@@ -272,42 +270,52 @@ impl State {
     // }
 
     // TODO: not sure if we want an option here...
-    pub fn next_default(self, default: AnyTime, rng: &mut XorShiftRng) -> Option<Self> {
+    pub fn next_default(
+        self,
+        default: AnyTime,
+        params: &PandemicParams,
+        rng: &mut XorShiftRng,
+    ) -> Option<Self> {
         // TODO: when #![feature(bindings_after_at)] reaches stable
         // rewrite this part with it
         match self {
             Self::Sane((ev, _)) => Some(Self::Sane((ev, default.into()))),
-            Self::Exposed((ev, _)) => Some(ev.next(default, rng)),
-            Self::Infectious((ev, _)) => Some(ev.next(default, rng)),
-            Self::Hospitalized((ev, _)) => Some(ev.next(default, rng)),
+            Self::Exposed((ev, _)) => Some(ev.next(default, params, rng)),
+            Self::Infectious((ev, _)) => Some(ev.next(default, params, rng)),
+            Self::Hospitalized((ev, _)) => Some(ev.next(default, params, rng)),
             Self::Recovered(_) => Some(Self::Recovered(default.into())),
             Self::Dead(_) => Some(Self::Dead(default.into())),
         }
     }
 
     // TODO: not sure if we want an option here...
-    pub fn next(self, now: AnyTime, rng: &mut XorShiftRng) -> Option<Self> {
+    pub fn next(
+        self,
+        now: AnyTime,
+        params: &PandemicParams,
+        rng: &mut XorShiftRng,
+    ) -> Option<Self> {
         // TODO: when #![feature(bindings_after_at)] reaches stable
         // rewrite this part with it
         match self {
             Self::Sane((ev, t)) => Some(Self::Sane((ev, t))),
             Self::Exposed((ev, t)) => {
                 if ev.t <= now {
-                    Some(ev.next(now, rng))
+                    Some(ev.next(now, params, rng))
                 } else {
                     Some(Self::Exposed((ev, t)))
                 }
             }
             Self::Infectious((ev, t)) => {
                 if ev.t <= now {
-                    Some(ev.next(now, rng))
+                    Some(ev.next(now, params, rng))
                 } else {
                     Some(Self::Infectious((ev, t)))
                 }
             }
             Self::Hospitalized((ev, t)) => {
                 if ev.t <= now {
-                    Some(ev.next(now, rng))
+                    Some(ev.next(now, params, rng))
                 } else {
                     Some(Self::Hospitalized((ev, t)))
                 }
@@ -322,13 +330,14 @@ impl State {
         self,
         now: AnyTime,
         overlap: Duration,
+        params: &PandemicParams,
         rng: &mut XorShiftRng,
     ) -> Result<Self, String> {
         // rewrite this part with it
         match self {
             Self::Sane((ev, t)) => {
-                if overlap >= Self::get_time_exp(State::R_0 / State::T_INF, rng) {
-                    Ok(ev.next(now, rng))
+                if overlap >= Self::get_time_exp(params.transmission_rate(), rng) {
+                    Ok(ev.next(now, params, rng))
                 } else {
                     Ok(Self::Sane((ev, t)))
                 }
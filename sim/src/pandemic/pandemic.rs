@@ -1,56 +1,120 @@
-use crate::pandemic::{AnyTime, State};
-use crate::{CarID, Event, OffMapLocation, Person, PersonID, Scheduler, TripPhaseType};
+use crate::pandemic::{AnyTime, PandemicParams, PandemicPolicies, State};
+use crate::{
+    CarID, Command, Event, OffMapLocation, Person, PersonID, Scheduler, TripManager, TripPhaseType,
+};
+use abstutil::{deserialize_btreemap, serialize_btreemap};
+use derivative::Derivative;
 use geom::{Duration, Time};
-use map_model::{BuildingID, BusStopID};
+use map_model::{BuildingID, BusStopID, Map};
 use rand::Rng;
+use rand_distr::{Distribution, Exp};
 use rand_xorshift::XorShiftRng;
 use serde::{Deserialize, Serialize};
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 
 // TODO This does not model transmission by surfaces; only person-to-person.
 // TODO If two people are in the same shared space indefinitely and neither leaves, we don't model
 // transmission. It only occurs when people leave a space.
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize, Derivative)]
+#[derivative(PartialEq)]
 pub struct PandemicModel {
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
     pop: BTreeMap<PersonID, State>,
 
     bldgs: SharedSpace<BuildingID>,
     remote_bldgs: SharedSpace<OffMapLocation>,
     bus_stops: SharedSpace<BusStopID>,
     buses: SharedSpace<CarID>,
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
     person_to_bus: BTreeMap<PersonID, CarID>,
 
+    params: PandemicParams,
+    policies: PandemicPolicies,
+    hospitalized: BTreeSet<PersonID>,
+    quarantined: BTreeSet<PersonID>,
+    // Who has each person spent time with, and when did that contact end? Only tracked for
+    // contact tracing.
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    contacts: BTreeMap<PersonID, Vec<(PersonID, Time)>>,
+    tests: usize,
+    positive_tests: usize,
+    contacts_traced: usize,
+    history: Vec<PandemicCounts>,
+
+    #[derivative(PartialEq(compare_with = "same_rng_state"))]
     rng: XorShiftRng,
     initialized: bool,
 }
 
+// XorShiftRng doesn't implement PartialEq
+fn same_rng_state(rng1: &XorShiftRng, rng2: &XorShiftRng) -> bool {
+    abstutil::to_json(rng1) == abstutil::to_json(rng2)
+}
+
+// How often to record counts for the time series
+const HISTORY_FREQUENCY: Duration = Duration::const_seconds(600.0);
+
+// A snapshot of the model at some time
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct PandemicCounts {
+    pub time: Time,
+    pub sane: usize,
+    pub exposed: usize,
+    pub infectious: usize,
+    pub hospitalized: usize,
+    pub recovered: usize,
+    pub dead: usize,
+    pub quarantined: usize,
+    // These are cumulative
+    pub tests: usize,
+    pub positive_tests: usize,
+    pub contacts_traced: usize,
+}
+
 // You can schedule callbacks in the future by doing scheduler.push(future time, one of these)
 #[derive(Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Clone, Debug)]
 pub enum Cmd {
     BecomeHospitalized(PersonID),
     BecomeQuarantined(PersonID),
+    EndQuarantine(PersonID),
+    Test(PersonID),
 }
 
-// TODO Pretend handle_event and handle_cmd also take in some object that lets you do things like:
-//
-// - replace_future_trips(PersonID, Vec<IndividTrip>)
-//
-// I'm not exactly sure how this should work yet. Any place you want to change the rest of the
-// simulation, just add a comment describing what you want to do exactly, and we'll figure it out
-// from there.
-
 impl PandemicModel {
-    pub fn new(rng: XorShiftRng) -> PandemicModel {
+    pub fn new(
+        rng: XorShiftRng,
+        params: PandemicParams,
+        policies: PandemicPolicies,
+    ) -> PandemicModel {
         PandemicModel {
             pop: BTreeMap::new(),
 
-            bldgs: SharedSpace::new(),
-            remote_bldgs: SharedSpace::new(),
+            bldgs: SharedSpace::with_capacity(policies.building_capacity),
+            remote_bldgs: SharedSpace::with_capacity(policies.building_capacity),
             bus_stops: SharedSpace::new(),
             buses: SharedSpace::new(),
             person_to_bus: BTreeMap::new(),
 
+            params,
+            policies,
+            hospitalized: BTreeSet::new(),
+            quarantined: BTreeSet::new(),
+            contacts: BTreeMap::new(),
+            tests: 0,
+            positive_tests: 0,
+            contacts_traced: 0,
+            history: Vec::new(),
+
             rng,
             initialized: false,
         }
@@ -58,7 +122,7 @@ impl PandemicModel {
 
     // Sorry, initialization order of simulations is still a bit messy. This'll be called at
     // Time::START_OF_DAY after all of the people have been created from a Scenario.
-    pub fn initialize(&mut self, population: &Vec<Person>, _scheduler: &mut Scheduler) {
+    pub fn initialize(&mut self, population: &Vec<Person>, scheduler: &mut Scheduler) {
         assert!(!self.initialized);
         self.initialized = true;
 
@@ -66,18 +130,23 @@ impl PandemicModel {
         // TODO the intial time is not well set. it should start "before"
         // the beginning of the day. Also
         for p in population {
-            let state = State::new(0.5, 0.5);
-            let state = if self.rng.gen_bool(State::ini_exposed_ratio()) {
+            let state = State::new(self.params.p_hospitalized, self.params.p_death);
+            let state = if self.rng.gen_bool(self.params.initial_exposed_ratio) {
                 let next_state = state
                     .start(
                         AnyTime::from(Time::START_OF_DAY),
                         Duration::seconds(std::f64::MAX),
+                        &self.params,
                         &mut self.rng,
                     )
                     .unwrap();
-                let next_state = if self.rng.gen_bool(State::ini_infectious_ratio()) {
+                let next_state = if self.rng.gen_bool(self.params.initial_infectious_ratio) {
                     next_state
-                        .next_default(AnyTime::from(Time::START_OF_DAY), &mut self.rng)
+                        .next_default(
+                            AnyTime::from(Time::START_OF_DAY),
+                            &self.params,
+                            &mut self.rng,
+                        )
                         .unwrap()
                 } else {
                     next_state
@@ -86,7 +155,15 @@ impl PandemicModel {
             } else {
                 state
             };
+            let infectious = state.is_infectious();
             self.pop.insert(p.id, state);
+            if infectious {
+                self.symptoms_appear(Time::START_OF_DAY, p.id, scheduler);
+            }
+
+            if let Some(dt) = self.time_until_next_test() {
+                scheduler.push(Time::START_OF_DAY + dt, Command::Pandemic(Cmd::Test(p.id)));
+            }
         }
     }
 
@@ -145,6 +222,20 @@ impl PandemicModel {
         // self.recovered.len()
     }
 
+    pub fn count_hospitalized(&self) -> usize {
+        self.pop
+            .values()
+            .filter(|state| match state {
+                State::Hospitalized(_) => true,
+                _ => false,
+            })
+            .count()
+    }
+
+    pub fn count_quarantined(&self) -> usize {
+        self.quarantined.len()
+    }
+
     pub fn count_total(&self) -> usize {
         self.count_sane()
             + self.count_exposed()
@@ -153,8 +244,68 @@ impl PandemicModel {
             + self.count_dead()
     }
 
+    // The counts over time, recorded every few minutes
+    pub fn get_history(&self) -> &Vec<PandemicCounts> {
+        &self.history
+    }
+
+    pub fn export_history(&self, path: &str) -> Result<(), std::io::Error> {
+        use std::fs::File;
+        use std::io::Write;
+
+        let mut f = File::create(path)?;
+        writeln!(
+            f,
+            "time,sane,exposed,infectious,hospitalized,recovered,dead,quarantined,tests,\
+             positive_tests,contacts_traced"
+        )?;
+        for c in &self.history {
+            writeln!(
+                f,
+                "{},{},{},{},{},{},{},{},{},{},{}",
+                c.time.inner_seconds(),
+                c.sane,
+                c.exposed,
+                c.infectious,
+                c.hospitalized,
+                c.recovered,
+                c.dead,
+                c.quarantined,
+                c.tests,
+                c.positive_tests,
+                c.contacts_traced
+            )?;
+        }
+        Ok(())
+    }
+
+    fn record_history(&mut self, now: Time) {
+        let mut next = self
+            .history
+            .last()
+            .map(|c| c.time + HISTORY_FREQUENCY)
+            .unwrap_or(Time::START_OF_DAY);
+        while next <= now {
+            self.history.push(PandemicCounts {
+                time: next,
+                sane: self.count_sane(),
+                exposed: self.count_exposed(),
+                infectious: self.count_infected(),
+                hospitalized: self.count_hospitalized(),
+                recovered: self.count_recovered(),
+                dead: self.count_dead(),
+                quarantined: self.count_quarantined(),
+                tests: self.tests,
+                positive_tests: self.positive_tests,
+                contacts_traced: self.contacts_traced,
+            });
+            next += HISTORY_FREQUENCY;
+        }
+    }
+
     pub fn handle_event(&mut self, now: Time, ev: &Event, scheduler: &mut Scheduler) {
         assert!(self.initialized);
+        self.record_history(now);
 
         match ev {
             Event::PersonEntersBuilding(person, bldg) => {
@@ -162,7 +313,7 @@ impl PandemicModel {
             }
             Event::PersonLeavesBuilding(person, bldg) => {
                 if let Some(others) = self.bldgs.person_leaves_space(now, *person, *bldg) {
-                    self.transmission(now, *person, others, scheduler);
+                    self.transmission(now, *person, others, 1.0, scheduler);
                 } else {
                     panic!("{} left {}, but they weren't inside", person, bldg);
                 }
@@ -176,7 +327,7 @@ impl PandemicModel {
                     self.remote_bldgs
                         .person_leaves_space(now, *person, loc.clone())
                 {
-                    self.transmission(now, *person, others, scheduler);
+                    self.transmission(now, *person, others, 1.0, scheduler);
                 } else {
                     panic!("{} left {:?}, but they weren't inside", person, loc);
                 }
@@ -192,7 +343,7 @@ impl PandemicModel {
                             .bus_stops
                             .person_leaves_space(now, person, *stop)
                             .unwrap();
-                        self.transmission(now, person, others, scheduler);
+                        self.transmission(now, person, others, 1.0, scheduler);
 
                         self.buses.person_enters_space(now, person, *bus);
                         self.person_to_bus.insert(person, *bus);
@@ -203,7 +354,8 @@ impl PandemicModel {
                         // of a bus ride.
                         if let Some(car) = self.person_to_bus.remove(&person) {
                             let others = self.buses.person_leaves_space(now, person, car).unwrap();
-                            self.transmission(now, person, others, scheduler);
+                            let factor = self.policies.bus_mask_factor;
+                            self.transmission(now, person, others, factor, scheduler);
                         }
                     }
                     _ => {
@@ -228,22 +380,109 @@ impl PandemicModel {
         }
     }
 
-    pub fn handle_cmd(&mut self, _now: Time, cmd: Cmd, _scheduler: &mut Scheduler) {
+    pub fn handle_cmd(
+        &mut self,
+        now: Time,
+        cmd: Cmd,
+        scheduler: &mut Scheduler,
+        trips: &mut TripManager,
+        map: &Map,
+    ) {
         assert!(self.initialized);
+        self.record_history(now);
 
-        // TODO Here we might enforce policies. Like severe -> become hospitalized
-        // Symptomatic -> stay quaratined, and/or track contacts to quarantine them too (or test
-        // them)
         match cmd {
-            Cmd::BecomeHospitalized(_person) => {
-                // self.hospitalized.insert(person);
+            Cmd::BecomeHospitalized(person) => {
+                self.hospitalized.insert(person);
+                self.quarantined.remove(&person);
+                trips.cancel_future_trips(person, scheduler);
+            }
+            Cmd::BecomeQuarantined(person) => {
+                if self.hospitalized.contains(&person)
+                    || self.quarantined.contains(&person)
+                    || self.is_dead(person)
+                {
+                    return;
+                }
+                if !self
+                    .rng
+                    .gen_bool(self.policies.quarantine_compliance.max(0.0).min(1.0))
+                {
+                    return;
+                }
+                self.quarantined.insert(person);
+                trips.replace_future_trips(now, person, scheduler, map);
+                scheduler.push(
+                    now + self.policies.quarantine_duration,
+                    Command::Pandemic(Cmd::EndQuarantine(person)),
+                );
+            }
+            Cmd::EndQuarantine(person) => {
+                // Hospitalization already ended it
+                if !self.quarantined.remove(&person) {
+                    return;
+                }
+                // A positive test puts them right back. This replaces any random test pending.
+                // TODO Their trips stay cancelled after quarantine ends.
+                scheduler.update(now, Command::Pandemic(Cmd::Test(person)));
+            }
+            Cmd::Test(person) => {
+                if self.hospitalized.contains(&person)
+                    || self.quarantined.contains(&person)
+                    || self.is_dead(person)
+                {
+                    return;
+                }
+                self.tests += 1;
+                if self.is_exposed(person) || self.is_infectious(person) {
+                    self.positive_tests += 1;
+                    scheduler.update(now, Command::Pandemic(Cmd::BecomeQuarantined(person)));
+                    self.trace_contacts(now, person, scheduler);
+                } else if let Some(dt) = self.time_until_next_test() {
+                    scheduler.push(now + dt, Command::Pandemic(Cmd::Test(person)));
+                }
             }
-            Cmd::BecomeQuarantined(_person) => {
-                // self.quarantined.insert(person);
+        }
+    }
+
+    fn time_until_next_test(&mut self) -> Option<Duration> {
+        if self.policies.tests_per_day <= 0.0 {
+            return None;
+        }
+        let days = Exp::new(self.policies.tests_per_day)
+            .unwrap()
+            .sample(&mut self.rng);
+        Some(Duration::hours(24) * days)
+    }
+
+    // Quarantine everybody the person recently spent time with
+    fn trace_contacts(&mut self, now: Time, person: PersonID, scheduler: &mut Scheduler) {
+        if !self.policies.contact_tracing {
+            return;
+        }
+        for (other, when) in self.contacts.remove(&person).unwrap_or_else(Vec::new) {
+            if now - when > self.policies.trace_window
+                || self.quarantined.contains(&other)
+                || self.hospitalized.contains(&other)
+            {
+                continue;
             }
+            self.contacts_traced += 1;
+            scheduler.update(
+                now + self.policies.tracing_delay,
+                Command::Pandemic(Cmd::BecomeQuarantined(other)),
+            );
         }
     }
 
+    // The person just became infectious
+    fn symptoms_appear(&mut self, now: Time, person: PersonID, scheduler: &mut Scheduler) {
+        if self.policies.quarantine_symptomatic {
+            scheduler.update(now, Command::Pandemic(Cmd::BecomeQuarantined(person)));
+        }
+        self.trace_contacts(now, person, scheduler);
+    }
+
     pub fn get_time(&self, person: PersonID) -> Option<Time> {
         match self.pop.get(&person) {
             Some(state) => state.get_time(),
@@ -295,34 +534,52 @@ impl PandemicModel {
         None
     }
 
+    // factor scales the overlap, to model things like masks
     fn transmission(
         &mut self,
         now: Time,
         person: PersonID,
         other_occupants: Vec<(PersonID, Duration)>,
+        factor: f64,
         scheduler: &mut Scheduler,
     ) {
         // person has spent some duration in the same space as other people. Does transmission
         // occur?
         for (other, overlap) in other_occupants {
+            if self.policies.contact_tracing && overlap >= self.policies.min_contact_duration {
+                self.contacts
+                    .entry(person)
+                    .or_insert_with(Vec::new)
+                    .push((other, now));
+                self.contacts
+                    .entry(other)
+                    .or_insert_with(Vec::new)
+                    .push((person, now));
+            }
             if let Some(pid) = self.infectious_contact(person, other) {
-                self.become_exposed(now, overlap, pid, scheduler);
+                self.become_exposed(now, overlap * factor, pid, scheduler);
             }
         }
     }
 
     // transition from a state to another without interaction with others
-    fn transition(&mut self, now: Time, person: PersonID, _scheduler: &mut Scheduler) {
+    fn transition(&mut self, now: Time, person: PersonID, scheduler: &mut Scheduler) {
         let state = self.pop.remove(&person).unwrap();
-        let state = state.next(AnyTime::from(now), &mut self.rng).unwrap();
+        let was_infectious = state.is_infectious();
+        let was_hospitalized = state.is_hospitalized();
+        let state = state
+            .next(AnyTime::from(now), &self.params, &mut self.rng)
+            .unwrap();
+        let infectious = state.is_infectious();
+        let hospitalized = state.is_hospitalized();
         self.pop.insert(person, state);
 
-        // if self.rng.gen_bool(0.1) {
-        //     scheduler.push(
-        //         now + self.rand_duration(Duration::hours(1), Duration::hours(3)),
-        //         Command::Pandemic(Cmd::BecomeHospitalized(person)),
-        //     );
-        // }
+        if infectious && !was_infectious {
+            self.symptoms_appear(now, person, scheduler);
+        }
+        if hospitalized && !was_hospitalized {
+            scheduler.update(now, Command::Pandemic(Cmd::BecomeHospitalized(person)));
+        }
     }
 
     fn become_exposed(
@@ -339,7 +596,7 @@ impl PandemicModel {
             std::f64::INFINITY
         );
         let state = state
-            .start(AnyTime::from(now), overlap, &mut self.rng)
+            .start(AnyTime::from(now), overlap, &self.params, &mut self.rng)
             .unwrap();
         self.pop.insert(person, state);
 
@@ -352,19 +609,31 @@ impl PandemicModel {
     }
 }
 
-#[derive(Clone)]
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[serde(bound(serialize = "T: Serialize", deserialize = "T: Deserialize<'de>"))]
 struct SharedSpace<T: Ord> {
     // Since when has a person been in some shared space?
     // TODO This is an awkward data structure; abstutil::MultiMap is also bad, because key removal
     // would require knowing the time. Want something closer to
     // https://guava.dev/releases/19.0/api/docs/com/google/common/collect/Table.html.
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
     occupants: BTreeMap<T, Vec<(PersonID, Time)>>,
+    // Only the first few people to enter the space mix with each other
+    capacity: Option<usize>,
 }
 
 impl<T: Ord> SharedSpace<T> {
     fn new() -> SharedSpace<T> {
+        SharedSpace::with_capacity(None)
+    }
+
+    fn with_capacity(capacity: Option<usize>) -> SharedSpace<T> {
         SharedSpace {
             occupants: BTreeMap::new(),
+            capacity,
         }
     }
 
@@ -384,23 +653,23 @@ impl<T: Ord> SharedSpace<T> {
         person: PersonID,
         space: T,
     ) -> Option<Vec<(PersonID, Duration)>> {
-        // TODO Messy to mutate state inside a retain closure
-        let mut inside_since: Option<Time> = None;
         let occupants = self.occupants.entry(space).or_insert_with(Vec::new);
-        occupants.retain(|(p, t)| {
-            if *p == person {
-                inside_since = Some(*t);
-                false
-            } else {
-                true
-            }
-        });
         // TODO Bug!
-        let inside_since = inside_since?;
+        let idx = occupants.iter().position(|(p, _)| *p == person)?;
+        let (_, inside_since) = occupants.remove(idx);
 
+        // People are stored in the order they entered
+        let admitted = match self.capacity {
+            Some(cap) if idx >= cap => {
+                return Some(Vec::new());
+            }
+            Some(cap) => cap - 1,
+            None => occupants.len(),
+        };
         Some(
             occupants
                 .iter()
+                .take(admitted)
                 .map(|(p, t)| (*p, now - (*t).max(inside_since)))
                 .collect(),
         )
@@ -410,11 +679,113 @@ impl<T: Ord> SharedSpace<T> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use geom::Speed;
+    use map_model::RoutingParams;
+    use rand::SeedableRng;
 
     fn time(x: usize) -> Time {
         Time::START_OF_DAY + Duration::hours(x)
     }
 
+    fn new_model(seed: u8) -> (PandemicModel, Scheduler, TripManager) {
+        let mut trips = TripManager::new(false, RoutingParams::new());
+        for _ in 0..100 {
+            trips.random_person(Speed::meters_per_second(1.0), Vec::new());
+        }
+        let mut params = PandemicParams::new();
+        // Make sure plenty of transmission happens
+        params.initial_exposed_ratio = 0.5;
+        params.initial_infectious_ratio = 0.5;
+        // And that the model schedules some of its own commands
+        let mut policies = PandemicPolicies::new();
+        policies.quarantine_symptomatic = true;
+        policies.tests_per_day = 1.0;
+        policies.contact_tracing = true;
+        let mut model = PandemicModel::new(XorShiftRng::from_seed([seed; 16]), params, policies);
+        let mut scheduler = Scheduler::new();
+        model.initialize(trips.get_all_people(), &mut scheduler);
+        (model, scheduler, trips)
+    }
+
+    // Every hour, everybody spends some time in one of a few buildings
+    fn simulate(
+        model: &mut PandemicModel,
+        scheduler: &mut Scheduler,
+        trips: &mut TripManager,
+        hours: std::ops::Range<usize>,
+    ) {
+        let map = Map::blank();
+        for hour in hours {
+            run_commands(model, scheduler, trips, &map, time(hour));
+            for p in 0..100 {
+                let ev = Event::PersonEntersBuilding(PersonID(p), BuildingID((p + hour) % 5));
+                model.handle_event(time(hour), &ev, scheduler);
+            }
+            let leave = time(hour) + Duration::minutes(50);
+            run_commands(model, scheduler, trips, &map, leave);
+            for p in 0..100 {
+                let ev = Event::PersonLeavesBuilding(PersonID(p), BuildingID((p + hour) % 5));
+                model.handle_event(leave, &ev, scheduler);
+            }
+        }
+    }
+
+    // Like Sim does, dispatch everything scheduled up to this time
+    fn run_commands(
+        model: &mut PandemicModel,
+        scheduler: &mut Scheduler,
+        trips: &mut TripManager,
+        map: &Map,
+        now: Time,
+    ) {
+        while let Some(t) = scheduler.peek_next_time() {
+            if t > now {
+                break;
+            }
+            match scheduler.get_next() {
+                Some(Command::Pandemic(cmd)) => model.handle_cmd(t, cmd, scheduler, trips, map),
+                Some(cmd) => panic!("Unexpected {:?}", cmd),
+                None => {}
+            }
+        }
+    }
+
+    fn run(seed: u8) -> String {
+        let (mut model, mut scheduler, mut trips) = new_model(seed);
+        simulate(&mut model, &mut scheduler, &mut trips, 0..8);
+        abstutil::to_json(&model)
+    }
+
+    #[test]
+    fn test_deterministic() {
+        assert_eq!(run(42), run(42));
+        assert_ne!(run(42), run(43));
+    }
+
+    #[test]
+    fn test_savestate() {
+        let (mut model1, mut scheduler1, mut trips1) = new_model(42);
+        simulate(&mut model1, &mut scheduler1, &mut trips1, 0..8);
+        assert!(model1.tests > 0);
+
+        // Save and restore halfway through. The result should match the uninterrupted run.
+        let (mut model2, mut scheduler2, mut trips2) = new_model(42);
+        simulate(&mut model2, &mut scheduler2, &mut trips2, 0..4);
+        let dir = std::env::temp_dir();
+        let model_path = format!("{}/pandemic_savestate_test.bin", dir.display());
+        let scheduler_path = format!("{}/pandemic_savestate_scheduler_test.bin", dir.display());
+        abstutil::write_binary(model_path.clone(), &model2);
+        abstutil::write_binary(scheduler_path.clone(), &scheduler2);
+        let mut model2: PandemicModel =
+            abstutil::read_binary(model_path, &mut abstutil::Timer::throwaway());
+        let mut scheduler2: Scheduler =
+            abstutil::read_binary(scheduler_path, &mut abstutil::Timer::throwaway());
+        simulate(&mut model2, &mut scheduler2, &mut trips2, 4..8);
+
+        assert!(model1 == model2);
+        assert!(scheduler1 == scheduler2);
+    }
+
     #[test]
     fn test_overlap() {
         let mut space = SharedSpace::new();
@@ -469,4 +840,27 @@ mod tests {
             Some(vec![(person3, Duration::hours(5))])
         );
     }
+
+    #[test]
+    fn test_capacity() {
+        let mut space = SharedSpace::with_capacity(Some(2));
+        let bldg = BuildingID(1);
+        let person1 = PersonID(1);
+        let person2 = PersonID(2);
+        let person3 = PersonID(3);
+
+        space.person_enters_space(time(0), person1, bldg);
+        space.person_enters_space(time(1), person2, bldg);
+        space.person_enters_space(time(2), person3, bldg);
+
+        // The third person was turned away
+        assert_eq!(
+            space.person_leaves_space(time(3), person3, bldg),
+            Some(Vec::new())
+        );
+        assert_eq!(
+            space.person_leaves_space(time(4), person1, bldg),
+            Some(vec![(person2, Duration::hours(3))])
+        );
+    }
 }
@@ -0,0 +1,40 @@
+use geom::Duration;
+use serde::{Deserialize, Serialize};
+
+// The epidemiological parameters of the model. The defaults are dummy values, not calibrated to
+// any real disease.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct PandemicParams {
+    // The basic reproduction number. Together with the infectious period, this sets the
+    // transmission rate.
+    pub r0: f64,
+    // The mean time between exposure and becoming infectious
+    pub incubation: Duration,
+    // The mean time spent infectious or hospitalized before the next transition
+    pub infectious: Duration,
+    // The ratio of people exposed at the start of the simulation
+    pub initial_exposed_ratio: f64,
+    // Of the people exposed initially, the ratio already infectious
+    pub initial_infectious_ratio: f64,
+    pub p_hospitalized: f64,
+    pub p_death: f64,
+}
+
+impl PandemicParams {
+    pub fn new() -> PandemicParams {
+        PandemicParams {
+            r0: 2.5,
+            incubation: Duration::hours(1),
+            infectious: Duration::hours(1),
+            initial_exposed_ratio: 0.01,
+            initial_infectious_ratio: 0.05,
+            p_hospitalized: 0.5,
+            p_death: 0.5,
+        }
+    }
+
+    // Per second of shared time
+    pub fn transmission_rate(&self) -> f64 {
+        self.r0 / self.infectious.inner_seconds()
+    }
+}
@@ -0,0 +1,49 @@
+use geom::Duration;
+use serde::{Deserialize, Serialize};
+
+// Interventions the pandemic model can enforce. The defaults don't do anything.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct PandemicPolicies {
+    // When somebody becomes infectious, they notice symptoms and quarantine.
+    pub quarantine_symptomatic: bool,
+    // The probability that somebody told to quarantine actually does
+    pub quarantine_compliance: f64,
+    // People go home and stay there this long, then get retested before leaving quarantine
+    pub quarantine_duration: Duration,
+
+    // On average, how many times per day is each person tested? 0 disables random testing.
+    pub tests_per_day: f64,
+
+    // When somebody tests positive or has symptoms, quarantine everybody they spent time with
+    // recently.
+    pub contact_tracing: bool,
+    // Only overlaps at least this long count as a contact
+    pub min_contact_duration: Duration,
+    // How far back to trace contacts
+    pub trace_window: Duration,
+    // How long after a positive case are their contacts reached?
+    pub tracing_delay: Duration,
+
+    // If a building has more people inside than this, later arrivals are assumed to be turned
+    // away at the door and don't mix with anybody inside.
+    pub building_capacity: Option<usize>,
+    // Scales the time people share on a bus, to model the effect of masks. 1.0 means no masks.
+    pub bus_mask_factor: f64,
+}
+
+impl PandemicPolicies {
+    pub fn new() -> PandemicPolicies {
+        PandemicPolicies {
+            quarantine_symptomatic: false,
+            quarantine_compliance: 1.0,
+            quarantine_duration: Duration::hours(14 * 24),
+            tests_per_day: 0.0,
+            contact_tracing: false,
+            min_contact_duration: Duration::minutes(15),
+            trace_window: Duration::hours(48),
+            tracing_delay: Duration::hours(2),
+            building_capacity: None,
+            bus_mask_factor: 1.0,
+        }
+    }
+}
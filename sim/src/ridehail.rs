@@ -0,0 +1,563 @@
+use crate::sim::Ctx;
+use crate::{
+    AlertLocation, CarID, Command, CreateCar, Event, PersonID, Router, TripID, TripManager,
+    TripPhaseType, Vehicle, VehicleSpec, VehicleType, MIN_CAR_LENGTH,
+};
+use abstutil::{deserialize_btreemap, serialize_btreemap};
+use geom::{Distance, Time};
+use map_model::{BuildingID, Map, Path, PathConstraints, PathRequest, Position, RoutingParams};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+
+// How the ride-hail fleet is set up. By default there are no vehicles, so ride-hail trips just
+// abort.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct RideHailParams {
+    // Each depot and how many vehicles start there. Idle vehicles wait off the road at their
+    // depot.
+    pub depots: Vec<(BuildingID, usize)>,
+}
+
+impl RideHailParams {
+    pub fn new() -> RideHailParams {
+        RideHailParams { depots: Vec::new() }
+    }
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct RideHailRequest {
+    pub trip: TripID,
+    pub person: PersonID,
+    pub pickup: BuildingID,
+    pub dropoff: BuildingID,
+    pub requested: Time,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Clone)]
+struct Taxi {
+    vehicle: Vehicle,
+    depot: BuildingID,
+    state: TaxiState,
+    // Where the vehicle was last seen: the depot's curb while idle, then updated at every stop and
+    // at the end of every lane while heading back to the depot
+    pos: Position,
+    // How long the current path is. Reported once the vehicle reaches the end of it.
+    path_length: Distance,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Clone)]
+enum TaxiState {
+    AtDepot,
+    DrivingToPickup(RideHailRequest),
+    PickingUp(RideHailRequest),
+    DrivingToDropoff(RideHailRequest),
+    DroppingOff,
+    DrivingToDepot,
+    // Still heading back to the depot, but it'll turn around for this request at the end of the
+    // current lane
+    DivertingToPickup(RideHailRequest),
+}
+
+// Like TransitSimState, except the stops are decided as requests come in.
+// TODO Pooling: let a vehicle pick up more requests headed the same way before dropping off.
+#[derive(Serialize, Deserialize, PartialEq, Clone)]
+pub struct RideHailSimState {
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    vehicles: BTreeMap<CarID, Taxi>,
+    // Requests that no vehicle was free to take yet, oldest first
+    waiting: VecDeque<RideHailRequest>,
+
+    events: Vec<Event>,
+}
+
+impl RideHailSimState {
+    pub fn new(params: RideHailParams, trips: &mut TripManager, map: &Map) -> RideHailSimState {
+        let mut vehicles = BTreeMap::new();
+        for (depot, count) in params.depots {
+            let pos = if let Some(pos) = curb_pos(depot, map) {
+                pos
+            } else {
+                println!(
+                    "WARNING: ride-hail depot {} isn't next to a driving lane, skipping it",
+                    depot
+                );
+                continue;
+            };
+            for _ in 0..count {
+                let id = CarID(trips.new_car_id(), VehicleType::Car);
+                let vehicle = VehicleSpec {
+                    vehicle_type: VehicleType::Car,
+                    length: MIN_CAR_LENGTH,
+                    max_speed: None,
+                    battery: None,
+                }
+                .make(id, None);
+                vehicles.insert(
+                    id,
+                    Taxi {
+                        vehicle,
+                        depot,
+                        state: TaxiState::AtDepot,
+                        pos,
+                        path_length: Distance::ZERO,
+                    },
+                );
+            }
+        }
+
+        RideHailSimState {
+            vehicles,
+            waiting: VecDeque::new(),
+            events: Vec::new(),
+        }
+    }
+
+    // Send the nearest idle vehicle, or wait for one to free up. Vehicles driving back to their
+    // depot count as idle.
+    pub fn request(
+        &mut self,
+        now: Time,
+        req: RideHailRequest,
+        trips: &mut TripManager,
+        ctx: &mut Ctx,
+    ) {
+        if self.vehicles.is_empty() {
+            self.events.push(Event::Alert(
+                AlertLocation::Person(req.person),
+                format!(
+                    "{} wants a ride-hail vehicle, but there's no fleet",
+                    req.person
+                ),
+            ));
+            trips.abort_trip(now, req.trip, None, ctx);
+            return;
+        }
+
+        let pt = ctx.map.get_b(req.pickup).label_center;
+        let map = ctx.map;
+        if let Some((id, req)) = self.dispatch(req, |taxi| taxi.pos.pt(map).dist_to(pt)) {
+            self.leave_depot(now, id, req, trips, ctx);
+        }
+    }
+
+    // Picks the idle vehicle closest to the pickup. A vehicle heading back to its depot is told
+    // to divert; one at the depot is returned, for the caller to send. If nobody's free, the
+    // request waits.
+    fn dispatch<F: Fn(&Taxi) -> Distance>(
+        &mut self,
+        req: RideHailRequest,
+        dist_to_pickup: F,
+    ) -> Option<(CarID, RideHailRequest)> {
+        let nearest = self
+            .vehicles
+            .iter()
+            .filter(|(_, taxi)| {
+                taxi.state == TaxiState::AtDepot || taxi.state == TaxiState::DrivingToDepot
+            })
+            .min_by_key(|(_, taxi)| dist_to_pickup(taxi))
+            .map(|(id, _)| *id);
+        if let Some(id) = nearest {
+            let taxi = self.vehicles.get_mut(&id).unwrap();
+            if taxi.state == TaxiState::AtDepot {
+                return Some((id, req));
+            }
+            taxi.state = TaxiState::DivertingToPickup(req);
+        } else {
+            self.waiting.push_back(req);
+        }
+        None
+    }
+
+    fn leave_depot(
+        &mut self,
+        now: Time,
+        id: CarID,
+        req: RideHailRequest,
+        trips: &mut TripManager,
+        ctx: &mut Ctx,
+    ) {
+        let taxi = self.vehicles.get_mut(&id).unwrap();
+        let start = curb_pos(taxi.depot, ctx.map).unwrap();
+        let end = curb_pos(req.pickup, ctx.map).unwrap();
+        if let Some((path_req, path)) =
+            path_between(start, end, now, &trips.routing_params, ctx.map)
+        {
+            taxi.path_length = path.total_length();
+            taxi.state = TaxiState::DrivingToPickup(req);
+            ctx.scheduler.push(
+                now,
+                Command::SpawnCar(
+                    CreateCar {
+                        start_dist: start.dist_along(),
+                        router: Router::ride_hail(id, path, path_req.end.dist_along()),
+                        vehicle: taxi.vehicle.clone(),
+                        req: path_req,
+                        maybe_parked_car: None,
+                        trip_and_person: None,
+                        maybe_route: None,
+                    },
+                    true,
+                ),
+            );
+        } else {
+            self.events.push(Event::Alert(
+                AlertLocation::Person(req.person),
+                format!("{} can't reach the pickup for {}", id, req.trip),
+            ));
+            trips.abort_trip(now, req.trip, None, ctx);
+        }
+    }
+
+    // If true, the vehicle is idling at the curb. If false, it's back at the depot and should
+    // vanish.
+    pub fn vehicle_arrived(
+        &mut self,
+        now: Time,
+        id: CarID,
+        pos: Position,
+        trips: &mut TripManager,
+        ctx: &mut Ctx,
+    ) -> bool {
+        let taxi = self.vehicles.get_mut(&id).unwrap();
+        let dist = taxi.path_length;
+        taxi.pos = pos;
+        match std::mem::replace(&mut taxi.state, TaxiState::AtDepot) {
+            TaxiState::DrivingToPickup(req) => {
+                self.events.push(Event::RideHailLegFinished {
+                    car: id,
+                    dist,
+                    occupied: false,
+                });
+                trips.person_boarded_ridehail(req.trip, id);
+                self.events
+                    .push(Event::RideHailPickup(req.person, id, now - req.requested));
+                self.events.push(Event::TripPhaseStarting(
+                    req.trip,
+                    req.person,
+                    Some(PathRequest {
+                        start: curb_pos(req.pickup, ctx.map).unwrap(),
+                        end: curb_pos(req.dropoff, ctx.map).unwrap(),
+                        constraints: PathConstraints::Car,
+                    }),
+                    TripPhaseType::RidingRideHail(id),
+                ));
+                taxi.state = TaxiState::PickingUp(req);
+                true
+            }
+            TaxiState::DrivingToDropoff(req) => {
+                self.events.push(Event::RideHailLegFinished {
+                    car: id,
+                    dist,
+                    occupied: true,
+                });
+                trips.person_left_ridehail(now, req.person, id, ctx);
+                taxi.state = TaxiState::DroppingOff;
+                true
+            }
+            TaxiState::DrivingToDepot => {
+                self.events.push(Event::RideHailLegFinished {
+                    car: id,
+                    dist,
+                    occupied: false,
+                });
+                // Somebody might've called while the vehicle was on its way back
+                if let Some(req) = self.waiting.pop_front() {
+                    self.leave_depot(now, id, req, trips, ctx);
+                }
+                false
+            }
+            TaxiState::DivertingToPickup(req) => {
+                // Reached the depot before it could turn around
+                self.events.push(Event::RideHailLegFinished {
+                    car: id,
+                    dist,
+                    occupied: false,
+                });
+                self.leave_depot(now, id, req, trips, ctx);
+                false
+            }
+            TaxiState::AtDepot | TaxiState::PickingUp(_) | TaxiState::DroppingOff => {
+                unreachable!()
+            }
+        }
+    }
+
+    pub fn vehicle_departed(
+        &mut self,
+        now: Time,
+        id: CarID,
+        pos: Position,
+        trips: &mut TripManager,
+        ctx: &mut Ctx,
+    ) -> Router {
+        let taxi = self.vehicles.get_mut(&id).unwrap();
+        taxi.pos = pos;
+        match std::mem::replace(&mut taxi.state, TaxiState::DrivingToDepot) {
+            TaxiState::PickingUp(req) => {
+                let end = curb_pos(req.dropoff, ctx.map).unwrap();
+                if let Some((path_req, path)) =
+                    path_between(pos, end, now, &trips.routing_params, ctx.map)
+                {
+                    taxi.path_length = path.total_length();
+                    taxi.state = TaxiState::DrivingToDropoff(req);
+                    return Router::ride_hail(id, path, path_req.end.dist_along());
+                }
+                // The ride was checked when the trip started, but the map might've changed since.
+                self.events.push(Event::Alert(
+                    AlertLocation::Person(req.person),
+                    format!("{} can't reach the dropoff for {} anymore", id, req.trip),
+                ));
+                trips.ridehail_trip_aborted(now, req.person, id, ctx);
+                self.next_job(now, id, pos, trips, ctx)
+            }
+            TaxiState::DroppingOff => self.next_job(now, id, pos, trips, ctx),
+            TaxiState::AtDepot
+            | TaxiState::DrivingToPickup(_)
+            | TaxiState::DrivingToDropoff(_)
+            | TaxiState::DrivingToDepot
+            | TaxiState::DivertingToPickup(_) => unreachable!(),
+        }
+    }
+
+    // Called when a ride-hail vehicle finishes crossing a lane. If it was sent to a new pickup while
+    // heading back to the depot, returns the route there.
+    pub fn vehicle_reached_lane_end(
+        &mut self,
+        now: Time,
+        id: CarID,
+        pos: Position,
+        old_path: &Path,
+        trips: &mut TripManager,
+        ctx: &mut Ctx,
+    ) -> Option<Router> {
+        let req = self.take_diversion(id, pos)?;
+
+        // The trip back to the depot ends here
+        self.events.push(Event::RideHailLegFinished {
+            car: id,
+            dist: old_path.crossed_so_far() + pos.dist_along(),
+            occupied: false,
+        });
+        let end = curb_pos(req.pickup, ctx.map).unwrap();
+        if let Some((path_req, path)) = path_between(pos, end, now, &trips.routing_params, ctx.map)
+        {
+            let taxi = self.vehicles.get_mut(&id).unwrap();
+            taxi.path_length = path.total_length();
+            taxi.state = TaxiState::DrivingToPickup(req);
+            return Some(Router::ride_hail(id, path, path_req.end.dist_along()));
+        }
+        self.events.push(Event::Alert(
+            AlertLocation::Person(req.person),
+            format!("{} can't reach the pickup for {}", id, req.trip),
+        ));
+        trips.abort_trip(now, req.trip, None, ctx);
+        Some(self.next_job(now, id, pos, trips, ctx))
+    }
+
+    // If the vehicle was sent to a new pickup while heading back to the depot, it stops heading
+    // there and gets the request. Vehicles that aren't ride-hail are ignored.
+    fn take_diversion(&mut self, id: CarID, pos: Position) -> Option<RideHailRequest> {
+        let taxi = self.vehicles.get_mut(&id)?;
+        taxi.pos = pos;
+        match std::mem::replace(&mut taxi.state, TaxiState::DrivingToDepot) {
+            TaxiState::DivertingToPickup(req) => Some(req),
+            state => {
+                taxi.state = state;
+                None
+            }
+        }
+    }
+
+    // Deadhead to the oldest waiting request, or head back to the depot
+    fn next_job(
+        &mut self,
+        now: Time,
+        id: CarID,
+        pos: Position,
+        trips: &mut TripManager,
+        ctx: &mut Ctx,
+    ) -> Router {
+        let taxi = self.vehicles.get_mut(&id).unwrap();
+        while let Some(req) = self.waiting.pop_front() {
+            let end = curb_pos(req.pickup, ctx.map).unwrap();
+            if let Some((path_req, path)) =
+                path_between(pos, end, now, &trips.routing_params, ctx.map)
+            {
+                taxi.path_length = path.total_length();
+                taxi.state = TaxiState::DrivingToPickup(req);
+                return Router::ride_hail(id, path, path_req.end.dist_along());
+            }
+            self.events.push(Event::Alert(
+                AlertLocation::Person(req.person),
+                format!("{} can't reach the pickup for {}", id, req.trip),
+            ));
+            trips.abort_trip(now, req.trip, None, ctx);
+        }
+
+        taxi.state = TaxiState::DrivingToDepot;
+        let end = curb_pos(taxi.depot, ctx.map).unwrap();
+        if let Some((path_req, path)) = path_between(pos, end, now, &trips.routing_params, ctx.map)
+        {
+            taxi.path_length = path.total_length();
+            Router::ride_hail(id, path, path_req.end.dist_along())
+        } else {
+            // TODO Just vanish here and reappear at the depot
+            taxi.path_length = Distance::ZERO;
+            Router::ride_hail(id, Path::one_step(pos.lane(), ctx.map), pos.dist_along())
+        }
+    }
+
+    pub fn collect_events(&mut self) -> Vec<Event> {
+        self.events.drain(..).collect()
+    }
+
+    // Vehicles not idling at their depot
+    pub fn active_vehicles(&self) -> usize {
+        self.vehicles
+            .values()
+            .filter(|taxi| taxi.state != TaxiState::AtDepot)
+            .count()
+    }
+}
+
+// Where ride-hail vehicles stop for a building
+pub(crate) fn curb_pos(b: BuildingID, map: &Map) -> Option<Position> {
+    map.get_b(b).driving_connection(map).map(|(pos, _)| pos)
+}
+
+// Vehicles can't back up, so if the stop is just behind them on the same lane, stop right there
+// instead.
+fn path_between(
+    start: Position,
+    end: Position,
+    now: Time,
+    params: &RoutingParams,
+    map: &Map,
+) -> Option<(PathRequest, Path)> {
+    let end = if start.lane() == end.lane() && end.dist_along() < start.dist_along() {
+        start
+    } else {
+        end
+    };
+    let req = PathRequest {
+        start,
+        end,
+        constraints: PathConstraints::Car,
+    };
+    let (path, _) = map.pathfind_at(req.clone(), now, params)?;
+    Some((req, path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use map_model::LaneID;
+
+    fn fleet(taxis: Vec<(f64, TaxiState)>) -> RideHailSimState {
+        let mut vehicles = BTreeMap::new();
+        for (idx, (dist, state)) in taxis.into_iter().enumerate() {
+            let id = CarID(idx, VehicleType::Car);
+            let vehicle = VehicleSpec {
+                vehicle_type: VehicleType::Car,
+                length: MIN_CAR_LENGTH,
+                max_speed: None,
+                battery: None,
+            }
+            .make(id, None);
+            vehicles.insert(
+                id,
+                Taxi {
+                    vehicle,
+                    depot: BuildingID(0),
+                    state,
+                    pos: Position::new(LaneID(0), Distance::meters(dist)),
+                    path_length: Distance::ZERO,
+                },
+            );
+        }
+        RideHailSimState {
+            vehicles,
+            waiting: VecDeque::new(),
+            events: Vec::new(),
+        }
+    }
+
+    fn request(idx: usize) -> RideHailRequest {
+        RideHailRequest {
+            trip: TripID(idx),
+            person: PersonID(idx),
+            pickup: BuildingID(1),
+            dropoff: BuildingID(2),
+            requested: Time::START_OF_DAY,
+        }
+    }
+
+    // The pickup is at the start of the lane
+    fn dist_to_pickup(taxi: &Taxi) -> Distance {
+        taxi.pos.dist_along()
+    }
+
+    fn state(rh: &RideHailSimState, idx: usize) -> &TaxiState {
+        &rh.vehicles[&CarID(idx, VehicleType::Car)].state
+    }
+
+    #[test]
+    fn test_dispatch_nearest_idle() {
+        let mut rh = fleet(vec![
+            (100.0, TaxiState::AtDepot),
+            (10.0, TaxiState::AtDepot),
+            (5.0, TaxiState::DrivingToPickup(request(9))),
+        ]);
+        let (id, req) = rh.dispatch(request(0), dist_to_pickup).unwrap();
+        assert_eq!(id, CarID(1, VehicleType::Car));
+        assert_eq!(req, request(0));
+        assert!(*state(&rh, 0) == TaxiState::AtDepot);
+        assert!(rh.waiting.is_empty());
+    }
+
+    #[test]
+    fn test_divert_vehicle_heading_to_depot() {
+        let mut rh = fleet(vec![
+            (100.0, TaxiState::AtDepot),
+            (10.0, TaxiState::DrivingToDepot),
+        ]);
+        assert!(rh.dispatch(request(0), dist_to_pickup).is_none());
+        assert!(*state(&rh, 1) == TaxiState::DivertingToPickup(request(0)));
+
+        // Nothing happens to vehicles that weren't diverted
+        let pos = Position::new(LaneID(1), Distance::meters(50.0));
+        assert!(rh.take_diversion(CarID(0, VehicleType::Car), pos).is_none());
+        assert!(*state(&rh, 0) == TaxiState::AtDepot);
+        assert!(rh
+            .take_diversion(CarID(99, VehicleType::Car), pos)
+            .is_none());
+
+        // At the end of the lane, the diverted vehicle gets the request, once
+        assert_eq!(
+            rh.take_diversion(CarID(1, VehicleType::Car), pos),
+            Some(request(0))
+        );
+        assert_eq!(rh.vehicles[&CarID(1, VehicleType::Car)].pos, pos);
+        assert!(rh.take_diversion(CarID(1, VehicleType::Car), pos).is_none());
+    }
+
+    #[test]
+    fn test_requests_wait_oldest_first() {
+        let mut rh = fleet(vec![
+            (10.0, TaxiState::DrivingToDepot),
+            (20.0, TaxiState::DroppingOff),
+        ]);
+        assert!(rh.dispatch(request(0), dist_to_pickup).is_none());
+        // The only free vehicle is already diverting, so these wait
+        assert!(rh.dispatch(request(1), dist_to_pickup).is_none());
+        assert!(rh.dispatch(request(2), dist_to_pickup).is_none());
+        assert_eq!(
+            rh.waiting.iter().cloned().collect::<Vec<_>>(),
+            vec![request(1), request(2)]
+        );
+        assert!(*state(&rh, 0) == TaxiState::DivertingToPickup(request(0)));
+    }
+}
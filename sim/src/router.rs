@@ -1,12 +1,12 @@
 use crate::mechanics::Queue;
 use crate::{
-    CarID, Event, ParkingSimState, ParkingSpot, PersonID, SidewalkSpot, TripID, TripPhaseType,
-    Vehicle, VehicleType,
+    AlertLocation, CarID, Event, ParkingSimState, ParkingSpot, PersonID, SidewalkSpot, TripID,
+    TripPhaseType, Vehicle, VehicleType,
 };
-use geom::Distance;
+use geom::{Distance, Time};
 use map_model::{
-    BuildingID, IntersectionID, LaneID, Map, Path, PathConstraints, PathRequest, PathStep,
-    Position, Traversable, TurnID,
+    BuildingID, IntersectionID, LaneID, Map, ParkingLotID, Path, PathConstraints, PathRequest,
+    PathStep, Position, RoutingParams, Traversable, TurnID,
 };
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
@@ -26,13 +26,13 @@ pub enum ActionAtEnd {
     GotoLaneEnd,
     StopBiking(SidewalkSpot),
     BusAtStop,
+    RideHailStop,
     GiveUpOnParking,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 enum Goal {
     // Spot and cached distance along the last driving lane
-    // TODO Right now, the building is ignored when choosing the best spot.
     ParkNearBuilding {
         target: BuildingID,
         spot: Option<(ParkingSpot, Distance)>,
@@ -40,6 +40,21 @@ enum Goal {
         stuck_end_dist: Option<Distance>,
         started_looking: bool,
     },
+    // Park-and-ride. Unlike ParkNearBuilding, don't wander off looking elsewhere if the lot is
+    // full.
+    ParkInLot {
+        lot: ParkingLotID,
+        // Cached distance along the lot's driving lane
+        end_dist: Distance,
+        spot: Option<ParkingSpot>,
+        stuck_end_dist: Option<Distance>,
+    },
+    // An electric vehicle heading to a charger near the target building
+    ParkAtCharger {
+        target: BuildingID,
+        spot: ParkingSpot,
+        end_dist: Distance,
+    },
     EndAtBorder {
         end_dist: Distance,
         i: IntersectionID,
@@ -50,6 +65,9 @@ enum Goal {
     FollowBusRoute {
         end_dist: Distance,
     },
+    RideHailStop {
+        end_dist: Distance,
+    },
 }
 
 impl Router {
@@ -90,6 +108,37 @@ impl Router {
         }
     }
 
+    pub fn park_in_lot(owner: CarID, path: Path, lot: ParkingLotID, map: &Map) -> Router {
+        Router {
+            path,
+            goal: Goal::ParkInLot {
+                lot,
+                end_dist: map.get_pl(lot).driving_pos.dist_along(),
+                spot: None,
+                stuck_end_dist: None,
+            },
+            owner,
+        }
+    }
+
+    pub fn park_at_charger(
+        owner: CarID,
+        path: Path,
+        target: BuildingID,
+        spot: ParkingSpot,
+        end_dist: Distance,
+    ) -> Router {
+        Router {
+            path,
+            goal: Goal::ParkAtCharger {
+                target,
+                spot,
+                end_dist,
+            },
+            owner,
+        }
+    }
+
     pub fn bike_then_stop(owner: CarID, path: Path, goal: SidewalkSpot) -> Router {
         Router {
             goal: Goal::BikeThenStop { goal },
@@ -106,6 +155,14 @@ impl Router {
         }
     }
 
+    pub fn ride_hail(owner: CarID, path: Path, end_dist: Distance) -> Router {
+        Router {
+            path,
+            goal: Goal::RideHailStop { end_dist },
+            owner,
+        }
+    }
+
     pub fn head(&self) -> Traversable {
         self.path.current_step().as_traversable()
     }
@@ -136,8 +193,15 @@ impl Router {
                 stuck_end_dist,
                 ..
             } => stuck_end_dist.unwrap_or_else(|| spot.unwrap().1),
+            Goal::ParkInLot {
+                end_dist,
+                stuck_end_dist,
+                ..
+            } => stuck_end_dist.unwrap_or(end_dist),
+            Goal::ParkAtCharger { end_dist, .. } => end_dist,
             Goal::BikeThenStop { ref goal } => goal.sidewalk_pos.dist_along(),
             Goal::FollowBusRoute { end_dist } => end_dist,
+            Goal::RideHailStop { end_dist } => end_dist,
         }
     }
 
@@ -145,19 +209,80 @@ impl Router {
         &self.path
     }
 
+    // Lanes that change type during the day might've closed to this vehicle since its path was
+    // chosen. Call before leaving the current lane to route around them. Returns false if there's
+    // no way around right now.
+    pub fn avoid_closed_lanes(
+        &mut self,
+        now: Time,
+        vehicle: &Vehicle,
+        params: &RoutingParams,
+        map: &Map,
+    ) -> bool {
+        if self.path.currently_inside_ut().is_some() {
+            return true;
+        }
+        let constraints = vehicle.vehicle_type.to_constraints();
+        let steps = self.path.get_steps();
+        let closed = steps
+            .iter()
+            .skip(1)
+            .take(steps.len().saturating_sub(2))
+            .any(|step| match step {
+                PathStep::Lane(l) => {
+                    let lane = map.get_l(*l);
+                    !lane.schedule.is_empty()
+                        && (!constraints.can_use_at(lane, map, now) || lane.reversed_at(now))
+                }
+                PathStep::ContraflowLane(l) => {
+                    let lane = map.get_l(*l);
+                    !constraints.can_use_at(lane, map, now) || !lane.reversed_at(now)
+                }
+                _ => false,
+            });
+        if !closed {
+            return true;
+        }
+
+        // Pathfinding only starts from the end of a lane going its usual direction. Finish
+        // driving against a reversible lane first.
+        let current = match self.path.current_step() {
+            PathStep::Lane(l) => l,
+            _ => {
+                return true;
+            }
+        };
+        if current == self.path.last_step().as_lane() {
+            return true;
+        }
+        let req = PathRequest {
+            start: Position::new(current, map.get_l(current).length()),
+            end: Position::new(self.path.last_step().as_lane(), self.path.end_dist()),
+            constraints,
+        };
+        if let Some((path, _)) = map.pathfind_at(req, now, params) {
+            self.path = path;
+            true
+        } else {
+            false
+        }
+    }
+
     // Returns the step just finished
     pub fn advance(
         &mut self,
+        now: Time,
         vehicle: &Vehicle,
         parking: &ParkingSimState,
         map: &Map,
         trip_and_person: Option<(TripID, PersonID)>,
         events: &mut Vec<Event>,
-    ) -> Traversable {
-        let prev = self.path.shift(map).as_traversable();
+    ) -> PathStep {
+        let prev = self.path.shift(map);
         if self.last_step() {
             // Do this to trigger the side-effect of looking for parking.
             self.maybe_handle_end(
+                now,
                 Distance::ZERO,
                 vehicle,
                 parking,
@@ -167,15 +292,39 @@ impl Router {
             );
         }
 
-        // Sanity check laws haven't been broken
+        // Sanity check laws haven't been broken. avoid_closed_lanes keeps vehicles off of lanes
+        // closed right now, except for the destination, lanes in the middle of an uber-turn, and
+        // right after driving against a reversible lane.
         if let Traversable::Lane(l) = self.head() {
             let lane = map.get_l(l);
-            if !vehicle.vehicle_type.to_constraints().can_use(lane, map) {
+            let constraints = vehicle.vehicle_type.to_constraints();
+            if !constraints.can_ever_use(lane, map) {
                 panic!(
                     "{} just wound up on {}, a {:?} (check the OSM tags)",
                     vehicle.id, l, lane.lane_type
                 );
             }
+            if !self.last_step() && !constraints.can_use_at(lane, map, now) {
+                events.push(Event::Alert(
+                    AlertLocation::Intersection(lane.src_i),
+                    format!(
+                        "{} entered {} while it's a {:?}",
+                        vehicle.id,
+                        l,
+                        lane.lane_type_at(now)
+                    ),
+                ));
+            }
+            let contraflow = matches!(self.path.current_step(), PathStep::ContraflowLane(_));
+            if !self.last_step() && lane.reversed_at(now) != contraflow {
+                events.push(Event::Alert(
+                    AlertLocation::Intersection(lane.src_i),
+                    format!(
+                        "{} entered {} against the direction it runs right now",
+                        vehicle.id, l
+                    ),
+                ));
+            }
         }
 
         prev
@@ -185,6 +334,7 @@ impl Router {
     // step.
     pub fn maybe_handle_end(
         &mut self,
+        now: Time,
         front: Distance,
         vehicle: &Vehicle,
         parking: &ParkingSimState,
@@ -216,40 +366,27 @@ impl Router {
                 }
 
                 let need_new_spot = match spot {
-                    Some((s, _)) => !parking.is_free(*s),
+                    // The rules might've changed since the spot was picked
+                    Some((s, _)) => {
+                        !parking.is_free(*s) || !parking.is_allowed(*s, vehicle, now, map)
+                    }
                     None => true,
                 };
                 if need_new_spot {
                     *started_looking = true;
                     let current_lane = self.path.current_step().as_lane();
-                    let candidates = parking.get_all_free_spots(
-                        Position::new(current_lane, front),
-                        vehicle,
-                        target,
-                        map,
-                    );
-                    let best =
-                        if let Some((driving_pos, _)) = map.get_b(target).driving_connection(map) {
-                            if driving_pos.lane() == current_lane {
-                                let target_dist = driving_pos.dist_along();
-                                // Closest to the building
-                                candidates
-                                    .into_iter()
-                                    .min_by_key(|(_, pos)| (pos.dist_along() - target_dist).abs())
-                            } else {
-                                // Closest to the road endpoint, I guess
-                                candidates
-                                    .into_iter()
-                                    .min_by_key(|(_, pos)| pos.dist_along())
-                            }
-                        } else {
-                            // Closest to the road endpoint, I guess
-                            candidates
-                                .into_iter()
-                                .min_by_key(|(_, pos)| pos.dist_along())
-                        };
-                    if let Some((new_spot, new_pos)) = best {
+                    if let Some((new_path_steps, new_spot, new_pos)) = parking
+                        .path_to_free_parking_spot(
+                            Position::new(current_lane, front),
+                            vehicle,
+                            target,
+                            now,
+                            map,
+                        )
+                    {
                         if let Some((t, p)) = trip_and_person {
+                            // TODO If the path was amended, this might not be the same as the one
+                            // found here...
                             events.push(Event::TripPhaseStarting(
                                 t,
                                 p,
@@ -261,40 +398,24 @@ impl Router {
                                 TripPhaseType::Parking,
                             ));
                         }
-                        assert_eq!(new_pos.lane(), current_lane);
-                        assert!(new_pos.dist_along() >= front);
                         *spot = Some((new_spot, new_pos.dist_along()));
-                    } else {
-                        if let Some((new_path_steps, new_spot, new_pos)) =
-                            parking.path_to_free_parking_spot(current_lane, vehicle, target, map)
-                        {
-                            assert!(!new_path_steps.is_empty());
+                        if new_path_steps.is_empty() {
+                            assert_eq!(new_pos.lane(), current_lane);
+                            assert!(new_pos.dist_along() >= front);
+                        } else {
                             for step in new_path_steps {
                                 self.path.add(step, map);
                             }
-                            *spot = Some((new_spot, new_pos.dist_along()));
                             events.push(Event::PathAmended(self.path.clone()));
-                            // TODO This path might not be the same as the one found here...
-                            if let Some((t, p)) = trip_and_person {
-                                events.push(Event::TripPhaseStarting(
-                                    t,
-                                    p,
-                                    Some(PathRequest {
-                                        start: Position::new(current_lane, front),
-                                        end: new_pos,
-                                        constraints: PathConstraints::Car,
-                                    }),
-                                    TripPhaseType::Parking,
-                                ));
-                            }
-                        } else {
-                            println!(
-                                "WARNING: {} can't find parking on {} or anywhere reachable from \
-                                 it. Possibly we're just totally out of parking space!",
-                                vehicle.id, current_lane
-                            );
-                            *stuck_end_dist = Some(map.get_l(current_lane).length());
+                            return Some(ActionAtEnd::GotoLaneEnd);
                         }
+                    } else {
+                        println!(
+                            "WARNING: {} can't find parking on {} or anywhere reachable from it. \
+                             Possibly we're just totally out of parking space!",
+                            vehicle.id, current_lane
+                        );
+                        *stuck_end_dist = Some(map.get_l(current_lane).length());
                         return Some(ActionAtEnd::GotoLaneEnd);
                     }
                 }
@@ -305,6 +426,87 @@ impl Router {
                     None
                 }
             }
+            Goal::ParkInLot {
+                lot,
+                end_dist,
+                ref mut spot,
+                ref mut stuck_end_dist,
+            } => {
+                if let Some(d) = stuck_end_dist {
+                    if *d == front {
+                        return Some(ActionAtEnd::GiveUpOnParking);
+                    } else {
+                        return None;
+                    }
+                }
+
+                let need_new_spot = match spot {
+                    Some(s) => !parking.is_free(*s) || !parking.is_allowed(*s, vehicle, now, map),
+                    None => true,
+                };
+                if need_new_spot {
+                    let current_lane = self.path.current_step().as_lane();
+                    *spot = parking
+                        .get_free_lot_spots(lot)
+                        .into_iter()
+                        .find(|s| parking.is_allowed(*s, vehicle, now, map));
+                    if spot.is_none() {
+                        println!(
+                            "WARNING: {} can't park at full park-and-ride lot {}",
+                            vehicle.id, lot
+                        );
+                        *stuck_end_dist = Some(map.get_l(current_lane).length());
+                        return Some(ActionAtEnd::GotoLaneEnd);
+                    }
+                    if let Some((t, p)) = trip_and_person {
+                        events.push(Event::TripPhaseStarting(
+                            t,
+                            p,
+                            Some(PathRequest {
+                                start: Position::new(current_lane, front),
+                                end: map.get_pl(lot).driving_pos,
+                                constraints: PathConstraints::Car,
+                            }),
+                            TripPhaseType::Parking,
+                        ));
+                    }
+                }
+
+                if end_dist == front {
+                    Some(ActionAtEnd::StartParking(spot.unwrap()))
+                } else {
+                    None
+                }
+            }
+            Goal::ParkAtCharger {
+                target,
+                spot,
+                end_dist,
+            } => {
+                if !parking.is_free(spot) || !parking.is_allowed(spot, vehicle, now, map) {
+                    // Somebody else got there first, so just park normally
+                    self.goal = Goal::ParkNearBuilding {
+                        target,
+                        spot: None,
+                        stuck_end_dist: None,
+                        started_looking: false,
+                    };
+                    return self.maybe_handle_end(
+                        now,
+                        front,
+                        vehicle,
+                        parking,
+                        map,
+                        trip_and_person,
+                        events,
+                    );
+                }
+                if end_dist == front {
+                    Some(ActionAtEnd::StartParking(spot))
+                } else {
+                    None
+                }
+            }
             Goal::BikeThenStop { ref goal } => {
                 if goal.sidewalk_pos.dist_along() == front {
                     Some(ActionAtEnd::StopBiking(goal.clone()))
@@ -319,12 +521,20 @@ impl Router {
                     None
                 }
             }
+            Goal::RideHailStop { end_dist } => {
+                if end_dist == front {
+                    Some(ActionAtEnd::RideHailStop)
+                } else {
+                    None
+                }
+            }
         }
     }
 
     pub fn opportunistically_lanechange(
         &mut self,
         queues: &BTreeMap<Traversable, Queue>,
+        now: Time,
         map: &Map,
         handle_uber_turns: bool,
     ) {
@@ -338,8 +548,9 @@ impl Router {
             if steps.len() < 5 {
                 return;
             }
-            match (steps[1], steps[4]) {
-                (PathStep::Turn(t), PathStep::Lane(l)) => (t, l),
+            // Only lane-change onto lanes going their usual direction
+            match (steps[1], steps[2], steps[4]) {
+                (PathStep::Turn(t), PathStep::Lane(_), PathStep::Lane(l)) => (t, l),
                 _ => {
                     return;
                 }
@@ -353,10 +564,13 @@ impl Router {
         // Look for other candidates, and assign a cost to each.
         let constraints = self.owner.1.to_constraints();
         let dir = parent.dir(orig_target_lane);
-        let (_, turn1, best_lane, turn2) = parent
+        let best = parent
             .lanes_ltr()
             .into_iter()
-            .filter(|(l, d, _)| dir == *d && constraints.can_use(map.get_l(*l), map))
+            .filter(|(l, d, _)| {
+                let l = map.get_l(*l);
+                dir == *d && constraints.can_use_at(l, map, now) && !l.reversed_at(now)
+            })
             .filter_map(|(l, _, _)| {
                 let t1 = TurnID {
                     parent: current_turn.parent,
@@ -404,8 +618,14 @@ impl Router {
 
                 (cost, turn1, l, turn2)
             })
-            .min_by_key(|(cost, _, _, _)| *cost)
-            .unwrap();
+            .min_by_key(|(cost, _, _, _)| *cost);
+        // The original lane might've just closed or reversed; avoid_closed_lanes will notice.
+        let (_, turn1, best_lane, turn2) = match best {
+            Some(x) => x,
+            None => {
+                return;
+            }
+        };
         // TODO Only switch if the target queue is some amount better; don't oscillate
         // unnecessarily.
         if best_lane == orig_target_lane {
@@ -426,6 +646,7 @@ impl Router {
             Goal::ParkNearBuilding {
                 started_looking, ..
             } => started_looking,
+            Goal::ParkInLot { spot, .. } => spot.is_some(),
             _ => false,
         }
     }
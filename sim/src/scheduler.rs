@@ -1,9 +1,10 @@
 use crate::{
-    pandemic, AgentID, CarID, CreateCar, CreatePedestrian, PedestrianID, TripID, TripSpec,
+    pandemic, AgentID, CarID, CreateCar, CreatePedestrian, PedestrianID, RideHailRequest, TripID,
+    TripSpec,
 };
 use derivative::Derivative;
 use geom::{Duration, Histogram, Time};
-use map_model::{BusRouteID, IntersectionID, Path, PathRequest};
+use map_model::{BusRouteID, IntersectionID, LaneID, Path, PathRequest};
 use serde::{Deserialize, Serialize};
 use std::cmp::Ordering;
 use std::collections::btree_map::Entry;
@@ -14,7 +15,8 @@ pub enum Command {
     // If true, retry when there's no room to spawn somewhere
     SpawnCar(CreateCar, bool),
     SpawnPed(CreatePedestrian),
-    StartTrip(TripID, TripSpec, Option<PathRequest>, Option<Path>),
+    // The path is paired with whether zones diverted it
+    StartTrip(TripID, TripSpec, Option<PathRequest>, Option<(Path, bool)>),
     UpdateCar(CarID),
     // Distinguish this from UpdateCar to avoid confusing things
     UpdateLaggyHead(CarID),
@@ -25,6 +27,9 @@ pub enum Command {
     FinishRemoteTrip(TripID),
     // The Time is redundant, just used to dedupe commands
     StartBus(BusRouteID, Time),
+    RequestRideHail(RideHailRequest),
+    // A parking lane opens to traffic. The Time is just used to dedupe commands.
+    ClearParkingLane(LaneID, Time),
 }
 
 impl Command {
@@ -49,6 +54,8 @@ impl Command {
             Command::Pandemic(ref p) => CommandType::Pandemic(p.clone()),
             Command::FinishRemoteTrip(t) => CommandType::FinishRemoteTrip(*t),
             Command::StartBus(r, t) => CommandType::StartBus(*r, *t),
+            Command::RequestRideHail(ref req) => CommandType::RequestRideHail(req.trip),
+            Command::ClearParkingLane(l, t) => CommandType::ClearParkingLane(*l, *t),
         }
     }
 }
@@ -66,6 +73,8 @@ pub enum CommandType {
     Pandemic(pandemic::Cmd),
     FinishRemoteTrip(TripID),
     StartBus(BusRouteID, Time),
+    RequestRideHail(TripID),
+    ClearParkingLane(LaneID, Time),
 }
 
 #[derive(Serialize, Deserialize, PartialEq, Eq, Clone)]
@@ -1,20 +1,21 @@
 use crate::analytics::Window;
 use crate::{
-    AgentID, AgentType, AlertLocation, Analytics, CapSimState, CarID, Command, CreateCar,
-    DrawCarInput, DrawPedCrowdInput, DrawPedestrianInput, DrivingSimState, Event, GetDrawAgents,
-    IntersectionSimState, OrigPersonID, PandemicModel, ParkedCar, ParkingSimState, ParkingSpot,
-    PedestrianID, Person, PersonID, PersonState, Router, Scenario, Scheduler, SidewalkPOI,
-    SidewalkSpot, TransitSimState, TripID, TripInfo, TripManager, TripPhaseType, TripResult,
-    TripSpawner, UnzoomedAgent, Vehicle, VehicleSpec, VehicleType, WalkingSimState, BUS_LENGTH,
-    LIGHT_RAIL_LENGTH, MIN_CAR_LENGTH, SPAWN_DIST,
+    AgentID, AgentType, AlertLocation, Analytics, BikeShareParams, BikeShareSimState, CapSimState,
+    CarID, ChargingParams, ChargingSimState, Command, CreateCar, DrawCarInput, DrawPedCrowdInput,
+    DrawPedestrianInput, DrivingSimState, Event, GetDrawAgents, IntersectionSimState, OrigPersonID,
+    PandemicModel, PandemicParams, PandemicPolicies, ParkedCar, ParkingSimState, ParkingSpot,
+    PedestrianID, Person, PersonID, PersonState, RideHailParams, RideHailSimState, Router,
+    Scenario, Scheduler, SidewalkPOI, SidewalkSpot, TransitSimState, TripID, TripInfo, TripManager,
+    TripPhaseType, TripResult, TripSpawner, UnzoomedAgent, Vehicle, VehicleSpec, VehicleType,
+    WalkingSimState, BUS_LENGTH, LIGHT_RAIL_LENGTH, MIN_CAR_LENGTH, SPAWN_DIST,
 };
 use abstutil::{prettyprint_usize, serialized_size_bytes, Counter, Parallelism, Timer};
 use derivative::Derivative;
 use geom::{Distance, Duration, PolyLine, Pt2D, Speed, Time};
 use instant::Instant;
 use map_model::{
-    BuildingID, BusRoute, BusRouteID, BusStopID, IntersectionID, Lane, LaneID, Map, ParkingLotID,
-    Path, PathConstraints, PathRequest, Position, RoadID, Traversable,
+    BuildingID, BusRoute, BusRouteID, BusStopID, IntersectionID, Lane, LaneID, LaneType, Map,
+    ParkingLotID, Path, PathConstraints, PathRequest, Position, RoadID, RoutingParams, Traversable,
 };
 use rand_xorshift::XorShiftRng;
 use serde::{Deserialize, Serialize};
@@ -32,10 +33,11 @@ pub struct Sim {
     walking: WalkingSimState,
     intersections: IntersectionSimState,
     transit: TransitSimState,
+    ridehail: RideHailSimState,
+    bikeshare: BikeShareSimState,
+    charging: ChargingSimState,
     cap: CapSimState,
     trips: TripManager,
-    #[derivative(PartialEq = "ignore")]
-    #[serde(skip_serializing, skip_deserializing)]
     pandemic: Option<PandemicModel>,
     scheduler: Scheduler,
     time: Time,
@@ -65,6 +67,8 @@ pub struct Ctx<'a> {
     pub parking: &'a mut ParkingSimState,
     pub intersections: &'a mut IntersectionSimState,
     pub cap: &'a mut CapSimState,
+    pub bikeshare: &'a mut BikeShareSimState,
+    pub charging: &'a mut ChargingSimState,
     pub scheduler: &'a mut Scheduler,
     pub map: &'a Map,
 }
@@ -78,8 +82,16 @@ pub struct SimOptions {
     pub break_turn_conflict_cycles: bool,
     pub handle_uber_turns: bool,
     pub enable_pandemic_model: Option<XorShiftRng>,
+    pub pandemic_params: PandemicParams,
+    pub pandemic_policies: PandemicPolicies,
+    pub ridehail_params: RideHailParams,
+    pub bikeshare_params: BikeShareParams,
+    pub charging_params: ChargingParams,
+    pub routing_params: RoutingParams,
     pub alerts: AlertHandler,
     pub pathfinding_upfront: bool,
+    // How long vehicles pause at a stop sign before starting their turn
+    pub stop_sign_wait: Duration,
 }
 
 #[derive(Clone)]
@@ -108,8 +120,15 @@ impl SimOptions {
             break_turn_conflict_cycles: true,
             handle_uber_turns: true,
             enable_pandemic_model: None,
+            pandemic_params: PandemicParams::new(),
+            pandemic_policies: PandemicPolicies::new(),
+            ridehail_params: RideHailParams::new(),
+            bikeshare_params: BikeShareParams::new(),
+            charging_params: ChargingParams::new(),
+            routing_params: RoutingParams::new(),
             alerts: AlertHandler::Print,
             pathfinding_upfront: false,
+            stop_sign_wait: Duration::seconds(0.5),
         }
     }
 }
@@ -118,9 +137,25 @@ impl SimOptions {
 impl Sim {
     pub fn new(map: &Map, opts: SimOptions, timer: &mut Timer) -> Sim {
         let mut scheduler = Scheduler::new();
+        let mut trips = TripManager::new(opts.pathfinding_upfront, opts.routing_params);
+        let ridehail = RideHailSimState::new(opts.ridehail_params, &mut trips, map);
+        let bikeshare = BikeShareSimState::new(opts.bikeshare_params, &mut trips, map);
+        let mut parking = ParkingSimState::new(map, timer);
+        let charging = ChargingSimState::new(opts.charging_params, &mut parking);
+        // Parking lanes that open to traffic during part of the day have to be cleared first
+        for l in map.all_lanes() {
+            if l.lane_type == LaneType::Parking {
+                for period in &l.schedule {
+                    if period.lt != LaneType::Parking {
+                        let t = Time::START_OF_DAY + period.start;
+                        scheduler.push(t, Command::ClearParkingLane(l.id, t));
+                    }
+                }
+            }
+        }
         Sim {
             driving: DrivingSimState::new(map, opts.recalc_lanechanging, opts.handle_uber_turns),
-            parking: ParkingSimState::new(map, timer),
+            parking,
             walking: WalkingSimState::new(),
             intersections: IntersectionSimState::new(
                 map,
@@ -129,12 +164,20 @@ impl Sim {
                 opts.dont_block_the_box,
                 opts.break_turn_conflict_cycles,
                 opts.handle_uber_turns,
+                opts.stop_sign_wait,
             ),
             transit: TransitSimState::new(map),
+            ridehail,
+            bikeshare,
+            charging,
             cap: CapSimState::new(map),
-            trips: TripManager::new(opts.pathfinding_upfront),
+            trips,
             pandemic: if let Some(rng) = opts.enable_pandemic_model {
-                Some(PandemicModel::new(rng))
+                Some(PandemicModel::new(
+                    rng,
+                    opts.pandemic_params,
+                    opts.pandemic_policies,
+                ))
             } else {
                 None
             },
@@ -194,24 +237,17 @@ impl Sim {
             vehicle_type: VehicleType::Car,
             length: MIN_CAR_LENGTH,
             max_speed: None,
+            battery: None,
         };
         let driving_lane = map.find_driving_lane_near_building(b);
 
-        // Anything on the current lane? TODO Should find the closest one to the sidewalk, but
-        // need a new method in ParkingSimState to make that easy.
-        // TODO Refactor the logic in router
-        let spot = if let Some((spot, _)) = self
-            .parking
-            .get_all_free_spots(Position::start(driving_lane), &vehicle, b, map)
-            .get(0)
-        {
-            spot.clone()
-        } else {
-            let (_, spot, _) =
-                self.parking
-                    .path_to_free_parking_spot(driving_lane, &vehicle, b, map)?;
-            spot
-        };
+        let (_, spot, _) = self.parking.path_to_free_parking_spot(
+            Position::start(driving_lane),
+            &vehicle,
+            b,
+            self.time,
+            map,
+        )?;
 
         let start = SidewalkSpot::building(b, map).sidewalk_pos;
         let end = SidewalkSpot::parking_spot(spot, map, &self.parking).sidewalk_pos;
@@ -231,6 +267,7 @@ impl Sim {
         ped_speed: Speed,
         vehicle_specs: Vec<VehicleSpec>,
     ) {
+        let vehicle_specs = self.charging.electrify(vehicle_specs);
         self.trips.new_person(p, orig_id, ped_speed, vehicle_specs);
     }
     pub fn random_person(&mut self, ped_speed: Speed, vehicle_specs: Vec<VehicleSpec>) -> &Person {
@@ -238,7 +275,22 @@ impl Sim {
     }
     pub(crate) fn seed_parked_car(&mut self, vehicle: Vehicle, spot: ParkingSpot) {
         self.parking.reserve_spot(spot);
-        self.parking.add_parked_car(ParkedCar { vehicle, spot });
+        self.parking
+            .add_parked_car(ParkedCar { vehicle, spot }, self.time);
+    }
+
+    // Lets the car park in its owner's permit zone
+    pub(crate) fn grant_parking_permit(&mut self, car: CarID, home: BuildingID) {
+        self.parking.grant_permit(car, home);
+    }
+
+    pub(crate) fn is_parking_allowed(
+        &self,
+        spot: ParkingSpot,
+        vehicle: &Vehicle,
+        map: &Map,
+    ) -> bool {
+        self.parking.is_allowed(spot, vehicle, self.time, map)
     }
 
     pub(crate) fn seed_bus_route(&mut self, route: &BusRoute) {
@@ -262,6 +314,7 @@ impl Sim {
             vehicle_type,
             length,
             max_speed: None,
+            battery: None,
         }
         .make(CarID(self.trips.new_car_id(), vehicle_type), None);
         let start_lane = map.get_l(path.current_step().as_lane());
@@ -418,6 +471,8 @@ impl Sim {
             parking: &mut self.parking,
             intersections: &mut self.intersections,
             cap: &mut self.cap,
+            bikeshare: &mut self.bikeshare,
+            charging: &mut self.charging,
             scheduler: &mut self.scheduler,
             map,
         };
@@ -458,11 +513,16 @@ impl Sim {
                                 b,
                             ));
                         }
-                        self.parking.remove_parked_car(parked_car);
+                        self.parking.remove_parked_car(parked_car, self.time, map);
                     }
                     if let Some(route) = create_car.maybe_route {
                         self.transit.bus_created(create_car.vehicle.id, route);
                     }
+                    self.charging.car_departed(
+                        &create_car.vehicle,
+                        create_car.router.get_path().total_length(),
+                        self.time,
+                    );
                     self.analytics
                         .record_demand(create_car.router.get_path(), map);
                 } else if retry_if_no_room {
@@ -483,6 +543,8 @@ impl Sim {
                         parking: &mut self.parking,
                         intersections: &mut self.intersections,
                         cap: &mut self.cap,
+                        bikeshare: &mut self.bikeshare,
+                        charging: &mut self.charging,
                         scheduler: &mut self.scheduler,
                         map,
                     };
@@ -534,6 +596,7 @@ impl Sim {
                     &mut ctx,
                     &mut self.trips,
                     &mut self.transit,
+                    &mut self.ridehail,
                     &mut self.walking,
                 );
             }
@@ -567,10 +630,13 @@ impl Sim {
                 }
             }
             Command::Pandemic(cmd) => {
-                self.pandemic
-                    .as_mut()
-                    .unwrap()
-                    .handle_cmd(self.time, cmd, &mut self.scheduler);
+                self.pandemic.as_mut().unwrap().handle_cmd(
+                    self.time,
+                    cmd,
+                    &mut self.scheduler,
+                    &mut self.trips,
+                    map,
+                );
             }
             Command::FinishRemoteTrip(trip) => {
                 self.trips.remote_trip_finished(self.time, trip, &mut ctx);
@@ -578,6 +644,17 @@ impl Sim {
             Command::StartBus(r, _) => {
                 self.start_bus(map.get_br(r), map);
             }
+            Command::RequestRideHail(req) => {
+                self.ridehail
+                    .request(self.time, req, &mut self.trips, &mut ctx);
+            }
+            Command::ClearParkingLane(l, t) => {
+                ctx.parking
+                    .tow_cars(self.time, l, &self.trips.cars_being_fetched(), map);
+                let tomorrow = t + Duration::hours(24);
+                ctx.scheduler
+                    .push(tomorrow, Command::ClearParkingLane(l, tomorrow));
+            }
         }
 
         // Record events at precisely the time they occur.
@@ -589,6 +666,10 @@ impl Sim {
     fn dispatch_events(&mut self, mut events: Vec<Event>, map: &Map) {
         events.extend(self.trips.collect_events());
         events.extend(self.transit.collect_events());
+        events.extend(self.ridehail.collect_events());
+        events.extend(self.bikeshare.collect_events());
+        events.extend(self.charging.collect_events());
+        events.extend(self.cap.collect_events());
         events.extend(self.driving.collect_events());
         events.extend(self.walking.collect_events());
         events.extend(self.intersections.collect_events());
@@ -597,6 +678,7 @@ impl Sim {
             if let Some(ref mut m) = self.pandemic {
                 m.handle_event(self.time, &ev, &mut self.scheduler);
             }
+            self.charging.handle_event(self.time, &ev);
 
             self.analytics.event(ev, self.time, map);
         }
@@ -807,6 +889,18 @@ impl Sim {
                 "- transit: {} bytes",
                 prettyprint_usize(serialized_size_bytes(&self.transit))
             );
+            println!(
+                "- ridehail: {} bytes",
+                prettyprint_usize(serialized_size_bytes(&self.ridehail))
+            );
+            println!(
+                "- bikeshare: {} bytes",
+                prettyprint_usize(serialized_size_bytes(&self.bikeshare))
+            );
+            println!(
+                "- charging: {} bytes",
+                prettyprint_usize(serialized_size_bytes(&self.charging))
+            );
             println!(
                 "- cap: {} bytes",
                 prettyprint_usize(serialized_size_bytes(&self.cap))
@@ -882,7 +976,7 @@ impl Sim {
         self.trips.num_trips()
     }
     pub fn num_agents(&self) -> Counter<AgentType> {
-        self.trips.num_agents(&self.transit)
+        self.trips.num_agents(&self.transit, &self.ridehail)
     }
     // (total number of people, just in buildings, just off map)
     pub fn num_ppl(&self) -> (usize, usize, usize) {
@@ -1201,7 +1295,26 @@ impl Sim {
     }
 
     pub fn get_cap_counter(&self, l: LaneID) -> usize {
-        self.cap.get_cap_counter(l)
+        self.cap.get_cap_counter(l, self.time)
+    }
+
+    // (building, shared vehicles parked there, docks) for every bike-share station
+    pub fn get_bikeshare_stations(&self) -> Vec<(BuildingID, usize, usize)> {
+        self.bikeshare.get_stations()
+    }
+
+    // (spot, kW, who's plugged in) for every charger
+    pub fn get_chargers(&self) -> Vec<(ParkingSpot, f64, Option<CarID>)> {
+        self.charging.get_chargers()
+    }
+
+    // In kWh, for electric vehicles
+    pub fn get_state_of_charge(&self, id: CarID) -> Option<f64> {
+        let vehicle = self
+            .trips
+            .get_person(self.get_owner_of_car(id)?)?
+            .get_vehicle(id);
+        self.charging.state_of_charge(&vehicle)
     }
 }
 
@@ -1220,6 +1333,8 @@ impl Sim {
                 parking: &mut self.parking,
                 intersections: &mut self.intersections,
                 cap: &mut self.cap,
+                bikeshare: &mut self.bikeshare,
+                charging: &mut self.charging,
                 scheduler: &mut self.scheduler,
                 map,
             };
@@ -1255,6 +1370,12 @@ impl Sim {
         self.scheduler
             .cancel(Command::Callback(Duration::seconds(1.0)));
     }
+
+    // Truck some shared bikes or scooters between stations. Meant for rebalancing policies, maybe
+    // run from a periodic callback. Returns how many actually moved.
+    pub fn rebalance_bikeshare(&mut self, from: BuildingID, to: BuildingID, count: usize) -> usize {
+        self.bikeshare.rebalance(from, to, count)
+    }
 }
 
 pub struct AgentProperties {
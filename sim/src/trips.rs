@@ -1,19 +1,20 @@
+use crate::ridehail::curb_pos;
 use crate::sim::Ctx;
 use crate::{
-    AgentID, AgentType, AlertLocation, CarID, Command, CreateCar, CreatePedestrian, DrivingGoal,
-    Event, IndividTrip, OffMapLocation, OrigPersonID, ParkedCar, ParkingSpot, PedestrianID,
-    PersonID, PersonSpec, Scenario, Scheduler, SidewalkPOI, SidewalkSpot, SpawnTrip,
-    TransitSimState, TripID, TripPhaseType, TripSpec, Vehicle, VehicleSpec, VehicleType,
-    WalkingSimState,
+    AgentID, AgentType, AlertLocation, CarID, Command, CommandType, CreateCar, CreatePedestrian,
+    DrivingGoal, Event, IndividTrip, OffMapLocation, OrigPersonID, ParkedCar, ParkingSpot,
+    PedestrianID, PersonID, PersonSpec, RideHailRequest, RideHailSimState, Router, Scenario,
+    Scheduler, SidewalkPOI, SidewalkSpot, SpawnTrip, TransitSimState, TripID, TripPhaseType,
+    TripSpec, Vehicle, VehicleSpec, VehicleType, WalkingSimState,
 };
 use abstutil::{deserialize_btreemap, serialize_btreemap, Counter};
 use geom::{Duration, Speed, Time};
 use map_model::{
-    BuildingID, BusRouteID, BusStopID, IntersectionID, Map, Path, PathConstraints, PathRequest,
-    Position,
+    BuildingID, BusRouteID, BusStopID, IntersectionID, Map, ParkingLotID, Path, PathConstraints,
+    PathRequest, Position, RoutingParams,
 };
 use serde::{Deserialize, Serialize};
-use std::collections::{BTreeMap, VecDeque};
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
 
 #[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
 pub struct TripManager {
@@ -27,22 +28,32 @@ pub struct TripManager {
     active_trip_mode: BTreeMap<AgentID, TripID>,
     unfinished_trips: usize,
     pub pathfinding_upfront: bool,
+    pub routing_params: RoutingParams,
 
     car_id_counter: usize,
 
+    // Cars left at a park-and-ride lot
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    park_and_ride_cars: BTreeMap<CarID, ParkingLotID>,
+
     events: Vec<Event>,
 }
 
 impl TripManager {
-    pub fn new(pathfinding_upfront: bool) -> TripManager {
+    pub fn new(pathfinding_upfront: bool, routing_params: RoutingParams) -> TripManager {
         TripManager {
             trips: Vec::new(),
             people: Vec::new(),
             active_trip_mode: BTreeMap::new(),
             unfinished_trips: 0,
             car_id_counter: 0,
+            park_and_ride_cars: BTreeMap::new(),
             events: Vec::new(),
             pathfinding_upfront,
+            routing_params,
         }
     }
 
@@ -110,6 +121,8 @@ impl TripManager {
             Some(TripLeg::Drive(_, ref goal)) => match goal {
                 DrivingGoal::ParkNear(b) => TripEndpoint::Bldg(*b),
                 DrivingGoal::Border(i, _, loc) => TripEndpoint::Border(*i, loc.clone()),
+                // There's always transit afterwards
+                DrivingGoal::ParkAndRide(_) | DrivingGoal::BikeAndRide(_) => unreachable!(),
             },
             Some(TripLeg::Remote(ref to)) => {
                 TripEndpoint::Border(map.all_incoming_borders()[0].id, Some(to.clone()))
@@ -119,6 +132,7 @@ impl TripManager {
                 // TODO No way to plumb OffMapLocation here
                 TripEndpoint::Border(map.get_l(map.get_br(*r).end_border.unwrap()).dst_i, None)
             }
+            Some(TripLeg::RideHail(b)) => TripEndpoint::Bldg(*b),
             _ => unreachable!(),
         };
         let trip = Trip {
@@ -156,7 +170,14 @@ impl TripManager {
                 }
             };
         }
-        if let Some(t) = person.trips.last() {
+        // Cancelled trips may depart later; see replace_future_trips.
+        let all_trips = &self.trips;
+        if let Some(t) = person
+            .trips
+            .iter()
+            .rev()
+            .find(|t| !all_trips[t.0].cancelled)
+        {
             // TODO If it's exactly ==, what?! See the ID.
             if self.trips[t.0].info.departure > trip.info.departure {
                 panic!(
@@ -192,37 +213,54 @@ impl TripManager {
             Some(TripLeg::Drive(c, DrivingGoal::ParkNear(_))) => {
                 assert_eq!(car, c);
             }
+            Some(TripLeg::Drive(c, DrivingGoal::ParkAndRide(pl))) => {
+                assert_eq!(car, c);
+                self.park_and_ride_cars.insert(car, pl);
+                self.events
+                    .push(park_and_ride_occupancy(&self.park_and_ride_cars, pl));
+            }
             _ => unreachable!(),
         };
 
+        let walk_start = SidewalkSpot::parking_spot(spot, ctx.map, ctx.parking);
         match &trip.legs[0] {
-            TripLeg::Walk(to) => match (spot, &to.connection) {
-                (ParkingSpot::Offstreet(b1, _), SidewalkPOI::Building(b2)) if b1 == *b2 => {
-                    // Do the relevant parts of ped_reached_parking_spot.
-                    assert_eq!(trip.legs.len(), 1);
-                    assert!(!trip.finished_at.is_some());
-                    trip.finished_at = Some(now);
-                    self.unfinished_trips -= 1;
-                    self.events.push(Event::TripFinished {
-                        trip: trip.id,
-                        mode: trip.info.mode,
-                        total_time: now - trip.info.departure,
-                        blocked_time: trip.total_blocked_time,
-                    });
-                    let person = trip.person;
-                    self.people[person.0].state = PersonState::Inside(b1);
-                    self.events.push(Event::PersonEntersBuilding(person, b1));
-                    self.person_finished_trip(now, person, ctx);
-                    return;
+            TripLeg::Walk(to) => {
+                self.events.push(Event::TripParkedCar {
+                    trip: trip.id,
+                    spot,
+                    walking_dist: walk_start
+                        .sidewalk_pos
+                        .pt(ctx.map)
+                        .dist_to(to.sidewalk_pos.pt(ctx.map)),
+                });
+                match (spot, &to.connection) {
+                    (ParkingSpot::Offstreet(b1, _), SidewalkPOI::Building(b2)) if b1 == *b2 => {
+                        // Do the relevant parts of ped_reached_parking_spot.
+                        assert_eq!(trip.legs.len(), 1);
+                        assert!(!trip.finished_at.is_some());
+                        trip.finished_at = Some(now);
+                        self.unfinished_trips -= 1;
+                        self.events.push(Event::TripFinished {
+                            trip: trip.id,
+                            mode: trip.info.mode,
+                            total_time: now - trip.info.departure,
+                            blocked_time: trip.total_blocked_time,
+                        });
+                        let person = trip.person;
+                        self.people[person.0].state = PersonState::Inside(b1);
+                        self.events.push(Event::PersonEntersBuilding(person, b1));
+                        self.person_finished_trip(now, person, ctx);
+                        return;
+                    }
+                    _ => {}
                 }
-                _ => {}
-            },
+            }
             _ => unreachable!(),
         };
 
         if !trip.spawn_ped(
             now,
-            SidewalkSpot::parking_spot(spot, ctx.map, ctx.parking),
+            walk_start,
             &self.people[trip.person.0],
             ctx.map,
             ctx.scheduler,
@@ -250,6 +288,10 @@ impl TripManager {
 
         trip.assert_walking_leg(SidewalkSpot::deferred_parking_spot());
         let parked_car = ctx.parking.get_car_at_spot(spot).unwrap().clone();
+        if let Some(pl) = self.park_and_ride_cars.remove(&parked_car.vehicle.id) {
+            self.events
+                .push(park_and_ride_occupancy(&self.park_and_ride_cars, pl));
+        }
         let drive_to = match trip.legs[0] {
             TripLeg::Drive(c, ref to) => {
                 assert_eq!(c, parked_car.vehicle.id);
@@ -274,46 +316,107 @@ impl TripManager {
             }
         }
         let end = drive_to.goal_pos(PathConstraints::Car, ctx.map).unwrap();
-        let req = PathRequest {
+        let mut req = PathRequest {
             start,
             end,
             constraints: PathConstraints::Car,
         };
-        let path = if let Some(p) = ctx.map.pathfind(req.clone()) {
-            p
-        } else {
+        let (mut path, mut diverted) =
+            if let Some(pair) = ctx.map.pathfind_at(req.clone(), now, &self.routing_params) {
+                pair
+            } else {
+                self.events.push(Event::Alert(
+                    AlertLocation::Person(trip.person),
+                    format!(
+                        "Aborting {} because no path for the car portion! {} to {}",
+                        trip.id, start, end
+                    ),
+                ));
+                // Move the car to the destination...
+                ctx.parking
+                    .remove_parked_car(parked_car.clone(), now, ctx.map);
+                let trip = trip.id;
+                self.abort_trip(now, trip, Some(parked_car.vehicle), ctx);
+                return;
+            };
+
+        // An electric vehicle that'd arrive low on charge parks at a charger near the destination
+        // instead, if one's free.
+        let mut charger = None;
+        if let DrivingGoal::ParkNear(b) = drive_to {
+            if ctx
+                .charging
+                .needs_charge(&parked_car.vehicle, path.total_length(), now)
+            {
+                if let Some(charger_spot) =
+                    ctx.charging
+                        .find_charger(&parked_car.vehicle, b, now, ctx.parking, ctx.map)
+                {
+                    let charger_pos =
+                        ctx.parking
+                            .spot_to_driving_pos(charger_spot, &parked_car.vehicle, ctx.map);
+                    let charger_req = PathRequest {
+                        start,
+                        end: charger_pos,
+                        constraints: PathConstraints::Car,
+                    };
+                    if let Some((p, d)) =
+                        ctx.map
+                            .pathfind_at(charger_req.clone(), now, &self.routing_params)
+                    {
+                        path = p;
+                        diverted = d;
+                        req = charger_req;
+                        charger = Some((b, charger_spot, charger_pos.dist_along()));
+                    }
+                }
+            }
+        }
+
+        if !ctx.cap.allow_trip(now, parked_car.vehicle.id, &path) {
+            // TODO Different ways to handle this: abort the trip, delay it an hour, route around
+            // the zone, switch modes...
             self.events.push(Event::Alert(
                 AlertLocation::Person(trip.person),
                 format!(
-                    "Aborting {} because no path for the car portion! {} to {}",
-                    trip.id, start, end
+                    "Aborting {} because it would exceed the cap through some zone",
+                    trip.id
                 ),
             ));
             // Move the car to the destination...
-            ctx.parking.remove_parked_car(parked_car.clone());
+            ctx.parking
+                .remove_parked_car(parked_car.clone(), now, ctx.map);
             let trip = trip.id;
             self.abort_trip(now, trip, Some(parked_car.vehicle), ctx);
             return;
-        };
-
-        if !ctx.cap.allow_trip(parked_car.vehicle.id, &path) {
-            // TODO Different ways to handle this: abort the trip, delay it an hour, route around
-            // the zone, switch modes...
+        }
+        if !ctx
+            .charging
+            .has_range(&parked_car.vehicle, path.total_length(), now)
+        {
             self.events.push(Event::Alert(
                 AlertLocation::Person(trip.person),
                 format!(
-                    "Aborting {} because it would exceed the cap through some zone",
-                    trip.id
+                    "Aborting {} because {} doesn't have enough charge",
+                    trip.id, parked_car.vehicle.id
                 ),
             ));
             // Move the car to the destination...
-            ctx.parking.remove_parked_car(parked_car.clone());
+            ctx.parking
+                .remove_parked_car(parked_car.clone(), now, ctx.map);
             let trip = trip.id;
             self.abort_trip(now, trip, Some(parked_car.vehicle), ctx);
             return;
         }
+        if diverted {
+            self.events.push(Event::TripDiverted(trip.id));
+        }
 
-        let router = drive_to.make_router(parked_car.vehicle.id, path, ctx.map);
+        let router = if let Some((b, charger_spot, end_dist)) = charger {
+            Router::park_at_charger(parked_car.vehicle.id, path, b, charger_spot, end_dist)
+        } else {
+            drive_to.make_router(parked_car.vehicle.id, path, ctx.map)
+        };
         ctx.scheduler.push(
             now,
             Command::SpawnCar(
@@ -374,16 +477,21 @@ impl TripManager {
             end,
             constraints: PathConstraints::Bike,
         };
-        if let Some(router) = ctx
-            .map
-            .pathfind(req.clone())
-            .map(|path| drive_to.make_router(bike, path, ctx.map))
+        if let Some((path, diverted)) = ctx.map.pathfind_at(req.clone(), now, &self.routing_params)
         {
+            if diverted {
+                self.events.push(Event::TripDiverted(trip.id));
+            }
+            let router = drive_to.make_router(bike, path, ctx.map);
             ctx.scheduler.push(
                 now,
                 Command::SpawnCar(
                     CreateCar::for_appearing(
-                        self.people[trip.person.0].get_vehicle(bike),
+                        if let Some(vehicle) = ctx.bikeshare.get_vehicle(bike) {
+                            vehicle.clone()
+                        } else {
+                            self.people[trip.person.0].get_vehicle(bike)
+                        },
                         driving_pos,
                         router,
                         req,
@@ -413,18 +521,21 @@ impl TripManager {
         bike: CarID,
         bike_rack: SidewalkSpot,
         blocked_time: Duration,
-        map: &Map,
-        scheduler: &mut Scheduler,
+        ctx: &mut Ctx,
     ) {
         self.events.push(Event::BikeStoppedAtSidewalk(
             bike,
             bike_rack.sidewalk_pos.lane(),
         ));
+        if ctx.bikeshare.is_in_use(bike) {
+            ctx.bikeshare.vehicle_returned(bike);
+        }
         let trip = &mut self.trips[self.active_trip_mode.remove(&AgentID::Car(bike)).unwrap().0];
         trip.total_blocked_time += blocked_time;
 
         match trip.legs.pop_front() {
-            Some(TripLeg::Drive(c, DrivingGoal::ParkNear(_))) => {
+            Some(TripLeg::Drive(c, DrivingGoal::ParkNear(_)))
+            | Some(TripLeg::Drive(c, DrivingGoal::BikeAndRide(_))) => {
                 assert_eq!(c, bike);
             }
             _ => unreachable!(),
@@ -434,8 +545,8 @@ impl TripManager {
             now,
             bike_rack,
             &self.people[trip.person.0],
-            map,
-            scheduler,
+            ctx.map,
+            ctx.scheduler,
             &mut self.events,
         ) {
             self.unfinished_trips -= 1;
@@ -552,11 +663,11 @@ impl TripManager {
 
     // TODO Need to characterize delay the bus experienced
     pub fn person_left_bus(&mut self, now: Time, person: PersonID, bus: CarID, ctx: &mut Ctx) {
-        let trip = &mut self.trips[self
+        let id = self
             .active_trip_mode
             .remove(&AgentID::BusPassenger(person, bus))
-            .unwrap()
-            .0];
+            .unwrap();
+        let trip = &mut self.trips[id.0];
         let start = match trip.legs.pop_front().unwrap() {
             TripLeg::RideBus(_, maybe_stop2) => SidewalkSpot::bus_stop(
                 maybe_stop2.expect("someone left a bus, even though they should've ridden off-map"),
@@ -566,6 +677,62 @@ impl TripManager {
         };
         self.people[person.0].on_bus.take().unwrap();
 
+        // Heading back to a car left at a park-and-ride lot
+        if trip.legs[0] == TripLeg::Walk(SidewalkSpot::deferred_parking_spot()) {
+            let car = match trip.legs[1] {
+                TripLeg::Drive(c, _) => c,
+                _ => unreachable!(),
+            };
+            if let Some(parked_car) = ctx.parking.lookup_parked_car(car).cloned() {
+                let walking_goal =
+                    SidewalkSpot::parking_spot(parked_car.spot, ctx.map, ctx.parking);
+                let req = PathRequest {
+                    start: start.sidewalk_pos,
+                    end: walking_goal.sidewalk_pos,
+                    constraints: PathConstraints::Pedestrian,
+                };
+                let p = &self.people[person.0];
+                if let Some(path) = ctx.map.pathfind(req.clone()) {
+                    ctx.scheduler.push(
+                        now,
+                        Command::SpawnPed(CreatePedestrian {
+                            id: p.ped,
+                            speed: p.ped_speed,
+                            start,
+                            goal: walking_goal,
+                            path,
+                            req,
+                            trip: id,
+                            person,
+                        }),
+                    );
+                } else {
+                    self.events.push(Event::Alert(
+                        AlertLocation::Person(person),
+                        format!("Park-and-ride trip couldn't find the walking path {}", req),
+                    ));
+                    // Move the car to the destination
+                    ctx.parking
+                        .remove_parked_car(parked_car.clone(), now, ctx.map);
+                    if let Some(pl) = self.park_and_ride_cars.remove(&car) {
+                        self.events
+                            .push(park_and_ride_occupancy(&self.park_and_ride_cars, pl));
+                    }
+                    self.abort_trip(now, id, Some(parked_car.vehicle), ctx);
+                }
+            } else {
+                self.events.push(Event::Alert(
+                    AlertLocation::Person(person),
+                    format!(
+                        "{} should have {} parked somewhere, but it's unavailable, so aborting {}",
+                        person, car, id
+                    ),
+                ));
+                self.abort_trip(now, id, None, ctx);
+            }
+            return;
+        }
+
         if !trip.spawn_ped(
             now,
             start,
@@ -578,6 +745,61 @@ impl TripManager {
         }
     }
 
+    pub fn person_boarded_ridehail(&mut self, trip: TripID, car: CarID) {
+        let trip = &self.trips[trip.0];
+        match trip.legs[0] {
+            TripLeg::RideHail(_) => {}
+            _ => unreachable!(),
+        }
+        self.active_trip_mode
+            .insert(AgentID::BusPassenger(trip.person, car), trip.id);
+        self.people[trip.person.0].on_bus = Some(car);
+    }
+
+    // TODO Need to characterize delay the vehicle experienced
+    pub fn person_left_ridehail(&mut self, now: Time, person: PersonID, car: CarID, ctx: &mut Ctx) {
+        let trip = &mut self.trips[self
+            .active_trip_mode
+            .remove(&AgentID::BusPassenger(person, car))
+            .unwrap()
+            .0];
+        let bldg = match trip.legs.pop_front() {
+            Some(TripLeg::RideHail(b)) => b,
+            _ => unreachable!(),
+        };
+        self.people[person.0].on_bus.take().unwrap();
+
+        assert!(trip.legs.is_empty());
+        assert!(!trip.finished_at.is_some());
+        trip.finished_at = Some(now);
+        self.unfinished_trips -= 1;
+        self.events.push(Event::TripFinished {
+            trip: trip.id,
+            mode: trip.info.mode,
+            total_time: now - trip.info.departure,
+            blocked_time: trip.total_blocked_time,
+        });
+        self.people[person.0].state = PersonState::Inside(bldg);
+        self.events.push(Event::PersonEntersBuilding(person, bldg));
+        self.person_finished_trip(now, person, ctx);
+    }
+
+    // The vehicle can't finish the ride, so the passenger gets out and warps to their destination.
+    pub fn ridehail_trip_aborted(
+        &mut self,
+        now: Time,
+        person: PersonID,
+        car: CarID,
+        ctx: &mut Ctx,
+    ) {
+        let trip = self
+            .active_trip_mode
+            .remove(&AgentID::BusPassenger(person, car))
+            .unwrap();
+        self.people[person.0].on_bus.take().unwrap();
+        self.abort_trip(now, trip, None, ctx);
+    }
+
     pub fn ped_reached_border(
         &mut self,
         now: Time,
@@ -732,6 +954,87 @@ impl TripManager {
         self.events.push(Event::TripAborted(trip.id));
     }
 
+    // Cancel all of the person's trips that haven't started yet. If they're in the middle of a
+    // trip, it continues. Returns the number of trips cancelled.
+    pub fn cancel_future_trips(&mut self, person: PersonID, scheduler: &mut Scheduler) -> usize {
+        let delayed: Vec<TripID> =
+            std::mem::replace(&mut self.people[person.0].delayed_trips, Vec::new())
+                .into_iter()
+                .map(|(t, _, _, _)| t)
+                .collect();
+        let mut cancel = Vec::new();
+        for t in &self.people[person.0].trips {
+            let trip = &self.trips[t.0];
+            if trip.started || trip.cancelled || trip.aborted {
+                continue;
+            }
+            // Delayed trips were already removed from the scheduler
+            if !delayed.contains(t) {
+                scheduler.must_cancel_by_type(CommandType::StartTrip(*t));
+            }
+            cancel.push(*t);
+        }
+        for t in &cancel {
+            self.cancel_trip(*t);
+        }
+        cancel.len()
+    }
+
+    // Cancel all of the person's trips that haven't started yet, and send them home instead, once
+    // they're done with any trip in progress. Returns the trip home, if one was needed. People
+    // whose first trip doesn't start at a building don't have a home to go to.
+    pub fn replace_future_trips(
+        &mut self,
+        now: Time,
+        person: PersonID,
+        scheduler: &mut Scheduler,
+        map: &Map,
+    ) -> Option<TripID> {
+        self.cancel_future_trips(person, scheduler);
+
+        let home = match self.trips[self.people[person.0].trips.get(0)?.0].info.start {
+            TripEndpoint::Bldg(b) => b,
+            TripEndpoint::Border(_, _) => {
+                return None;
+            }
+        };
+        let current = match self.people[person.0].state {
+            PersonState::Inside(b) => b,
+            PersonState::Trip(t) => match self.trips[t.0].info.end {
+                TripEndpoint::Bldg(b) => b,
+                TripEndpoint::Border(_, _) => {
+                    return None;
+                }
+            },
+            PersonState::OffMap => {
+                return None;
+            }
+        };
+        if current == home {
+            return None;
+        }
+
+        let start = SidewalkSpot::building(current, map);
+        let goal = SidewalkSpot::building(home, map);
+        let trip = self.new_trip(
+            person,
+            now,
+            TripEndpoint::Bldg(current),
+            TripMode::Walk,
+            false,
+            vec![TripLeg::Walk(goal.clone())],
+            map,
+        );
+        let spec = TripSpec::JustWalking { start, goal };
+        let req = spec.get_pathfinding_request(map);
+        let path = req
+            .clone()
+            .and_then(|r| map.pathfind_at(r, now, &self.routing_params));
+        // If they're still in the middle of a trip, this'll be delayed until they finish
+        scheduler.push(now, Command::StartTrip(trip, spec, req, path));
+        Some(trip)
+    }
+
     pub fn abort_trip(
         &mut self,
         now: Time,
@@ -769,18 +1072,13 @@ impl TripManager {
             if vehicle.vehicle_type == VehicleType::Car {
                 if let TripEndpoint::Bldg(b) = trip.info.end {
                     let driving_lane = ctx.map.find_driving_lane_near_building(b);
-                    if let Some(spot) = ctx
-                        .parking
-                        .get_all_free_spots(Position::start(driving_lane), &vehicle, b, ctx.map)
-                        // TODO Could pick something closer, but meh, aborted trips are bugs anyway
-                        .get(0)
-                        .map(|(spot, _)| spot.clone())
-                        .or_else(|| {
-                            ctx.parking
-                                .path_to_free_parking_spot(driving_lane, &vehicle, b, ctx.map)
-                                .map(|(_, spot, _)| spot)
-                        })
-                    {
+                    if let Some((_, spot, _)) = ctx.parking.path_to_free_parking_spot(
+                        Position::start(driving_lane),
+                        &vehicle,
+                        b,
+                        now,
+                        ctx.map,
+                    ) {
                         self.events.push(Event::Alert(
                             AlertLocation::Person(person),
                             format!(
@@ -789,7 +1087,7 @@ impl TripManager {
                             ),
                         ));
                         ctx.parking.reserve_spot(spot);
-                        ctx.parking.add_parked_car(ParkedCar { vehicle, spot });
+                        ctx.parking.add_parked_car(ParkedCar { vehicle, spot }, now);
                     } else {
                         self.events.push(Event::Alert(
                             AlertLocation::Person(person),
@@ -810,6 +1108,14 @@ impl TripManager {
                 }
             }
         }
+        // Shared bikes warp to wherever they were going to be returned
+        for leg in &trip.legs {
+            if let TripLeg::Drive(c, _) = leg {
+                if ctx.bikeshare.is_in_use(*c) {
+                    ctx.bikeshare.vehicle_returned(*c);
+                }
+            }
+        }
 
         self.person_finished_trip(now, person, ctx);
     }
@@ -848,6 +1154,14 @@ impl TripManager {
             TripLeg::Walk(_) => AgentID::Pedestrian(person.ped),
             TripLeg::Drive(c, _) => AgentID::Car(*c),
             TripLeg::RideBus(_, _) => AgentID::BusPassenger(person.id, person.on_bus.unwrap()),
+            TripLeg::RideHail(_) => {
+                if let Some(car) = person.on_bus {
+                    AgentID::BusPassenger(person.id, car)
+                } else {
+                    // Still waiting for pickup
+                    return TripResult::ModeChange;
+                }
+            }
             TripLeg::Remote(_) => {
                 return TripResult::RemoteTrip;
             }
@@ -880,7 +1194,11 @@ impl TripManager {
             self.unfinished_trips,
         )
     }
-    pub fn num_agents(&self, transit: &TransitSimState) -> Counter<AgentType> {
+    pub fn num_agents(
+        &self,
+        transit: &TransitSimState,
+        ridehail: &RideHailSimState,
+    ) -> Counter<AgentType> {
         let mut cnt = Counter::new();
         for a in self.active_trip_mode.keys() {
             cnt.inc(a.to_type());
@@ -888,6 +1206,7 @@ impl TripManager {
         let (buses, trains) = transit.active_vehicles();
         cnt.add(AgentType::Bus, buses);
         cnt.add(AgentType::Train, trains);
+        cnt.add(AgentType::Car, ridehail.active_vehicles());
         cnt
     }
     pub fn num_ppl(&self) -> (usize, usize, usize) {
@@ -926,6 +1245,22 @@ impl TripManager {
         Some((t.finished_at? - t.info.departure, t.total_blocked_time))
     }
 
+    // Cars that somebody is walking to right now
+    pub fn cars_being_fetched(&self) -> BTreeSet<CarID> {
+        let mut cars = BTreeSet::new();
+        for (agent, trip) in &self.active_trip_mode {
+            if let AgentID::Pedestrian(_) = agent {
+                let legs = &self.trips[trip.0].legs;
+                if let (Some(TripLeg::Walk(_)), Some(TripLeg::Drive(car, _))) =
+                    (legs.get(0), legs.get(1))
+                {
+                    cars.insert(*car);
+                }
+            }
+        }
+        cars
+    }
+
     pub fn bldg_to_people(&self, b: BuildingID) -> Vec<PersonID> {
         let mut people = Vec::new();
         for p in &self.people {
@@ -971,13 +1306,15 @@ impl TripManager {
         trip: TripID,
         spec: TripSpec,
         maybe_req: Option<PathRequest>,
-        mut maybe_path: Option<Path>,
+        mut maybe_path: Option<(Path, bool)>,
         ctx: &mut Ctx,
     ) {
         assert!(!self.trips[trip.0].cancelled);
         assert!(!self.trips[trip.0].aborted);
         if !self.pathfinding_upfront && maybe_path.is_none() && maybe_req.is_some() {
-            maybe_path = ctx.map.pathfind(maybe_req.clone().unwrap());
+            maybe_path = ctx
+                .map
+                .pathfind_at(maybe_req.clone().unwrap(), now, &self.routing_params);
         }
 
         let person = &mut self.people[self.trips[trip.0].person.0];
@@ -1004,6 +1341,10 @@ impl TripManager {
             return;
         }
         self.trips[trip.0].started = true;
+        let (maybe_path, diverted) = match maybe_path {
+            Some((path, diverted)) => (Some(path), diverted),
+            None => (None, false),
+        };
 
         match spec {
             TripSpec::VehicleAppearing {
@@ -1028,7 +1369,7 @@ impl TripManager {
                 if let Some(router) =
                     maybe_path.map(|path| goal.make_router(vehicle.id, path, ctx.map))
                 {
-                    if !ctx.cap.allow_trip(vehicle.id, router.get_path()) {
+                    if !ctx.cap.allow_trip(now, vehicle.id, router.get_path()) {
                         self.events.push(Event::Alert(
                             AlertLocation::Person(person.id),
                             format!(
@@ -1039,6 +1380,9 @@ impl TripManager {
                         self.abort_trip(now, trip, Some(vehicle), ctx);
                         return;
                     }
+                    if diverted {
+                        self.events.push(Event::TripDiverted(trip));
+                    }
 
                     ctx.scheduler.push(
                         now,
@@ -1075,6 +1419,9 @@ impl TripManager {
             }
             TripSpec::UsingParkedCar {
                 car, start_bldg, ..
+            }
+            | TripSpec::DriveThenTransit {
+                car, start_bldg, ..
             } => {
                 assert_eq!(person.state, PersonState::Inside(start_bldg));
                 person.state = PersonState::Trip(trip);
@@ -1111,7 +1458,12 @@ impl TripManager {
                             format!("UsingParkedCar trip couldn't find the walking path {}", req),
                         ));
                         // Move the car to the destination
-                        ctx.parking.remove_parked_car(parked_car.clone());
+                        ctx.parking
+                            .remove_parked_car(parked_car.clone(), now, ctx.map);
+                        if let Some(pl) = self.park_and_ride_cars.remove(&car) {
+                            self.events
+                                .push(park_and_ride_occupancy(&self.park_and_ride_cars, pl));
+                        }
                         self.abort_trip(now, trip, Some(parked_car.vehicle), ctx);
                     }
                 } else {
@@ -1181,7 +1533,7 @@ impl TripManager {
                     self.abort_trip(now, trip, None, ctx);
                 }
             }
-            TripSpec::UsingBike { start, .. } => {
+            TripSpec::UsingBike { start, .. } | TripSpec::BikeThenTransit { start, .. } => {
                 assert_eq!(person.state, PersonState::Inside(start));
                 person.state = PersonState::Trip(trip);
 
@@ -1219,7 +1571,9 @@ impl TripManager {
                     self.abort_trip(now, trip, None, ctx);
                 }
             }
-            TripSpec::UsingTransit { start, stop1, .. } => {
+            TripSpec::UsingTransit { start, stop1, .. }
+            | TripSpec::TransitThenDrive { start, stop1, .. }
+            | TripSpec::TransitThenBike { start, stop1, .. } => {
                 assert_eq!(
                     person.state,
                     match start.connection {
@@ -1273,6 +1627,92 @@ impl TripManager {
                     self.abort_trip(now, trip, None, ctx);
                 }
             }
+            TripSpec::UsingBikeShare { start, goal } => {
+                assert_eq!(person.state, PersonState::Inside(start));
+                person.state = PersonState::Trip(trip);
+
+                let walk_to = if let Some((bike, pickup, dropoff)) =
+                    ctx.bikeshare.checkout(start, goal, ctx.map)
+                {
+                    let spot = SidewalkSpot::bike_rack(pickup, ctx.map).unwrap();
+                    let legs = &mut self.trips[trip.0].legs;
+                    legs.push_front(TripLeg::Drive(bike, DrivingGoal::ParkNear(dropoff)));
+                    legs.push_front(TripLeg::Walk(spot.clone()));
+                    spot
+                } else {
+                    self.events.push(Event::Alert(
+                        AlertLocation::Person(person.id),
+                        format!(
+                            "{} couldn't find a shared vehicle near {} with somewhere to leave it \
+                             near {}, so walking instead",
+                            person.id, start, goal
+                        ),
+                    ));
+                    self.trips[trip.0].info.mode = TripMode::Walk;
+                    SidewalkSpot::building(goal, ctx.map)
+                };
+                let req = PathRequest {
+                    start: ctx.map.get_b(start).sidewalk_pos,
+                    end: walk_to.sidewalk_pos,
+                    constraints: PathConstraints::Pedestrian,
+                };
+                if let Some(path) = ctx.map.pathfind(req.clone()) {
+                    ctx.scheduler.push(
+                        now,
+                        Command::SpawnPed(CreatePedestrian {
+                            id: person.ped,
+                            speed: person.ped_speed,
+                            start: SidewalkSpot::building(start, ctx.map),
+                            goal: walk_to,
+                            path,
+                            req,
+                            trip,
+                            person: person.id,
+                        }),
+                    );
+                } else {
+                    self.events.push(Event::Alert(
+                        AlertLocation::Person(person.id),
+                        format!("UsingBikeShare trip couldn't find the first path {}", req),
+                    ));
+                    self.abort_trip(now, trip, None, ctx);
+                }
+            }
+            TripSpec::UsingRideHail { start, goal } => {
+                assert_eq!(person.state, PersonState::Inside(start));
+                person.state = PersonState::Trip(trip);
+
+                // The path isn't used; just make sure the ride is possible before calling.
+                if maybe_path.is_some() {
+                    self.events
+                        .push(Event::PersonLeavesBuilding(person.id, start));
+                    self.events.push(Event::TripPhaseStarting(
+                        trip,
+                        person.id,
+                        None,
+                        TripPhaseType::WaitingForRideHail,
+                    ));
+                    ctx.scheduler.push(
+                        now,
+                        Command::RequestRideHail(RideHailRequest {
+                            trip,
+                            person: person.id,
+                            pickup: start,
+                            dropoff: goal,
+                            requested: now,
+                        }),
+                    );
+                } else {
+                    self.events.push(Event::Alert(
+                        AlertLocation::Person(person.id),
+                        format!(
+                            "UsingRideHail trip couldn't find a path from {} to {}",
+                            start, goal
+                        ),
+                    ));
+                    self.abort_trip(now, trip, None, ctx);
+                }
+            }
             TripSpec::Remote {
                 trip_time, from, ..
             } => {
@@ -1308,6 +1748,8 @@ impl TripManager {
                         // TODO Not true for long. People will be able to spawn at borders already
                         // on a bus.
                         TripMode::Transit => AgentType::Pedestrian,
+                        // Ride-hail trips only start at buildings
+                        TripMode::RideHail => unreachable!(),
                     };
                     times.push((t.info.departure, agent_type));
                 }
@@ -1440,6 +1882,8 @@ pub enum TripLeg {
     Drive(CarID, DrivingGoal),
     // Maybe get off at a stop, maybe ride off-map
     RideBus(BusRouteID, Option<BusStopID>),
+    // Picked up and dropped off at buildings
+    RideHail(BuildingID),
     Remote(OffMapLocation),
 }
 
@@ -1449,6 +1893,7 @@ pub enum TripMode {
     Bike,
     Transit,
     Drive,
+    RideHail,
 }
 
 impl TripMode {
@@ -1458,6 +1903,7 @@ impl TripMode {
             TripMode::Bike,
             TripMode::Transit,
             TripMode::Drive,
+            TripMode::RideHail,
         ]
     }
 
@@ -1467,6 +1913,7 @@ impl TripMode {
             TripMode::Bike => "bike",
             TripMode::Transit => "use transit",
             TripMode::Drive => "drive",
+            TripMode::RideHail => "hail a ride",
         }
     }
 
@@ -1477,6 +1924,7 @@ impl TripMode {
             TripMode::Bike => "biking",
             TripMode::Transit => "using transit",
             TripMode::Drive => "driving",
+            TripMode::RideHail => "riding in a ride-hail vehicle",
         }
     }
 
@@ -1486,6 +1934,7 @@ impl TripMode {
             TripMode::Bike => "Bike",
             TripMode::Transit => "Bus",
             TripMode::Drive => "Car",
+            TripMode::RideHail => "Ride-hail",
         }
     }
 
@@ -1495,7 +1944,7 @@ impl TripMode {
             TripMode::Bike => PathConstraints::Bike,
             // TODO WRONG
             TripMode::Transit => PathConstraints::Bus,
-            TripMode::Drive => PathConstraints::Car,
+            TripMode::Drive | TripMode::RideHail => PathConstraints::Car,
         }
     }
 
@@ -1528,7 +1977,7 @@ impl TripEndpoint {
             end: pos(to, mode, false, map)?,
             constraints: match mode {
                 TripMode::Walk | TripMode::Transit => PathConstraints::Pedestrian,
-                TripMode::Drive => PathConstraints::Car,
+                TripMode::Drive | TripMode::RideHail => PathConstraints::Car,
                 TripMode::Bike => PathConstraints::Bike,
             },
         })
@@ -1545,6 +1994,7 @@ fn pos(endpt: TripEndpoint, mode: TripMode, from: bool, map: &Map) -> Option<Pos
                     .goal_pos(PathConstraints::Car, map)
                     .unwrap(),
             ),
+            TripMode::RideHail => curb_pos(b, map),
         },
         TripEndpoint::Border(i, _) => match mode {
             TripMode::Walk | TripMode::Transit => if from {
@@ -1553,7 +2003,7 @@ fn pos(endpt: TripEndpoint, mode: TripMode, from: bool, map: &Map) -> Option<Pos
                 SidewalkSpot::end_at_border(i, None, map)
             }
             .map(|spot| spot.sidewalk_pos),
-            TripMode::Bike | TripMode::Drive => (if from {
+            TripMode::Bike | TripMode::Drive | TripMode::RideHail => (if from {
                 map.get_i(i).some_outgoing_road(map)
             } else {
                 map.get_i(i).some_incoming_road(map)
@@ -1574,6 +2024,10 @@ fn pos(endpt: TripEndpoint, mode: TripMode, from: bool, map: &Map) -> Option<Pos
     }
 }
 
+fn park_and_ride_occupancy(cars: &BTreeMap<CarID, ParkingLotID>, pl: ParkingLotID) -> Event {
+    Event::ParkAndRideOccupancy(pl, cars.values().filter(|x| **x == pl).count())
+}
+
 pub enum TripResult<T> {
     Ok(T),
     ModeChange,
@@ -1620,7 +2074,7 @@ pub struct Person {
     // Both cars and bikes
     pub vehicles: Vec<Vehicle>,
 
-    delayed_trips: Vec<(TripID, TripSpec, Option<PathRequest>, Option<Path>)>,
+    delayed_trips: Vec<(TripID, TripSpec, Option<PathRequest>, Option<(Path, bool)>)>,
     on_bus: Option<CarID>,
 }
 